@@ -0,0 +1,210 @@
+use std::path::Path;
+use poc_pipeline::common::font::face::use_test_fonts;
+use poc_pipeline::common::get_texture_store;
+use poc_pipeline::common::geo::Rect;
+use poc_pipeline::display_list::{DisplayListSnapshot, LayerDisplayList};
+use poc_pipeline::rasterizer::Rasterable;
+use poc_pipeline::tiler::{to_tiled_element, Tile, TileId, TileState};
+
+// Replays a display list that has been saved with `DisplayList::save()` through one of the rasterizers and
+// writes the result into a PNG. Parsing and layout are skipped entirely, so rasterizer issues can be reproduced
+// from a (small) display list file, and the output of the different backends can be compared for the exact same
+// paint commands.
+//
+// Usage: display-list-replay [--test-fonts] <display_list.json> <output.png> [skia|cairo|vello]
+//
+// With --test-fonts, all generic font families resolve to the bundled test fonts (resources/fonts), so the
+// output does not depend on the fonts that are installed on the machine.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    Skia,
+    Cairo,
+    Vello,
+}
+
+impl Backend {
+    fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "skia" => Some(Backend::Skia),
+            "cairo" => Some(Backend::Cairo),
+            "vello" => Some(Backend::Vello),
+            _ => None,
+        }
+    }
+
+    /// Returns the first backend that is compiled in, or None when no backend is compiled in
+    fn first_available() -> Option<Backend> {
+        if cfg!(feature = "backend_skia") {
+            Some(Backend::Skia)
+        } else if cfg!(feature = "backend_cairo") {
+            Some(Backend::Cairo)
+        } else if cfg!(feature = "backend_vello") {
+            Some(Backend::Vello)
+        } else {
+            None
+        }
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    let test_fonts = args.iter().any(|arg| arg == "--test-fonts");
+    args.retain(|arg| arg != "--test-fonts");
+    if args.len() < 3 {
+        eprintln!("Usage: {} [--test-fonts] <display_list.json> <output.png> [skia|cairo|vello]", args[0]);
+        std::process::exit(1);
+    }
+
+    if test_fonts {
+        if let Err(e) = use_test_fonts() {
+            eprintln!("Failed to load test fonts: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let backend = match args.get(3) {
+        Some(name) => match Backend::from_name(name) {
+            Some(backend) => backend,
+            None => {
+                eprintln!("Unknown backend: {}", name);
+                std::process::exit(1);
+            }
+        },
+        None => match Backend::first_available() {
+            Some(backend) => backend,
+            None => {
+                eprintln!("No backend is compiled in, enable one of the backend_skia, backend_cairo or backend_vello features");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let snapshot = match DisplayListSnapshot::load(Path::new(&args[1])) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            eprintln!("Failed to load display list: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let page_rect = snapshot.page_rect();
+    let width = page_rect.width.ceil() as u32;
+    let height = page_rect.height.ceil() as u32;
+    if width == 0 || height == 0 {
+        eprintln!("Display list does not contain anything to paint");
+        std::process::exit(1);
+    }
+
+    println!("Replaying {} layers ({}x{}) with backend {:?}", snapshot.layers.len(), width, height, backend);
+
+    // Each layer is rasterized onto a single tile that covers the whole page, and the layers are blended on
+    // top of each other in layer order.
+    let page_rect = Rect::new(0.0, 0.0, width as f64, height as f64);
+    let mut output = image::RgbaImage::new(width, height);
+    for layer in &snapshot.layers {
+        let tile = create_page_tile(layer, page_rect);
+        let pixels = rasterize(backend, &tile);
+
+        let Some(layer_image) = image::RgbaImage::from_raw(width, height, pixels) else {
+            eprintln!("Rasterizer returned an unexpected texture size");
+            std::process::exit(1);
+        };
+        image::imageops::overlay(&mut output, &layer_image, 0, 0);
+    }
+
+    if let Err(e) = output.save(&args[2]) {
+        eprintln!("Failed to save image: {}", e);
+        std::process::exit(1);
+    }
+
+    println!("Written {}", args[2]);
+}
+
+/// Creates a tile that covers the given page rect and holds all the items of the layer
+fn create_page_tile(layer: &LayerDisplayList, page_rect: Rect) -> Tile {
+    Tile {
+        id: TileId::new(0),
+        layer_id: layer.layer_id,
+        elements: layer.items.iter().cloned().map(|item| to_tiled_element(page_rect, item)).collect(),
+        texture_id: None,
+        state: TileState::Dirty,
+        rect: page_rect,
+    }
+}
+
+/// Rasterizes the tile with the given backend and returns the pixels as (non-premultiplied) RGBA
+fn rasterize(backend: Backend, tile: &Tile) -> Vec<u8> {
+    let texture_id = match backend {
+        #[cfg(feature = "backend_skia")]
+        Backend::Skia => poc_pipeline::rasterizer::skia::SkiaRasterizer::new().rasterize(tile),
+        #[cfg(feature = "backend_cairo")]
+        Backend::Cairo => poc_pipeline::rasterizer::cairo::CairoRasterizer::new().rasterize(tile),
+        #[cfg(feature = "backend_vello")]
+        Backend::Vello => rasterize_vello(tile),
+        #[allow(unreachable_patterns)]
+        _ => {
+            eprintln!("Backend {:?} is not compiled in", backend);
+            std::process::exit(1);
+        }
+    };
+
+    let binding = get_texture_store();
+    let texture_store = binding.read().expect("Failed to get texture store");
+    let texture = texture_store.get(texture_id).expect("Failed to get texture");
+    let mut pixels = texture.data.clone();
+
+    // Each backend has its own texture format. Normalize them to non-premultiplied RGBA.
+    match backend {
+        Backend::Cairo => {
+            // Cairo's ARGB32 is stored as BGRA on little endian machines
+            for px in pixels.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+            unpremultiply(&mut pixels);
+        }
+        Backend::Skia => unpremultiply(&mut pixels),
+        Backend::Vello => {}
+    }
+
+    pixels
+}
+
+fn unpremultiply(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        if a == 0 || a == 255 {
+            continue;
+        }
+
+        for c in px.iter_mut().take(3) {
+            *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+#[cfg(feature = "backend_vello")]
+fn rasterize_vello(tile: &Tile) -> poc_pipeline::common::texture::TextureId {
+    use std::cell::RefCell;
+    use poc_pipeline::rasterizer::vello::VelloRasterizer;
+    use vello::util::RenderContext;
+    use vello::{AaSupport, Renderer, RendererOptions};
+
+    // We don't have a window, so we just grab any device that can render offscreen
+    let mut render_ctx = RenderContext::new();
+    let dev_id = pollster::block_on(render_ctx.device(None)).expect("Failed to find a render device");
+    let dev_handle = &render_ctx.devices[dev_id];
+
+    let renderer = Renderer::new(
+        &dev_handle.device,
+        RendererOptions {
+            surface_format: None,
+            use_cpu: false,
+            antialiasing_support: AaSupport::all(),
+            num_init_threads: None,
+        },
+    ).expect("Failed to create renderer");
+    let renderer = RefCell::new(renderer);
+
+    VelloRasterizer::new(&dev_handle.device, &dev_handle.queue, &renderer).rasterize(tile)
+}
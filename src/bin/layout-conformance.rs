@@ -0,0 +1,103 @@
+use std::sync::Arc;
+use poc_pipeline::common::document::parser::document_from_json;
+use poc_pipeline::common::geo::Dimension;
+use poc_pipeline::layouter::conformance::{compare_layout, DEFAULT_TOLERANCE};
+use poc_pipeline::layouter::text::{default_shaper, shaper_by_name};
+use poc_pipeline::layouter::{layouter_by_name, Viewport, LAYOUTER_NAMES};
+use poc_pipeline::rendertree_builder::RenderTree;
+
+// Lays out pages that have been exported with tools/souper, and compares the border box of every element with
+// the box Chromium laid out. Prints a score per page (the fraction of elements that match within the tolerance)
+// and the elements that are off the most.
+//
+// Usage: layout-conformance [--tolerance <pixels>] [--worst <count>] [--shaper <name>] [--layouter <name>] <page.json> ...
+
+/// Viewport that tools/souper lays out the pages in
+const VIEWPORT: Dimension = Dimension { width: 1280.0, height: 1144.0 };
+
+fn main() {
+    let mut tolerance = DEFAULT_TOLERANCE;
+    let mut worst = 10;
+    let mut shaper = None;
+    let mut layouter_name = LAYOUTER_NAMES[0].to_string();
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tolerance" => tolerance = parse_number(args.next()),
+            "--worst" => worst = parse_number(args.next()) as usize,
+            "--shaper" => shaper = args.next(),
+            "--layouter" => layouter_name = args.next().unwrap_or(layouter_name),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("Usage: layout-conformance [--tolerance <pixels>] [--worst <count>] [--shaper <name>] [--layouter <name>] <page.json> ...");
+        std::process::exit(1);
+    }
+
+    let shaper = match shaper {
+        Some(name) => match shaper_by_name(&name) {
+            Some(shaper) => shaper,
+            None => {
+                eprintln!("Text shaper '{}' is not compiled in", name);
+                std::process::exit(1);
+            }
+        },
+        None => default_shaper(),
+    };
+    if let Err(e) = layouter_by_name(&layouter_name, shaper.clone()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let mut compared = 0;
+    let mut matched = 0;
+    for path in &paths {
+        let doc = document_from_json("https://example.com", path);
+        if doc.reference_boxes.is_empty() {
+            println!("{}: no reference boxes, export the page again with tools/souper", path);
+            continue;
+        }
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+
+        let mut layouter = layouter_by_name(&layouter_name, shaper.clone()).expect("Unknown layouter");
+        let layout_tree = layouter.layout(render_tree, Viewport::new(Some(VIEWPORT), 1.0));
+
+        let report = compare_layout(&layout_tree, tolerance);
+        println!(
+            "{}: {:.1}% ({} of {} elements within {}px, {} not laid out)",
+            path, report.score() * 100.0, report.matched, report.compared, tolerance, report.missing.len(),
+        );
+        for deviation in report.worst(worst) {
+            let (e, a) = (deviation.expected, deviation.actual);
+            println!(
+                "  {:>8.1}px  {} ({})  expected {:.1},{:.1} {:.1}x{:.1}  got {:.1},{:.1} {:.1}x{:.1}",
+                deviation.error, deviation.label, deviation.dom_node_id,
+                e.x, e.y, e.width, e.height, a.x, a.y, a.width, a.height,
+            );
+        }
+
+        compared += report.compared;
+        matched += report.matched;
+    }
+
+    if paths.len() > 1 && compared > 0 {
+        println!();
+        println!("Total: {:.1}% ({} of {} elements)", matched as f64 / compared as f64 * 100.0, matched, compared);
+    }
+}
+
+fn parse_number(arg: Option<String>) -> f64 {
+    match arg.as_deref().map(str::parse::<f64>) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("Expected a number");
+            std::process::exit(1);
+        }
+    }
+}
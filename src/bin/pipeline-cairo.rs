@@ -17,66 +17,63 @@ use poc_pipeline::common::browser_state::{
 use poc_pipeline::common::geo::{Dimension, Rect};
 use poc_pipeline::compositor::cairo::{CairoCompositor, CairoCompositorConfig};
 use poc_pipeline::compositor::Composable;
+use poc_pipeline::display_list::DisplayList;
 use poc_pipeline::layering::layer::{LayerId, LayerList};
-use poc_pipeline::layouter::taffy::TaffyLayouter;
-use poc_pipeline::layouter::CanLayout;
-use poc_pipeline::painter::Painter;
+use poc_pipeline::layouter::text::pango::PangoShaper;
+use poc_pipeline::layouter::{layouter_by_name, Viewport, LAYOUTER_NAMES};
 use poc_pipeline::rasterizer::cairo::CairoRasterizer;
 use poc_pipeline::rasterizer::Rasterable;
 use poc_pipeline::rendertree_builder::RenderTree;
 use poc_pipeline::tiler::{TileList, TileState};
-use std::sync::RwLock;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 const TILE_DIMENSION: f64 = 256.0;
+/// Directory where the display list is saved to (press "s")
+const DISPLAY_LIST_DIR: &str = "display-list";
 
 const WINDOW_WIDTH: f64 = 1024.0;
 const WINDOW_HEIGHT: f64 = 768.0;
 
+/// Command line help. The layouter lays out the document again when the window is resized.
+const USAGE: &str = "Usage: pipeline-cairo [--layouter <name>]
+
+  --layouter <name>  Layouter to use: taffy (default) or flow. Only taffy lays out incrementally when the
+                     window is resized, flow lays out the whole document again.";
+
 fn main() {
     // --------------------------------------------------------------------
     // Generate a DOM tree
     // let doc = common::document::create_document();
     // let doc = common::document::parser::document_from_json("tables.json");
     // let doc = common::document::parser::document_from_json("news.ycombinator.com.json");
-    let doc = common::document::parser::document_from_json("cm.json");
+    let doc = common::document::parser::document_from_json("https://codemusings.nl", "cm.json");
+
+    let mut layouter_name = LAYOUTER_NAMES[0].to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layouter" => layouter_name = args.next().unwrap_or(layouter_name),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+    let layouter = match layouter_by_name(&layouter_name, Arc::new(PangoShaper::new())) {
+        Ok(layouter) => layouter,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let mut output = String::new();
     doc.print_tree(&mut output).expect("");
     println!("{}", output);
 
     // --------------------------------------------------------------------
-    // Convert the DOM tree into a render-tree that has all the non-visible elements removed
-    let mut render_tree = RenderTree::new(doc);
-    render_tree.parse();
-    // render_tree.print();
-
-    // --------------------------------------------------------------------
-    // Layout the render-tree into a layout-tree
-    let mut layouter = TaffyLayouter::new();
-    let layout_tree = layouter.layout(render_tree, None);
-    layouter.print_tree();
-    println!(
-        "Layout width: {}, height: {}",
-        layout_tree.root_dimension.width, layout_tree.root_dimension.height
-    );
-
-    // -------------------------------------------------------------------  -
-    // Generate render layers
-    let layer_list = LayerList::new(layout_tree);
-    // for (layer_id, layer) in layer_list.layers.read().expect("").iter() {
-    //     println!("Layer: {} (order: {})", layer_id, layer.order);
-    //     for element in layer.elements.iter() {
-    //         println!("  Element: {}", element);
-    //     }
-    // }
-
-    // --------------------------------------------------------------------
-    // Tiling phase
-    let mut tile_list = TileList::new(layer_list, Dimension::new(TILE_DIMENSION, TILE_DIMENSION));
-    tile_list.generate();
-    // tile_list.print_list();
-
-    // --------------------------------------------------------------------
-    // At this point, we have done everything we can before painting. The rest
+    // The document is laid out when the window is built, for the size and scale factor of the window. The rest
     // is completed in the draw function of the UI.
 
     // Render the layout-tree into a GTK window
@@ -89,9 +86,13 @@ fn main() {
         wireframed: WireframeState::None,
         debug_hover: false,
         current_hovered_element: None,
-        tile_list: RwLock::new(tile_list),
         show_tilegrid: true,
-        viewport: Rect::ZERO,
+        viewport: Rect::new(0.0, 0.0, WINDOW_WIDTH, WINDOW_HEIGHT),
+        // The scale factor of the window is set when the window is built
+        device_pixel_ratio: 1.0,
+        document: Arc::new(doc),
+        layouter: Some(layouter),
+        tile_list: None,
     };
     init_browser_state(browser_state);
 
@@ -114,7 +115,67 @@ Available key commands:
 "
     );
 
-    app.run();
+    // The arguments have been parsed above, GTK would reject the ones it does not know
+    app.run_with_args::<&str>(&[]);
+}
+
+/// Lays out the document for the viewport. The document is laid out once, after that the layouter only computes
+/// the layout again for the new viewport and scale factor. The display list and tiles are only updated for the
+/// elements that the layouter has changed.
+fn reflow() {
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+
+    println!("reflowing to dimension: {:?}", state.viewport);
+
+    let document = state.document.clone();
+    let viewport = Viewport::new(Some(Dimension::new(state.viewport.width, state.viewport.height)), state.device_pixel_ratio);
+    let Some(mut layouter) = state.layouter.take() else {
+        log::error!("No layouter found");
+        return;
+    };
+    let tile_list = state.tile_list.take().map(|tile_list| tile_list.into_inner().expect("Failed to get tile list"));
+    // The painter needs the browser state, so don't hold on to it
+    drop(state);
+
+    // Only the elements that the layouter has changed are painted and tiled again
+    let tile_list = tile_list.and_then(|mut tile_list| {
+        let changed = layouter.relayout(tile_list.layout_tree_mut()?, viewport);
+        tile_list.relayout(&changed);
+        Some(tile_list)
+    });
+    let tile_list = tile_list.unwrap_or_else(|| {
+        let mut render_tree = RenderTree::new(document);
+        render_tree.parse();
+        let layout_tree = layouter.layout(render_tree, viewport);
+
+        let layer_list = LayerList::new(layout_tree);
+        let display_list = DisplayList::new(layer_list);
+
+        let mut tile_list = TileList::new(display_list, Dimension::new(TILE_DIMENSION, TILE_DIMENSION));
+        tile_list.generate();
+        tile_list
+    });
+    let root_dimension = tile_list.display_list.layer_list.layout_tree.root_dimension;
+    println!("Layout width: {}, height: {}", root_dimension.width, root_dimension.height);
+
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    state.layouter = Some(layouter);
+    state.tile_list = Some(RwLock::new(tile_list));
+}
+
+/// Sets the size of the drawing area to the size of the laid out document, so it can be scrolled
+fn resize_area(area: &DrawingArea) {
+    let binding = get_browser_state();
+    let state = binding.read().unwrap();
+    let Some(ref tile_list) = state.tile_list else {
+        return;
+    };
+
+    let root_dimension = tile_list.read().unwrap().display_list.layer_list.layout_tree.root_dimension;
+    area.set_content_width(root_dimension.width as i32);
+    area.set_content_height(root_dimension.height as i32);
 }
 
 fn build_ui(app: &Application) {
@@ -125,35 +186,28 @@ fn build_ui(app: &Application) {
         .default_height(WINDOW_HEIGHT as i32)
         .build();
 
-    // Find the root layout dimension so we can set the viewport correctly
-    let binding = get_browser_state().clone();
-    let state = binding.read().unwrap();
-    let dim = state
-        .tile_list
-        .read()
-        .unwrap()
-        .layer_list
-        .layout_tree
-        .clone()
-        .root_dimension
-        .clone();
+    // Lay out the document for the scale factor of the window
+    let binding = get_browser_state();
+    let mut state = binding.write().unwrap();
+    state.device_pixel_ratio = window.scale_factor() as f64;
+    drop(state);
+    reflow();
 
     let area = DrawingArea::new();
-    area.set_content_width(dim.width as i32);
-    area.set_content_height(dim.height as i32);
+    resize_area(&area);
     area.set_draw_func(move |_area, cr, _width, _height| {
         let binding = get_browser_state();
         let state = binding.read().unwrap();
-        let vis_layers = state.visible_layer_list.clone();
+        let layer_ids = match state.tile_list {
+            Some(ref tile_list) => tile_list.read().unwrap().display_list.layer_list.composited_layers(&state.visible_layer_list),
+            None => Vec::new(),
+        };
         drop(state);
 
-        if vis_layers[0] {
-            do_paint(LayerId::new(0));
-            do_rasterize(LayerId::new(0));
-        }
-        if vis_layers[1] {
-            do_paint(LayerId::new(1));
-            do_rasterize(LayerId::new(1));
+        // Paint and rasterize the layers that are composited
+        for layer_id in layer_ids {
+            do_paint(layer_id);
+            do_rasterize(layer_id);
         }
 
         CairoCompositor::compose(CairoCompositorConfig { cr: cr.clone() });
@@ -167,88 +221,54 @@ fn build_ui(app: &Application) {
     motion_controller.connect_motion(move |_, x, y| {
         let binding = get_browser_state();
         let state = binding.read().expect("Failed to get browser state");
-        let el_id = state
-            .tile_list
+        let Some(ref tile_list) = state.tile_list else {
+            return;
+        };
+        let el_id = tile_list
             .read()
             .unwrap()
+            .display_list
             .layer_list
-            .find_element_at(x, y)
-            .clone();
-        let che = state.current_hovered_element.clone();
-
-        let mut tile_ids = vec![];
-        match (che, el_id) {
-            (Some(current_id), Some(new_id)) if current_id != new_id => {
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(current_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(new_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-            }
-            (None, Some(new_id)) => {
-                let mut tile_ids = vec![];
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(new_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-            }
-            (Some(current_id), None) => {
-                let mut tile_ids = vec![];
-                state
-                    .tile_list
-                    .read()
-                    .unwrap()
-                    .get_tiles_for_element(current_id)
-                    .iter()
-                    .for_each(|tile_id| {
-                        tile_ids.push(*tile_id);
-                    });
-            }
-            _ => {}
-        }
+            .find_element_at(x, y);
         drop(state);
 
         let mut state = binding.write().expect("Failed to get browser state");
-        if state.current_hovered_element != el_id {
-            if el_id.is_some() {
-                let binding = state.tile_list.read().unwrap();
-                let layout_element = binding
-                    .layer_list
-                    .layout_tree
-                    .get_node_by_id(el_id.unwrap())
-                    .unwrap();
-                println!("Hovered element id:");
-                println!("   Layout ID : {:?}", el_id);
-                println!("   DOM ID    : {:?}", layout_element.dom_node_id);
-                drop(binding);
-            }
+        if state.current_hovered_element == el_id {
+            return;
+        }
+        let previous_id = state.current_hovered_element;
+        state.current_hovered_element = el_id;
+        // The painter needs to read the browser state, so we cannot keep the write lock
+        drop(state);
 
-            for tile_id in &tile_ids {
-                // It's ok when we have double tiles in the list. We just set the tile to dirty again.
-                state.tile_list.write().unwrap().invalidate_tile(*tile_id);
-            }
+        let state = binding.read().expect("Failed to get browser state");
+        let Some(ref tile_list) = state.tile_list else {
+            return;
+        };
+        if el_id.is_some() {
+            let binding = tile_list.read().unwrap();
+            let layout_element = binding
+                .display_list
+                .layer_list
+                .layout_tree
+                .get_node_by_id(el_id.unwrap())
+                .unwrap();
+            println!("Hovered element id:");
+            println!("   Layout ID : {:?}", el_id);
+            println!("   DOM ID    : {:?}", layout_element.dom_node_id);
+            drop(binding);
+        }
 
-            state.current_hovered_element = el_id;
-            area_clone.queue_draw();
+        // Repaint the previously and currently hovered elements. This will also invalidate their tiles.
+        let mut tile_list = tile_list.write().unwrap();
+        if let Some(previous_id) = previous_id {
+            tile_list.repaint_element(previous_id);
         }
+        if let Some(new_id) = el_id {
+            tile_list.repaint_element(new_id);
+        }
+
+        area_clone.queue_draw();
     });
     area.add_controller(motion_controller);
 
@@ -261,6 +281,22 @@ fn build_ui(app: &Application) {
 
     connect_viewport_signals(&scroll, &area);
 
+    // Images are selected again when the window moves to a monitor with another scale factor
+    window.connect_scale_factor_notify(clone!(
+        #[weak]
+        area,
+        move |window| {
+            let binding = get_browser_state();
+            let mut state = binding.write().expect("Failed to get browser state");
+            state.device_pixel_ratio = window.scale_factor() as f64;
+            drop(state);
+
+            reflow();
+            resize_area(&area);
+            area.queue_draw();
+        }
+    ));
+
     // Add keyboard shortcuts to trigger some of the rendering options
     let controller = gtk4::EventControllerKey::new();
     controller.connect_key_pressed(move |_controller, keyval, _keycode, _state| {
@@ -316,21 +352,36 @@ fn build_ui(app: &Application) {
                     WireframeState::Only => state.wireframed = WireframeState::Both,
                     WireframeState::Both => state.wireframed = WireframeState::None,
                 }
-                state
-                    .tile_list
+                // The painter needs to read the browser state, so we cannot keep the write lock
+                drop(state);
+
+                let state = binding.read().expect("Failed to get browser state");
+                let Some(ref tile_list) = state.tile_list else {
+                    log::error!("No tile list found");
+                    return glib::Propagation::Proceed;
+                };
+
+                tile_list
                     .write()
                     .expect("Failed to get tile list")
-                    .invalidate_all();
+                    .repaint_all();
                 area.queue_draw();
             }
             // toggle displaying only the hovered element
             key if key == gtk4::gdk::Key::d => {
                 state.debug_hover = !state.debug_hover;
-                state
-                    .tile_list
+                drop(state);
+
+                let state = binding.read().expect("Failed to get browser state");
+                let Some(ref tile_list) = state.tile_list else {
+                    log::error!("No tile list found");
+                    return glib::Propagation::Proceed;
+                };
+
+                tile_list
                     .write()
                     .expect("Failed to get tile list")
-                    .invalidate_all();
+                    .repaint_all();
                 area.queue_draw();
             }
             // toggle tile grid
@@ -338,6 +389,19 @@ fn build_ui(app: &Application) {
                 state.show_tilegrid = !state.show_tilegrid;
                 area.queue_draw();
             }
+            // save display list
+            key if key == gtk4::gdk::Key::s => {
+                let Some(ref tile_list) = state.tile_list else {
+                    log::error!("No tile list found");
+                    return glib::Propagation::Proceed;
+                };
+
+                let tile_list = tile_list.read().expect("Failed to get tile list");
+                match tile_list.display_list.save(Path::new(DISPLAY_LIST_DIR)) {
+                    Ok(path) => log::info!("Display list saved to {}", path.display()),
+                    Err(e) => log::error!("Failed to save display list: {}", e),
+                }
+            }
             _ => (),
         }
 
@@ -354,17 +418,19 @@ fn do_paint(layer_id: LayerId) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
-    let painter = Painter::new(state.tile_list.read().unwrap().layer_list.clone());
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return;
+    };
 
-    let tile_ids = state
-        .tile_list
+    let tile_ids = tile_list
         .read()
         .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
+        .get_visible_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
         // get tile
-        let mut binding = state.tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
+        let mut binding = tile_list.write().expect("Failed to get tile list");
+        let Some(tile) = binding.get_tile(tile_id) else {
             log::warn!("Tile not found: {:?}", tile_id);
             continue;
         };
@@ -374,10 +440,8 @@ fn do_paint(layer_id: LayerId) {
             continue;
         }
 
-        // Paint all the elements in each tile
-        for tiled_layout_element in &mut tile.elements {
-            tiled_layout_element.paint_commands = painter.paint(tiled_layout_element);
-        }
+        // Select the paint commands for all the elements in the tile from the display list
+        binding.paint_tile(tile_id);
     }
 }
 
@@ -385,14 +449,18 @@ fn do_rasterize(layer_id: LayerId) {
     let binding = get_browser_state();
     let state = binding.read().unwrap();
 
-    let tile_ids = state
-        .tile_list
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return;
+    };
+
+    let tile_ids = tile_list
         .read()
         .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
+        .get_visible_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
         // get tile
-        let mut binding = state.tile_list.write().expect("Failed to get tile list");
+        let mut binding = tile_list.write().expect("Failed to get tile list");
         let Some(tile) = binding.get_tile(tile_id) else {
             log::warn!("Tile not found: {:?}", tile_id);
             continue;
@@ -473,16 +541,15 @@ fn on_viewport_changed(area: &DrawingArea, hadj: &Adjustment, vadj: &Adjustment)
     let binding = get_browser_state();
     let mut state = binding.write().expect("Failed to get browser state");
 
-    // If we changed the viewport size, we need to invalidate all tiles
-    if width != state.viewport.width || height != state.viewport.height {
-        state
-            .tile_list
-            .write()
-            .expect("Failed to get tile list")
-            .invalidate_all();
-    }
-
+    let resized = width != state.viewport.width || height != state.viewport.height;
     state.viewport = Rect::new(x, y, width, height);
+    drop(state);
+
+    // If we changed the viewport size, the document is laid out again for the new size, which generates new tiles
+    if resized {
+        reflow();
+        resize_area(area);
+    }
 
     area.queue_draw();
 }
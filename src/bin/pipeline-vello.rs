@@ -8,16 +8,17 @@ use poc_pipeline::common::browser_state::{
 use poc_pipeline::common::geo::{Dimension, Rect};
 use poc_pipeline::compositor::vello::{VelloCompositor, VelloCompositorConfig};
 use poc_pipeline::compositor::Composable;
+use poc_pipeline::display_list::DisplayList;
 use poc_pipeline::layering::layer::{LayerId, LayerList};
-use poc_pipeline::layouter::taffy::TaffyLayouter;
-use poc_pipeline::layouter::CanLayout;
-use poc_pipeline::painter::Painter;
+use poc_pipeline::layouter::text;
+use poc_pipeline::layouter::{layouter_by_name, Viewport, LAYOUTER_NAMES};
 use poc_pipeline::rasterizer::vello::VelloRasterizer;
 use poc_pipeline::rasterizer::Rasterable;
 use poc_pipeline::rendertree_builder::RenderTree;
 use poc_pipeline::tiler::{TileList, TileState};
 use std::cell::RefCell;
 use std::fmt::Formatter;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::time::Instant;
@@ -33,12 +34,39 @@ use winit::keyboard::PhysicalKey::Code;
 use winit::window::{Window, WindowId};
 
 const TILE_DIMENSION: f64 = 256.0;
+/// Directory where the display list is saved to (press "s")
+const DISPLAY_LIST_DIR: &str = "display-list";
+
+/// Command line help. The layouter lays out the document again when the window is resized.
+const USAGE: &str = "Usage: pipeline-vello [--layouter <name>]
+
+  --layouter <name>  Layouter to use: taffy (default) or flow. Only taffy lays out incrementally when the
+                     window is resized, flow lays out the whole document again.";
 
 fn main() {
     // --------------------------------------------------------------------
     // Generate a DOM tree
     let doc = common::document::parser::document_from_json("https://codemusings.nl", "cm.json");
 
+    let mut layouter_name = LAYOUTER_NAMES[0].to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layouter" => layouter_name = args.next().unwrap_or(layouter_name),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+    let layouter = match layouter_by_name(&layouter_name, text::default_shaper()) {
+        Ok(layouter) => layouter,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let window_dimension = Dimension::new(800.0, 600.0);
     let viewport_dimension = Dimension::new(1024.0, 768.0);
 
@@ -54,7 +82,10 @@ fn main() {
             viewport_dimension.width,
             viewport_dimension.height,
         ),
+        // The viewport and scale factor of the window are set when the window is created
+        device_pixel_ratio: 1.0,
         document: Arc::new(doc),
+        layouter: Some(layouter),
         tile_list: None,
     };
     init_browser_state(browser_state);
@@ -66,30 +97,47 @@ fn main() {
     let _ = event_loop.run_app(&mut app);
 }
 
+/// Lays out the document for the viewport. The document is laid out once, after that the layouter only computes
+/// the layout again for the new viewport. The display list and tiles are only updated for the elements that
+/// the layouter has changed.
 fn reflow() {
     let binding = get_browser_state();
-    let state = binding.read().unwrap();
+    let mut state = binding.write().unwrap();
 
     println!("reflowing to dimension: {:?}", state.viewport);
 
-    let mut render_tree = RenderTree::new(state.document.clone());
-    render_tree.parse();
-
-    let mut layouter = TaffyLayouter::new();
-    let layout_tree = layouter.layout(
-        render_tree,
-        Some(Dimension::new(state.viewport.width, state.viewport.height)),
-    );
-
-    let layer_list = LayerList::new(layout_tree);
-
-    let mut tile_list = TileList::new(layer_list, Dimension::new(TILE_DIMENSION, TILE_DIMENSION));
-    tile_list.generate();
-
+    let document = state.document.clone();
+    let viewport = Viewport::new(Some(Dimension::new(state.viewport.width, state.viewport.height)), state.device_pixel_ratio);
+    let Some(mut layouter) = state.layouter.take() else {
+        log::error!("No layouter found");
+        return;
+    };
+    let tile_list = state.tile_list.take().map(|tile_list| tile_list.into_inner().expect("Failed to get tile list"));
+    // The painter needs the browser state, so don't hold on to it
     drop(state);
 
+    // Only the elements that the layouter has changed are painted and tiled again
+    let tile_list = tile_list.and_then(|mut tile_list| {
+        let changed = layouter.relayout(tile_list.layout_tree_mut()?, viewport);
+        tile_list.relayout(&changed);
+        Some(tile_list)
+    });
+    let tile_list = tile_list.unwrap_or_else(|| {
+        let mut render_tree = RenderTree::new(document);
+        render_tree.parse();
+        let layout_tree = layouter.layout(render_tree, viewport);
+
+        let layer_list = LayerList::new(layout_tree);
+        let display_list = DisplayList::new(layer_list);
+
+        let mut tile_list = TileList::new(display_list, Dimension::new(TILE_DIMENSION, TILE_DIMENSION));
+        tile_list.generate();
+        tile_list
+    });
+
     let binding = get_browser_state();
     let mut state = binding.write().unwrap();
+    state.layouter = Some(layouter);
     state.tile_list = Some(RwLock::new(tile_list));
 }
 
@@ -143,6 +191,19 @@ impl ApplicationHandler for App<'_> {
             self.window_title.as_str(),
             self.window_size
         ));
+
+        // Lay out the document for the window that has been created
+        if let Some(window) = self.env.as_ref().and_then(|env| env.window.as_ref()) {
+            let size = window.inner_size();
+
+            let binding = get_browser_state();
+            let mut state = binding.write().unwrap();
+            state.viewport = Rect::new(0.0, 0.0, size.width as f64, size.height as f64);
+            state.device_pixel_ratio = window.scale_factor();
+            drop(state);
+
+            reflow();
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
@@ -170,6 +231,17 @@ impl ApplicationHandler for App<'_> {
 
                 reflow();
             }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                println!("Scale factor changed to {}", scale_factor);
+
+                // Images are selected again for the new scale factor
+                let binding = get_browser_state();
+                let mut state = binding.write().unwrap();
+                state.device_pixel_ratio = scale_factor;
+                drop(state);
+
+                reflow();
+            }
             WindowEvent::RedrawRequested => {
                 self.frame += 1;
                 self.pfs = Instant::now();
@@ -181,18 +253,18 @@ impl ApplicationHandler for App<'_> {
 
                 let binding = get_browser_state();
                 let state = binding.read().unwrap();
-                let vis_layers = state.visible_layer_list.clone();
+                let layer_ids = match state.tile_list {
+                    Some(ref tile_list) => tile_list.read().unwrap().display_list.layer_list.composited_layers(&state.visible_layer_list),
+                    None => Vec::new(),
+                };
                 drop(state);
 
                 let renderer = &mut env.renderer.as_mut().unwrap();
 
-                if vis_layers[0] {
-                    do_paint(LayerId::new(0));
-                    do_rasterize(device, queue, renderer.clone(), LayerId::new(0));
-                }
-                if vis_layers[1] {
-                    do_paint(LayerId::new(1));
-                    do_rasterize(device, queue, renderer.clone(), LayerId::new(1));
+                // Paint and rasterize the layers that are composited
+                for layer_id in layer_ids {
+                    do_paint(layer_id);
+                    do_rasterize(device, queue, renderer.clone(), layer_id);
                 }
 
                 let surface_texture = surface
@@ -274,7 +346,10 @@ impl ApplicationHandler for App<'_> {
                         WireframeState::Only => state.wireframed = WireframeState::Both,
                         WireframeState::Both => state.wireframed = WireframeState::None,
                     }
+                    // The painter needs to read the browser state, so we cannot keep the write lock
+                    drop(state);
 
+                    let state = binding.read().unwrap();
                     let Some(ref tile_list) = state.tile_list else {
                         log::error!("No tile list found");
                         return;
@@ -283,7 +358,7 @@ impl ApplicationHandler for App<'_> {
                     tile_list
                         .write()
                         .expect("Failed to get tile list")
-                        .invalidate_all();
+                        .repaint_all();
                     window.request_redraw();
                 }
 
@@ -303,6 +378,22 @@ impl ApplicationHandler for App<'_> {
                     window.request_redraw();
                 }
 
+                if logical_key == "s" {
+                    let binding = get_browser_state();
+                    let state = binding.read().unwrap();
+
+                    let Some(ref tile_list) = state.tile_list else {
+                        log::error!("No tile list found");
+                        return;
+                    };
+
+                    let tile_list = tile_list.read().expect("Failed to get tile list");
+                    match tile_list.display_list.save(Path::new(DISPLAY_LIST_DIR)) {
+                        Ok(path) => log::info!("Display list saved to {}", path.display()),
+                        Err(e) => log::error!("Failed to save display list: {}", e),
+                    }
+                }
+
             }
             _ => (),
         }
@@ -365,16 +456,14 @@ fn do_paint(layer_id: LayerId) {
         return;
     };
 
-    let painter = Painter::new(tile_list.read().unwrap().layer_list.clone());
-
     let tile_ids = tile_list
         .read()
         .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
+        .get_visible_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
         // get tile
         let mut binding = tile_list.write().expect("Failed to get tile list");
-        let Some(tile) = binding.get_tile_mut(tile_id) else {
+        let Some(tile) = binding.get_tile(tile_id) else {
             log::warn!("Tile not found: {:?}", tile_id);
             continue;
         };
@@ -384,10 +473,8 @@ fn do_paint(layer_id: LayerId) {
             continue;
         }
 
-        // Paint all the elements in each tile
-        for tiled_layout_element in &mut tile.elements {
-            tiled_layout_element.paint_commands = painter.paint(tiled_layout_element);
-        }
+        // Select the paint commands for all the elements in the tile from the display list
+        binding.paint_tile(tile_id);
     }
 }
 
@@ -408,7 +495,7 @@ fn do_rasterize(
     let tile_ids = tile_list
         .read()
         .unwrap()
-        .get_intersecting_tiles(layer_id, state.viewport);
+        .get_visible_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
         // get til
         let mut binding = tile_list.write().expect("Failed to get tile list");
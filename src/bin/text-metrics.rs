@@ -0,0 +1,100 @@
+use poc_pipeline::common::font::{shaped_text_cache_stats, FontDescriptor};
+use poc_pipeline::layouter::text::{available_shapers, shaper_by_name, Alignment, TextDirection, TextStyle};
+
+// Measures and line-breaks text with all the text shapers that are compiled in (or only the given ones),
+// so their metrics can be compared side by side.
+//
+// Usage: text-metrics [--width <pixels>] [--font <family>] [--size <pixels>] [shaper ...] [-- <text>]
+
+const DEFAULT_TEXT: &str = "The quick brown fox jumps over the lazy dog. Pack my box with five dozen liquor jugs.";
+
+fn main() {
+    let mut width = 200.0;
+    let mut font_family = "sans-serif".to_string();
+    let mut font_size = 16.0;
+    let mut names = Vec::new();
+    let mut text = DEFAULT_TEXT.to_string();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => width = parse_number(args.next()),
+            "--size" => font_size = parse_number(args.next()),
+            "--font" => font_family = args.next().unwrap_or(font_family),
+            "--" => {
+                text = args.collect::<Vec<_>>().join(" ");
+                break;
+            }
+            _ => names.push(arg),
+        }
+    }
+
+    let shapers = if names.is_empty() {
+        available_shapers()
+    } else {
+        names.iter().map(|name| match shaper_by_name(name) {
+            Some(shaper) => shaper,
+            None => {
+                eprintln!("Text shaper '{}' is not compiled in", name);
+                std::process::exit(1);
+            }
+        }).collect()
+    };
+
+    let style = TextStyle {
+        font: FontDescriptor::new(&font_family, font_size),
+        line_height: font_size * 1.2,
+        alignment: Alignment::Start,
+        direction: TextDirection::Ltr,
+    };
+
+    println!("Text: {:?}", text);
+    println!("Style: {:?}, max width: {}", style, width);
+
+    for shaper in shapers {
+        println!();
+        println!("[{}]", shaper.name());
+
+        match shaper.measure(&text, &style, width) {
+            Ok(dimension) => println!("  dimension: {:.2} x {:.2}", dimension.width, dimension.height),
+            Err(e) => println!("  failed to measure: {}", e),
+        }
+
+        match shaper.line_break(&text, &style, width) {
+            Ok(lines) => {
+                for line in lines {
+                    println!(
+                        "  line {:?} x: {:.2} width: {:.2} baseline: {:.2} height: {:.2} {:?}",
+                        line.range, line.x, line.width, line.baseline, line.height,
+                        text.get(line.range.clone()).unwrap_or_default(),
+                    );
+                }
+            }
+            Err(e) => println!("  failed to break lines: {}", e),
+        }
+
+        match shaper.shape(&text, &style, width) {
+            Ok(runs) => {
+                for run in runs {
+                    println!("  run: {} {:.2}px, {} glyphs", run.font_family, run.font_size, run.glyphs.len());
+                }
+            }
+            Err(e) => println!("  failed to shape: {}", e),
+        }
+    }
+
+    println!();
+    for (name, stats) in shaped_text_cache_stats() {
+        println!("[{} cache, main thread] hits: {} misses: {} entries: {}", name, stats.hits, stats.misses, stats.entries);
+    }
+}
+
+fn parse_number(arg: Option<String>) -> f64 {
+    match arg.as_deref().map(str::parse::<f64>) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("Expected a number");
+            std::process::exit(1);
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::Debug;
 use std::sync::{Arc, OnceLock, RwLock};
 use crate::common::document::document::Document;
 use crate::common::geo::Rect;
-use crate::layouter::LayoutElementId;
+use crate::layouter::{CanLayout, LayoutElementId};
 use crate::tiler::TileList;
 
 #[derive(Debug)]
@@ -27,9 +27,14 @@ pub struct BrowserState {
     pub current_hovered_element: Option<LayoutElementId>,
     /// Current viewport offset + size
     pub viewport: Rect,
+    /// Number of device pixels per CSS pixel, which selects the source of responsive images
+    pub device_pixel_ratio: f64,
     /// Main document that is currently being rendered
     pub document: Arc<Document>,
-    /// LayerList that is currently being rendered
+    /// Layouter that has laid out the document, which lays it out again when the viewport changes
+    pub layouter: Option<Box<dyn CanLayout>>,
+    /// TileList that is currently being rendered. It holds the display list, layer list and layout tree of the
+    /// document, which are updated when the layouter lays out the document again.
     pub tile_list: Option<RwLock<TileList>>,
 }
 
@@ -42,6 +47,7 @@ impl Debug for BrowserState {
             .field("show_tilegrid", &self.show_tilegrid)
             .field("current_hovered_element", &self.current_hovered_element)
             .field("viewport", &self.viewport)
+            .field("device_pixel_ratio", &self.device_pixel_ratio)
             .finish()
     }
 }
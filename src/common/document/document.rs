@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use crate::common::document::node::{Node, NodeType, NodeId, AttrMap};
 use crate::common::document::style::StylePropertyList;
+use crate::common::font::face::FontFaceRule;
+use crate::common::geo::Rect;
 
 /// Main DOM document structure
 #[derive(Clone)]
@@ -11,6 +13,11 @@ pub struct Document {
     pub root_id: Option<NodeId>,
 
     pub base_url: String,
+    /// Font faces as declared by the @font-face rules of the document
+    pub font_faces: Vec<FontFaceRule>,
+    /// Border boxes of the elements as laid out by the browser the document was exported from (see tools/souper).
+    /// These are used as reference for the layout conformance tests.
+    pub reference_boxes: HashMap<NodeId, Rect>,
 }
 
 impl Document {
@@ -20,6 +27,8 @@ impl Document {
             root_id: None,
             next_node_id: Arc::new(RwLock::new(NodeId::new(1))),
             base_url: base_url.to_string(),
+            font_faces: Vec::new(),
+            reference_boxes: HashMap::new(),
         }
     }
 
@@ -86,6 +95,10 @@ impl Document {
         self.arena.get(&node_id)
     }
 
+    pub fn get_node_by_id_mut(&mut self, node_id: NodeId) -> Option<&mut Node> {
+        self.arena.get_mut(&node_id)
+    }
+
     pub fn set_root(&mut self, root_id: NodeId) {
         self.root_id = Some(root_id);
     }
@@ -176,31 +189,34 @@ impl Document {
 
 #[cfg(test)]
 mod tests {
-    use crate::common::document;
+    use super::*;
 
     #[test]
     fn test_walk_depth_first() {
-        let document = document::create_document();
+        let mut document = Document::new("https://example.com");
+        let html_id = document.new_element(None, "html", None, false, None);
+        let body_id = document.new_element(Some(html_id), "body", None, false, None);
+        document.add_child(html_id, body_id);
+        let p_id = document.new_element(Some(body_id), "p", None, false, None);
+        document.add_child(body_id, p_id);
+        let text_id = document.new_text(Some(p_id), "paragraph", None);
+        document.add_child(p_id, text_id);
+        let img_id = document.new_element(Some(p_id), "img", None, true, None);
+        document.add_child(p_id, img_id);
+        document.set_root(html_id);
 
         let mut s = String::new();
         let _ = document.print_tree(&mut s);
 
-        println!("{}", s);
-        let result = r#"(NodeID(10)) <html lang="en"/>
-    (NodeID(9)) <body />
-        (NodeID(6)) <h1 class="title" data-alpine="x-wrap"/>
-            (NodeID(7)) header
-        </h1>
-        (NodeID(8)) <script async="true" src="script.js" type="text/javascript">
-        (NodeID(4)) <p class="paragraph"/>
-            (NodeID(2)) <strong />
-                (NodeID(3)) strong
-            </strong>
-            (NodeID(1)) <img alt="image" src="image.jpg">
+        let result = r#"(NodeID(1)) <html >
+    (NodeID(2)) <body >
+        (NodeID(3)) <p >
+            (NodeID(4)) 'paragraph'
+            (NodeID(5)) <img />
         </p>
     </body>
 </html>
 "#;
         assert_eq!(result, s);
     }
-}
\ No newline at end of file
+}
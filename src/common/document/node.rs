@@ -2,8 +2,7 @@ use std::cmp::PartialEq;
 use std::collections::HashMap;
 use std::ops::AddAssign;
 use crate::common::document::document::Document;
-use crate::common::document::style::{StylePropertyList, StyleValue, StyleProperty, Display};
-use crate::rendertree_builder::RenderNodeId;
+use crate::common::document::style::{StylePropertyList, StyleValue, StyleProperty, Display, PseudoElement};
 
 /// Map of attributes for a html element (a href, src, data-*, etc)
 #[derive(Debug, Clone)]
@@ -60,6 +59,8 @@ pub struct ElementData {
     pub self_closing: bool,
     /// Element styles (color, font-size etc)
     pub styles: StylePropertyList,
+    /// Styles of the pseudo-elements of the element (::before, ::after and ::marker)
+    pub pseudo_styles: HashMap<PseudoElement, StylePropertyList>,
 }
 
 impl ElementData {
@@ -69,6 +70,7 @@ impl ElementData {
             attributes: attributes.unwrap_or(AttrMap::new()),
             self_closing: is_self_closing,
             styles: styles.unwrap_or(StylePropertyList::new()),
+            pseudo_styles: HashMap::new(),
         }
     }
 
@@ -76,6 +78,11 @@ impl ElementData {
         self.styles.properties.get(&key)
     }
 
+    /// Returns the styles of the given pseudo-element, if the element has it
+    pub fn get_pseudo_style(&self, pseudo: PseudoElement) -> Option<&StylePropertyList> {
+        self.pseudo_styles.get(&pseudo)
+    }
+
     #[allow(unused)]
     pub fn get_attribute(&self, key: &str) -> Option<&String> {
         self.attributes.get(key)
@@ -134,13 +141,6 @@ impl NodeId {
     }
 }
 
-/// RenderNodeId and NodeId are interchangeable. This is a convenience function to convert between the two.
-impl From<RenderNodeId> for NodeId {
-    fn from(node_id: RenderNodeId) -> Self {
-        Self(node_id.to_u64())
-    }
-}
-
 impl AddAssign<i32> for NodeId {
     fn add_assign(&mut self, rhs: i32) {
         self.0 += rhs as u64;
@@ -169,7 +169,7 @@ impl Node {
             NodeType::Element(data) => {
                 match data.get_style(StyleProperty::Display) {
                     Some(StyleValue::Display(display)) => {
-                        *display == Display::Block
+                        *display == Display::Block || *display == Display::ListItem
                     }
                     _ => false,
                 }
@@ -184,7 +184,7 @@ impl Node {
             NodeType::Element(data) => {
                 match data.get_style(StyleProperty::Display) {
                     Some(StyleValue::Display(display)) => {
-                        *display == Display::Inline || *display == Display::InlineBlock
+                        matches!(display, Display::Inline | Display::InlineBlock | Display::InlineGrid | Display::InlineTable)
                     }
                     _ => false,
                 }
@@ -1,10 +1,12 @@
 use crate::common::document::style::TextAlign;
-use regex::Regex;
 use serde::Deserialize;
 use std::collections::HashMap;
 use crate::common::document::document::Document;
 use crate::common::document::node::{AttrMap, NodeId, NodeType};
-use crate::common::document::style::{Color, Display, FontWeight, StyleProperty, StylePropertyList, StyleValue, TextWrap, Unit};
+use crate::common::font::face::{FontFaceRule, FontFaceSource};
+use crate::common::font::FontSlant;
+use crate::common::geo::Rect;
+use crate::common::document::style::{Color, Display, FontStyle, FontWeight, StyleProperty, StylePropertyList, StyleValue, TextDecorationLine, TextDecorationStyle, TextWrap, Unit, WhiteSpace, WordBreak, OverflowWrap, Hyphens, Direction, UnicodeBidi, WritingMode, TextOrientation, ListStyleType, ListStylePosition, ContentItem, PseudoElement, BorderCollapse, CaptionSide, TableLayout, GridLine, GridPlacement, GridRepetition, GridTrack, GridTrackList, TrackSize, Float, Clear, ObjectFit};
 // This parses uses the tools/souper.py to load a JSON file and create a DOM from it. This allows us to render
 // a webpage with minimal effort, and without connecting a whole html5 and css parser to it.
 
@@ -25,6 +27,20 @@ struct DomNode {
     styles: HashMap<String, String>,
     #[serde(default)]
     children: Vec<DomNode>,
+    /// Computed styles of the pseudo-elements (::before, ::after and ::marker) of the element
+    #[serde(default)]
+    pseudo_elements: HashMap<String, HashMap<String, String>>,
+    /// Border box of the element as laid out by the browser
+    #[serde(default)]
+    rect: Option<DomRect>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
 }
 
 #[allow(unused)]
@@ -36,15 +52,30 @@ struct DomRoot {
     #[serde(default)]
     styles: HashMap<String, String>,
     children: Vec<DomNode>,
+    #[serde(default)]
+    font_faces: Vec<DomFontFace>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomFontFaceSource {
+    url: String,
+    #[serde(default)]
+    format: Option<String>,
 }
 
-// Text is "as-is" from the JSON, but we don't want text with multiple spaces and newlines.
-fn clean_text(input: &str) -> String {
-    let no_newlines = input.replace('\n', " ");
-    let space_regex = Regex::new(r"\s{2,}").unwrap();
-    space_regex.replace_all(&no_newlines, " ").to_string()
+/// Font face as extracted from the @font-face rules of the stylesheets
+#[derive(Debug, Deserialize)]
+struct DomFontFace {
+    family: String,
+    src: Vec<DomFontFaceSource>,
+    #[serde(default)]
+    weight: Option<String>,
+    #[serde(default)]
+    style: Option<String>,
 }
 
+/// Creates the DOM node (and its children) from the JSON node. Text is kept as-is, its white space is processed
+/// at layout time.
 fn create_dom_from_json(doc: &mut Document, node: &DomNode, parent_id: Option<NodeId>) -> Option<NodeId> {
     let mut attrs = AttrMap::new();
     for (key, value) in &node.attributes {
@@ -58,7 +89,8 @@ fn create_dom_from_json(doc: &mut Document, node: &DomNode, parent_id: Option<No
             NodeType::Element(parent_element) => Some(parent_element.styles.clone()),
             _ => None,
         };
-        return Some(doc.new_text(parent_id, clean_text(text).as_str(), parent_styles));
+        // White space is kept as-is. It will be collapsed during layout, depending on the white-space property.
+        return Some(doc.new_text(parent_id, text.as_str(), parent_styles));
     }
 
     if let Some(comment) = &node.comment {
@@ -71,9 +103,25 @@ fn create_dom_from_json(doc: &mut Document, node: &DomNode, parent_id: Option<No
     };
 
 
-    let style = get_style_from_node(node);
+    let style = get_style_from_node(&node.styles);
     let node_id = doc.new_element(parent_id, &tag, Some(attrs), node.self_closing, Some(style.clone()));
 
+    if let Some(NodeType::Element(data)) = doc.get_node_by_id_mut(node_id).map(|node| &mut node.node_type) {
+        for (name, styles) in &node.pseudo_elements {
+            let pseudo = match name.as_str() {
+                "::before" => PseudoElement::Before,
+                "::after" => PseudoElement::After,
+                "::marker" => PseudoElement::Marker,
+                _ => continue,
+            };
+            data.pseudo_styles.insert(pseudo, get_style_from_node(styles));
+        }
+    }
+
+    if let Some(rect) = &node.rect {
+        doc.reference_boxes.insert(node_id, Rect::new(rect.x, rect.y, rect.width, rect.height));
+    }
+
     // if node_id.is_greater_than(24) {
     //     return None
     // }
@@ -88,10 +136,10 @@ fn create_dom_from_json(doc: &mut Document, node: &DomNode, parent_id: Option<No
     Some(node_id)
 }
 
-fn get_style_from_node(node: &DomNode) -> StylePropertyList {
+fn get_style_from_node(styles: &HashMap<String, String>) -> StylePropertyList {
     let mut style = StylePropertyList::new();
 
-    for (key, value) in &node.styles {
+    for (key, value) in styles {
         match key.as_str() {
             "display" => style.set_property(StyleProperty::Display, parse_display(value)),
             "position" => style.set_property(StyleProperty::Position, parse_position(value)),
@@ -126,6 +174,8 @@ fn get_style_from_node(node: &DomNode) -> StylePropertyList {
             "background-color" => style.set_property(StyleProperty::BackgroundColor, StyleValue::Color(Color::Named(value.to_string()))),
 
             "font-weight" => style.set_property(StyleProperty::FontWeight, parse_font_weight(value)),
+            "font-style" => style.set_property(StyleProperty::FontStyle, parse_font_style(value)),
+            "font-stretch" => style.set_property(StyleProperty::FontStretch, parse_font_stretch(value)),
             "font-size" => style.set_property(StyleProperty::FontSize, parse_style_value(value)),
             "font-family" => style.set_property(StyleProperty::FontFamily, StyleValue::Keyword(value.to_string())),
 
@@ -141,8 +191,30 @@ fn get_style_from_node(node: &DomNode) -> StylePropertyList {
             "align-self" => style.set_property(StyleProperty::AlignSelf, parse_style_str(value)),
             "align-content" => style.set_property(StyleProperty::AlignContent, parse_style_str(value)),
             "text-align" => style.set_property(StyleProperty::TextAlign, parse_text_align(value)),
+            "text-align-last" => style.set_property(StyleProperty::TextAlignLast, parse_text_align_last(value)),
+            "direction" => style.set_property(StyleProperty::Direction, parse_direction(value)),
+            "unicode-bidi" => style.set_property(StyleProperty::UnicodeBidi, parse_unicode_bidi(value)),
+            "writing-mode" => style.set_property(StyleProperty::WritingMode, parse_writing_mode(value)),
+            "text-orientation" => style.set_property(StyleProperty::TextOrientation, parse_text_orientation(value)),
+            "list-style-type" => style.set_property(StyleProperty::ListStyleType, parse_list_style_type(value)),
+            "list-style-position" => style.set_property(StyleProperty::ListStylePosition, parse_list_style_position(value)),
+            "counter-reset" => style.set_property(StyleProperty::CounterReset, parse_counters(value, 0)),
+            "counter-increment" => style.set_property(StyleProperty::CounterIncrement, parse_counters(value, 1)),
+            "content" => style.set_property(StyleProperty::Content, parse_content(value)),
+            "border-collapse" => style.set_property(StyleProperty::BorderCollapse, parse_border_collapse(value)),
+            "border-spacing" => style.set_property(StyleProperty::BorderSpacing, parse_border_spacing(value)),
+            "caption-side" => style.set_property(StyleProperty::CaptionSide, parse_caption_side(value)),
+            "table-layout" => style.set_property(StyleProperty::TableLayout, parse_table_layout(value)),
             "line-height" => style.set_property(StyleProperty::LineHeight, parse_style_value(value)),
             "text-wrap" => style.set_property(StyleProperty::TextWrap, parse_text_wrap(value)),
+            "white-space" => style.set_property(StyleProperty::WhiteSpace, parse_white_space(value)),
+            "word-break" => style.set_property(StyleProperty::WordBreak, parse_word_break(value)),
+            "overflow-wrap" | "word-wrap" => style.set_property(StyleProperty::OverflowWrap, parse_overflow_wrap(value)),
+            "hyphens" | "-webkit-hyphens" => style.set_property(StyleProperty::Hyphens, parse_hyphens(value)),
+            "text-decoration-line" => style.set_property(StyleProperty::TextDecorationLine, parse_text_decoration_line(value)),
+            "text-decoration-style" => style.set_property(StyleProperty::TextDecorationStyle, parse_text_decoration_style(value)),
+            "text-decoration-color" => style.set_property(StyleProperty::TextDecorationColor, StyleValue::Color(Color::Named(value.to_string()))),
+            "text-decoration-thickness" => style.set_property(StyleProperty::TextDecorationThickness, parse_style_value(value)),
 
             "inset-block-end" => style.set_property(StyleProperty::InsetBlockEnd, parse_style_value(value)),
             "inset-block-start" => style.set_property(StyleProperty::InsetBlockStart, parse_style_value(value)),
@@ -156,14 +228,584 @@ fn get_style_from_node(node: &DomNode) -> StylePropertyList {
             "overflow-x" => style.set_property(StyleProperty::OverflowX, parse_style_str(value)),
             "overflow-y" => style.set_property(StyleProperty::OverflowY, parse_style_str(value)),
             "box-sizing" => style.set_property(StyleProperty::BoxSizing, parse_style_str(value)),
+            "float" => style.set_property(StyleProperty::Float, parse_float(value)),
+            "clear" => style.set_property(StyleProperty::Clear, parse_clear(value)),
+            "object-fit" => style.set_property(StyleProperty::ObjectFit, parse_object_fit(value)),
+            "object-position" => style.set_property(StyleProperty::ObjectPosition, parse_object_position(value)),
+
+            "grid-template-rows" => style.set_property(StyleProperty::GridTemplateRows, parse_grid_template(value)),
+            "grid-template-columns" => style.set_property(StyleProperty::GridTemplateColumns, parse_grid_template(value)),
+            "grid-template-areas" => style.set_property(StyleProperty::GridTemplateAreas, parse_grid_template_areas(value)),
+            "grid-auto-rows" => style.set_property(StyleProperty::GridAutoRows, parse_grid_auto_tracks(value)),
+            "grid-auto-columns" => style.set_property(StyleProperty::GridAutoColumns, parse_grid_auto_tracks(value)),
+            "grid-auto-flow" => style.set_property(StyleProperty::GridAutoFlow, parse_style_str(value)),
+            "grid-row" => style.set_property(StyleProperty::GridRow, parse_grid_line(value)),
+            "grid-column" => style.set_property(StyleProperty::GridColumn, parse_grid_line(value)),
+            "grid-area" => {
+                let (row, column) = parse_grid_area(value);
+                style.set_property(StyleProperty::GridRow, row);
+                style.set_property(StyleProperty::GridColumn, column);
+            }
 
             _ => {}
         }
     }
 
+    // Newer browsers only report the white-space longhands in the computed style
+    if style.get_property(StyleProperty::WhiteSpace).is_none() {
+        if let Some(collapse) = styles.get("white-space-collapse") {
+            let wrap_mode = styles.get("text-wrap-mode").map(String::as_str).unwrap_or("wrap");
+            style.set_property(StyleProperty::WhiteSpace, parse_white_space_longhands(collapse, wrap_mode));
+        }
+    }
+
+    // Newer browsers report text-wrap through its longhands
+    if style.get_property(StyleProperty::TextWrap).is_none() {
+        match (styles.get("text-wrap-mode").map(String::as_str), styles.get("text-wrap-style").map(String::as_str)) {
+            (Some("nowrap"), _) => style.set_property(StyleProperty::TextWrap, StyleValue::TextWrap(TextWrap::NoWrap)),
+            (_, Some(wrap_style)) => style.set_property(StyleProperty::TextWrap, parse_text_wrap(wrap_style)),
+            _ => {}
+        }
+    }
+
+    // Browsers report the placement of grid items through the longhands
+    for (prop, start, end) in [
+        (StyleProperty::GridRow, "grid-row-start", "grid-row-end"),
+        (StyleProperty::GridColumn, "grid-column-start", "grid-column-end"),
+    ] {
+        if style.get_property(prop.clone()).is_some() || (!styles.contains_key(start) && !styles.contains_key(end)) {
+            continue;
+        }
+        let placement = |key: &str| styles.get(key).map_or(GridPlacement::Auto, |value| parse_grid_placement(value));
+        style.set_property(prop, StyleValue::GridLine(GridLine { start: placement(start), end: placement(end) }));
+    }
+
     style
 }
 
+fn parse_direction(value: &str) -> StyleValue {
+    match value {
+        "rtl" => StyleValue::Direction(Direction::Rtl),
+        _ => StyleValue::Direction(Direction::Ltr),
+    }
+}
+
+fn parse_writing_mode(value: &str) -> StyleValue {
+    match value {
+        // tb-rl and tb are the SVG 1.1 values
+        "vertical-rl" | "sideways-rl" | "tb-rl" | "tb" => StyleValue::WritingMode(WritingMode::VerticalRl),
+        "vertical-lr" | "sideways-lr" => StyleValue::WritingMode(WritingMode::VerticalLr),
+        _ => StyleValue::WritingMode(WritingMode::HorizontalTb),
+    }
+}
+
+fn parse_text_orientation(value: &str) -> StyleValue {
+    match value {
+        "upright" => StyleValue::TextOrientation(TextOrientation::Upright),
+        "sideways" | "sideways-right" => StyleValue::TextOrientation(TextOrientation::Sideways),
+        _ => StyleValue::TextOrientation(TextOrientation::Mixed),
+    }
+}
+
+fn parse_list_style_type(value: &str) -> StyleValue {
+    StyleValue::ListStyleType(to_list_style_type(value))
+}
+
+fn to_list_style_type(value: &str) -> ListStyleType {
+    match value.trim() {
+        "none" => ListStyleType::None,
+        "disc" => ListStyleType::Disc,
+        "circle" => ListStyleType::Circle,
+        "square" => ListStyleType::Square,
+        "decimal" => ListStyleType::Decimal,
+        "decimal-leading-zero" => ListStyleType::DecimalLeadingZero,
+        "lower-alpha" | "lower-latin" => ListStyleType::LowerAlpha,
+        "upper-alpha" | "upper-latin" => ListStyleType::UpperAlpha,
+        "lower-roman" => ListStyleType::LowerRoman,
+        "upper-roman" => ListStyleType::UpperRoman,
+        // Counter styles we do not support (yet) are rendered as decimals
+        _ => ListStyleType::Decimal,
+    }
+}
+
+/// Parses the content property of a pseudo-element. Returns StyleValue::None when the pseudo-element does not
+/// generate a box (none, normal).
+fn parse_content(value: &str) -> StyleValue {
+    let value = value.trim();
+    if value == "none" || value == "normal" {
+        return StyleValue::None;
+    }
+
+    let mut items = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' || c == '\'' {
+            chars.next();
+            items.push(ContentItem::String(read_css_string(&mut chars, c)));
+            continue;
+        }
+
+        // Identifier, optionally followed by function arguments
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '(' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+
+        let mut args = Vec::new();
+        if chars.peek() == Some(&'(') {
+            chars.next();
+            let mut arg = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    ')' => break,
+                    ',' => args.push(std::mem::take(&mut arg)),
+                    '"' | '\'' => arg.push_str(&read_css_string(&mut chars, c)),
+                    c if c.is_whitespace() => {}
+                    c => arg.push(c),
+                }
+            }
+            args.push(arg);
+        }
+        let arg = |index: usize| args.get(index).cloned().unwrap_or_default();
+
+        match name.as_str() {
+            "attr" => items.push(ContentItem::Attr(arg(0))),
+            "counter" => items.push(ContentItem::Counter(arg(0), args.get(1).map_or(ListStyleType::Decimal, |style| to_list_style_type(style)))),
+            "counters" => items.push(ContentItem::Counters(arg(0), arg(1), args.get(2).map_or(ListStyleType::Decimal, |style| to_list_style_type(style)))),
+            "url" => items.push(ContentItem::Url(arg(0))),
+            "open-quote" => items.push(ContentItem::OpenQuote),
+            "close-quote" => items.push(ContentItem::CloseQuote),
+            // no-open-quote and no-close-quote only change the nesting level of quotes, which we do not track
+            _ => {}
+        }
+    }
+
+    StyleValue::Content(items)
+}
+
+/// Reads a CSS string up to the closing quote, resolving escapes. The opening quote has already been read.
+fn read_css_string(chars: &mut std::iter::Peekable<std::str::Chars>, quote: char) -> String {
+    let mut result = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => break,
+            '\\' => {
+                // Escapes are either a hex code point (followed by an optional space), or a literal character
+                let mut hex = String::new();
+                while let Some(&c) = chars.peek() {
+                    if hex.len() == 6 || !c.is_ascii_hexdigit() {
+                        break;
+                    }
+                    hex.push(c);
+                    chars.next();
+                }
+
+                if hex.is_empty() {
+                    if let Some(c) = chars.next() {
+                        result.push(c);
+                    }
+                } else {
+                    if chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                    result.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+                }
+            }
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn parse_list_style_position(value: &str) -> StyleValue {
+    match value {
+        "inside" => StyleValue::ListStylePosition(ListStylePosition::Inside),
+        _ => StyleValue::ListStylePosition(ListStylePosition::Outside),
+    }
+}
+
+/// Parses the float property. The logical values are resolved against the direction at layout time.
+fn parse_float(value: &str) -> StyleValue {
+    match value {
+        "left" => StyleValue::Float(Float::Left),
+        "right" => StyleValue::Float(Float::Right),
+        "inline-start" => StyleValue::Float(Float::InlineStart),
+        "inline-end" => StyleValue::Float(Float::InlineEnd),
+        _ => StyleValue::Float(Float::None),
+    }
+}
+
+fn parse_clear(value: &str) -> StyleValue {
+    match value {
+        "left" => StyleValue::Clear(Clear::Left),
+        "right" => StyleValue::Clear(Clear::Right),
+        "inline-start" => StyleValue::Clear(Clear::InlineStart),
+        "inline-end" => StyleValue::Clear(Clear::InlineEnd),
+        "both" => StyleValue::Clear(Clear::Both),
+        _ => StyleValue::Clear(Clear::None),
+    }
+}
+
+fn parse_object_fit(value: &str) -> StyleValue {
+    match value {
+        "contain" => StyleValue::ObjectFit(ObjectFit::Contain),
+        "cover" => StyleValue::ObjectFit(ObjectFit::Cover),
+        "none" => StyleValue::ObjectFit(ObjectFit::None),
+        "scale-down" => StyleValue::ObjectFit(ObjectFit::ScaleDown),
+        _ => StyleValue::ObjectFit(ObjectFit::Fill),
+    }
+}
+
+/// Parses object-position. Browsers report it as two lengths or percentages, but keywords are accepted as well.
+/// Offsets from the right or bottom edge (like "right 10px bottom 20px") are not supported.
+fn parse_object_position(value: &str) -> StyleValue {
+    let offset = |part: &str| match part {
+        "left" | "top" => (0.0, Unit::Percent),
+        "center" => (50.0, Unit::Percent),
+        "right" | "bottom" => (100.0, Unit::Percent),
+        _ => match part.strip_suffix('%') {
+            Some(percent) => (percent.parse().unwrap_or(50.0), Unit::Percent),
+            None => (part.trim_end_matches("px").parse().unwrap_or(0.0), Unit::Px),
+        },
+    };
+
+    let mut parts: Vec<&str> = value.split_whitespace().collect();
+    // A single vertical keyword, or keywords in vertical-horizontal order
+    if parts.first().is_some_and(|part| matches!(*part, "top" | "bottom")) {
+        parts.reverse();
+    }
+    match parts.as_slice() {
+        [x] if matches!(*x, "top" | "bottom") => StyleValue::ObjectPosition((50.0, Unit::Percent), offset(x)),
+        [x] => StyleValue::ObjectPosition(offset(x), (50.0, Unit::Percent)),
+        [x, y, ..] => StyleValue::ObjectPosition(offset(x), offset(y)),
+        [] => StyleValue::ObjectPosition((50.0, Unit::Percent), (50.0, Unit::Percent)),
+    }
+}
+
+fn parse_grid_template(value: &str) -> StyleValue {
+    if value == "none" {
+        return StyleValue::None;
+    }
+
+    match parse_track_list(value) {
+        Some(list) => StyleValue::GridTracks(list),
+        None => StyleValue::Keyword(value.to_string()),
+    }
+}
+
+/// Parses a track list with (optional) line names, like "[full-start] minmax(1em, 1fr) [main-start] repeat(3, 100px)".
+/// Repetitions with a fixed count are expanded, so the names of their lines can be found like any other line.
+fn parse_track_list(value: &str) -> Option<GridTrackList> {
+    let mut list = GridTrackList {
+        tracks: Vec::new(),
+        line_names: vec![Vec::new()],
+    };
+
+    for token in split_grid_tokens(value) {
+        if let Some(names) = token.strip_prefix('[').and_then(|names| names.strip_suffix(']')) {
+            list.line_names.last_mut()?.extend(names.split_whitespace().map(str::to_string));
+        } else if let Some(args) = css_function(&token, "repeat") {
+            let (count, tracks) = args.split_once(',')?;
+            let repeated = parse_track_list(tracks)?;
+
+            let repetition = match count.trim() {
+                "auto-fill" => GridRepetition::AutoFill,
+                "auto-fit" => GridRepetition::AutoFit,
+                count => {
+                    for _ in 0..count.parse::<usize>().ok()? {
+                        list.line_names.last_mut()?.extend(repeated.line_names[0].iter().cloned());
+                        for (track, names) in repeated.tracks.iter().zip(&repeated.line_names[1..]) {
+                            list.tracks.push(track.clone());
+                            list.line_names.push(names.clone());
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            // The number of repetitions is only known during layout, so the names of these lines are dropped
+            let sizes = repeated
+                .tracks
+                .into_iter()
+                .map(|track| match track {
+                    GridTrack::Size(size) => Some(size),
+                    GridTrack::Repeat(..) => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            list.tracks.push(GridTrack::Repeat(repetition, sizes));
+            list.line_names.push(Vec::new());
+        } else {
+            list.tracks.push(GridTrack::Size(parse_track_size(&token)?));
+            list.line_names.push(Vec::new());
+        }
+    }
+
+    Some(list)
+}
+
+fn parse_track_size(value: &str) -> Option<TrackSize> {
+    let size = match value {
+        "auto" => TrackSize::Auto,
+        "min-content" => TrackSize::MinContent,
+        "max-content" => TrackSize::MaxContent,
+        "0" => TrackSize::Length(0.0),
+        _ => {
+            if let Some(args) = css_function(value, "minmax") {
+                let (min, max) = args.split_once(',')?;
+                TrackSize::MinMax(Box::new(parse_track_size(min.trim())?), Box::new(parse_track_size(max.trim())?))
+            } else if let Some(arg) = css_function(value, "fit-content") {
+                TrackSize::FitContent(arg.trim().strip_suffix("px")?.parse().ok()?)
+            } else if let Some(fraction) = value.strip_suffix("fr") {
+                TrackSize::Fraction(fraction.parse().ok()?)
+            } else if let Some(percent) = value.strip_suffix('%') {
+                TrackSize::Percent(percent.parse().ok()?)
+            } else {
+                TrackSize::Length(value.strip_suffix("px")?.parse().ok()?)
+            }
+        }
+    };
+
+    Some(size)
+}
+
+fn parse_grid_auto_tracks(value: &str) -> StyleValue {
+    match split_grid_tokens(value).iter().map(|token| parse_track_size(token)).collect::<Option<Vec<_>>>() {
+        Some(sizes) => StyleValue::GridAutoTracks(sizes),
+        None => StyleValue::Keyword(value.to_string()),
+    }
+}
+
+/// Parses grid-template-areas, which is a string of cell names for each row of the grid
+fn parse_grid_template_areas(value: &str) -> StyleValue {
+    let mut rows = Vec::new();
+
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '"' || c == '\'' {
+            let row = read_css_string(&mut chars, c)
+                .split_whitespace()
+                // A sequence of dots is a cell without an area
+                .map(|name| if name.chars().all(|c| c == '.') { ".".to_string() } else { name.to_string() })
+                .collect();
+            rows.push(row);
+        }
+    }
+
+    if rows.is_empty() {
+        return StyleValue::None;
+    }
+    StyleValue::GridAreas(rows)
+}
+
+/// Parses a grid-row or grid-column value ("start / end"). A single name is used for both the start and end line.
+fn parse_grid_line(value: &str) -> StyleValue {
+    let (start, end) = match value.split_once('/') {
+        Some((start, end)) => (parse_grid_placement(start), Some(parse_grid_placement(end))),
+        None => (parse_grid_placement(value), None),
+    };
+
+    let end = end.unwrap_or_else(|| same_name(&start));
+    StyleValue::GridLine(GridLine { start, end })
+}
+
+/// Parses a grid-area value ("row-start / column-start / row-end / column-end") into the grid-row and grid-column
+/// values. Missing lines take the name of the line they are paired with.
+fn parse_grid_area(value: &str) -> (StyleValue, StyleValue) {
+    let mut parts = value.split('/').map(parse_grid_placement);
+
+    let row_start = parts.next().unwrap_or(GridPlacement::Auto);
+    let column_start = parts.next().unwrap_or_else(|| same_name(&row_start));
+    let row_end = parts.next().unwrap_or_else(|| same_name(&row_start));
+    let column_end = parts.next().unwrap_or_else(|| same_name(&column_start));
+
+    (
+        StyleValue::GridLine(GridLine { start: row_start, end: row_end }),
+        StyleValue::GridLine(GridLine { start: column_start, end: column_end }),
+    )
+}
+
+/// Returns the placement that refers to the same name as the given placement, or auto when it is not a name
+fn same_name(placement: &GridPlacement) -> GridPlacement {
+    match placement {
+        GridPlacement::Named(name, None) => GridPlacement::Named(name.clone(), None),
+        _ => GridPlacement::Auto,
+    }
+}
+
+/// Parses the start or end of a grid placement, like "auto", "3", "span 2", "main-start" or "2 main"
+fn parse_grid_placement(value: &str) -> GridPlacement {
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    let span = tokens.contains(&"span");
+    let number = tokens.iter().find_map(|token| token.parse::<i16>().ok());
+    let name = tokens
+        .iter()
+        .find(|token| **token != "span" && **token != "auto" && token.parse::<i16>().is_err())
+        .map(|token| token.to_string());
+
+    match (span, number, name) {
+        // Taffy cannot span up to a named line, so named spans only span a single track
+        (true, number, _) => GridPlacement::Span(number.map_or(1, |number| number.max(1) as u16)),
+        (false, number, Some(name)) => GridPlacement::Named(name, number),
+        (false, Some(number), None) if number != 0 => GridPlacement::Line(number),
+        _ => GridPlacement::Auto,
+    }
+}
+
+/// Splits a grid value on white space, but keeps line names ("[a b]") and functions ("minmax(0, 1fr)") together
+fn split_grid_tokens(value: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut depth = 0usize;
+
+    for c in value.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        token.push(c);
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Returns the arguments of a CSS function call, like "0, 1fr" for "minmax(0, 1fr)"
+fn css_function<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+fn parse_border_collapse(value: &str) -> StyleValue {
+    match value {
+        "collapse" => StyleValue::BorderCollapse(BorderCollapse::Collapse),
+        _ => StyleValue::BorderCollapse(BorderCollapse::Separate),
+    }
+}
+
+/// Parses the border-spacing value, which is either a single length for both directions, or the horizontal
+/// spacing followed by the vertical spacing
+fn parse_border_spacing(value: &str) -> StyleValue {
+    let lengths: Vec<f32> = value
+        .split_whitespace()
+        .filter_map(|length| length.trim_end_matches("px").parse::<f32>().ok())
+        .collect();
+
+    match lengths.as_slice() {
+        [spacing] => StyleValue::BorderSpacing(*spacing, *spacing),
+        [horizontal, vertical] => StyleValue::BorderSpacing(*horizontal, *vertical),
+        _ => StyleValue::Keyword(value.to_string()),
+    }
+}
+
+fn parse_caption_side(value: &str) -> StyleValue {
+    match value {
+        "bottom" => StyleValue::CaptionSide(CaptionSide::Bottom),
+        _ => StyleValue::CaptionSide(CaptionSide::Top),
+    }
+}
+
+fn parse_table_layout(value: &str) -> StyleValue {
+    match value {
+        "fixed" => StyleValue::TableLayout(TableLayout::Fixed),
+        _ => StyleValue::TableLayout(TableLayout::Auto),
+    }
+}
+
+/// Parses a counter-reset or counter-increment value ("none", or a list of counter names each optionally
+/// followed by an integer). Counters without an integer get the default value.
+fn parse_counters(value: &str, default: i32) -> StyleValue {
+    let mut counters: Vec<(String, i32)> = Vec::new();
+    for token in value.split_whitespace() {
+        if token == "none" {
+            continue;
+        }
+        match (token.parse::<i32>(), counters.last_mut()) {
+            (Ok(num), Some(last)) => last.1 = num,
+            (Ok(_), None) => {}
+            (Err(_), _) => counters.push((token.to_string(), default)),
+        }
+    }
+
+    StyleValue::Counters(counters)
+}
+
+fn parse_unicode_bidi(value: &str) -> StyleValue {
+    match value {
+        "embed" => StyleValue::UnicodeBidi(UnicodeBidi::Embed),
+        "isolate" | "-webkit-isolate" => StyleValue::UnicodeBidi(UnicodeBidi::Isolate),
+        "bidi-override" => StyleValue::UnicodeBidi(UnicodeBidi::BidiOverride),
+        "isolate-override" | "-webkit-isolate-override" => StyleValue::UnicodeBidi(UnicodeBidi::IsolateOverride),
+        "plaintext" | "-webkit-plaintext" => StyleValue::UnicodeBidi(UnicodeBidi::Plaintext),
+        _ => StyleValue::UnicodeBidi(UnicodeBidi::Normal),
+    }
+}
+
+fn parse_word_break(value: &str) -> StyleValue {
+    match value {
+        "break-all" => StyleValue::WordBreak(WordBreak::BreakAll),
+        "keep-all" => StyleValue::WordBreak(WordBreak::KeepAll),
+        "break-word" => StyleValue::WordBreak(WordBreak::BreakWord),
+        _ => StyleValue::WordBreak(WordBreak::Normal),
+    }
+}
+
+fn parse_overflow_wrap(value: &str) -> StyleValue {
+    match value {
+        "break-word" => StyleValue::OverflowWrap(OverflowWrap::BreakWord),
+        "anywhere" => StyleValue::OverflowWrap(OverflowWrap::Anywhere),
+        _ => StyleValue::OverflowWrap(OverflowWrap::Normal),
+    }
+}
+
+fn parse_hyphens(value: &str) -> StyleValue {
+    match value {
+        "none" => StyleValue::Hyphens(Hyphens::None),
+        "auto" => StyleValue::Hyphens(Hyphens::Auto),
+        _ => StyleValue::Hyphens(Hyphens::Manual),
+    }
+}
+
+fn parse_white_space(value: &str) -> StyleValue {
+    match value {
+        "pre" => StyleValue::WhiteSpace(WhiteSpace::Pre),
+        "pre-wrap" => StyleValue::WhiteSpace(WhiteSpace::PreWrap),
+        "pre-line" => StyleValue::WhiteSpace(WhiteSpace::PreLine),
+        "nowrap" => StyleValue::WhiteSpace(WhiteSpace::NoWrap),
+        "break-spaces" => StyleValue::WhiteSpace(WhiteSpace::BreakSpaces),
+        _ => StyleValue::WhiteSpace(WhiteSpace::Normal),
+    }
+}
+
+fn parse_white_space_longhands(collapse: &str, wrap_mode: &str) -> StyleValue {
+    let wrap = wrap_mode != "nowrap";
+    match (collapse, wrap) {
+        ("preserve", true) => StyleValue::WhiteSpace(WhiteSpace::PreWrap),
+        ("preserve", false) => StyleValue::WhiteSpace(WhiteSpace::Pre),
+        ("preserve-breaks", _) => StyleValue::WhiteSpace(WhiteSpace::PreLine),
+        ("break-spaces", _) => StyleValue::WhiteSpace(WhiteSpace::BreakSpaces),
+        (_, false) => StyleValue::WhiteSpace(WhiteSpace::NoWrap),
+        _ => StyleValue::WhiteSpace(WhiteSpace::Normal),
+    }
+}
+
 fn parse_text_wrap(value: &str) -> StyleValue {
     match value {
         "wrap" => StyleValue::TextWrap(TextWrap::Wrap),
@@ -180,6 +822,30 @@ fn parse_text_wrap(value: &str) -> StyleValue {
     }
 }
 
+fn parse_text_decoration_line(value: &str) -> StyleValue {
+    let mut line = TextDecorationLine { underline: false, overline: false, line_through: false };
+    for keyword in value.split_whitespace() {
+        match keyword {
+            "underline" => line.underline = true,
+            "overline" => line.overline = true,
+            "line-through" => line.line_through = true,
+            _ => {}
+        }
+    }
+
+    StyleValue::TextDecorationLine(line)
+}
+
+fn parse_text_decoration_style(value: &str) -> StyleValue {
+    match value {
+        "double" => StyleValue::TextDecorationStyle(TextDecorationStyle::Double),
+        "dotted" => StyleValue::TextDecorationStyle(TextDecorationStyle::Dotted),
+        "dashed" => StyleValue::TextDecorationStyle(TextDecorationStyle::Dashed),
+        "wavy" => StyleValue::TextDecorationStyle(TextDecorationStyle::Wavy),
+        _ => StyleValue::TextDecorationStyle(TextDecorationStyle::Solid),
+    }
+}
+
 fn parse_position(position: &str) -> StyleValue {
     StyleValue::Keyword(position.to_string())
 }
@@ -190,16 +856,29 @@ fn parse_style_str(val: &str) -> StyleValue {
 
 fn parse_text_align(val: &str) -> StyleValue {
     match val {
-        "left" => StyleValue::TextAlign(TextAlign::Start),
-        "right" => StyleValue::TextAlign(TextAlign::End),
+        // Browsers use the -webkit- variants for elements like <th> and <center>
+        "left" | "-webkit-left" => StyleValue::TextAlign(TextAlign::Left),
+        "right" | "-webkit-right" => StyleValue::TextAlign(TextAlign::Right),
+        "center" | "-webkit-center" => StyleValue::TextAlign(TextAlign::Center),
         "start" => StyleValue::TextAlign(TextAlign::Start),
         "end" => StyleValue::TextAlign(TextAlign::End),
-        "center" => StyleValue::TextAlign(TextAlign::Center),
         "justify" => StyleValue::TextAlign(TextAlign::Justify),
+        "match-parent" => StyleValue::TextAlign(TextAlign::MatchParent),
+        "initial" => StyleValue::TextAlign(TextAlign::Initial),
+        "inherit" => StyleValue::TextAlign(TextAlign::Inherit),
+        "revert" => StyleValue::TextAlign(TextAlign::Revert),
+        "unset" => StyleValue::TextAlign(TextAlign::Unset),
         _ => StyleValue::TextAlign(TextAlign::Start)
     }
 }
 
+fn parse_text_align_last(val: &str) -> StyleValue {
+    match val {
+        "auto" => StyleValue::Keyword(val.to_string()),
+        _ => parse_text_align(val),
+    }
+}
+
 fn parse_style_num(val: &str) -> StyleValue {
     if let Ok(num) = val.parse::<f32>() {
         StyleValue::Number(num)
@@ -216,12 +895,18 @@ fn parse_display(value: &String) -> StyleValue {
         "none" => StyleValue::Display(Display::None),
         "flex" => StyleValue::Display(Display::Flex),
         "table" => StyleValue::Display(Display::Table),
+        "inline-table" => StyleValue::Display(Display::InlineTable),
         "table-caption" => StyleValue::Display(Display::TableCaption),
         "table-cell" => StyleValue::Display(Display::TableCell),
         "table-footer-group" => StyleValue::Display(Display::TableFooterGroup),
         "table-header-group" => StyleValue::Display(Display::TableHeaderGroup),
         "table-row" => StyleValue::Display(Display::TableRow),
         "table-row-group" => StyleValue::Display(Display::TableRowGroup),
+        "table-column" => StyleValue::Display(Display::TableColumn),
+        "table-column-group" => StyleValue::Display(Display::TableColumnGroup),
+        "list-item" => StyleValue::Display(Display::ListItem),
+        "grid" => StyleValue::Display(Display::Grid),
+        "inline-grid" => StyleValue::Display(Display::InlineGrid),
         _ => StyleValue::Keyword(value.to_string()),
     }
 }
@@ -236,7 +921,8 @@ fn parse_style_value(value: &str) -> StyleValue {
 
 fn parse_font_weight(value: &str) -> StyleValue {
     match value {
-        "bold" | "bolder" => StyleValue::FontWeight(FontWeight::Bolder),
+        "bold" => StyleValue::FontWeight(FontWeight::Bold),
+        "bolder" => StyleValue::FontWeight(FontWeight::Bolder),
         "lighter" => StyleValue::FontWeight(FontWeight::Lighter),
         "normal" => StyleValue::FontWeight(FontWeight::Normal),
         _ => {
@@ -249,11 +935,46 @@ fn parse_font_weight(value: &str) -> StyleValue {
     }
 }
 
+fn parse_font_style(value: &str) -> StyleValue {
+    match value {
+        "normal" => StyleValue::FontStyle(FontStyle::Normal),
+        "italic" => StyleValue::FontStyle(FontStyle::Italic),
+        // Oblique can have an angle (ie: "oblique 14deg"), which we ignore
+        _ if value.starts_with("oblique") => StyleValue::FontStyle(FontStyle::Oblique),
+        _ => StyleValue::Keyword(value.to_string()),
+    }
+}
+
+fn parse_font_stretch(value: &str) -> StyleValue {
+    let percentage = match value {
+        "ultra-condensed" => 50.0,
+        "extra-condensed" => 62.5,
+        "condensed" => 75.0,
+        "semi-condensed" => 87.5,
+        "normal" => 100.0,
+        "semi-expanded" => 112.5,
+        "expanded" => 125.0,
+        "extra-expanded" => 150.0,
+        "ultra-expanded" => 200.0,
+        _ => match value.strip_suffix('%').map(str::parse::<f32>) {
+            Some(Ok(num)) => num,
+            _ => return StyleValue::Keyword(value.to_string()),
+        },
+    };
+
+    StyleValue::Percentage(percentage)
+}
+
 pub fn document_from_json(base_url: &str, path: &str) -> Document {
+    let json_data = std::fs::read_to_string(path).expect("Failed to read JSON file");
+    document_from_json_str(base_url, &json_data)
+}
+
+/// Creates a document from JSON as generated by tools/souper
+pub fn document_from_json_str(base_url: &str, json_data: &str) -> Document {
     let mut doc = Document::new(base_url);
 
-    let json_data = std::fs::read_to_string(path).expect("Failed to read JSON file");
-    let dom_root: DomRoot = serde_json::from_str(&json_data).expect("Failed to parse JSON");
+    let dom_root: DomRoot = serde_json::from_str(json_data).expect("Failed to parse JSON");
 
     let root_node_id = doc.new_element(None, "DocumentRoot", None, false, None);
     for node in dom_root.children {
@@ -262,6 +983,94 @@ pub fn document_from_json(base_url: &str, path: &str) -> Document {
         }
     }
 
+    doc.font_faces = dom_root.font_faces.iter().map(to_font_face_rule).collect();
+
     doc.set_root(root_node_id);
     doc
-}
\ No newline at end of file
+}
+
+fn to_font_face_rule(font_face: &DomFontFace) -> FontFaceRule {
+    // Weights can be a range for variable fonts (ie: "100 900"), in which case we use the start of the range
+    let weight = match font_face.weight.as_deref().and_then(|w| w.split_whitespace().next()) {
+        Some("bold") => 700,
+        Some(value) => value.parse::<f32>().map_or(400, |w| w as usize),
+        None => 400,
+    };
+
+    let slant = match font_face.style.as_deref() {
+        Some("italic") => FontSlant::Italic,
+        Some(style) if style.starts_with("oblique") => FontSlant::Oblique,
+        _ => FontSlant::Normal,
+    };
+
+    FontFaceRule {
+        family: font_face.family.trim().trim_matches(|c| c == '"' || c == '\'').to_string(),
+        sources: font_face.src.iter().map(|src| FontFaceSource {
+            url: src.url.clone(),
+            format: src.format.clone(),
+        }).collect(),
+        weight,
+        slant,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content() {
+        assert_eq!(parse_content("none"), StyleValue::None);
+        assert_eq!(parse_content("normal"), StyleValue::None);
+        assert_eq!(
+            parse_content(r#""\201C" attr(title) " - " counter(section, upper-roman) counters(item, ".") url("a.png")"#),
+            StyleValue::Content(vec![
+                ContentItem::String("\u{201C}".to_string()),
+                ContentItem::Attr("title".to_string()),
+                ContentItem::String(" - ".to_string()),
+                ContentItem::Counter("section".to_string(), ListStyleType::UpperRoman),
+                ContentItem::Counters("item".to_string(), ".".to_string(), ListStyleType::Decimal),
+                ContentItem::Url("a.png".to_string()),
+            ])
+        );
+        assert_eq!(
+            parse_content(r#"open-quote "it's \"quoted\"" close-quote"#),
+            StyleValue::Content(vec![
+                ContentItem::OpenQuote,
+                ContentItem::String("it's \"quoted\"".to_string()),
+                ContentItem::CloseQuote,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let StyleValue::GridTracks(list) = parse_grid_template("[full-start] minmax(10px, 1fr) repeat(2, [col] 100px) repeat(auto-fill, 20%) [full-end]") else {
+            panic!("Failed to parse track list");
+        };
+        assert_eq!(list.tracks.len(), 4);
+        assert_eq!(list.tracks[0], GridTrack::Size(TrackSize::MinMax(Box::new(TrackSize::Length(10.0)), Box::new(TrackSize::Fraction(1.0)))));
+        assert_eq!(list.tracks[1], GridTrack::Size(TrackSize::Length(100.0)));
+        assert_eq!(list.tracks[3], GridTrack::Repeat(GridRepetition::AutoFill, vec![TrackSize::Percent(20.0)]));
+        assert_eq!(list.line_names, vec![vec!["full-start"], vec!["col"], vec!["col"], vec![], vec!["full-end"]]);
+
+        assert_eq!(
+            parse_grid_line("2 main / span 3"),
+            StyleValue::GridLine(GridLine {
+                start: GridPlacement::Named("main".to_string(), Some(2)),
+                end: GridPlacement::Span(3),
+            })
+        );
+        assert_eq!(
+            parse_grid_area("header"),
+            (
+                StyleValue::GridLine(GridLine { start: GridPlacement::Named("header".to_string(), None), end: GridPlacement::Named("header".to_string(), None) }),
+                StyleValue::GridLine(GridLine { start: GridPlacement::Named("header".to_string(), None), end: GridPlacement::Named("header".to_string(), None) }),
+            )
+        );
+        assert_eq!(
+            parse_grid_template_areas(r#""head head" ". main""#),
+            StyleValue::GridAreas(vec![vec!["head".to_string(), "head".to_string()], vec![".".to_string(), "main".to_string()]])
+        );
+    }
+}
@@ -6,6 +6,8 @@ pub enum StyleProperty {
     BackgroundColor,
     FontSize,
     FontWeight,
+    FontStyle,
+    FontStretch,
     Display,
     Width,
     Height,
@@ -45,6 +47,20 @@ pub enum StyleProperty {
     AlignSelf,
     AlignContent,
     TextAlign,
+    TextAlignLast,
+    Direction,
+    UnicodeBidi,
+    WritingMode,
+    TextOrientation,
+    ListStyleType,
+    ListStylePosition,
+    CounterReset,
+    CounterIncrement,
+    Content,
+    BorderCollapse,
+    BorderSpacing,
+    CaptionSide,
+    TableLayout,
 
     InsetBlockEnd,
     InsetBlockStart,
@@ -58,6 +74,14 @@ pub enum StyleProperty {
     BoxSizing,
     LineHeight,
     TextWrap,
+    WhiteSpace,
+    WordBreak,
+    OverflowWrap,
+    Hyphens,
+    TextDecorationLine,
+    TextDecorationStyle,
+    TextDecorationColor,
+    TextDecorationThickness,
     GridRow,
     GridColumn,
     GridAutoFlow,
@@ -65,6 +89,11 @@ pub enum StyleProperty {
     GridTemplateColumns,
     GridAutoRows,
     GridAutoColumns,
+    GridTemplateAreas,
+    Float,
+    Clear,
+    ObjectFit,
+    ObjectPosition,
 }
 
 #[allow(unused)]
@@ -93,12 +122,18 @@ pub enum Display {
     None,
     Flex,
     Table,
+    InlineTable,
     TableCaption,
     TableCell,
     TableFooterGroup,
     TableHeaderGroup,
     TableRow,
     TableRowGroup,
+    TableColumn,
+    TableColumnGroup,
+    ListItem,
+    Grid,
+    InlineGrid,
 }
 
 #[allow(unused)]
@@ -111,6 +146,13 @@ pub enum FontWeight {
     Number(f32),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
 #[allow(unused)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum StyleValue {
@@ -122,8 +164,40 @@ pub enum StyleValue {
     None,
     Display(Display),
     FontWeight(FontWeight),
+    FontStyle(FontStyle),
     TextWrap(TextWrap),
+    WhiteSpace(WhiteSpace),
+    Direction(Direction),
+    UnicodeBidi(UnicodeBidi),
+    WritingMode(WritingMode),
+    TextOrientation(TextOrientation),
+    WordBreak(WordBreak),
+    OverflowWrap(OverflowWrap),
+    Hyphens(Hyphens),
     TextAlign(TextAlign),
+    TextDecorationLine(TextDecorationLine),
+    TextDecorationStyle(TextDecorationStyle),
+    ListStyleType(ListStyleType),
+    ListStylePosition(ListStylePosition),
+    /// List of counter names with their value, as used by counter-reset and counter-increment
+    Counters(Vec<(String, i32)>),
+    Content(Vec<ContentItem>),
+    BorderCollapse(BorderCollapse),
+    /// Horizontal and vertical spacing between the cells of a table
+    BorderSpacing(f32, f32),
+    CaptionSide(CaptionSide),
+    TableLayout(TableLayout),
+    GridTracks(GridTrackList),
+    GridAutoTracks(Vec<TrackSize>),
+    /// Names of the cells of grid-template-areas, per row. Cells without an area are named ".".
+    GridAreas(Vec<Vec<String>>),
+    GridLine(GridLine),
+    Float(Float),
+    Clear(Clear),
+    ObjectFit(ObjectFit),
+    /// Horizontal and vertical offset of the content of a replaced element. Percentages are relative to the space
+    /// that is left next to the content.
+    ObjectPosition((f32, Unit), (f32, Unit)),
 }
 
 #[derive(Debug, Clone)]
@@ -146,7 +220,7 @@ pub enum TextAlign {
     Unset,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextWrap {
     Wrap,
     NoWrap,
@@ -160,6 +234,327 @@ pub enum TextWrap {
     Unset
 }
 
+/// How white space inside text is collapsed, and whether lines may wrap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhiteSpace {
+    Normal,
+    Pre,
+    PreWrap,
+    PreLine,
+    NoWrap,
+    BreakSpaces,
+}
+
+impl WhiteSpace {
+    /// Returns true when sequences of spaces and tabs are collapsed into a single space
+    pub fn collapses_spaces(&self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::NoWrap | WhiteSpace::PreLine)
+    }
+
+    /// Returns true when newlines are kept as forced line breaks
+    pub fn preserves_newlines(&self) -> bool {
+        !matches!(self, WhiteSpace::Normal | WhiteSpace::NoWrap)
+    }
+
+    /// Returns true when lines may be wrapped at soft wrap opportunities
+    pub fn wraps(&self) -> bool {
+        !matches!(self, WhiteSpace::Pre | WhiteSpace::NoWrap)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritingMode {
+    HorizontalTb,
+    VerticalRl,
+    VerticalLr,
+}
+
+impl WritingMode {
+    /// Returns true when lines run from top to bottom
+    pub fn is_vertical(&self) -> bool {
+        !matches!(self, WritingMode::HorizontalTb)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOrientation {
+    Mixed,
+    Upright,
+    Sideways,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnicodeBidi {
+    Normal,
+    Embed,
+    Isolate,
+    BidiOverride,
+    IsolateOverride,
+    Plaintext,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordBreak {
+    Normal,
+    BreakAll,
+    KeepAll,
+    /// Deprecated, behaves as word-break: normal with overflow-wrap: anywhere
+    BreakWord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowWrap {
+    Normal,
+    BreakWord,
+    Anywhere,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hyphens {
+    None,
+    Manual,
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListStyleType {
+    None,
+    Disc,
+    Circle,
+    Square,
+    Decimal,
+    DecimalLeadingZero,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+}
+
+impl ListStyleType {
+    /// Returns true when the marker is a bullet that is painted as a shape instead of text
+    pub fn is_bullet(&self) -> bool {
+        matches!(self, ListStyleType::Disc | ListStyleType::Circle | ListStyleType::Square)
+    }
+}
+
+/// Part of the content property of a pseudo-element
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentItem {
+    String(String),
+    /// Value of the attribute with the given name
+    Attr(String),
+    /// Innermost value of the counter with the given name
+    Counter(String, ListStyleType),
+    /// All values of the counter with the given name, joined by the separator
+    Counters(String, String, ListStyleType),
+    /// Image with the given url
+    Url(String),
+    OpenQuote,
+    CloseQuote,
+}
+
+/// Pseudo-elements that generate boxes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoElement {
+    Before,
+    After,
+    Marker,
+}
+
+impl PseudoElement {
+    /// Returns the tag name of the anonymous element that is generated for the pseudo-element
+    pub fn tag_name(&self) -> &'static str {
+        match self {
+            PseudoElement::Before => "::before",
+            PseudoElement::After => "::after",
+            PseudoElement::Marker => "::marker",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListStylePosition {
+    Outside,
+    Inside,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderCollapse {
+    Separate,
+    Collapse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionSide {
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableLayout {
+    Auto,
+    Fixed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Float {
+    None,
+    Left,
+    Right,
+    /// Logical sides, which depend on the direction of the text (see resolve)
+    InlineStart,
+    InlineEnd,
+}
+
+impl Float {
+    /// Returns the physical side of a logical float. The inline start is on the right side for right-to-left text.
+    pub fn resolve(self, rtl: bool) -> Float {
+        match (self, rtl) {
+            (Float::InlineStart, false) | (Float::InlineEnd, true) => Float::Left,
+            (Float::InlineStart, true) | (Float::InlineEnd, false) => Float::Right,
+            (float, _) => float,
+        }
+    }
+}
+
+/// How the content of a replaced element is sized inside its content box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectFit {
+    /// Stretched to the content box
+    Fill,
+    /// Scaled to fit inside the content box, keeping its aspect ratio
+    Contain,
+    /// Scaled to cover the content box, keeping its aspect ratio. The content is clipped.
+    Cover,
+    /// Not scaled
+    None,
+    /// Like none or contain, whichever is smaller
+    ScaleDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clear {
+    None,
+    Left,
+    Right,
+    Both,
+    /// Logical sides, which depend on the direction of the text (see resolve)
+    InlineStart,
+    InlineEnd,
+}
+
+impl Clear {
+    /// Returns the physical side of a logical clear. The inline start is on the right side for right-to-left text.
+    pub fn resolve(self, rtl: bool) -> Clear {
+        match (self, rtl) {
+            (Clear::InlineStart, false) | (Clear::InlineEnd, true) => Clear::Left,
+            (Clear::InlineStart, true) | (Clear::InlineEnd, false) => Clear::Right,
+            (clear, _) => clear,
+        }
+    }
+
+    /// Returns true when floats on the given (physical) side are cleared. Both the clear and the float must have
+    /// been resolved.
+    pub fn clears(&self, side: Float) -> bool {
+        match self {
+            Clear::None | Clear::InlineStart | Clear::InlineEnd => false,
+            Clear::Left => side == Float::Left,
+            Clear::Right => side == Float::Right,
+            Clear::Both => side != Float::None,
+        }
+    }
+}
+
+/// Sizing function of a grid track. Percentages are in the range 0-100.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackSize {
+    Length(f32),
+    Percent(f32),
+    Fraction(f32),
+    Auto,
+    MinContent,
+    MaxContent,
+    FitContent(f32),
+    MinMax(Box<TrackSize>, Box<TrackSize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridRepetition {
+    AutoFill,
+    AutoFit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridTrack {
+    Size(TrackSize),
+    /// Tracks that are repeated as often as they fit. Repetitions with a fixed count are expanded by the parser.
+    Repeat(GridRepetition, Vec<TrackSize>),
+}
+
+/// Track list of grid-template-rows or grid-template-columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridTrackList {
+    pub tracks: Vec<GridTrack>,
+    /// Names of the lines of the grid, starting with the line before the first track. There is one more line than
+    /// there are tracks.
+    pub line_names: Vec<Vec<String>>,
+}
+
+/// Start or end of the placement of a grid item
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridPlacement {
+    Auto,
+    /// Line number, negative numbers count from the end of the explicit grid
+    Line(i16),
+    /// Named line, optionally the nth line with that name
+    Named(String, Option<i16>),
+    Span(u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridLine {
+    pub start: GridPlacement,
+    pub end: GridPlacement,
+}
+
+impl Default for GridLine {
+    fn default() -> Self {
+        Self {
+            start: GridPlacement::Auto,
+            end: GridPlacement::Auto,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextDecorationLine {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+}
+
+impl TextDecorationLine {
+    /// Returns true when there are no decoration lines at all
+    pub fn is_none(&self) -> bool {
+        !self.underline && !self.overline && !self.line_through
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextDecorationStyle {
+    Solid,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
 impl StylePropertyList {
     pub fn new() -> Self {
         Self {
@@ -1,9 +1,141 @@
 #[cfg(not(any(feature = "text_parley", feature = "text_pango", feature = "text_skia")))]
 compile_error!("Either the 'text_parley' 'text_skia' or 'text_pango' feature must be enabled");
 
+pub mod cache;
+pub mod face;
+
 #[cfg(feature = "text_parley")]
 pub mod parley;
 #[cfg(feature = "text_pango")]
 pub mod pango;
 #[cfg(feature = "text_skia")]
-pub mod skia;
\ No newline at end of file
+pub mod skia;
+
+use serde::{Deserialize, Serialize};
+use crate::common::font::cache::CacheStats;
+
+/// Generic family that is used as last fallback when the font families don't end with a generic family
+pub const FALLBACK_GENERIC_FAMILY: &str = "sans-serif";
+
+/// Generic font families as defined by CSS. These are resolved by the text backend.
+const GENERIC_FAMILIES: [&str; 9] = ["serif", "sans-serif", "monospace", "cursive", "fantasy", "system-ui", "ui-serif", "ui-sans-serif", "ui-monospace"];
+
+/// Slant of a font
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontSlant {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// Describes the font that is used for a piece of text. This is passed as a whole from the layouter to the
+/// text backends, so they all select the same font.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FontDescriptor {
+    /// Font family (can be multiple fonts separated by ,)
+    pub family: String,
+    /// Size of the font in pixels
+    pub size: f64,
+    /// Weight of the font (1-1000, 400 is normal, 700 is bold)
+    pub weight: usize,
+    /// Slant of the font
+    pub slant: FontSlant,
+    /// Width of the font in percentage (50% - 200%, 100% is normal)
+    pub stretch: f64,
+}
+
+impl FontDescriptor {
+    pub fn new(family: &str, size: f64) -> Self {
+        Self {
+            family: family.to_string(),
+            size,
+            weight: 400,
+            slant: FontSlant::Normal,
+            stretch: 100.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: usize) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_slant(mut self, slant: FontSlant) -> Self {
+        self.slant = slant;
+        self
+    }
+
+    pub fn with_stretch(mut self, stretch: f64) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
+    /// Returns the font families in order of preference, without any quotes. If the list does not contain a
+    /// generic family, the fallback generic family is added, so there is always a font to fall back to.
+    ///
+    /// Web fonts are followed by the family names found inside their font files, and generic families are
+    /// replaced when a generic family override is set (see `face::set_generic_family_override()`).
+    pub fn families(&self) -> Vec<String> {
+        let mut families: Vec<String> = self.family
+            .split(',')
+            .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+            .filter(|f| !f.is_empty())
+            .collect();
+
+        if !families.iter().any(|f| is_generic_family(f)) {
+            families.push(FALLBACK_GENERIC_FAMILY.to_string());
+        }
+
+        let generic_override = face::generic_family_override();
+
+        let mut resolved: Vec<String> = Vec::new();
+        for family in families {
+            let family = match &generic_override {
+                Some(generic_override) if is_generic_family(&family) => generic_override.clone(),
+                _ => family,
+            };
+
+            let aliases = face::font_family_aliases(&family);
+            for family in std::iter::once(family).chain(aliases) {
+                if !resolved.contains(&family) {
+                    resolved.push(family);
+                }
+            }
+        }
+
+        resolved
+    }
+}
+
+/// Returns true when the family is a CSS generic family (like serif or monospace)
+pub fn is_generic_family(family: &str) -> bool {
+    GENERIC_FAMILIES.contains(&family.to_ascii_lowercase().as_str())
+}
+
+/// Returns the shaped text cache counters of each text backend that is compiled in, for the calling thread only.
+/// The caches are per thread, so this is not the hit rate of the process: text that is shaped or rasterized on
+/// other threads is not counted.
+pub fn shaped_text_cache_stats() -> Vec<(&'static str, CacheStats)> {
+    vec![
+        #[cfg(feature = "text_skia")]
+        ("skia", skia::cache_stats()),
+        #[cfg(feature = "text_parley")]
+        ("parley", parley::cache_stats()),
+        #[cfg(feature = "text_pango")]
+        ("pango", pango::cache_stats()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_families() {
+        let font = FontDescriptor::new("\"Helvetica Neue\", Arial", 16.0);
+        assert_eq!(font.families(), vec!["Helvetica Neue", "Arial", "sans-serif"]);
+
+        let font = FontDescriptor::new("'Fira Code', monospace", 16.0);
+        assert_eq!(font.families(), vec!["Fira Code", "monospace"]);
+    }
+}
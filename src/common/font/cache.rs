@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use crate::common::font::{FontDescriptor, FontSlant};
+use crate::layouter::text::{Alignment, TextDirection};
+
+/// Maximum number of entries in a cache. When the cache is full, it will be cleared completely.
+const MAX_ENTRIES: usize = 4096;
+
+/// Key of shaped text. Floats are stored as their bits so the key can be hashed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShapedTextKey {
+    text: String,
+    font_family: String,
+    font_size: u64,
+    font_weight: usize,
+    font_slant: FontSlant,
+    font_stretch: u64,
+    line_height: u64,
+    max_width: u64,
+    alignment: Alignment,
+    direction: TextDirection,
+}
+
+impl ShapedTextKey {
+    pub fn new(text: &str, font: &FontDescriptor, line_height: f64, max_width: f64, alignment: Alignment, direction: TextDirection) -> Self {
+        Self {
+            text: text.to_string(),
+            font_family: font.family.clone(),
+            font_size: font.size.to_bits(),
+            font_weight: font.weight,
+            font_slant: font.slant,
+            font_stretch: font.stretch.to_bits(),
+            line_height: line_height.to_bits(),
+            max_width: max_width.to_bits(),
+            alignment,
+            direction,
+        }
+    }
+}
+
+/// Hit and miss counters of a shaped text cache
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of entries currently in the cache
+    pub entries: usize,
+}
+
+/// Cache for shaped text (paragraphs, layouts). The same text is shaped by the layouter (often multiple times
+/// per node while measuring) and by the rasterizer (once for every tile the text is on), so we only want to
+/// shape it once. The cached value is specific for each text backend.
+///
+/// The shaped text of the backends cannot be shared between threads, so each backend keeps a cache per thread.
+/// The binaries lay out and rasterize the document on the same thread (the thread that runs the event loop), so
+/// the rasterizer finds the text that was shaped by the layouter. A rasterizer that runs on another thread starts
+/// with an empty cache and shapes all text again.
+pub struct ShapedTextCache<T: Clone> {
+    entries: HashMap<ShapedTextKey, T>,
+    stats: CacheStats,
+}
+
+impl<T: Clone> Default for ShapedTextCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> ShapedTextCache<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the cached value for the key, or creates (and caches) it with the given function
+    pub fn get_or_insert_with(&mut self, key: ShapedTextKey, f: impl FnOnce() -> T) -> T {
+        match self.try_get_or_insert_with(key, || Ok::<T, ()>(f())) {
+            Ok(value) => value,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the cached value for the key, or creates (and caches) it with the given function. Nothing is
+    /// cached when the function fails.
+    pub fn try_get_or_insert_with<E>(&mut self, key: ShapedTextKey, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        if let Some(value) = self.entries.get(&key) {
+            self.stats.hits += 1;
+            return Ok(value.clone());
+        }
+
+        self.stats.misses += 1;
+        let value = f()?;
+
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.clear();
+        }
+        self.entries.insert(key, value.clone());
+
+        Ok(value)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+
+    /// Removes all entries and resets the counters
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stats = CacheStats::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str, max_width: f64) -> ShapedTextKey {
+        ShapedTextKey::new(text, &FontDescriptor::new("sans", 16.0), 20.0, max_width, Alignment::Start, TextDirection::Ltr)
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = ShapedTextCache::new();
+
+        assert_eq!(cache.get_or_insert_with(key("foo", 100.0), || 1), 1);
+        assert_eq!(cache.get_or_insert_with(key("foo", 100.0), || 2), 1);
+        assert_eq!(cache.get_or_insert_with(key("foo", 200.0), || 3), 3);
+
+        let result: Result<i32, &str> = cache.try_get_or_insert_with(key("bar", 100.0), || Err("failed"));
+        assert!(result.is_err());
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3, entries: 2 });
+
+        cache.clear();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+}
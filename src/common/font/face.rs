@@ -0,0 +1,193 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use crate::common::font::FontSlant;
+use crate::common::get_media_store;
+use crate::common::media::{MediaId, MediaType};
+
+// Web fonts (@font-face) are fetched into the media store, and registered here. The text backends each have
+// their own font collection, so they check the generation of the registry and (re)load the registered font
+// faces into their collection when it has changed.
+
+/// Directory with the fonts that are bundled for (headless) rendering tests
+pub const TEST_FONTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts");
+/// Family of the bundled test fonts
+pub const TEST_FONT_FAMILY: &str = "DejaVu Sans";
+
+/// Source of a font face
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontFaceSource {
+    pub url: String,
+    /// Format hint as found in the src descriptor (ie: "woff2" or "truetype")
+    pub format: Option<String>,
+}
+
+/// A font face as declared by a @font-face rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontFaceRule {
+    pub family: String,
+    /// Sources in order of preference
+    pub sources: Vec<FontFaceSource>,
+    pub weight: usize,
+    pub slant: FontSlant,
+}
+
+/// A font face that has been loaded into the media store
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontFace {
+    /// Family as declared by the @font-face rule
+    pub family: String,
+    /// Family as found inside the font file (if known). Some backends can only match on this name.
+    pub font_family: Option<String>,
+    pub weight: usize,
+    pub slant: FontSlant,
+    pub media_id: MediaId,
+}
+
+static FONT_FACES: RwLock<Vec<FontFace>> = RwLock::new(Vec::new());
+static GENERIC_FAMILY_OVERRIDE: RwLock<Option<String>> = RwLock::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the generation of the registry. This is increased each time the registered fonts change.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// Registers a font face that has been loaded into the media store
+pub fn register_font_face(face: FontFace) {
+    let mut faces = FONT_FACES.write().expect("Failed to lock font faces");
+    if faces.contains(&face) {
+        return;
+    }
+
+    faces.push(face);
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Returns all registered font faces
+pub fn font_faces() -> Vec<FontFace> {
+    FONT_FACES.read().expect("Failed to lock font faces").clone()
+}
+
+/// Returns the font families (as found inside the font files) of the font faces that are registered under the
+/// given (declared) family
+pub fn font_family_aliases(family: &str) -> Vec<String> {
+    let faces = FONT_FACES.read().expect("Failed to lock font faces");
+
+    let mut aliases: Vec<String> = Vec::new();
+    for face in faces.iter().filter(|face| face.family.eq_ignore_ascii_case(family)) {
+        if let Some(font_family) = &face.font_family {
+            if !font_family.eq_ignore_ascii_case(family) && !aliases.contains(font_family) {
+                aliases.push(font_family.clone());
+            }
+        }
+    }
+
+    aliases
+}
+
+/// When set, all generic families (like sans-serif) resolve to the given family. This keeps rendering
+/// reproducible across machines, as it no longer depends on the system fonts.
+pub fn set_generic_family_override(family: Option<&str>) {
+    *GENERIC_FAMILY_OVERRIDE.write().expect("Failed to lock generic family override") = family.map(str::to_string);
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+pub fn generic_family_override() -> Option<String> {
+    GENERIC_FAMILY_OVERRIDE.read().expect("Failed to lock generic family override").clone()
+}
+
+/// Loads the font faces of the given @font-face rules into the media store and registers them. The first
+/// source of each rule that can be loaded is used. Returns the number of registered font faces.
+pub fn load_font_faces(rules: &[FontFaceRule]) -> usize {
+    let mut count = 0;
+
+    for rule in rules {
+        let media_id = rule.sources.iter()
+            .filter(|source| is_supported_format(source.format.as_deref()))
+            .find_map(|source| {
+                println!("Loading (font) resource: {}", source.url);
+                match get_media_store().read().unwrap().load_media(source.url.as_str()) {
+                    Ok(media_id) => Some(media_id),
+                    Err(e) => {
+                        log::info!("Could not load font from {}: {:?}", source.url, e);
+                        None
+                    }
+                }
+            });
+
+        let Some(media_id) = media_id else {
+            log::warn!("No loadable source found for font family {}", rule.family);
+            continue;
+        };
+
+        let Some(media_font) = get_media_store().read().unwrap().get_font(media_id) else {
+            log::warn!("Source for font family {} is not a font", rule.family);
+            continue;
+        };
+
+        register_font_face(FontFace {
+            family: rule.family.clone(),
+            font_family: media_font.font.family_name.clone(),
+            weight: rule.weight,
+            slant: rule.slant,
+            media_id,
+        });
+        count += 1;
+    }
+
+    count
+}
+
+/// Loads a font file and registers it under the family, weight and slant as found inside the font
+pub fn load_font_file(path: &Path) -> anyhow::Result<FontFace> {
+    let data = std::fs::read(path)?;
+
+    let media_store = get_media_store().read().expect("Failed to get media store");
+    let media_id = media_store.load_media_from_data(MediaType::Font, &data)?;
+    let Some(media_font) = media_store.get_font(media_id) else {
+        anyhow::bail!("Media is not a font: {}", path.display());
+    };
+
+    let Some(family) = media_font.font.family_name.clone() else {
+        anyhow::bail!("Font has no family name: {}", path.display());
+    };
+
+    let face = FontFace {
+        family: family.clone(),
+        font_family: Some(family),
+        weight: media_font.font.weight.unwrap_or(400),
+        slant: if media_font.font.italic { FontSlant::Italic } else { FontSlant::Normal },
+        media_id,
+    };
+    register_font_face(face.clone());
+
+    Ok(face)
+}
+
+/// Loads and registers all TrueType and OpenType fonts in the given directory
+pub fn load_font_dir(dir: &Path) -> anyhow::Result<Vec<FontFace>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| matches!(path.extension().and_then(|ext| ext.to_str()), Some("ttf" | "otf" | "ttc")))
+        .collect();
+    paths.sort();
+
+    paths.iter().map(|path| load_font_file(path)).collect()
+}
+
+/// Registers the bundled test fonts and lets all generic families resolve to them, so text renders the same
+/// on every machine.
+pub fn use_test_fonts() -> anyhow::Result<()> {
+    load_font_dir(Path::new(TEST_FONTS_DIR))?;
+    set_generic_family_override(Some(TEST_FONT_FAMILY));
+
+    Ok(())
+}
+
+fn is_supported_format(format: Option<&str>) -> bool {
+    match format {
+        None => true,
+        Some(format) => matches!(format, "truetype" | "opentype" | "collection" | "woff" | "woff2"),
+    }
+}
@@ -1,28 +1,255 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::ffi::{c_void, CString};
+use std::sync::Mutex;
+use gtk4::cairo::{Context, Format, ImageSurface};
 use gtk4::gio::Settings;
+use gtk4::glib::prelude::ObjectType;
 use gtk4::pango;
-use gtk4::pango::Weight;
+use gtk4::pango::{FontDescription, Layout, Stretch, Style, Weight, WrapMode, SCALE};
 use gtk4::prelude::{FontFamilyExt, SettingsExt};
+use pangocairo::functions::{context_set_resolution, create_layout};
+use crate::common::font::cache::{CacheStats, ShapedTextCache, ShapedTextKey};
+use crate::common::font::{face, is_generic_family, FontDescriptor, FontSlant};
+use crate::common::get_media_store;
+use crate::common::media::MediaId;
+use crate::layouter::text::{Alignment, TextDirection};
 
 const DEFAULT_FONT_FAMILY: &str = "sans";
 
-pub fn find_available_font(families: &str, ctx: &pango::Context) -> String {
-    let available_fonts: Vec<String> = ctx.list_families().iter().map(|f| f.name().to_ascii_lowercase()).collect();
+thread_local! {
+    // Pango layouts cannot be shared between threads, so each thread has its own cache, which is only shared with
+    // the rasterizer when it runs on the layout thread (see ShapedTextCache)
+    static LAYOUT_CACHE: RefCell<ShapedTextCache<Layout>> = RefCell::new(ShapedTextCache::new());
+    // Generation of the font face registry the font map of this thread is synced with
+    static FONT_MAP_GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Web fonts that have been added to fontconfig. Fontconfig is shared by all threads.
+static ADDED_FONTS: Mutex<Option<AddedFonts>> = Mutex::new(None);
+
+/// Web fonts that have been added to fontconfig, together with the directory their files are written to.
+/// Fontconfig reads the files whenever it needs a glyph, so they are kept until the process exits.
+struct AddedFonts {
+    media_ids: HashSet<MediaId>,
+    dir: tempfile::TempDir,
+}
+
+// Pango does not have an API to load fonts from memory, so web fonts are written to disk and added to the
+// (process wide) fontconfig configuration directly.
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    #[link(name = "fontconfig")]
+    extern "C" {
+        pub fn FcConfigAppFontAddFile(config: *mut c_void, file: *const c_char) -> c_int;
+    }
 
-    for font in families.split(',') {
+    #[link(name = "pangoft2-1.0")]
+    extern "C" {
+        pub fn pango_fc_font_map_config_changed(fcfontmap: *mut c_void);
+    }
+
+    extern "C" {
+        pub fn atexit(callback: extern "C" fn()) -> c_int;
+    }
+}
+
+/// Returns the (cached) pango layout for the given text. It will wrap any long lines based on the pixels
+/// found in max_width. The layout is shared between the layouter and the rasterizer.
+pub fn get_pango_layout(text: &str, font: &FontDescriptor, line_height: f64, max_width: f64, alignment: Alignment, direction: TextDirection) -> anyhow::Result<Layout> {
+    sync_font_map();
+
+    let key = ShapedTextKey::new(text, font, line_height, max_width, alignment, direction);
+
+    LAYOUT_CACHE.with(|cache| {
+        cache.borrow_mut().try_get_or_insert_with(key, || {
+            build_layout(text, font, max_width, alignment, direction)
+        })
+    })
+}
 
+/// Returns the counters of the layout cache of the current thread
+pub fn cache_stats() -> CacheStats {
+    LAYOUT_CACHE.with(|cache| cache.borrow().stats())
+}
+
+/// Adds the web fonts that have been registered since the last call to fontconfig, and lets the font map of
+/// the current thread reload its configuration. Fontconfig registers the fonts under the family name found
+/// inside the font file, which is why the font descriptor also lists these names for web fonts.
+fn sync_font_map() {
+    let generation = face::generation();
+    if FONT_MAP_GENERATION.get() == generation {
+        return;
+    }
+    FONT_MAP_GENERATION.set(generation);
+
+    if let Err(e) = add_font_files() {
+        log::warn!("Failed to add web fonts to fontconfig: {:?}", e);
+    }
+
+    // The default font map is a fontconfig font map on the platforms that we support
+    let font_map = pangocairo::FontMap::default();
+    unsafe { ffi::pango_fc_font_map_config_changed(font_map.as_ptr() as *mut c_void) };
+
+    LAYOUT_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+fn add_font_files() -> anyhow::Result<()> {
+    let mut added = ADDED_FONTS.lock().expect("Failed to lock added fonts");
+    if added.is_none() {
+        *added = Some(AddedFonts {
+            media_ids: HashSet::new(),
+            dir: tempfile::Builder::new().prefix("poc-pipeline-fonts-").tempdir()?,
+        });
+        // Statics are never dropped, so the directory is removed when the process exits
+        unsafe { ffi::atexit(remove_font_files) };
+    }
+    let Some(AddedFonts { media_ids: added, dir }) = added.as_mut() else {
+        return Ok(());
+    };
+
+    let media_store = get_media_store().read().expect("Failed to get media store");
+    for font_face in face::font_faces() {
+        if added.contains(&font_face.media_id) {
+            continue;
+        }
+        let Some(media_font) = media_store.get_font(font_face.media_id) else {
+            continue;
+        };
+
+        let path = dir.path().join(format!("{}.{}", added.len(), media_font.font.format.extension()));
+        std::fs::write(&path, media_font.font.data.as_slice())?;
+
+        let c_path = CString::new(path.to_string_lossy().as_bytes())?;
+        if unsafe { ffi::FcConfigAppFontAddFile(std::ptr::null_mut(), c_path.as_ptr()) } == 0 {
+            log::warn!("Fontconfig cannot load the font for family {}", font_face.family);
+        }
+        added.insert(font_face.media_id);
+    }
+
+    Ok(())
+}
+
+/// Removes the files of the web fonts that have been added to fontconfig
+extern "C" fn remove_font_files() {
+    if let Ok(mut added) = ADDED_FONTS.lock() {
+        // Dropping the temporary directory removes it with all its files
+        added.take();
+    }
+}
+
+fn build_layout(text: &str, font: &FontDescriptor, max_width: f64, alignment: Alignment, direction: TextDirection) -> anyhow::Result<Layout> {
+    let surface = ImageSurface::create(Format::ARgb32, 1, 1)?;
+    let cr = Context::new(&surface)?;
+    let layout = create_layout(&cr);
+
+    // @TODO: I need to set the DPI resolution to 72dpi, otherwise the text will be too large
+    context_set_resolution(&layout.context(), 72.0);
+
+    layout.set_font_description(Some(&to_pango_font_description(font, &layout.context())));
+
+    // Use the base direction of the paragraph instead of guessing it from the text. This also keeps the
+    // alignment physical, as pango flips left and right alignment for right-to-left paragraphs.
+    layout.set_auto_dir(false);
+    layout.context().set_base_dir(match direction {
+        TextDirection::Ltr => pango::Direction::Ltr,
+        TextDirection::Rtl => pango::Direction::Rtl,
+    });
+    layout.context_changed();
+
+    layout.set_text(text);
+    // A width of -1 disables wrapping, which is what we want for max-content sizes
+    if max_width < (i32::MAX / SCALE) as f64 {
+        layout.set_width((max_width * SCALE as f64) as i32);
+    } else {
+        layout.set_width(-1);
+    }
+
+    // @TODO: This should be configurable
+    layout.set_wrap(WrapMode::Word);
+
+    layout.set_spacing(0);
+    layout.set_line_spacing(0.0);
+
+    match alignment {
+        Alignment::Start => layout.set_alignment(pango::Alignment::Left),
+        Alignment::Middle => layout.set_alignment(pango::Alignment::Center),
+        Alignment::End => layout.set_alignment(pango::Alignment::Right),
+        Alignment::Justified => layout.set_justify(true),
+        Alignment::JustifiedAll => {
+            layout.set_justify(true);
+            // Older pango versions cannot justify the last line, which is then aligned at the start
+            #[cfg(feature = "text_pango_v1_50")]
+            layout.set_justify_last_line(true);
+        }
+    }
+
+    Ok(layout)
+}
+
+/// Converts the font descriptor into a pango font description
+pub fn to_pango_font_description(font: &FontDescriptor, ctx: &pango::Context) -> FontDescription {
+    let mut font_desc = FontDescription::new();
+    font_desc.set_family(&find_available_fonts(font, ctx));
+    font_desc.set_size((font.size * SCALE as f64) as i32);
+    font_desc.set_weight(to_pango_weight(font.weight));
+    font_desc.set_style(match font.slant {
+        FontSlant::Normal => Style::Normal,
+        FontSlant::Italic => Style::Italic,
+        FontSlant::Oblique => Style::Oblique,
+    });
+    font_desc.set_stretch(to_pango_stretch(font.stretch));
+
+    font_desc
+}
+
+/// Returns a comma separated list of the font families that are available. Pango will select the first family
+/// that has a glyph for each character, so the generic family at the end acts as the last fallback.
+pub fn find_available_fonts(font: &FontDescriptor, ctx: &pango::Context) -> String {
+    let available_fonts: Vec<String> = ctx.list_families().iter().map(|f| f.name().to_ascii_lowercase()).collect();
+
+    let mut families = Vec::new();
+    for family in font.families() {
         // System-ui is a special font that should be handled by us.
-        if font == "system-ui" {
-            return "Ubuntu Sans".into();
-            // return get_system_ui_font();
+        if family == "system-ui" {
+            families.push("Ubuntu Sans".to_string());
+            // families.push(get_system_ui_font());
+            continue;
+        }
+
+        // Generic families are resolved by fontconfig
+        if is_generic_family(&family) {
+            families.push(match family.as_str() {
+                "sans-serif" | "ui-sans-serif" => DEFAULT_FONT_FAMILY.to_string(),
+                "ui-serif" => "serif".to_string(),
+                "ui-monospace" => "monospace".to_string(),
+                _ => family,
+            });
+            continue;
         }
 
-        let font_name = font.trim().replace('"', "");
-        if available_fonts.contains(&font_name.to_ascii_lowercase()) {
-            return font_name;
+        if available_fonts.contains(&family.to_ascii_lowercase()) {
+            families.push(family);
         }
     }
 
-    DEFAULT_FONT_FAMILY.to_string()
+    families.join(",")
+}
+
+/// Converts a font-stretch percentage into the nearest pango stretch
+fn to_pango_stretch(stretch: f64) -> Stretch {
+    match stretch {
+        s if s <= 56.25 => Stretch::UltraCondensed,
+        s if s <= 68.75 => Stretch::ExtraCondensed,
+        s if s <= 81.25 => Stretch::Condensed,
+        s if s <= 93.75 => Stretch::SemiCondensed,
+        s if s <= 106.25 => Stretch::Normal,
+        s if s <= 118.75 => Stretch::SemiExpanded,
+        s if s <= 137.5 => Stretch::Expanded,
+        s if s <= 175.0 => Stretch::ExtraExpanded,
+        _ => Stretch::UltraExpanded,
+    }
 }
 
 pub fn to_pango_weight(w: usize) -> Weight {
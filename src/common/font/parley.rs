@@ -1,10 +1,18 @@
-use parley::GenericFamily;
-use crate::layouter::text::Alignment;
+use crate::layouter::text::{Alignment, TextDirection};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
 use std::sync::{Mutex, OnceLock};
 use parley::{AlignmentOptions, Layout};
+use crate::common::font::cache::{CacheStats, ShapedTextCache, ShapedTextKey};
+use crate::common::font::{face, is_generic_family, FontDescriptor, FontSlant};
+use crate::common::get_media_store;
+use crate::common::media::MediaId;
 
 static FONT_CTX: OnceLock<Mutex<parley::FontContext>> = OnceLock::new();
 static LAYOUT_CTX: OnceLock<Mutex<parley::LayoutContext>> = OnceLock::new();
+/// Generation of the font face registry and the font faces that have been registered with the font context
+static REGISTERED_FONTS: Mutex<(u64, Option<HashSet<MediaId>>)> = Mutex::new((0, None));
 
 pub fn get_font_context() -> std::sync::MutexGuard<'static, parley::FontContext> {
     FONT_CTX
@@ -20,9 +28,90 @@ fn get_layout_context() -> std::sync::MutexGuard<'static, parley::LayoutContext>
         .expect("Failed to lock layout context")
 }
 
+/// Shaped text layout of parley, shared between the layouter and the rasterizer of a thread
+pub type SharedLayout = Rc<Layout<[u8; 4]>>;
 
-pub fn get_parley_layout(text: &str, font_family: &str, font_size: f64, line_height: f64, max_width: f64, alignment: Alignment) -> Layout<[u8; 4]> {
-    let font_stack = parley::FontStack::from(font_family);
+thread_local! {
+    // Layouts cannot be shared between threads, so each thread has its own cache, which is only shared with the
+    // rasterizer when it runs on the layout thread (see ShapedTextCache). The cache holds the generation of the
+    // font face registry it was filled for.
+    static LAYOUT_CACHE: RefCell<(u64, ShapedTextCache<SharedLayout>)> = RefCell::new((face::generation(), ShapedTextCache::default()));
+}
+
+/// Returns the counters of the layout cache of the current thread
+pub fn cache_stats() -> CacheStats {
+    LAYOUT_CACHE.with(|cache| cache.borrow().1.stats())
+}
+
+/// Returns the (cached) layout for the given text. The layout is shared between the layouter and the rasterizer.
+/// Parley has no way to set the base direction of a paragraph, it always takes the direction of the first
+/// strong character. The direction is still part of the cache key, so the layout is cached per direction.
+pub fn get_parley_layout(text: &str, font: &FontDescriptor, line_height: f64, max_width: f64, alignment: Alignment, direction: TextDirection) -> SharedLayout {
+    sync_font_context();
+
+    let key = ShapedTextKey::new(text, font, line_height, max_width, alignment, direction);
+
+    LAYOUT_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+
+        // Generic family overrides might have changed as well, so all layouts need to be shaped again
+        let generation = face::generation();
+        if cache.0 != generation {
+            *cache = (generation, ShapedTextCache::default());
+        }
+
+        cache.1.get_or_insert_with(key, || {
+            Rc::new(build_layout(text, font, line_height, max_width, alignment))
+        })
+    })
+}
+
+/// Registers the web fonts that have been added to the font face registry since the last call. Fonts are
+/// registered under the family name found inside the font file, which is why the font descriptor also lists
+/// these names for web fonts.
+fn sync_font_context() {
+    let generation = face::generation();
+
+    let mut registered = REGISTERED_FONTS.lock().expect("Failed to lock registered fonts");
+    if registered.0 == generation {
+        return;
+    }
+    registered.0 = generation;
+    let media_ids = registered.1.get_or_insert_with(HashSet::new);
+
+    let mut font_ctx = get_font_context();
+    let media_store = get_media_store().read().expect("Failed to get media store");
+    for font_face in face::font_faces() {
+        if media_ids.contains(&font_face.media_id) {
+            continue;
+        }
+        let Some(media_font) = media_store.get_font(font_face.media_id) else {
+            continue;
+        };
+
+        // Parley can only read uncompressed fonts
+        if !media_font.font.format.is_sfnt() {
+            log::warn!("Parley cannot load {:?} fonts (family {})", media_font.font.format, font_face.family);
+            continue;
+        }
+
+        font_ctx.collection.register_fonts(media_font.font.data.to_vec());
+        media_ids.insert(font_face.media_id);
+    }
+}
+
+fn build_layout(text: &str, font: &FontDescriptor, line_height: f64, max_width: f64, alignment: Alignment) -> Layout<[u8; 4]> {
+    // Parley parses the CSS font list (including generic families) itself, and falls back per glyph
+    let families = font.families().iter().map(|f| {
+        if is_generic_family(f) { f.clone() } else { format!("\"{}\"", f) }
+    }).collect::<Vec<_>>().join(", ");
+    let font_stack = parley::FontStack::from(families.as_str());
+
+    let font_style = match font.slant {
+        FontSlant::Normal => parley::FontStyle::Normal,
+        FontSlant::Italic => parley::FontStyle::Italic,
+        FontSlant::Oblique => parley::FontStyle::Oblique(None),
+    };
 
     let display_scale = 1.0;
     let max_advance = (max_width * display_scale) as f32;
@@ -32,20 +121,27 @@ pub fn get_parley_layout(text: &str, font_family: &str, font_size: f64, line_hei
 
     let mut builder = layout_ctx.ranged_builder(&mut font_ctx, text, display_scale as f32);
     builder.push_default(font_stack);
-    builder.push_default(parley::StyleProperty::LineHeight(line_height as f32 / font_size as f32));
-    builder.push_default(parley::StyleProperty::FontSize(font_size as f32));
-    builder.push_default(GenericFamily::SystemUi);
+    builder.push_default(parley::StyleProperty::LineHeight(line_height as f32 / font.size as f32));
+    builder.push_default(parley::StyleProperty::FontSize(font.size as f32));
+    builder.push_default(parley::StyleProperty::FontWeight(parley::FontWeight::new(font.weight as f32)));
+    builder.push_default(parley::StyleProperty::FontStyle(font_style));
+    builder.push_default(parley::StyleProperty::FontWidth(parley::FontWidth::from_percentage(font.stretch as f32)));
 
     let align = match alignment {
         Alignment::Start => parley::layout::Alignment::Start,
         Alignment::Middle => parley::layout::Alignment::Middle,
         Alignment::End => parley::layout::Alignment::End,
-        Alignment::Justified => parley::layout::Alignment::Justified,
+        // Parley never justifies the last line of a paragraph
+        Alignment::Justified | Alignment::JustifiedAll => parley::layout::Alignment::Justified,
     };
 
+    // Lines can only be aligned within a known width. Without it (max-content), they are aligned within the
+    // width of the longest line.
+    let align_width = if max_width < f32::MAX as f64 { Some(max_advance) } else { None };
+
     let mut layout: Layout<[u8; 4]> = builder.build(text);
     layout.break_all_lines(Some(max_advance * 1.01));
-    layout.align(Some(max_advance), align, AlignmentOptions::default());
+    layout.align(align_width, align, AlignmentOptions::default());
 
     layout
 }
\ No newline at end of file
@@ -1,28 +1,145 @@
-use skia_safe::Paint;
-use crate::layouter::text::Alignment;
-use skia_safe::textlayout::{Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle};
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::common::font::cache::{CacheStats, ShapedTextCache, ShapedTextKey};
+use crate::common::font::face;
+use crate::common::get_media_store;
+use crate::common::font::{FontDescriptor, FontSlant};
+use crate::layouter::text::{Alignment, TextDirection};
+use crate::painter::commands::text::{DecorationLine, DecorationMetrics, TextDecoration, TextDecorationStyle};
+use skia_safe::font_style::{Slant, Weight, Width};
+use skia_safe::textlayout::{FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextAlign, TextDirection as SkTextDirection, TextStyle, TypefaceFontProvider};
+use skia_safe::{Canvas, Font, FontMgr, FontStyle, Paint, PaintStyle, Path, PathEffect};
+
+/// Paragraphs are shared between the layouter and the rasterizer. Some operations (like visiting the glyph
+/// runs) need a mutable paragraph.
+pub type SharedParagraph = Rc<RefCell<Paragraph>>;
 
 thread_local! {
-    static FC: skia_safe::textlayout::FontCollection = {
-        let mut fc = skia_safe::textlayout::FontCollection::new();
-        fc.set_default_font_manager(skia_safe::FontMgr::new(), None);
-        fc
-    };
+    // Font collection together with the generation of the font face registry it was created for
+    static FC: RefCell<(u64, FontCollection)> = RefCell::new((face::generation(), create_font_collection()));
+
+    // Paragraphs cannot be shared between threads, so each thread has its own cache, which is only shared with
+    // the rasterizer when it runs on the layout thread (see ShapedTextCache)
+    static PARAGRAPH_CACHE: RefCell<ShapedTextCache<SharedParagraph>> = RefCell::new(ShapedTextCache::new());
+}
+
+/// Returns the (cached) paragraph for the given text. The paragraph does not have a foreground paint, so
+/// the rasterizer must apply its own brush when painting.
+pub fn get_skia_paragraph(text: &str, font: &FontDescriptor, line_height: f64, max_width: f64, alignment: Alignment, direction: TextDirection) -> SharedParagraph {
+    sync_font_collection();
+
+    let key = ShapedTextKey::new(text, font, line_height, max_width, alignment, direction);
+
+    PARAGRAPH_CACHE.with(|cache| {
+        cache.borrow_mut().get_or_insert_with(key, || {
+            Rc::new(RefCell::new(build_paragraph(text, font, line_height, max_width, alignment, direction)))
+        })
+    })
 }
 
-pub fn get_skia_paragraph(text: &str, font_family: &str, font_size: f64, line_height: f64, max_width: f64, _alignment: Alignment, paint: Option<&Paint>) -> Paragraph {
-    let paragraph_style = ParagraphStyle::new();
-    let mut paragraph_builder = ParagraphBuilder::new(&paragraph_style, FC.with(|fc| fc.clone()));
+/// Creates a font collection with the system fonts and all the registered web fonts
+fn create_font_collection() -> FontCollection {
+    let mut fc = FontCollection::new();
+    fc.set_default_font_manager(FontMgr::new(), None);
+    // Glyphs that are not found in any of the requested families are looked up in the other system fonts
+    fc.enable_font_fallback();
+
+    let faces = face::font_faces();
+    if !faces.is_empty() {
+        let font_mgr = FontMgr::new();
+        let mut provider = TypefaceFontProvider::new();
+
+        let media_store = get_media_store().read().expect("Failed to get media store");
+        for font_face in faces {
+            let Some(media_font) = media_store.get_font(font_face.media_id) else {
+                continue;
+            };
+            match font_mgr.new_from_data(&media_font.font.data, None) {
+                Some(typeface) => {
+                    provider.register_typeface(typeface, Some(font_face.family.as_str()));
+                }
+                None => log::warn!("Skia cannot load the font for family {}", font_face.family),
+            }
+        }
+
+        fc.set_asset_font_manager(Some(provider.into()));
+    }
+
+    fc
+}
+
+/// Recreates the font collection of the current thread when fonts have been registered since it was created.
+/// Paragraphs that are shaped with the old collection are dropped from the cache.
+fn sync_font_collection() {
+    let generation = face::generation();
+
+    let changed = FC.with(|fc| {
+        let mut fc = fc.borrow_mut();
+        if fc.0 == generation {
+            return false;
+        }
+
+        *fc = (generation, create_font_collection());
+        true
+    });
+
+    if changed {
+        PARAGRAPH_CACHE.with(|cache| cache.borrow_mut().clear());
+    }
+}
+
+/// Returns the counters of the paragraph cache of the current thread
+pub fn cache_stats() -> CacheStats {
+    PARAGRAPH_CACHE.with(|cache| cache.borrow().stats())
+}
 
-    let paint = match paint {
-        Some(p) => p.clone(),
-        None => Paint::default(),
+/// Converts the font descriptor into a skia font style
+fn to_skia_font_style(font: &FontDescriptor) -> FontStyle {
+    let slant = match font.slant {
+        FontSlant::Normal => Slant::Upright,
+        FontSlant::Italic => Slant::Italic,
+        FontSlant::Oblique => Slant::Oblique,
     };
 
+    FontStyle::new(Weight::from(font.weight as i32), to_skia_width(font.stretch), slant)
+}
+
+/// Converts a font-stretch percentage into the nearest skia width
+fn to_skia_width(stretch: f64) -> Width {
+    match stretch {
+        s if s <= 56.25 => Width::ULTRA_CONDENSED,
+        s if s <= 68.75 => Width::EXTRA_CONDENSED,
+        s if s <= 81.25 => Width::CONDENSED,
+        s if s <= 93.75 => Width::SEMI_CONDENSED,
+        s if s <= 106.25 => Width::NORMAL,
+        s if s <= 118.75 => Width::SEMI_EXPANDED,
+        s if s <= 137.5 => Width::EXPANDED,
+        s if s <= 175.0 => Width::EXTRA_EXPANDED,
+        _ => Width::ULTRA_EXPANDED,
+    }
+}
+
+fn build_paragraph(text: &str, font: &FontDescriptor, line_height: f64, max_width: f64, alignment: Alignment, direction: TextDirection) -> Paragraph {
+    let mut paragraph_style = ParagraphStyle::new();
+    paragraph_style.set_text_direction(match direction {
+        TextDirection::Ltr => SkTextDirection::LTR,
+        TextDirection::Rtl => SkTextDirection::RTL,
+    });
+    // Lines can only be aligned within a known width, so max-content layouts are always aligned to the left
+    paragraph_style.set_text_align(match alignment {
+        _ if max_width >= f32::MAX as f64 => TextAlign::Left,
+        Alignment::Start => TextAlign::Left,
+        Alignment::End => TextAlign::Right,
+        Alignment::Middle => TextAlign::Center,
+        // Skia never justifies the last line of a paragraph
+        Alignment::Justified | Alignment::JustifiedAll => TextAlign::Justify,
+    });
+    let mut paragraph_builder = ParagraphBuilder::new(&paragraph_style, FC.with(|fc| fc.borrow().1.clone()));
+
     let mut ts = TextStyle::new();
-    ts.set_foreground_paint(&paint);
-    ts.set_font_size(font_size as f32);
-    ts.set_font_families(&[font_family]);
+    ts.set_font_size(font.size as f32);
+    ts.set_font_families(&font.families());
+    ts.set_font_style(to_skia_font_style(font));
     ts.set_height(line_height as f32);
 
     paragraph_builder.push_style(&ts);
@@ -32,4 +149,65 @@ pub fn get_skia_paragraph(text: &str, font_family: &str, font_size: f64, line_he
     paragraph.layout(max_width as f32);
 
     paragraph
-}
\ No newline at end of file
+}
+
+/// Returns the decoration lines of the paragraph when it is painted at the given origin. The underline and
+/// strikeout metrics are taken from the font that is used at the start of each line.
+pub fn paragraph_decoration_lines(paragraph: &Paragraph, decoration: &TextDecoration, origin: (f64, f64)) -> Vec<DecorationLine> {
+    let fonts = paragraph.get_fonts();
+
+    let mut lines = Vec::new();
+    for lm in paragraph.get_line_metrics() {
+        let font_info = fonts.iter()
+            .find(|fi| fi.text_range.start <= lm.start_index && lm.start_index < fi.text_range.end)
+            .or(fonts.first());
+        let Some(font_info) = font_info else {
+            continue;
+        };
+
+        let metrics = to_decoration_metrics(&font_info.font);
+        lines.extend(decoration.lines(origin.0 + lm.left, lm.width, origin.1 + lm.baseline, &metrics));
+    }
+
+    lines
+}
+
+/// Converts the skia font metrics into decoration metrics. Not all fonts provide underline and strikeout
+/// information, in which case we approximate them from the font size.
+fn to_decoration_metrics(font: &Font) -> DecorationMetrics {
+    let (_, metrics) = font.metrics();
+    let fallback = DecorationMetrics::from_font_size(font.size() as f64);
+
+    DecorationMetrics {
+        underline_offset: metrics.underline_position().map_or(fallback.underline_offset, |v| v as f64),
+        underline_thickness: metrics.underline_thickness().map_or(fallback.underline_thickness, |v| v as f64),
+        strikeout_offset: metrics.strikeout_position().map_or(fallback.strikeout_offset, |v| v as f64),
+        strikeout_thickness: metrics.strikeout_thickness().map_or(fallback.strikeout_thickness, |v| v as f64),
+        ascent: -metrics.ascent as f64,
+    }
+}
+
+/// Draws a single decoration line onto the canvas with the given paint
+pub fn draw_decoration_line(canvas: &Canvas, line: &DecorationLine, paint: &Paint) {
+    let mut paint = paint.clone();
+    paint.set_anti_alias(true);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(line.thickness as f32);
+    if let Some((on, off)) = line.dash_pattern() {
+        paint.set_path_effect(PathEffect::dash(&[on as f32, off as f32], 0.0));
+    }
+
+    if line.style == TextDecorationStyle::Wavy {
+        let mut path = Path::new();
+        path.move_to((line.x as f32, line.y as f32));
+        for (control, end) in line.wave_segments() {
+            path.quad_to((control.x as f32, control.y as f32), (end.x as f32, end.y as f32));
+        }
+        canvas.draw_path(&path, &paint);
+        return;
+    }
+
+    for y in line.strokes() {
+        canvas.draw_line((line.x as f32, y as f32), ((line.x + line.width) as f32, y as f32), &paint);
+    }
+}
@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 /// A simple rectangle with a position (x, y) and dimensions (width, height).
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Rect {
     pub x: f64,
     pub y: f64,
@@ -40,6 +42,18 @@ impl Rect {
             height: self.height,
         }
     }
+
+    /// Returns the smallest rectangle that contains both rectangles.
+    pub fn union(&self, other: &Rect) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Self {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
 }
 
 impl Into<Coordinate> for Rect {
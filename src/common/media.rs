@@ -1,3 +1,4 @@
+mod font;
 mod image;
 mod svg;
 
@@ -5,11 +6,13 @@ mod media;
 mod media_store;
 
 pub use media::Media;
+pub use media::MediaFont;
 pub use media::MediaImage;
 pub use media::MediaType;
 pub use media::MediaSvg;
 pub use media::MediaId;
 
+pub use font::{Font, FontFormat};
 pub use svg::Svg;
 pub use image::Image;
 
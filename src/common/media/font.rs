@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+/// Container format of a font file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontFormat {
+    TrueType,
+    OpenType,
+    Collection,
+    Woff,
+    Woff2,
+}
+
+impl FontFormat {
+    /// Detects the font format from the first bytes of the font data
+    pub fn detect(data: &[u8]) -> Option<FontFormat> {
+        match data.get(0..4)? {
+            [0x00, 0x01, 0x00, 0x00] | b"true" => Some(FontFormat::TrueType),
+            b"OTTO" => Some(FontFormat::OpenType),
+            b"ttcf" => Some(FontFormat::Collection),
+            b"wOFF" => Some(FontFormat::Woff),
+            b"wOF2" => Some(FontFormat::Woff2),
+            _ => None,
+        }
+    }
+
+    /// Returns the file extension that is commonly used for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FontFormat::TrueType => "ttf",
+            FontFormat::OpenType => "otf",
+            FontFormat::Collection => "ttc",
+            FontFormat::Woff => "woff",
+            FontFormat::Woff2 => "woff2",
+        }
+    }
+
+    /// Returns true when the data is a plain (uncompressed) sfnt font that can be read by all text backends
+    pub fn is_sfnt(&self) -> bool {
+        matches!(self, FontFormat::TrueType | FontFormat::OpenType | FontFormat::Collection)
+    }
+}
+
+/// A font file. The data is kept as-is, so each text backend can load it into its own font collection.
+#[derive(Clone)]
+pub struct Font {
+    pub data: Arc<Vec<u8>>,
+    pub format: FontFormat,
+    /// Family name as found inside the font. Not available for compressed (WOFF/WOFF2) fonts.
+    pub family_name: Option<String>,
+    /// Weight (1-1000) as found inside the font. Not available for compressed (WOFF/WOFF2) fonts.
+    pub weight: Option<usize>,
+    /// True when the font is marked as italic
+    pub italic: bool,
+}
+
+impl Font {
+    pub fn from_data(data: &[u8]) -> anyhow::Result<Font> {
+        let Some(format) = FontFormat::detect(data) else {
+            anyhow::bail!("Unknown font format");
+        };
+
+        let (family_name, weight, italic) = if format.is_sfnt() {
+            let os2 = sfnt_os2(data);
+            (sfnt_family_name(data), os2.map(|(weight, _)| weight), os2.is_some_and(|(_, italic)| italic))
+        } else {
+            (None, None, false)
+        };
+
+        Ok(Font {
+            data: Arc::new(data.to_vec()),
+            format,
+            family_name,
+            weight,
+            italic,
+        })
+    }
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font")
+            .field("format", &self.format)
+            .field("family_name", &self.family_name)
+            .field("weight", &self.weight)
+            .field("italic", &self.italic)
+            .field("size", &self.data.len())
+            .finish()
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Returns the offset of the given table in a TrueType/OpenType font (or the first font of a collection)
+fn sfnt_table(data: &[u8], tag: &[u8; 4]) -> Option<usize> {
+    let font_offset = if data.get(0..4)? == b"ttcf" { read_u32(data, 12)? as usize } else { 0 };
+
+    let num_tables = read_u16(data, font_offset + 4)? as usize;
+    (0..num_tables).find_map(|idx| {
+        let record = font_offset + 12 + idx * 16;
+        if data.get(record..record + 4)? == tag {
+            read_u32(data, record + 8).map(|offset| offset as usize)
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the weight class and the italic flag from the 'OS/2' table
+fn sfnt_os2(data: &[u8]) -> Option<(usize, bool)> {
+    let os2_offset = sfnt_table(data, b"OS/2")?;
+    let weight = read_u16(data, os2_offset + 4)? as usize;
+    let fs_selection = read_u16(data, os2_offset + 62)?;
+
+    Some((weight, fs_selection & 0x01 != 0))
+}
+
+/// Returns the family name from the 'name' table. The typographic family name is preferred over the (legacy)
+/// family name.
+fn sfnt_family_name(data: &[u8]) -> Option<String> {
+    let name_offset = sfnt_table(data, b"name")?;
+
+    let count = read_u16(data, name_offset + 2)? as usize;
+    let strings_offset = name_offset + read_u16(data, name_offset + 4)? as usize;
+
+    let mut family = None;
+    for idx in 0..count {
+        let record = name_offset + 6 + idx * 12;
+        let platform_id = read_u16(data, record)?;
+        let name_id = read_u16(data, record + 6)?;
+        let length = read_u16(data, record + 8)? as usize;
+        let offset = strings_offset + read_u16(data, record + 10)? as usize;
+        if name_id != 1 && name_id != 16 {
+            continue;
+        }
+
+        let bytes = data.get(offset..offset + length)?;
+        let name = match platform_id {
+            // Unicode and Windows platforms use UTF-16BE
+            0 | 3 => {
+                let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                String::from_utf16_lossy(&units)
+            }
+            // Macintosh platform uses (mostly ASCII compatible) Mac Roman
+            1 => bytes.iter().map(|&b| b as char).collect(),
+            _ => continue,
+        };
+
+        if name_id == 16 {
+            return Some(name);
+        }
+        family.get_or_insert(name);
+    }
+
+    family
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_from_data() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/fonts/DejaVuSans.ttf")).unwrap();
+        let font = Font::from_data(&data).unwrap();
+
+        assert_eq!(font.format, FontFormat::TrueType);
+        assert_eq!(font.family_name.as_deref(), Some("DejaVu Sans"));
+        assert_eq!(font.weight, Some(400));
+        assert!(!font.italic);
+
+        assert!(Font::from_data(b"not a font").is_err());
+    }
+}
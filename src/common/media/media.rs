@@ -1,10 +1,12 @@
 use std::ops::AddAssign;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use crate::common::hash::{hash_from_string, Sha256Hash};
+use crate::common::media::Font;
 use crate::common::media::Image;
 use crate::common::media::Svg;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct MediaId(u64);
 
 impl MediaId {
@@ -25,10 +27,11 @@ impl std::fmt::Display for MediaId {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MediaType {
     Svg,
     Image,
+    Font,
 }
 
 #[allow(unused)]
@@ -50,17 +53,26 @@ pub struct MediaImage {
     pub image: Image,
 }
 
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct MediaFont {
+    src: String,
+    hash: Sha256Hash,
+    pub font: Font,
+}
+
 #[derive(Clone)]
 pub enum Media {
     Svg(Arc<MediaSvg>),
     Image(Arc<MediaImage>),
+    Font(Arc<MediaFont>),
 }
 
 impl Media {
     pub fn svg(src: &str, svg: Svg) -> Self {
         Media::Svg(Arc::new(MediaSvg {
             src: src.to_string(),
-            hash: hash_from_string(&src),
+            hash: hash_from_string(src),
             svg
         }))
     }
@@ -68,10 +80,18 @@ impl Media {
     pub fn image(src: &str, image: Image) -> Self {
         Media::Image(Arc::new(MediaImage {
             src: src.to_string(),
-            hash: hash_from_string(&src),
+            hash: hash_from_string(src),
             image
         }))
     }
+
+    pub fn font(src: &str, font: Font) -> Self {
+        Media::Font(Arc::new(MediaFont {
+            src: src.to_string(),
+            hash: hash_from_string(src),
+            font
+        }))
+    }
 }
 
 impl std::fmt::Debug for Media {
@@ -79,6 +99,7 @@ impl std::fmt::Debug for Media {
         match self {
             Media::Svg(svg) => write!(f, "Media::Svg({:?})", svg),
             Media::Image(image) => write!(f, "Media::Image({:?})", image),
+            Media::Font(font) => write!(f, "Media::Font({:?})", font),
         }
     }
 }
@@ -5,8 +5,8 @@ use file_type::FileType;
 use reqwest::header::HeaderValue;
 use resvg::usvg;
 use crate::common::hash::{hash_from_data, hash_from_string, Sha256Hash};
-use crate::common::media::{Media, MediaId, MediaImage, MediaSvg, MediaType};
-use crate::common::media::Svg;
+use crate::common::media::{Media, MediaFont, MediaId, MediaImage, MediaSvg, MediaType};
+use crate::common::media::{Font, Svg};
 
 const DEFAULT_SVG_ID: MediaId = MediaId::new(0);
 const DEFAULT_IMAGE_ID: MediaId = MediaId::new(1);
@@ -129,6 +129,21 @@ impl MediaStore {
                 let mut cache = self.cache.write().expect("Failed to lock cache");
                 cache.insert(h, media_id);
 
+                media_id
+            }
+            MediaType::Font => {
+                let font = Font::from_data(data)?;
+
+                let media = Media::font("gosub://data/font", font);
+                let media_id = *self.next_id.read().expect("Failed to lock next media ID");
+                *self.next_id.write().expect("Failed to lock next media ID") += 1;
+
+                let mut entries = self.entries.write().expect("Failed to lock entries");
+                entries.insert(media_id, Arc::new(media));
+
+                let mut cache = self.cache.write().expect("Failed to lock cache");
+                cache.insert(h, media_id);
+
                 media_id
             }
         };
@@ -139,6 +154,38 @@ impl MediaStore {
         Ok(media_id)
     }
 
+    /// Loads media from data and stores it under the given media ID instead of allocating a new one. This is
+    /// used when media IDs must stay stable, for instance when replaying a saved display list.
+    pub fn load_media_from_data_with_id(&self, media_id: MediaId, media_type: MediaType, src: &str, data: &[u8]) -> anyhow::Result<()> {
+        let media = match media_type {
+            MediaType::Svg => {
+                let Ok(svg_tree) = usvg::Tree::from_data(data, &usvg::Options::default()) else {
+                    anyhow::bail!("Failed to parse SVG data");
+                };
+                Media::svg(src, Svg::new(svg_tree))
+            }
+            MediaType::Image => {
+                let Ok(img) = image::load_from_memory(data) else {
+                    anyhow::bail!("Failed to parse image data");
+                };
+                Media::image(src, img.to_rgba8())
+            }
+            MediaType::Font => Media::font(src, Font::from_data(data)?),
+        };
+
+        let mut entries = self.entries.write().expect("Failed to lock entries");
+        entries.insert(media_id, Arc::new(media));
+
+        // Make sure newly loaded media will never overwrite this media ID
+        let mut next_id = self.next_id.write().expect("Failed to lock next media ID");
+        if *next_id <= media_id {
+            *next_id = media_id;
+            *next_id += 1;
+        }
+
+        Ok(())
+    }
+
     fn load_media_from_source(&self, src: &str) -> anyhow::Result<MediaId> {
         println!("Loading non-cached media from path: {}", src);
         let Ok((media_type, raw_data)) = self.fetch_resource(src) else {
@@ -166,6 +213,7 @@ impl MediaStore {
 
                 Media::image(src, img.to_rgba8())
             }
+            MediaType::Font => Media::font(src, Font::from_data(&raw_data)?),
         };
 
         let media_id = *self.next_id.read().expect("Failed to lock next media ID");
@@ -195,6 +243,15 @@ impl MediaStore {
         }
     }
 
+    /// Returns a media font. There is no default font, so this returns None when the media is not a font.
+    pub fn get_font(&self, media_id: MediaId) -> Option<Arc<MediaFont>> {
+        let entries = self.entries.read().expect("Failed to lock images");
+        match entries.get(&media_id).map(|media| &**media) {
+            Some(Media::Font(media_font)) => Some(media_font.clone()),
+            _ => None,
+        }
+    }
+
     pub fn update_svg(&self, media_id: MediaId, media: Arc<Media>) {
         let mut entries = self.entries.write().expect("Failed to lock images");
         entries.insert(media_id, media);
@@ -217,6 +274,7 @@ impl MediaStore {
         match media_type {
             MediaType::Svg => entries.get(&DEFAULT_SVG_ID).expect("Failed to get default svg").clone(),
             MediaType::Image => entries.get(&DEFAULT_IMAGE_ID).expect("Failed to get default image").clone(),
+            MediaType::Font => panic!("There is no default font media, use get_font() instead"),
         }
     }
 
@@ -283,6 +341,9 @@ fn ft_detect(ft: &FileType) -> Option<MediaType> {
         if mt.starts_with("image/") {
             return Some(MediaType::Image);
         }
+        if mt.starts_with("font/") || mt == "application/font-woff" || mt == "application/vnd.ms-opentype" {
+            return Some(MediaType::Font);
+        }
     }
 
     None
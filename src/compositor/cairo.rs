@@ -2,7 +2,6 @@ use gtk4::cairo;
 use crate::common::browser_state::get_browser_state;
 use crate::compositor::cairo::compositor::cairo_compositor;
 use crate::compositor::Composable;
-use crate::layering::layer::LayerId;
 
 pub struct CairoCompositorConfig {
     pub cr: cairo::Context,
@@ -21,13 +20,15 @@ impl Composable for CairoCompositor {
         let binding = get_browser_state();
         let state = binding.read().expect("Failed to get browser state");
 
-        let mut layers = vec![];
-        if state.visible_layer_list[0] {
-            layers.push(LayerId::new(0));
-        }
-        if state.visible_layer_list[1] {
-            layers.push(LayerId::new(1));
-        }
+        let Some(ref tile_list) = state.tile_list else {
+            log::error!("No tile list found");
+            return;
+        };
+        let layers = tile_list.read().expect("Failed to get tile list")
+            .display_list
+            .layer_list
+            .composited_layers(&state.visible_layer_list);
+        drop(state);
 
         cairo_compositor(&config.cr, layers);
     }
@@ -14,9 +14,16 @@ pub fn compose_layer(cr: &cairo::Context, layer_id: LayerId) {
     let binding = get_browser_state();
     let state = binding.read().expect("Failed to get browser state");
 
-    let tile_ids = state.tile_list.read().expect("Failed to get tile list").get_intersecting_tiles(layer_id, state.viewport);
+    let Some(ref tile_list) = state.tile_list else {
+        log::error!("No tile list found");
+        return;
+    };
+
+    // Fixed and sticky layers are moved with the scroll offset, without repainting their tiles
+    let offset = tile_list.read().expect("Failed to get tile list").layer_offset(layer_id, state.viewport);
+    let tile_ids = tile_list.read().expect("Failed to get tile list").get_visible_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
-        let binding = state.tile_list.write().expect("Failed to get tile list");
+        let binding = tile_list.write().expect("Failed to get tile list");
         let Some(tile) = binding.get_tile(tile_id) else {
             log::warn!("Tile not found: {:?}", tile_id);
             continue;
@@ -45,12 +52,12 @@ pub fn compose_layer(cr: &cairo::Context, layer_id: LayerId) {
         ).expect("Failed to create image surface");
 
         cr.rectangle(
-            tile.rect.x,
-            tile.rect.y,
+            tile.rect.x + offset.x,
+            tile.rect.y + offset.y,
             tile.rect.height,
             tile.rect.width,
         );
-        _ = cr.set_source_surface(surface, tile.rect.x, tile.rect.y);
+        _ = cr.set_source_surface(surface, tile.rect.x + offset.x, tile.rect.y + offset.y);
         _ = cr.fill();
     }
 
@@ -18,7 +18,9 @@ pub fn compose_layer(canvas: &skia_safe::canvas::Canvas, layer_id: LayerId) {
         return;
     };
 
-    let tile_ids = tile_list.read().expect("Failed to get tile list").get_intersecting_tiles(layer_id, state.viewport);
+    // Fixed and sticky layers are moved with the scroll offset, without repainting their tiles
+    let offset = tile_list.read().expect("Failed to get tile list").layer_offset(layer_id, state.viewport);
+    let tile_ids = tile_list.read().expect("Failed to get tile list").get_visible_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
         let binding = tile_list.write().expect("Failed to get tile list");
         let Some(tile) = binding.get_tile(tile_id) else {
@@ -56,7 +58,7 @@ pub fn compose_layer(canvas: &skia_safe::canvas::Canvas, layer_id: LayerId) {
 
         canvas.draw_image(
             &img,
-            (tile.rect.x.round() as f32, tile.rect.y.round() as f32),
+            ((tile.rect.x + offset.x).round() as f32, (tile.rect.y + offset.y).round() as f32),
             None,
         );
     }
@@ -1,7 +1,6 @@
 use crate::common::browser_state::get_browser_state;
 use crate::compositor::Composable;
 use crate::compositor::vello::compositor::vello_compositor;
-use crate::layering::layer::LayerId;
 
 pub struct VelloCompositorConfig {}
 
@@ -17,13 +16,15 @@ impl Composable for VelloCompositor {
         let binding = get_browser_state();
         let state = binding.read().expect("Failed to get browser state");
 
-        let mut layers = vec![];
-        if state.visible_layer_list[0] {
-            layers.push(LayerId::new(0));
-        }
-        if state.visible_layer_list[1] {
-            layers.push(LayerId::new(1));
-        }
+        let Some(ref tile_list) = state.tile_list else {
+            log::error!("No tile list found");
+            return vello::Scene::new();
+        };
+        let layers = tile_list.read().expect("Failed to get tile list")
+            .display_list
+            .layer_list
+            .composited_layers(&state.visible_layer_list);
+        drop(state);
 
         // Compose the scene from the different layers we have selected
         vello_compositor(layers)
@@ -23,7 +23,9 @@ pub fn compose_layer(scene: &mut vello::Scene, layer_id: LayerId) {
         return;
     };
 
-    let tile_ids = tile_list.read().expect("Failed to get tile list").get_intersecting_tiles(layer_id, state.viewport);
+    // Fixed and sticky layers are moved with the scroll offset, without repainting their tiles
+    let offset = tile_list.read().expect("Failed to get tile list").layer_offset(layer_id, state.viewport);
+    let tile_ids = tile_list.read().expect("Failed to get tile list").get_visible_tiles(layer_id, state.viewport);
     // let tile_ids = tile_list.get_intersecting_tiles(layer_id, state.viewport);
     for tile_id in tile_ids {
         let binding = tile_list.write().expect("Failed to get tile list");
@@ -55,7 +57,7 @@ pub fn compose_layer(scene: &mut vello::Scene, layer_id: LayerId) {
 
         scene.draw_image(
             &surface,
-            Affine::translate((tile.rect.x.round(), tile.rect.y.round())),
+            Affine::translate(((tile.rect.x + offset.x).round(), (tile.rect.y + offset.y).round())),
         );
     }
 
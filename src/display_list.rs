@@ -0,0 +1,595 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use resvg::usvg;
+use rstar::AABB;
+use rstar::primitives::GeomWithData;
+use serde::{Deserialize, Serialize};
+use crate::common::font::face::{self, FontFace};
+use crate::common::font::FontSlant;
+use crate::common::geo::Rect;
+use crate::common::get_media_store;
+use crate::common::media::{FontFormat, MediaId, MediaType};
+use crate::layering::layer::{LayerId, LayerList};
+use crate::layouter::{LayoutElementId, LayoutTree};
+use crate::painter::commands::brush::Brush;
+use crate::painter::commands::PaintCommand;
+use crate::painter::Painter;
+
+/// Filename of the display list JSON when saving a display list to a directory
+const DISPLAY_LIST_FILENAME: &str = "display_list.json";
+
+/*
+
+DisplayList
+    wrapped(LayerList)
+    layers: hashmap<LayerId, LayerDisplayList>
+
+LayerDisplayList
+    layer_id
+    items: Vec<DisplayItem>     (in paint order)
+    rstar_tree
+
+DisplayItem
+    element_id
+    rect
+    commands
+
+DisplayListSnapshot         (serialized form)
+    media: Vec<MediaReference>
+    layers: Vec<LayerDisplayList>
+
+ */
+
+/// A single entry in the display list. It holds all the paint commands for a single layout element. These commands
+/// are generated once per element, no matter how many tiles the element spans.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DisplayItem {
+    /// Layout element that generated the paint commands
+    pub element_id: LayoutElementId,
+    /// Bounding box of the element in layer coordinates (the margin box)
+    pub rect: Rect,
+    /// Paint commands that will draw the element
+    pub commands: Vec<PaintCommand>,
+}
+
+/// The display list of a single layer. Items are stored in paint order.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "SerializedLayerDisplayList")]
+pub struct LayerDisplayList {
+    /// Layer ID of this display list
+    pub layer_id: LayerId,
+    /// Display items in paint order
+    pub items: Vec<DisplayItem>,
+    /// R* tree for fast spatial queries. The data points to the index of the item in `items`.
+    #[serde(skip)]
+    rstar_tree: rstar::RTree<GeomWithData<rstar::primitives::Rectangle<[f64; 2]>, usize>>,
+}
+
+/// Layer display lists are deserialized through this structure so we can rebuild the R* tree
+#[derive(Deserialize)]
+struct SerializedLayerDisplayList {
+    layer_id: LayerId,
+    items: Vec<DisplayItem>,
+}
+
+impl From<SerializedLayerDisplayList> for LayerDisplayList {
+    fn from(value: SerializedLayerDisplayList) -> Self {
+        LayerDisplayList::new(value.layer_id, value.items)
+    }
+}
+
+impl LayerDisplayList {
+    pub fn new(layer_id: LayerId, items: Vec<DisplayItem>) -> Self {
+        let rtree_data: Vec<_> = items.iter().enumerate().map(|(idx, item)| {
+            GeomWithData::new(
+                rstar::primitives::Rectangle::from_corners(
+                    [item.rect.x, item.rect.y],
+                    [item.rect.x + item.rect.width, item.rect.y + item.rect.height]
+                ),
+                idx
+            )
+        }).collect();
+
+        Self {
+            layer_id,
+            items,
+            rstar_tree: rstar::RTree::bulk_load(rtree_data),
+        }
+    }
+
+    /// Returns the bounding box of all items in this layer
+    pub fn bounding_rect(&self) -> Rect {
+        if self.items.is_empty() {
+            return Rect::ZERO;
+        }
+
+        let envelope = self.rstar_tree.root().envelope();
+        let lower = envelope.lower();
+        let upper = envelope.upper();
+        Rect::new(lower[0], lower[1], upper[0] - lower[0], upper[1] - lower[1])
+    }
+
+    /// Returns all the items that intersect with the given rect. The items are returned in paint order.
+    pub fn intersects_with(&self, rect: Rect) -> Vec<&DisplayItem> {
+        let mut indices: Vec<usize> = self.rstar_tree
+            .locate_in_envelope_intersecting(&AABB::from_corners(
+                [rect.x, rect.y],
+                [rect.x + rect.width, rect.y + rect.height]
+            ))
+            .map(|x| x.data)
+            .collect();
+
+        // The R* tree does not keep any order, so we need to restore the paint order
+        indices.sort_unstable();
+
+        indices.into_iter().map(|idx| &self.items[idx]).collect()
+    }
+}
+
+/// Media that is referenced by the paint commands. The media is saved next to the display list JSON, so a
+/// display list can be replayed without fetching anything.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MediaReference {
+    /// Media ID as used in the paint commands
+    pub media_id: MediaId,
+    /// Type of the media
+    pub media_type: MediaType,
+    /// Filename of the media, relative to the display list JSON
+    pub file: String,
+}
+
+/// Web font that was registered when the display list was saved. Text commands refer to fonts by family, so
+/// all registered fonts are saved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FontReference {
+    /// Family the font is registered under
+    pub family: String,
+    pub weight: usize,
+    pub slant: FontSlant,
+    /// Filename of the font, relative to the display list JSON
+    pub file: String,
+}
+
+/// Serialized form of a display list. It holds the display lists of all layers (in layer order) and the media
+/// that is referenced by their paint commands.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DisplayListSnapshot {
+    /// Media referenced by the paint commands
+    pub media: Vec<MediaReference>,
+    /// Registered web fonts
+    #[serde(default)]
+    pub fonts: Vec<FontReference>,
+    /// Display lists in layer order
+    pub layers: Vec<LayerDisplayList>,
+}
+
+impl DisplayListSnapshot {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<DisplayListSnapshot> {
+        serde_json::from_str(json)
+    }
+
+    /// Loads a display list that has been saved with `DisplayList::save()`. All referenced media is loaded into
+    /// the media store under its original media ID, so the paint commands can be replayed as-is.
+    pub fn load(path: &Path) -> anyhow::Result<DisplayListSnapshot> {
+        let json = std::fs::read_to_string(path)?;
+        let snapshot = DisplayListSnapshot::from_json(&json)?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        let media_store = get_media_store().read().expect("Failed to get media store");
+        for media in &snapshot.media {
+            let data = std::fs::read(base_dir.join(&media.file))?;
+            media_store.load_media_from_data_with_id(media.media_id, media.media_type.clone(), &media.file, &data)?;
+        }
+
+        for font in &snapshot.fonts {
+            let data = std::fs::read(base_dir.join(&font.file))?;
+            let media_id = media_store.load_media_from_data(MediaType::Font, &data)?;
+            let font_family = media_store.get_font(media_id).and_then(|media_font| media_font.font.family_name.clone());
+
+            face::register_font_face(FontFace {
+                family: font.family.clone(),
+                font_family,
+                weight: font.weight,
+                slant: font.slant,
+                media_id,
+            });
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Returns the rect that covers all the layers, starting at the origin
+    pub fn page_rect(&self) -> Rect {
+        let mut width: f64 = 0.0;
+        let mut height: f64 = 0.0;
+        for layer in &self.layers {
+            let rect = layer.bounding_rect();
+            width = width.max(rect.x + rect.width);
+            height = height.max(rect.y + rect.height);
+        }
+
+        Rect::new(0.0, 0.0, width, height)
+    }
+}
+
+/// The display list holds the paint commands of all elements per layer. It sits between the layering and the
+/// tiling stage: tiles select their paint commands from the display list by rect instead of painting each
+/// element themselves.
+pub struct DisplayList {
+    /// Wrapped layer list
+    pub layer_list: Arc<LayerList>,
+    /// Display list per layer
+    pub layers: RwLock<HashMap<LayerId, LayerDisplayList>>,
+}
+
+impl std::fmt::Debug for DisplayList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DisplayList")
+            .field("layer_list", &self.layer_list)
+            .field("layers", &self.layers)
+            .finish()
+    }
+}
+
+impl DisplayList {
+    pub fn new(layer_list: LayerList) -> DisplayList {
+        let display_list = DisplayList {
+            layer_list: Arc::new(layer_list),
+            layers: RwLock::new(HashMap::new()),
+        };
+
+        display_list.generate();
+        display_list
+    }
+
+    /// Repaints all elements in all layers. This is needed when the painting depends on state that has
+    /// been changed (like wireframing).
+    pub fn invalidate_all(&self) {
+        self.generate();
+    }
+
+    /// Repaints a single element. Its position in the display list does not change.
+    pub fn invalidate_element(&self, element_id: LayoutElementId) {
+        self.invalidate_elements(&[element_id]);
+    }
+
+    /// Repaints the given elements. Their bounding boxes are taken from the layout tree again, so this can be
+    /// used after the elements have been laid out again. Their position in the display list does not change.
+    pub fn invalidate_elements(&self, element_ids: &[LayoutElementId]) {
+        let painter = Painter::new(self.layer_list.clone());
+        let element_ids: HashSet<LayoutElementId> = element_ids.iter().copied().collect();
+
+        let mut layers = self.layers.write().expect("Failed to lock display list layers");
+        for layer in layers.values_mut() {
+            let mut moved = false;
+            for item in layer.items.iter_mut().filter(|item| element_ids.contains(&item.element_id)) {
+                if let Some(element) = self.layer_list.layout_tree.get_node_by_id(item.element_id) {
+                    moved |= item.rect != element.box_model.margin_box;
+                    item.rect = element.box_model.margin_box;
+                }
+                item.commands = painter.paint(item.element_id);
+            }
+
+            // The R* tree holds the bounding boxes of the items, so it needs to be built again
+            if moved {
+                *layer = LayerDisplayList::new(layer.layer_id, std::mem::take(&mut layer.items));
+            }
+        }
+    }
+
+    /// Returns the layout tree so it can be laid out again, or None when the layer list is shared (which
+    /// is only the case while it is being painted)
+    pub fn layout_tree_mut(&mut self) -> Option<&mut LayoutTree> {
+        Arc::get_mut(&mut self.layer_list).map(LayerList::layout_tree_mut)
+    }
+
+    /// Updates the layers and repaints the given elements after they have been laid out again
+    pub fn relayout(&self, changed: &[LayoutElementId]) {
+        self.layer_list.relayout(changed);
+        self.invalidate_elements(changed);
+    }
+
+    /// Returns (a copy of) all the items in the given layer that intersect with the rect, in paint order
+    pub fn intersects_with(&self, layer_id: LayerId, rect: Rect) -> Vec<DisplayItem> {
+        let layers = self.layers.read().expect("Failed to lock display list layers");
+        let Some(layer) = layers.get(&layer_id) else {
+            return vec![];
+        };
+
+        layer.intersects_with(rect).into_iter().cloned().collect()
+    }
+
+    /// Returns the serializable form of the display list. Media files are named after their position in the
+    /// media list, which is the order in which they are first used.
+    pub fn snapshot(&self) -> DisplayListSnapshot {
+        let layers = self.layers.read().expect("Failed to lock display list layers");
+        let layer_ids = self.layer_list.layer_ids.read().expect("Failed to lock layer IDs");
+
+        let ordered: Vec<LayerDisplayList> = layer_ids.iter().filter_map(|layer_id| layers.get(layer_id).cloned()).collect();
+
+        let media = referenced_media(&ordered).into_iter().enumerate().map(|(idx, (media_id, media_type))| {
+            let extension = match media_type {
+                MediaType::Svg => "svg",
+                MediaType::Image => "png",
+                MediaType::Font => "font",
+            };
+
+            MediaReference {
+                media_id,
+                media_type,
+                file: format!("media/{}.{}", idx, extension),
+            }
+        }).collect();
+
+        let media_store = get_media_store().read().expect("Failed to get media store");
+        let fonts = face::font_faces().into_iter().enumerate().filter_map(|(idx, font_face)| {
+            let media_font = media_store.get_font(font_face.media_id)?;
+
+            Some(FontReference {
+                family: font_face.family,
+                weight: font_face.weight,
+                slant: font_face.slant,
+                file: font_file(idx, media_font.font.format),
+            })
+        }).collect();
+
+        DisplayListSnapshot {
+            media,
+            fonts,
+            layers: ordered,
+        }
+    }
+
+    /// Serializes the display list of all layers (in layer order) to JSON. This allows us to dump, diff and
+    /// replay display lists.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        self.snapshot().to_json()
+    }
+
+    /// Saves the display list as JSON into the given directory, together with all the media it references.
+    /// Returns the path of the JSON file.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        let snapshot = self.snapshot();
+
+        std::fs::create_dir_all(dir.join("media"))?;
+        std::fs::create_dir_all(dir.join("fonts"))?;
+
+        let media_store = get_media_store().read().expect("Failed to get media store");
+        for media in &snapshot.media {
+            let path = dir.join(&media.file);
+            match media.media_type {
+                MediaType::Svg => {
+                    let media_svg = media_store.get_svg(media.media_id);
+                    std::fs::write(path, media_svg.svg.tree.to_string(&usvg::WriteOptions::default()))?;
+                }
+                MediaType::Image => {
+                    let media_image = media_store.get_image(media.media_id);
+                    media_image.image.save(path)?;
+                }
+                MediaType::Font => {
+                    if let Some(media_font) = media_store.get_font(media.media_id) {
+                        std::fs::write(path, media_font.font.data.as_slice())?;
+                    }
+                }
+            }
+        }
+
+        for (idx, font_face) in face::font_faces().into_iter().enumerate() {
+            if let Some(media_font) = media_store.get_font(font_face.media_id) {
+                std::fs::write(dir.join(font_file(idx, media_font.font.format)), media_font.font.data.as_slice())?;
+            }
+        }
+
+        let path = dir.join(DISPLAY_LIST_FILENAME);
+        std::fs::write(&path, snapshot.to_json()?)?;
+
+        Ok(path)
+    }
+
+    fn generate(&self) {
+        let painter = Painter::new(self.layer_list.clone());
+
+        let mut display_layers = HashMap::new();
+
+        let layers = self.layer_list.layers.read().expect("Failed to lock layers");
+        for layer_id in self.layer_list.layer_ids.read().expect("Failed to lock layer IDs").iter() {
+            let Some(layer) = layers.get(layer_id) else {
+                continue;
+            };
+
+            let items = layer.elements.iter().filter_map(|&element_id| {
+                let Some(element) = self.layer_list.layout_tree.get_node_by_id(element_id) else {
+                    log::warn!("Warning: Element {:?} not found in layout tree!", element_id);
+                    return None;
+                };
+
+                Some(DisplayItem {
+                    element_id,
+                    rect: element.box_model.margin_box,
+                    commands: painter.paint(element_id),
+                })
+            }).collect();
+
+            display_layers.insert(*layer_id, LayerDisplayList::new(*layer_id, items));
+        }
+
+        *self.layers.write().expect("Failed to lock display list layers") = display_layers;
+    }
+}
+
+/// Returns the filename of the n-th registered font when saving a display list
+fn font_file(idx: usize, format: FontFormat) -> String {
+    format!("fonts/{}.{}", idx, format.extension())
+}
+
+/// Returns all media that is referenced by the paint commands in the layers, in order of first use
+fn referenced_media(layers: &[LayerDisplayList]) -> Vec<(MediaId, MediaType)> {
+    let mut media = Vec::new();
+
+    let mut add = |media_id: MediaId, media_type: MediaType| {
+        if !media.iter().any(|(id, _)| *id == media_id) {
+            media.push((media_id, media_type));
+        }
+    };
+
+    for item in layers.iter().flat_map(|layer| layer.items.iter()) {
+        for command in &item.commands {
+            match command {
+                PaintCommand::Rectangle(rectangle) => {
+                    if let Some(Brush::Image(media_id)) = rectangle.background() {
+                        add(*media_id, MediaType::Image);
+                    }
+                    if let Brush::Image(media_id) = rectangle.border().brush() {
+                        add(media_id, MediaType::Image);
+                    }
+                }
+                PaintCommand::Text(text) => {
+                    if let Brush::Image(media_id) = &text.brush {
+                        add(*media_id, MediaType::Image);
+                    }
+                }
+                PaintCommand::Svg(svg) => {
+                    add(svg.media_id, MediaType::Svg);
+                }
+                PaintCommand::Image(image) => {
+                    add(image.media_id, MediaType::Image);
+                }
+            }
+        }
+    }
+
+    media
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::browser_state::{init_browser_state, BrowserState, WireframeState};
+    use crate::common::document::document::Document;
+    use crate::common::document::style::{Display, StyleProperty, StylePropertyList, StyleValue, Unit};
+    use crate::common::geo::Dimension;
+    use crate::layouter::taffy::TaffyLayouter;
+    use crate::layouter::text::NoShaper;
+    use crate::layouter::{CanLayout, Viewport};
+    use crate::rendertree_builder::RenderTree;
+    use std::collections::HashSet;
+    use std::sync::Once;
+
+    fn block_style(height: f32) -> Option<StylePropertyList> {
+        let mut style = StylePropertyList::new();
+        style.set_property(StyleProperty::Display, StyleValue::Display(Display::Block));
+        if height > 0.0 {
+            style.set_property(StyleProperty::Height, StyleValue::Unit(height, Unit::Px));
+        }
+        Some(style)
+    }
+
+    /// Returns the display list of <body><div/><div/><div/></body>, where each div is 100px high
+    fn display_list() -> DisplayList {
+        display_list_with(&mut TaffyLayouter::new(Arc::new(NoShaper)))
+    }
+
+    /// Returns the display list of the document laid out by the given layouter for an 800x600 viewport
+    fn display_list_with(layouter: &mut TaffyLayouter) -> DisplayList {
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, block_style(0.0));
+        for _ in 0..3 {
+            let div_id = doc.new_element(Some(body_id), "div", None, false, block_style(100.0));
+            doc.add_child(body_id, div_id);
+        }
+        doc.set_root(body_id);
+        let doc = Arc::new(doc);
+
+        // The painter reads the wireframe and hover state from the browser state
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            init_browser_state(BrowserState {
+                visible_layer_list: vec![true; 10],
+                wireframed: WireframeState::None,
+                debug_hover: false,
+                show_tilegrid: false,
+                current_hovered_element: None,
+                viewport: Rect::new(0.0, 0.0, 800.0, 600.0),
+                device_pixel_ratio: 1.0,
+                document: doc.clone(),
+                layouter: None,
+                tile_list: None,
+            });
+        });
+
+        let mut render_tree = RenderTree::new(doc);
+        render_tree.parse();
+        let layout_tree = layouter.layout(render_tree, Viewport::new(Some(Dimension::new(800.0, 600.0)), 1.0));
+
+        DisplayList::new(LayerList::new(layout_tree))
+    }
+
+    #[test]
+    fn test_display_list() {
+        let display_list = display_list();
+        let layout_tree = &display_list.layer_list.layout_tree;
+
+        // Each element is painted exactly once, no matter how many tiles it spans
+        let layers = display_list.layers.read().unwrap();
+        let painted: Vec<_> = layers.values().flat_map(|layer| layer.items.iter().map(|item| item.element_id)).collect();
+        let unique: HashSet<_> = painted.iter().copied().collect();
+        assert_eq!(painted.len(), unique.len());
+        assert_eq!(unique, layout_tree.arena.keys().copied().collect());
+
+        // A tile on the border of the second and third div selects the body and both divs, in paint order
+        let layer_id = display_list.layer_list.layer_ids.read().unwrap()[0];
+        let tile = Rect::new(0.0, 150.0, 800.0, 100.0);
+        let selected: Vec<_> = display_list.intersects_with(layer_id, tile).iter().map(|item| item.element_id).collect();
+        let expected: Vec<_> = layers[&layer_id]
+            .items
+            .iter()
+            .filter(|item| item.rect.y < tile.y + tile.height && item.rect.y + item.rect.height > tile.y)
+            .map(|item| item.element_id)
+            .collect();
+        assert_eq!(selected, expected);
+        let heights: Vec<_> = selected.iter().map(|id| layout_tree.get_node_by_id(*id).unwrap().box_model.margin_box.height).collect();
+        assert_eq!(heights, vec![300.0, 100.0, 100.0]);
+    }
+
+    #[test]
+    fn test_relayout() {
+        let mut layouter = TaffyLayouter::new(Arc::new(NoShaper));
+        let mut display_list = display_list_with(&mut layouter);
+        let layer_id = display_list.layer_list.layer_ids.read().unwrap()[0];
+        assert_eq!(display_list.intersects_with(layer_id, Rect::new(500.0, 0.0, 10.0, 10.0)).len(), 2);
+
+        // The layer list is not shared, so the layout tree can be laid out again in place
+        let layout_tree = display_list.layout_tree_mut().unwrap();
+        let changed = layouter.relayout(layout_tree, Viewport::new(Some(Dimension::new(400.0, 600.0)), 1.0));
+        assert_eq!(changed.len(), 4);
+        display_list.relayout(&changed);
+
+        // The items and the R* tree have the bounding boxes of the new layout
+        let layers = display_list.layers.read().unwrap();
+        assert!(layers[&layer_id].items.iter().all(|item| item.rect.width == 400.0));
+        assert_eq!(layers[&layer_id].bounding_rect(), Rect::new(0.0, 0.0, 400.0, 300.0));
+        drop(layers);
+        assert!(display_list.intersects_with(layer_id, Rect::new(500.0, 0.0, 10.0, 10.0)).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let display_list = display_list();
+
+        let json = display_list.to_json().unwrap();
+        let snapshot = DisplayListSnapshot::from_json(&json).unwrap();
+        assert_eq!(snapshot.to_json().unwrap(), json);
+        assert_eq!(snapshot.layers.len(), display_list.layers.read().unwrap().len());
+
+        // The R* tree is rebuilt when a snapshot is loaded
+        let layer = &snapshot.layers[0];
+        assert_eq!(layer.bounding_rect(), Rect::new(0.0, 0.0, 800.0, 300.0));
+        assert_eq!(layer.intersects_with(Rect::new(0.0, 250.0, 10.0, 10.0)).len(), 2);
+    }
+}
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock};
-use crate::layouter::{LayoutElementId, LayoutTree};
+use serde::{Deserialize, Serialize};
+use crate::common::geo::{Coordinate, Rect};
+use crate::layouter::{LayoutElementId, LayoutTree, ScrollPosition};
 
 /// ID for layers
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct LayerId(u64);
 
 impl LayerId {
@@ -34,7 +36,9 @@ pub struct Layer {
     #[allow(unused)]
     pub order: isize,
     /// Elements in this layer
-    pub elements: Vec<LayoutElementId>
+    pub elements: Vec<LayoutElementId>,
+    /// How the layer moves when the document is scrolled
+    pub scroll_position: ScrollPosition,
 }
 
 impl Layer {
@@ -42,7 +46,18 @@ impl Layer {
         Layer {
             layer_id,
             order,
-            elements: Vec::new()
+            elements: Vec::new(),
+            scroll_position: ScrollPosition::Scrolled,
+        }
+    }
+
+    /// Returns where the compositor draws the layer, relative to its position in the document, when the document
+    /// is scrolled to the given viewport
+    pub fn offset(&self, viewport: Rect) -> Coordinate {
+        match &self.scroll_position {
+            ScrollPosition::Scrolled => Coordinate::ZERO,
+            ScrollPosition::Fixed => Coordinate::new(viewport.x, viewport.y),
+            ScrollPosition::Sticky(constraint) => constraint.offset(viewport),
         }
     }
 
@@ -93,6 +108,34 @@ impl LayerList {
         layer_list
     }
 
+    /// Returns the layout tree so it can be laid out again. The tree is copied when it is shared.
+    pub fn layout_tree_mut(&mut self) -> &mut LayoutTree {
+        Arc::make_mut(&mut self.layout_tree)
+    }
+
+    /// Updates the layers after the given elements have been laid out again. A fixed or sticky layer takes the
+    /// scroll position of the element it was created for (its first element), as sticky constraints depend on
+    /// the layout.
+    pub fn relayout(&self, changed: &[LayoutElementId]) {
+        let mut layers = self.layers.write().expect("Failed to lock layers");
+        for layer in layers.values_mut() {
+            if matches!(layer.scroll_position, ScrollPosition::Scrolled) {
+                continue;
+            }
+
+            let Some(element_id) = layer.elements.first() else {
+                continue;
+            };
+            if !changed.contains(element_id) {
+                continue;
+            }
+
+            if let Some(layout_element) = self.layout_tree.get_node_by_id(*element_id) {
+                layer.scroll_position = layout_element.scroll_position.clone();
+            }
+        }
+    }
+
     /// @TODO: This must be done through rstar!
     /// Find the element at the given coordinates. It will return the given element if it is found or None otherwise
     pub fn find_element_at(&self, x: f64, y: f64) -> Option<LayoutElementId> {
@@ -120,6 +163,26 @@ impl LayerList {
         None
     }
 
+    /// Returns the layers in the order they are composited, from bottom to top. Layers that are switched off in
+    /// the visibility list (indexed by layer id) are left out.
+    pub fn composited_layers(&self, visible: &[bool]) -> Vec<LayerId> {
+        let layers = self.layers.read().expect("Failed to lock layers");
+        let mut layer_ids: Vec<LayerId> = self.layer_ids.read().expect("Failed to lock layer IDs")
+            .iter()
+            .filter(|layer_id| visible.get(layer_id.0 as usize).copied().unwrap_or(true))
+            .copied()
+            .collect();
+        layer_ids.sort_by_key(|layer_id| layers.get(layer_id).map_or(0, |layer| layer.order));
+
+        layer_ids
+    }
+
+    /// Returns where the compositor draws the layer, relative to its position in the document
+    pub fn layer_offset(&self, layer_id: LayerId, viewport: Rect) -> Coordinate {
+        let layers = self.layers.read().expect("Failed to lock layers");
+        layers.get(&layer_id).map_or(Coordinate::ZERO, |layer| layer.offset(viewport))
+    }
+
     // Create a new layer to the list at the given order
     pub fn new_layer(&self, order: isize) -> LayerId {
         let layer = Layer::new(self.next_layer_id(), order);
@@ -173,8 +236,25 @@ impl LayerList {
             })
             .unwrap_or(false);
 
-        // When we detect an image, we create a new layer for it
-        if is_image {
+        // Fixed and sticky elements are moved by the compositor, so they get a layer of their own, together with
+        // their descendants
+        let layer_id = match &layout_element.scroll_position {
+            ScrollPosition::Scrolled => layer_id,
+            scroll_position => {
+                let positioned_layer_id = self.new_layer(2);
+                if let Some(layer) = self.layers.write().expect("Failed to lock layers").get_mut(&positioned_layer_id) {
+                    layer.scroll_position = scroll_position.clone();
+                }
+                positioned_layer_id
+            }
+        };
+        let scrolled = self.layers.read().expect("Failed to lock layers")
+            .get(&layer_id)
+            .is_some_and(|layer| matches!(layer.scroll_position, ScrollPosition::Scrolled));
+
+        // When we detect an image, we create a new layer for it. Images inside fixed and sticky elements stay in
+        // the layer of that element, so they move together.
+        if is_image && scrolled {
             let image_layer_id = self.new_layer(1);
             if let Some(mut layers) = self.get_layer_mut(image_layer_id) {
                 if let Some(image_layer) = layers.get_mut(&image_layer_id) {
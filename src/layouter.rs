@@ -2,20 +2,33 @@ use std::collections::HashMap;
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock};
 use rstar::primitives::GeomWithData;
+use serde::{Deserialize, Serialize};
 use crate::layouter::box_model::BoxModel;
 use crate::rendertree_builder::{RenderTree, RenderNodeId};
 use crate::common::document::node::{NodeId as DomNodeId, NodeId};
-use crate::common::geo::{Coordinate, Dimension};
+use crate::common::document::style::WritingMode;
+use crate::common::font::FontDescriptor;
+use crate::common::geo::{Coordinate, Dimension, Rect};
 use crate::common::media::MediaId;
-use crate::layouter::text::Alignment;
+use crate::layouter::float::{Exclusion, FlowLine};
+use crate::layouter::text::{Alignment, TextDirection, TextStyle, TextWrapping};
 
+pub mod conformance;
+pub mod flow;
+pub mod replaced;
 pub mod taffy;
 pub mod text;
 mod box_model;
+mod context;
 mod css_taffy_converter;
+#[cfg(test)]
+mod fixtures;
+mod float;
+mod srcset;
+mod table;
 
 /// ID's for layout elements
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct LayoutElementId(u64);
 
 impl LayoutElementId {
@@ -41,12 +54,8 @@ impl std::fmt::Display for LayoutElementId {
 pub struct ElementContextText {
     /// Node ID of the text in the DOM
     pub node_id: DomNodeId,
-    /// Font family (can be comma separated)
-    pub font_family: String,
-    /// Size of the font in pixels
-    pub font_size: f64,
-    /// Weight (100-700) of the font
-    pub font_weight: usize,
+    /// Font (family, size, weight, slant and stretch) of the text
+    pub font: FontDescriptor,
     /// Line height of the text. Most likely not needed anymore since we already calculated the text_offset
     pub line_height: f64,
     pub text: String,
@@ -54,6 +63,31 @@ pub struct ElementContextText {
     pub text_offset: Coordinate,
     /// Alignment of font
     pub alignment: Alignment,
+    /// Base direction of the text
+    pub direction: TextDirection,
+    /// Line wrapping and word breaking of the text
+    pub wrapping: TextWrapping,
+    /// Writing mode of the text. Vertical text is laid out in columns.
+    pub writing_mode: WritingMode,
+    /// Columns of vertical text (see text::vertical_columns). Filled in when the final size of the text is known.
+    pub columns: Vec<String>,
+    /// Parts of the text box that are taken by floats (see float::Exclusion)
+    pub exclusions: Vec<Exclusion>,
+    /// Lines of text that flows around floats. Filled in when the final size of the text is known, and only when
+    /// there are exclusions.
+    pub lines: Vec<FlowLine>,
+}
+
+impl ElementContextText {
+    /// Returns the style that is needed to shape this text
+    pub fn text_style(&self) -> TextStyle {
+        TextStyle {
+            font: self.font.clone(),
+            line_height: self.line_height,
+            alignment: self.alignment,
+            direction: self.direction,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -64,7 +98,7 @@ pub struct ElementContextSvg {
     pub src: String,
     /// ID of the SVG inside the media store
     pub media_id: MediaId,
-    /// Dimension of the SVG. Can be Dimension::ZERO if not known yet
+    /// Natural size of the SVG, from its width and height or its view box
     pub dimension: Dimension,
 }
 
@@ -76,7 +110,7 @@ pub struct ElementContextImage {
     pub src: String,
     /// ID of the image inside the image store
     pub media_id: MediaId,
-    /// Dimension of the image. Can be Dimension::ZERO if not known yet
+    /// Natural size of the image, from the decoded bitmap
     pub dimension: Dimension,
 }
 
@@ -91,14 +125,19 @@ pub enum ElementContext {
 }
 
 impl ElementContext {
-    pub(crate) fn text(font_family: &str, font_size: f64, font_weight: usize, line_height: f64, alignment: Alignment, text: &str, node_id: DomNodeId, text_offset: Coordinate) -> ElementContext {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn text(font: &FontDescriptor, line_height: f64, alignment: Alignment, direction: TextDirection, wrapping: TextWrapping, writing_mode: WritingMode, text: &str, node_id: DomNodeId, text_offset: Coordinate) -> ElementContext {
         Self::Text(ElementContextText{
             node_id,
-            font_family: font_family.to_string(),
-            font_size,
-            font_weight,
+            font: font.clone(),
             line_height,
             alignment,
+            direction,
+            wrapping,
+            writing_mode,
+            columns: Vec::new(),
+            exclusions: Vec::new(),
+            lines: Vec::new(),
             text: text.to_string(),
             text_offset,
         })
@@ -137,8 +176,83 @@ pub struct LayoutElementNode {
     pub box_model: BoxModel,
     /// Element context. Used by different parts of the render engine
     pub context: ElementContext,
+    /// How the element moves when the document is scrolled
+    pub scroll_position: ScrollPosition,
+}
+
+/// How an element moves when the document is scrolled. Fixed and sticky elements are moved by the compositor, so
+/// scrolling does not repaint them.
+#[derive(Debug, Clone, Default)]
+pub enum ScrollPosition {
+    /// Element scrolls with the document
+    #[default]
+    Scrolled,
+    /// Element is positioned relative to the viewport (position: fixed). Its box model is laid out for a viewport
+    /// at the top left of the document.
+    Fixed,
+    /// Element scrolls with the document, but sticks to the edges of the viewport (position: sticky)
+    Sticky(StickyConstraint),
+}
+
+/// Constraint rectangle of a sticky element, which is resolved against the scroll offset at composite time
+#[derive(Debug, Clone)]
+pub struct StickyConstraint {
+    /// Border box of the element at its normal position
+    pub rect: Rect,
+    /// Content box of the containing block. The element never moves outside of it.
+    pub container: Rect,
+    /// Distance that the element keeps to each edge of the viewport, or None when the inset is auto
+    pub top: Option<f64>,
+    pub right: Option<f64>,
+    pub bottom: Option<f64>,
+    pub left: Option<f64>,
+}
+
+impl StickyConstraint {
+    /// Returns how far the element moves from its normal position when the document is scrolled to the given
+    /// viewport. When the element must stick to opposite edges, the top and left insets win.
+    pub fn offset(&self, viewport: Rect) -> Coordinate {
+        Coordinate::new(
+            sticky_offset(
+                (self.rect.x, self.rect.width),
+                (self.container.x, self.container.width),
+                (viewport.x, viewport.width),
+                self.left,
+                self.right,
+            ),
+            sticky_offset(
+                (self.rect.y, self.rect.height),
+                (self.container.y, self.container.height),
+                (viewport.y, viewport.height),
+                self.top,
+                self.bottom,
+            ),
+        )
+    }
+}
+
+/// Resolves the offset of a sticky element along one axis. Each range is given as (start, size).
+fn sticky_offset(rect: (f64, f64), container: (f64, f64), viewport: (f64, f64), start: Option<f64>, end: Option<f64>) -> f64 {
+    let rect_end = rect.0 + rect.1;
+    let container_end = container.0 + container.1;
+
+    if let Some(start) = start {
+        let shift = viewport.0 + start - rect.0;
+        if shift > 0.0 {
+            return shift.min((container_end - rect_end).max(0.0));
+        }
+    }
+    if let Some(end) = end {
+        let shift = viewport.0 + viewport.1 - end - rect_end;
+        if shift < 0.0 {
+            return shift.max((container.0 - rect.0).min(0.0));
+        }
+    }
+
+    0.0
 }
 
+#[derive(Clone)]
 pub struct LayoutTree {
     /// Wrapped render tree
     pub render_tree: RenderTree,
@@ -169,6 +283,24 @@ impl LayoutTree {
         *nid += 1;
         id
     }
+
+    /// Returns the elements whose margin box contains the given point
+    pub fn find_elements_at(&self, point: Coordinate) -> Vec<LayoutElementId> {
+        self.rstar_tree
+            .locate_all_at_point(&[point.x, point.y])
+            .map(|entry| entry.data)
+            .collect()
+    }
+
+    /// Moves the element in the R* tree from its old margin box to its new one
+    pub(crate) fn update_spatial_index(&mut self, node_id: LayoutElementId, old: Rect, new: Rect) {
+        self.rstar_tree.remove(&GeomWithData::new(to_rectangle(old), node_id));
+        self.rstar_tree.insert(GeomWithData::new(to_rectangle(new), node_id));
+    }
+}
+
+fn to_rectangle(rect: Rect) -> rstar::primitives::Rectangle<[f64; 2]> {
+    rstar::primitives::Rectangle::from_corners([rect.x, rect.y], [rect.x + rect.width, rect.y + rect.height])
 }
 
 impl std::fmt::Debug for LayoutTree {
@@ -181,7 +313,176 @@ impl std::fmt::Debug for LayoutTree {
     }
 }
 
-/// A layout engine should implement this trait and return a layout tree
-pub trait CanLayout {
-    fn layout(&mut self, render_tree: RenderTree, viewport: Option<Dimension>) -> LayoutTree;
-}
\ No newline at end of file
+/// Viewport that a document is laid out for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// Size of the viewport. Without a size, the document is as wide as its content.
+    pub size: Option<Dimension>,
+    /// Number of device pixels per CSS pixel, which selects the source of responsive images
+    pub device_pixel_ratio: f64,
+}
+
+impl Viewport {
+    pub fn new(size: Option<Dimension>, device_pixel_ratio: f64) -> Self {
+        Self { size, device_pixel_ratio }
+    }
+}
+
+/// A layout engine should implement this trait and return a layout tree. Layouters are kept in the browser state,
+/// so they can lay out the document again when the viewport changes.
+pub trait CanLayout: Send + Sync {
+    fn layout(&mut self, render_tree: RenderTree, viewport: Viewport) -> LayoutTree;
+
+    /// Computes the layout of a tree that has been laid out by this layouter again, for another viewport or after
+    /// elements have been marked dirty. Returns the elements whose box model or text has changed.
+    ///
+    /// Only the taffy layouter computes the layout incrementally. The flow layouter keeps no state between layouts,
+    /// so it lays out the whole tree again and returns all elements; its mark_dirty() always returns false.
+    fn relayout(&mut self, layout_tree: &mut LayoutTree, viewport: Viewport) -> Vec<LayoutElementId>;
+
+    /// Marks an element dirty after its style or text has changed in the document of the layout tree, so it is
+    /// laid out by the next relayout(). Returns false when the change cannot be applied this way, and the document
+    /// must be laid out again with layout().
+    fn mark_dirty(&mut self, layout_tree: &mut LayoutTree, layout_id: LayoutElementId) -> bool;
+}
+
+/// Names of the layouters that can be chosen with layouter_by_name()
+pub const LAYOUTER_NAMES: [&str; 2] = ["taffy", "flow"];
+
+/// Returns the layouter with the given name, which measures text with the given shaper. Fails with the names of
+/// the layouters when there is no layouter with the name.
+pub fn layouter_by_name(name: &str, shaper: Arc<dyn text::TextShaper>) -> anyhow::Result<Box<dyn CanLayout>> {
+    match name {
+        "taffy" => Ok(Box::new(taffy::TaffyLayouter::new(shaper))),
+        "flow" => Ok(Box::new(flow::FlowLayouter::new(shaper))),
+        _ => anyhow::bail!("Unknown layouter '{}', expected one of: {}", name, LAYOUTER_NAMES.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::node::NodeType;
+    use crate::common::document::style::{StyleProperty, StyleValue, Unit};
+    use crate::layouter::fixtures::{block_style, border_box, layout};
+    use crate::layouter::text::NoShaper;
+
+    #[test]
+    fn test_collapse_margins() {
+        // <body><section><p/><div/><p/></section><footer/></body>
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, Some(block_style(0.0, 0.0, 0.0, 5.0)));
+        let section_id = doc.new_element(Some(body_id), "section", None, false, Some(block_style(0.0, 0.0, 10.0, 0.0)));
+        let first_id = doc.new_element(Some(section_id), "p", None, false, Some(block_style(0.0, 50.0, 20.0, 0.0)));
+        let empty_id = doc.new_element(Some(section_id), "div", None, false, Some(block_style(0.0, 0.0, 40.0, 0.0)));
+        let last_id = doc.new_element(Some(section_id), "p", None, false, Some(block_style(0.0, 50.0, 30.0, 0.0)));
+        let footer_id = doc.new_element(Some(body_id), "footer", None, false, Some(block_style(0.0, 10.0, 25.0, 0.0)));
+        for (parent_id, child_id) in [(body_id, section_id), (section_id, first_id), (section_id, empty_id), (section_id, last_id), (body_id, footer_id)] {
+            doc.add_child(parent_id, child_id);
+        }
+        doc.set_root(body_id);
+
+        for name in LAYOUTER_NAMES {
+            let layout_tree = layout(name, doc.clone());
+            let border_box = |dom_node_id| border_box(&layout_tree, dom_node_id);
+
+            // The margins of the section and its first paragraph collapse into a single margin of 20px below the
+            // padding of the body. The empty div collapses through, so the paragraphs are 40px apart.
+            assert_eq!(border_box(section_id).y, 25.0, "{name}");
+            assert_eq!(border_box(first_id).y, 25.0, "{name}");
+            assert_eq!(border_box(last_id).y, 25.0 + 50.0 + 40.0, "{name}");
+            // The bottom margins of the last paragraph and the section collapse with the top margin of the footer
+            assert_eq!(border_box(section_id).height, 140.0, "{name}");
+            assert_eq!(border_box(footer_id).y, 25.0 + 140.0 + 30.0, "{name}");
+            assert_eq!(border_box(footer_id).width, 800.0, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_relayout() {
+        // <body><p/><p/></body>
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, Some(block_style(0.0, 0.0, 0.0, 0.0)));
+        let first_id = doc.new_element(Some(body_id), "p", None, false, Some(block_style(0.0, 50.0, 0.0, 0.0)));
+        let last_id = doc.new_element(Some(body_id), "p", None, false, Some(block_style(0.0, 50.0, 0.0, 0.0)));
+        doc.add_child(body_id, first_id);
+        doc.add_child(body_id, last_id);
+        doc.set_root(body_id);
+        let doc = Arc::new(doc);
+
+        for name in LAYOUTER_NAMES {
+            let mut render_tree = RenderTree::new(doc.clone());
+            render_tree.parse();
+
+            let mut layouter = layouter_by_name(name, Arc::new(NoShaper)).unwrap();
+            let mut layout_tree = layouter.layout(render_tree, Viewport::new(Some(Dimension::new(800.0, 600.0)), 1.0));
+            let layout_id = |layout_tree: &LayoutTree, dom_node_id| layout_tree.arena.values().find(|el| el.dom_node_id == dom_node_id).unwrap().id;
+            assert!(layout_tree.find_elements_at(Coordinate::new(10.0, 60.0)).contains(&layout_id(&layout_tree, last_id)), "{name}");
+
+            // Only taffy lays out incrementally, the flow layouter lays out the whole tree again
+            if name == "taffy" {
+                assert!(layouter.relayout(&mut layout_tree, Viewport::new(Some(Dimension::new(800.0, 600.0)), 1.0)).is_empty());
+            }
+
+            // Only the paragraph that grows and the boxes after and around it are updated
+            let first = layout_id(&layout_tree, first_id);
+            let doc = Arc::make_mut(&mut layout_tree.render_tree.doc);
+            if let Some(NodeType::Element(data)) = doc.get_node_by_id_mut(first_id).map(|node| &mut node.node_type) {
+                data.styles.set_property(StyleProperty::Height, StyleValue::Unit(80.0, Unit::Px));
+            }
+            if !layouter.mark_dirty(&mut layout_tree, first) {
+                // The layouter cannot apply the change, so the document is laid out again
+                layout_tree = layouter.layout(layout_tree.render_tree.clone(), Viewport::new(Some(Dimension::new(800.0, 600.0)), 1.0));
+            }
+            let mut changed = layouter.relayout(&mut layout_tree, Viewport::new(Some(Dimension::new(800.0, 600.0)), 1.0));
+            changed.sort_by_key(|id| id.0);
+            let mut expected = [body_id, first_id, last_id].map(|dom_node_id| layout_id(&layout_tree, dom_node_id));
+            expected.sort_by_key(|id| id.0);
+            assert_eq!(changed, expected, "{name}");
+            let last = layout_id(&layout_tree, last_id);
+            assert_eq!(border_box(&layout_tree, last_id).y, 80.0, "{name}");
+            assert!(!layout_tree.find_elements_at(Coordinate::new(10.0, 60.0)).contains(&last), "{name}");
+            assert!(layout_tree.find_elements_at(Coordinate::new(10.0, 100.0)).contains(&last), "{name}");
+
+            // A smaller viewport changes the width of all boxes
+            let changed = layouter.relayout(&mut layout_tree, Viewport::new(Some(Dimension::new(400.0, 600.0)), 1.0));
+            assert_eq!(changed.len(), 3, "{name}");
+            assert_eq!(border_box(&layout_tree, last_id), Rect::new(0.0, 80.0, 400.0, 50.0), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_sticky_offset() {
+        // A header of 50px high that sticks to the top of the viewport, inside a section from 100 to 400
+        let constraint = StickyConstraint {
+            rect: Rect::new(0.0, 100.0, 300.0, 50.0),
+            container: Rect::new(0.0, 100.0, 300.0, 300.0),
+            top: Some(10.0),
+            right: None,
+            bottom: None,
+            left: None,
+        };
+
+        let offset = |y: f64| constraint.offset(Rect::new(0.0, y, 800.0, 600.0)).y;
+        assert_eq!(offset(0.0), 0.0);
+        assert_eq!(offset(90.0), 0.0);
+        assert_eq!(offset(150.0), 60.0);
+        // The header does not leave the section
+        assert_eq!(offset(500.0), 250.0);
+
+        // A footer that sticks to the bottom of the viewport, but not above the top of its container
+        let constraint = StickyConstraint {
+            rect: Rect::new(0.0, 1000.0, 300.0, 50.0),
+            container: Rect::new(0.0, 800.0, 300.0, 250.0),
+            top: None,
+            right: None,
+            bottom: Some(0.0),
+            left: None,
+        };
+        let offset = |y: f64| constraint.offset(Rect::new(0.0, y, 800.0, 600.0)).y;
+        assert_eq!(offset(300.0), -150.0);
+        assert_eq!(offset(0.0), -200.0);
+        assert_eq!(offset(500.0), 0.0);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::common::geo;
 
 /// Represents the thickness (or spacing) on each side.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Edges {
     pub top: f64,
     pub right: f64,
@@ -10,7 +10,7 @@ pub struct Edges {
 }
 
 /// Represents a boxmodel of an element. It contains the margin, border and padding of the element.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BoxModel {
     /// Rectangle of the margin box, the outer box of the element.
     pub margin_box: geo::Rect,
@@ -0,0 +1,197 @@
+use crate::common::document::document::Document;
+use crate::common::document::node::{NodeId as DomNodeId, NodeType};
+use crate::common::geo::Rect;
+use crate::layouter::LayoutTree;
+use std::collections::HashMap;
+
+// Documents exported with tools/souper hold the border box of each element as laid out by Chromium. Comparing
+// those against our own layout gives a measurable score of how correct the layout is, and points to the elements
+// that are off the most.
+
+/// Maximum difference in pixels between the edges of two boxes that still counts as a match
+pub const DEFAULT_TOLERANCE: f64 = 1.0;
+
+/// An element whose border box differs from the reference box
+#[derive(Clone, Debug)]
+pub struct BoxDeviation {
+    pub dom_node_id: DomNodeId,
+    /// Short description of the element, like "div#main" or "p.intro"
+    pub label: String,
+    /// Border box as laid out by the browser
+    pub expected: Rect,
+    /// Border box as laid out by the layouter
+    pub actual: Rect,
+    /// Largest difference between the edges of both boxes
+    pub error: f64,
+}
+
+/// Result of comparing a layout tree with the reference boxes of its document
+#[derive(Clone, Debug, Default)]
+pub struct ConformanceReport {
+    /// Number of elements that have both a reference box and a layout element
+    pub compared: usize,
+    /// Number of elements that match their reference box within the tolerance
+    pub matched: usize,
+    /// Elements with a reference box that did not generate a layout element
+    pub missing: Vec<DomNodeId>,
+    /// Elements that do not match their reference box, worst first
+    pub deviations: Vec<BoxDeviation>,
+}
+
+impl ConformanceReport {
+    /// Returns the fraction of the compared elements that match their reference box
+    pub fn score(&self) -> f64 {
+        if self.compared == 0 {
+            return 1.0;
+        }
+        self.matched as f64 / self.compared as f64
+    }
+
+    /// Returns the elements that are off the most
+    pub fn worst(&self, count: usize) -> &[BoxDeviation] {
+        &self.deviations[..count.min(self.deviations.len())]
+    }
+}
+
+/// Compares the border box of every layout element with the reference box of its element in the document.
+/// Elements that are not displayed (ie: display: none) have an empty reference box, and are not compared.
+pub fn compare_layout(layout_tree: &LayoutTree, tolerance: f64) -> ConformanceReport {
+    let doc = &layout_tree.render_tree.doc;
+
+    // Elements can generate multiple layout elements (ie: table wrappers), the outer one is compared
+    let mut boxes: HashMap<DomNodeId, Rect> = HashMap::new();
+    let mut stack = vec![layout_tree.root_id];
+    while let Some(id) = stack.pop() {
+        let Some(el) = layout_tree.get_node_by_id(id) else {
+            continue;
+        };
+        boxes.entry(el.dom_node_id).or_insert_with(|| el.box_model.border_box());
+        stack.extend(el.children.iter().rev());
+    }
+
+    let mut expected_boxes: Vec<_> = doc.reference_boxes.iter().collect();
+    expected_boxes.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut report = ConformanceReport::default();
+    for (dom_node_id, expected) in expected_boxes {
+        let Some(actual) = boxes.get(dom_node_id) else {
+            if expected.width > 0.0 || expected.height > 0.0 {
+                report.missing.push(*dom_node_id);
+            }
+            continue;
+        };
+
+        report.compared += 1;
+        let error = box_error(expected, actual);
+        if error <= tolerance {
+            report.matched += 1;
+            continue;
+        }
+
+        report.deviations.push(BoxDeviation {
+            dom_node_id: *dom_node_id,
+            label: element_label(doc, *dom_node_id),
+            expected: *expected,
+            actual: *actual,
+            error,
+        });
+    }
+
+    report.deviations.sort_by(|a, b| b.error.total_cmp(&a.error));
+    report
+}
+
+/// Returns the largest difference between the edges of both boxes
+fn box_error(expected: &Rect, actual: &Rect) -> f64 {
+    [
+        expected.x - actual.x,
+        expected.y - actual.y,
+        (expected.x + expected.width) - (actual.x + actual.width),
+        (expected.y + expected.height) - (actual.y + actual.height),
+    ]
+    .iter()
+    .fold(0.0, |error, diff| f64::max(error, diff.abs()))
+}
+
+fn element_label(doc: &Document, dom_node_id: DomNodeId) -> String {
+    let Some(NodeType::Element(data)) = doc.get_node_by_id(dom_node_id).map(|node| &node.node_type) else {
+        return dom_node_id.to_string();
+    };
+
+    if let Some(id) = data.attributes.get("id") {
+        return format!("{}#{}", data.tag_name, id);
+    }
+    match data.attributes.get("class").and_then(|class| class.split_whitespace().next()) {
+        Some(class) => format!("{}.{}", data.tag_name, class),
+        None => data.tag_name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::parser::document_from_json_str;
+    use crate::layouter::fixtures::layout;
+
+    // A page without text as exported by tools/souper, with only the styles that matter for its layout. Like
+    // getComputedStyle(), the widths are resolved into pixels.
+    const BLOCKS: &str = r#"{
+        "tag": "DocumentRoot",
+        "children": [{
+            "tag": "html",
+            "styles": { "display": "block", "width": "800px" },
+            "rect": { "x": 0, "y": 0, "width": 800, "height": 178 },
+            "children": [{
+                "tag": "body",
+                "styles": { "display": "block", "width": "784px", "margin-top": "8px", "margin-right": "8px", "margin-bottom": "8px", "margin-left": "8px" },
+                "rect": { "x": 8, "y": 8, "width": 784, "height": 160 },
+                "children": [
+                    {
+                        "tag": "div",
+                        "attributes": { "id": "header" },
+                        "styles": { "display": "block", "width": "784px", "height": "100px" },
+                        "rect": { "x": 8, "y": 8, "width": 784, "height": 100 }
+                    },
+                    {
+                        "tag": "div",
+                        "attributes": { "class": "content wide" },
+                        "styles": { "display": "block", "width": "784px", "height": "50px", "margin-top": "10px", "margin-bottom": "10px" },
+                        "rect": { "x": 8, "y": 118, "width": 784, "height": 50 }
+                    },
+                    {
+                        "tag": "div",
+                        "styles": { "display": "none" },
+                        "rect": { "x": 0, "y": 0, "width": 0, "height": 0 }
+                    }
+                ]
+            }]
+        }]
+    }"#;
+
+    #[test]
+    fn test_compare_layout() {
+        let doc = document_from_json_str("https://example.com", BLOCKS);
+        assert_eq!(doc.reference_boxes.len(), 5);
+
+        let report = compare_layout(&layout("taffy", doc.clone()), DEFAULT_TOLERANCE);
+        assert_eq!(report.compared, 4);
+        assert!(report.missing.is_empty());
+        assert_eq!(report.score(), 1.0, "{:?}", report.deviations);
+
+        // Move the reference boxes of both divs, the one that is moved the most is reported first
+        let mut doc = doc;
+        let labels: HashMap<_, _> = doc.reference_boxes.keys().map(|id| (*id, element_label(&doc, *id))).collect();
+        for (dom_node_id, rect) in doc.reference_boxes.iter_mut() {
+            match labels[dom_node_id].as_str() {
+                "div#header" => rect.height += 5.0,
+                "div.content" => rect.x -= 20.0,
+                _ => {}
+            }
+        }
+
+        let report = compare_layout(&layout("taffy", doc), DEFAULT_TOLERANCE);
+        assert_eq!(report.score(), 0.5);
+        let worst: Vec<_> = report.worst(5).iter().map(|d| (d.label.as_str(), d.error)).collect();
+        assert_eq!(worst, vec![("div.content", 20.0), ("div#header", 5.0)]);
+    }
+}
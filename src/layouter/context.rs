@@ -0,0 +1,497 @@
+use crate::common::document::document::Document;
+use crate::common::document::node::{Node, NodeType};
+use crate::common::document::style::{Direction, FontStyle, FontWeight, Hyphens, OverflowWrap, StyleProperty, StyleValue, TextAlign, TextWrap, UnicodeBidi, Unit, WhiteSpace, WordBreak, WritingMode};
+use crate::common::font::{FontDescriptor, FontSlant};
+use crate::common::geo::Coordinate;
+use crate::common::media::{Media, MediaType, Svg};
+use crate::common::{geo, get_media_store};
+use crate::layouter::text::{prepare_text, process_white_space, resolve_bidi, Alignment, TextWrapping};
+use crate::layouter::srcset::{self, Candidate, Descriptor, Environment};
+use crate::layouter::ElementContext;
+use std::borrow::Borrow;
+
+// Element contexts hold what the layouters need to know about the content of an element: the text with its font,
+// or the media of an image. They are generated from the document in the same way for every layouter.
+
+const DEFAULT_FONT_SIZE: f64 = 16.0;
+const DEFAULT_FONT_FAMILY: &str = "Sans";
+
+/// Generates the context of an element. Images and SVGs are replaced elements, which get the context of their
+/// media. The source of an image is selected for the environment. Returns None when the media of an image cannot
+/// be loaded, in which case the element is not laid out.
+pub(crate) fn replaced_context(doc: &Document, dom_node: &Node, env: &Environment) -> Option<ElementContext> {
+    let NodeType::Element(data) = &dom_node.node_type else {
+        return Some(ElementContext::None);
+    };
+
+    let mut context = ElementContext::None;
+    if data.tag_name.eq_ignore_ascii_case("img") {
+        let Some((src, density)) = image_source(doc, dom_node, env) else {
+            log::info!("Image has no source");
+            return None;
+        };
+
+        println!("Loading (image) resource: {}", src);
+
+        let media_store = get_media_store();
+        let Ok(media_id) = media_store.read().unwrap().load_media(src.as_str()) else {
+            // Could not load media
+            log::info!("Could not load media from path: {}", src);
+            return None;
+        };
+
+        let media_store = get_media_store();
+        let binding = media_store.read().unwrap();
+        let media = binding.get(media_id, MediaType::Image);
+        context = match media.borrow() {
+            Media::Svg(media_svg) => {
+                ElementContext::svg(src.as_str(), media_id, svg_dimension(&media_svg.svg), dom_node.node_id)
+            }
+            Media::Image(media_image) => {
+                // A high density image is displayed smaller than its bitmap
+                let dimension = geo::Dimension::new(
+                    media_image.image.width() as f64 / density,
+                    media_image.image.height() as f64 / density,
+                );
+                ElementContext::image(
+                    src.as_str(),
+                    media_id,
+                    dimension,
+                    dom_node.node_id,
+                )
+            }
+            Media::Font(_) => {
+                log::info!("Image source is a font: {}", src);
+                ElementContext::None
+            }
+        }
+    }
+
+    if data.tag_name.eq_ignore_ascii_case("svg") {
+        let inner_html = doc.inner_html(dom_node.node_id);
+
+        let store = get_media_store();
+        let store = store.read().unwrap();
+        match store.load_media_from_data(MediaType::Svg, inner_html.into_bytes().as_slice()) {
+            Ok(media_id) => {
+                let dimension = svg_dimension(&store.get_svg(media_id).svg);
+                context = ElementContext::svg(
+                    "gosub://internal",
+                    media_id,
+                    dimension,
+                    dom_node.node_id,
+                );
+            }
+            Err(e) => {
+                log::info!("Could not load SVG media: {:?}", e);
+            }
+        }
+    }
+
+    Some(context)
+}
+
+/// Returns the absolute url of the image source that is selected for the environment, with its pixel density. The
+/// first <source> of a <picture> whose media and type match is used, before the srcset and src of the image.
+pub(crate) fn image_source(doc: &Document, dom_node: &Node, env: &Environment) -> Option<(String, f64)> {
+    let NodeType::Element(data) = &dom_node.node_type else {
+        return None;
+    };
+    let base_url = doc.base_url();
+
+    let parent = dom_node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id));
+    if let Some(NodeType::Element(parent_data)) = parent.map(|parent| &parent.node_type) {
+        if parent_data.tag_name.eq_ignore_ascii_case("picture") {
+            let sources = parent.unwrap().children.iter().take_while(|child_id| **child_id != dom_node.node_id);
+            for source in sources.filter_map(|child_id| doc.get_node_by_id(*child_id)) {
+                let NodeType::Element(source_data) = &source.node_type else {
+                    continue;
+                };
+                if !source_data.tag_name.eq_ignore_ascii_case("source") {
+                    continue;
+                }
+                if source_data.get_attribute("media").is_some_and(|media| !srcset::media_matches(media, env)) {
+                    continue;
+                }
+                if source_data.get_attribute("type").is_some_and(|media_type| !srcset::is_supported_type(media_type)) {
+                    continue;
+                }
+
+                let candidates = srcset::parse_srcset(source_data.get_attribute("srcset").map_or("", |s| s.as_str()));
+                let sizes = source_data.get_attribute("sizes").map(|s| s.as_str());
+                if let Some((url, density)) = srcset::select(&candidates, sizes, env) {
+                    return Some((to_absolute_url(&url, base_url), density));
+                }
+            }
+        }
+    }
+
+    let mut candidates = srcset::parse_srcset(data.get_attribute("srcset").map_or("", |s| s.as_str()));
+    // The src of the image is a 1x candidate, unless the srcset has width descriptors or its own 1x candidate
+    if let Some(src) = data.get_attribute("src").filter(|src| !src.trim().is_empty()) {
+        let has_1x = candidates.iter().any(|candidate| match candidate.descriptor {
+            Descriptor::Width(_) => true,
+            Descriptor::Density(density) => density == 1.0,
+        });
+        if !has_1x {
+            candidates.push(Candidate::new(src.trim(), Descriptor::Density(1.0)));
+        }
+    }
+
+    let sizes = data.get_attribute("sizes").map(|s| s.as_str());
+    let (url, density) = srcset::select(&candidates, sizes, env)?;
+    Some((to_absolute_url(&url, base_url), density))
+}
+
+/// Returns the natural size of an SVG, which comes from its width and height, or from its view box
+fn svg_dimension(svg: &Svg) -> geo::Dimension {
+    let size = svg.tree.size();
+    geo::Dimension::new(size.width() as f64, size.height() as f64)
+}
+
+fn to_absolute_url(uri: &str, base_uri: &str) -> String {
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return uri.to_string();
+    }
+
+    // We have a relative path, so we need to prepend the base URL
+    // Make sure we don't have double slashes
+    if base_uri.ends_with("/") && uri.starts_with("/") {
+        return format!("{}{}", base_uri, &uri[1..]).to_string();
+    }
+
+    // Neither has a /
+    if !base_uri.ends_with("/") && !uri.starts_with("/") {
+        return format!("{}/{}", base_uri, uri).to_string();
+    }
+
+    format!("{}{}", base_uri, uri).to_string()
+}
+
+/// Generates the context of a text node, with the font and text that are used to measure and shape it. Returns
+/// None when there is no text left to lay out. inline_element_counter is the number of inline elements that
+/// precede the text in its container: text after another inline element is separated from it by a space.
+pub(crate) fn text_context(doc: &Document, dom_node: &Node, inline_element_counter: usize) -> Option<ElementContext> {
+    let NodeType::Text(text, node_style) = &dom_node.node_type else {
+        return None;
+    };
+
+    let parent = doc.get_node_by_id(dom_node.parent_id?)?;
+
+    // Default font. Text nodes hold a copy of the styles of their parent, so the text has the font size of its parent.
+    let font_size = resolve_font_size(doc, parent);
+    let mut font_family = DEFAULT_FONT_FAMILY.to_string();
+
+    if let Some(StyleValue::Keyword(value)) = node_style.get_property(StyleProperty::FontFamily) {
+        font_family = value.clone();
+    }
+
+    let font_weight = resolve_font_weight(doc, dom_node);
+
+    let font_slant = match node_style.get_property(StyleProperty::FontStyle) {
+        Some(StyleValue::FontStyle(FontStyle::Italic)) => FontSlant::Italic,
+        Some(StyleValue::FontStyle(FontStyle::Oblique)) => FontSlant::Oblique,
+        _ => FontSlant::Normal,
+    };
+
+    let font_stretch = match node_style.get_property(StyleProperty::FontStretch) {
+        Some(StyleValue::Percentage(value)) => *value as f64,
+        _ => 100.0,
+    };
+
+    let alignment = resolve_alignment(doc, dom_node);
+
+    let line_height = match node_style.get_property(StyleProperty::LineHeight) {
+        Some(StyleValue::Unit(value, unit)) => match unit {
+            Unit::Px => *value as f64,
+            Unit::Em => *value as f64 * font_size,
+            Unit::Rem => *value as f64 * root_font_size(doc, parent),
+            Unit::Percent => *value as f64 / 100.0 * font_size,
+        },
+        _ => font_size,
+    };
+
+    // Calculate vertical offset for centering based on the line height.
+    let text_offset = Coordinate::new(0.0, (line_height - font_size) / 2.0);
+
+    let white_space = match node_style.get_property(StyleProperty::WhiteSpace) {
+        Some(StyleValue::WhiteSpace(value)) => *value,
+        _ => WhiteSpace::Normal,
+    };
+
+    let wrapping = TextWrapping {
+        wrap: match node_style.get_property(StyleProperty::TextWrap) {
+            Some(StyleValue::TextWrap(value)) => *value,
+            _ => TextWrap::Wrap,
+        },
+        word_break: match node_style.get_property(StyleProperty::WordBreak) {
+            Some(StyleValue::WordBreak(value)) => *value,
+            _ => WordBreak::Normal,
+        },
+        overflow_wrap: match node_style.get_property(StyleProperty::OverflowWrap) {
+            Some(StyleValue::OverflowWrap(value)) => *value,
+            _ => OverflowWrap::Normal,
+        },
+        hyphens: match node_style.get_property(StyleProperty::Hyphens) {
+            Some(StyleValue::Hyphens(value)) => *value,
+            _ => Hyphens::Manual,
+        },
+    };
+
+    let lang = resolve_lang(doc, dom_node);
+    let mut text = prepare_text(&process_white_space(text, white_space), &wrapping, lang.as_deref());
+    if text.is_empty() {
+        // Collapsed to nothing, so there is nothing to layout
+        return None;
+    }
+    if inline_element_counter > 0 && white_space.collapses_spaces() {
+        // If we are in an inline container, we need to add a space between the text nodes
+        text = format!(" {}", text).clone()
+    }
+
+    let (text, direction) = resolve_bidi(
+        &text,
+        if is_rtl(dom_node) { Direction::Rtl } else { Direction::Ltr },
+        match node_style.get_property(StyleProperty::UnicodeBidi) {
+            Some(StyleValue::UnicodeBidi(value)) => *value,
+            _ => UnicodeBidi::Normal,
+        },
+    );
+
+    let font = FontDescriptor::new(font_family.as_str(), font_size)
+        .with_weight(font_weight)
+        .with_slant(font_slant)
+        .with_stretch(font_stretch);
+
+    Some(ElementContext::text(
+        &font,
+        line_height,
+        alignment,
+        direction,
+        wrapping,
+        writing_mode(dom_node),
+        text.as_str(),
+        dom_node.node_id,
+        text_offset,
+    ))
+}
+
+/// Resolves the font weight of the node. Relative weights (bolder and lighter) and missing weights are
+/// resolved against the weight of the parent node.
+fn resolve_font_weight(doc: &Document, node: &Node) -> usize {
+    let style = match &node.node_type {
+        NodeType::Element(data) => data.get_style(StyleProperty::FontWeight),
+        NodeType::Text(_, node_style) => node_style.get_property(StyleProperty::FontWeight),
+        _ => None,
+    };
+
+    let parent_weight = || match node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id)) {
+        Some(parent) => resolve_font_weight(doc, parent),
+        None => 400,
+    };
+
+    match style {
+        Some(StyleValue::FontWeight(FontWeight::Normal)) => 400,
+        Some(StyleValue::FontWeight(FontWeight::Bold)) => 700,
+        Some(StyleValue::FontWeight(FontWeight::Number(value))) => (*value as usize).clamp(1, 1000),
+        // See https://drafts.csswg.org/css-fonts/#relative-weights
+        Some(StyleValue::FontWeight(FontWeight::Bolder)) => match parent_weight() {
+            w if w < 350 => 400,
+            w if w < 550 => 700,
+            w if w < 900 => 900,
+            w => w,
+        },
+        Some(StyleValue::FontWeight(FontWeight::Lighter)) => match parent_weight() {
+            w if w < 100 => w,
+            w if w < 550 => 100,
+            w if w < 750 => 400,
+            _ => 700,
+        },
+        _ => parent_weight(),
+    }
+}
+
+/// Resolves the alignment of the text node from the text-align and text-align-last properties. Only the
+/// justify value of text-align-last is supported by the text backends, other values are ignored.
+fn resolve_alignment(doc: &Document, node: &Node) -> Alignment {
+    match (
+        resolve_text_align(doc, node, StyleProperty::TextAlign),
+        resolve_text_align(doc, node, StyleProperty::TextAlignLast),
+    ) {
+        (_, Some(Alignment::Justified)) => Alignment::JustifiedAll,
+        (Some(alignment), _) => alignment,
+        (None, _) if is_rtl(node) => Alignment::End,
+        (None, _) => Alignment::Start,
+    }
+}
+
+/// Resolves the font size of the node in pixels. Font sizes in em and percentages are relative to the font size of
+/// the parent, and font sizes in rem to the font size of the root element. Nodes without a font size inherit the
+/// font size of their parent, and the root has the default font size.
+fn resolve_font_size(doc: &Document, node: &Node) -> f64 {
+    let parent_font_size = || match node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id)) {
+        Some(parent) => resolve_font_size(doc, parent),
+        None => DEFAULT_FONT_SIZE,
+    };
+    let NodeType::Element(data) = &node.node_type else {
+        return parent_font_size();
+    };
+
+    match data.get_style(StyleProperty::FontSize) {
+        Some(StyleValue::Unit(value, unit)) => match unit {
+            Unit::Px => *value as f64,
+            Unit::Em => *value as f64 * parent_font_size(),
+            Unit::Rem => *value as f64 * root_font_size(doc, node),
+            Unit::Percent => *value as f64 / 100.0 * parent_font_size(),
+        },
+        _ => parent_font_size(),
+    }
+}
+
+/// Returns the font size of the root element, which rem units are relative to. The root element is the html
+/// element, or the topmost element when the node has no html ancestor. Relative font sizes of the root element
+/// are relative to the default font size.
+fn root_font_size(doc: &Document, node: &Node) -> f64 {
+    let mut root = None;
+    let mut current = Some(node);
+    while let Some(node) = current {
+        if let NodeType::Element(data) = &node.node_type {
+            root = Some(data);
+            if data.tag_name.eq_ignore_ascii_case("html") {
+                break;
+            }
+        }
+        current = node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id));
+    }
+
+    match root.and_then(|data| data.get_style(StyleProperty::FontSize)) {
+        Some(StyleValue::Unit(value, unit)) => match unit {
+            Unit::Px => *value as f64,
+            Unit::Em | Unit::Rem => *value as f64 * DEFAULT_FONT_SIZE,
+            Unit::Percent => *value as f64 / 100.0 * DEFAULT_FONT_SIZE,
+        },
+        _ => DEFAULT_FONT_SIZE,
+    }
+}
+
+/// Resolves a text-align property of the node into an alignment. The logical values (start and end) depend
+/// on the direction of the text, and the inherited values are resolved against the parent node. Returns None
+/// when the property is set to auto (text-align-last), or when it is not set at all.
+fn resolve_text_align(doc: &Document, node: &Node, prop: StyleProperty) -> Option<Alignment> {
+    let style = match &node.node_type {
+        NodeType::Element(data) => data.get_style(prop.clone()),
+        NodeType::Text(_, node_style) => node_style.get_property(prop.clone()),
+        _ => None,
+    };
+
+    let rtl = is_rtl(node);
+    let parent_alignment = || {
+        let parent = node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id))?;
+        resolve_text_align(doc, parent, prop.clone())
+    };
+
+    match style {
+        Some(StyleValue::TextAlign(value)) => match value {
+            TextAlign::Left => Some(Alignment::Start),
+            TextAlign::Right => Some(Alignment::End),
+            TextAlign::Center => Some(Alignment::Middle),
+            TextAlign::Justify => Some(Alignment::Justified),
+            TextAlign::Start | TextAlign::Initial => Some(if rtl { Alignment::End } else { Alignment::Start }),
+            TextAlign::End => Some(if rtl { Alignment::Start } else { Alignment::End }),
+            // text-align is inherited, so all of these take the value of the parent
+            TextAlign::MatchParent | TextAlign::Inherit | TextAlign::Revert | TextAlign::Unset => parent_alignment(),
+        },
+        Some(_) => None,
+        None => parent_alignment(),
+    }
+}
+
+/// Returns true when the direction of the node is right-to-left
+fn is_rtl(node: &Node) -> bool {
+    let style = match &node.node_type {
+        NodeType::Element(data) => data.get_style(StyleProperty::Direction),
+        NodeType::Text(_, node_style) => node_style.get_property(StyleProperty::Direction),
+        _ => None,
+    };
+
+    matches!(style, Some(StyleValue::Direction(Direction::Rtl)))
+}
+
+/// Returns the writing mode of the node
+fn writing_mode(node: &Node) -> WritingMode {
+    let style = match &node.node_type {
+        NodeType::Element(data) => data.get_style(StyleProperty::WritingMode),
+        NodeType::Text(_, node_style) => node_style.get_property(StyleProperty::WritingMode),
+        _ => None,
+    };
+
+    match style {
+        Some(StyleValue::WritingMode(writing_mode)) => *writing_mode,
+        _ => WritingMode::HorizontalTb,
+    }
+}
+
+/// Returns the language of the node, as set by the lang attribute on the node or one of its ancestors
+fn resolve_lang(doc: &Document, node: &Node) -> Option<String> {
+    if let NodeType::Element(data) = &node.node_type {
+        if let Some(lang) = data.get_attribute("lang") {
+            return Some(lang.clone());
+        }
+    }
+
+    let parent = node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id))?;
+    resolve_lang(doc, parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::node::NodeId;
+    use crate::common::document::style::StylePropertyList;
+
+    /// Adds an element with the given font size to the document
+    fn element(doc: &mut Document, parent_id: Option<NodeId>, tag_name: &str, font_size: Option<(f32, Unit)>) -> NodeId {
+        let mut style = StylePropertyList::new();
+        if let Some((value, unit)) = font_size {
+            style.set_property(StyleProperty::FontSize, StyleValue::Unit(value, unit));
+        }
+        let node_id = doc.new_element(parent_id, tag_name, None, false, Some(style));
+        if let Some(parent_id) = parent_id {
+            doc.add_child(parent_id, node_id);
+        }
+        node_id
+    }
+
+    fn font_size(doc: &Document, node_id: NodeId) -> f64 {
+        resolve_font_size(doc, doc.get_node_by_id(node_id).unwrap())
+    }
+
+    #[test]
+    fn test_relative_font_sizes() {
+        let mut doc = Document::new("https://example.com");
+        let root_id = element(&mut doc, None, "DocumentRoot", None);
+        let html_id = element(&mut doc, Some(root_id), "html", Some((10.0, Unit::Px)));
+        let body_id = element(&mut doc, Some(html_id), "body", Some((2.0, Unit::Em)));
+        let div_id = element(&mut doc, Some(body_id), "div", Some((150.0, Unit::Percent)));
+        let p_id = element(&mut doc, Some(div_id), "p", Some((3.0, Unit::Rem)));
+        let span_id = element(&mut doc, Some(p_id), "span", None);
+
+        assert_eq!(font_size(&doc, root_id), DEFAULT_FONT_SIZE);
+        assert_eq!(font_size(&doc, body_id), 20.0);
+        assert_eq!(font_size(&doc, div_id), 30.0);
+        // Rem is relative to the html element, not to the root of the document
+        assert_eq!(font_size(&doc, p_id), 30.0);
+        assert_eq!(font_size(&doc, span_id), 30.0);
+    }
+
+    #[test]
+    fn test_rem_without_html() {
+        let mut doc = Document::new("https://example.com");
+        let div_id = element(&mut doc, None, "div", Some((20.0, Unit::Px)));
+        let section_id = element(&mut doc, Some(div_id), "section", Some((12.0, Unit::Px)));
+        let p_id = element(&mut doc, Some(section_id), "p", Some((2.0, Unit::Rem)));
+
+        // Without an html element, rem is relative to the topmost element
+        assert_eq!(font_size(&doc, p_id), 40.0);
+    }
+}
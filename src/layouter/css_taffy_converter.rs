@@ -1,20 +1,67 @@
-use taffy::{AlignContent, AlignItems, AlignSelf, BoxSizing, Dimension, Display, FlexDirection, FlexWrap, GridAutoFlow, GridPlacement, LengthPercentage, LengthPercentageAuto, Line, NonRepeatedTrackSizingFunction, Overflow, Point, Position, Rect, Size, Style, TextAlign, TrackSizingFunction};
-use taffy::prelude::{FromLength, TaffyAuto};
-use crate::common::document::node::NodeId;
-use crate::common::document::style::{StyleProperty, StylePropertyList, StyleValue, Display as CssDisplay, Unit as CssUnit };
+use taffy::{AlignContent, AlignItems, AlignSelf, BoxSizing, Dimension, Display, FlexDirection, FlexWrap, GridAutoFlow, GridPlacement, GridTrackRepetition, LengthPercentage, LengthPercentageAuto, Line, MaxTrackSizingFunction, MinTrackSizingFunction, NonRepeatedTrackSizingFunction, Overflow, Point, Position, Rect, Size, Style, TextAlign, TrackSizingFunction};
+use taffy::prelude::{line, FromLength, TaffyAuto};
+use crate::common::document::document::Document;
+use crate::common::document::node::{ElementData, Node, NodeId, NodeType};
+use crate::common::geo;
+use crate::layouter::replaced::parse_size_attribute;
+use crate::layouter::{ScrollPosition, StickyConstraint};
+use crate::common::document::style::{StyleProperty, StylePropertyList, StyleValue, Display as CssDisplay, Unit as CssUnit, Direction as CssDirection, WritingMode as CssWritingMode, GridPlacement as CssGridPlacement, GridRepetition, GridTrack, TrackSize, Float as CssFloat, Clear as CssClear };
 
 /// This struct convert CSS stylesheets into taffy style structure.
 pub struct CssTaffyConverter {
     data: StylePropertyList,
+    /// Styles of the parent element, which is the grid container for grid items
+    container: Option<StylePropertyList>,
 }
 
 impl CssTaffyConverter {
     pub fn new(data: &StylePropertyList) -> Self {
         Self {
             data: data.clone(),
+            container: None,
         }
     }
 
+    /// Returns the converter for the styles of a node in the document. The parent is the container of grid items.
+    pub fn for_node(doc: &Document, dom_node: &Node) -> Self {
+        let mut conv = match &dom_node.node_type {
+            NodeType::Element(data) => Self::new(&data.styles).with_size_attributes(data),
+            _ => Self::new(&StylePropertyList::new()),
+        };
+        let parent = dom_node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id));
+        if let Some(NodeType::Element(parent_data)) = parent.map(|parent| &parent.node_type) {
+            conv = conv.with_container(&parent_data.styles);
+        }
+        conv
+    }
+
+    /// The width and height attributes of images and SVGs are presentational hints: they size the element when
+    /// its styles do not
+    fn with_size_attributes(mut self, data: &ElementData) -> Self {
+        if !data.tag_name.eq_ignore_ascii_case("img") && !data.tag_name.eq_ignore_ascii_case("svg") {
+            return self;
+        }
+
+        for (name, prop) in [("width", StyleProperty::Width), ("height", StyleProperty::Height)] {
+            let is_auto = match self.data.get_property(prop.clone()) {
+                Some(StyleValue::Keyword(value)) => value == "auto",
+                Some(_) => false,
+                None => true,
+            };
+            let size = data.get_attribute(name).and_then(|value| parse_size_attribute(value));
+            if let (true, Some(size)) = (is_auto, size) {
+                self.data.set_property(prop, StyleValue::Unit(size as f32, CssUnit::Px));
+            }
+        }
+        self
+    }
+
+    /// Sets the styles of the parent element, which are needed to resolve the named lines of grid items
+    pub fn with_container(mut self, container: &StylePropertyList) -> Self {
+        self.container = Some(container.clone());
+        self
+    }
+
     fn get_f32(&self, prop: StyleProperty, default: f32) -> f32 {
         let Some(val) = self.data.get_property(prop) else {
             return default;
@@ -22,15 +69,7 @@ impl CssTaffyConverter {
 
         match *val {
             StyleValue::Number(num) => num,
-            StyleValue::Unit(val, _) => default,
-            StyleValue::Keyword(_) => default,
-            StyleValue::Color(_) => default,
-            StyleValue::None => default,
-            StyleValue::Display(_) => default,
-            StyleValue::FontWeight(_) => default,
-            StyleValue::TextWrap(_) => default,
-            StyleValue::Percentage(_) => default,
-            StyleValue::TextAlign(_) => default,
+            _ => default,
         }
     }
 
@@ -58,7 +97,10 @@ impl CssTaffyConverter {
         ts.scrollbar_width = self.get_f32(StyleProperty::ScrollbarWidth, ts.scrollbar_width);
         ts.position = self.get_position(ts.position);
 
-        ts.inset = self.get_inset(ts.inset);
+        // The insets of a sticky element do not move it, they are resolved by the compositor (see get_sticky_insets)
+        if !self.is_position("sticky") {
+            ts.inset = self.get_inset(ts.inset);
+        }
         ts.margin.top = self.get_lpa(StyleProperty::MarginTop, ts.margin.top);
         ts.margin.right = self.get_lpa(StyleProperty::MarginRight, ts.margin.right);
         ts.margin.bottom = self.get_lpa(StyleProperty::MarginBottom, ts.margin.bottom);
@@ -100,32 +142,9 @@ impl CssTaffyConverter {
         ts.grid_column = self.get_grid_line(StyleProperty::GridColumn, ts.grid_column);
 
         // If we have an inline element, set the correct properties for emulating inlining the element with taffy
+        let floated = self.get_float() != CssFloat::None;
         match self.data.get_property(StyleProperty::Display) {
-            Some(StyleValue::Display(CssDisplay::Table)) => {
-                ts.display = Display::Flex;
-                ts.flex_direction = FlexDirection::Column;
-            }
-            Some(StyleValue::Display(CssDisplay::TableRow)) => {
-                ts.display = Display::Flex;
-                ts.flex_direction = FlexDirection::Row;
-            }
-            Some(StyleValue::Display(CssDisplay::TableCell)) => {
-                ts.display = Display::Flex;
-                ts.flex_grow = 1.0;
-            }
-            Some(StyleValue::Display(CssDisplay::TableFooterGroup)) => {
-                ts.display = Display::Flex;
-                ts.flex_direction = FlexDirection::Column;
-            }
-            Some(StyleValue::Display(CssDisplay::TableHeaderGroup)) => {
-                ts.display = Display::Flex;
-                ts.flex_direction = FlexDirection::Column;
-            }
-            Some(StyleValue::Display(CssDisplay::TableRowGroup)) => {
-                ts.display = Display::Flex;
-                ts.flex_direction = FlexDirection::Column;
-            }
-            Some(StyleValue::Display(CssDisplay::Inline)) => {
+            Some(StyleValue::Display(CssDisplay::Inline)) if !floated => {
                 ts.display = Display::Flex;
                 ts.flex_direction = FlexDirection::Row;
                 ts.flex_wrap = FlexWrap::Wrap;
@@ -134,11 +153,58 @@ impl CssTaffyConverter {
             _ => {},
         }
 
+        // Floats are taken out of the flow. The taffy layouter places them inside their containing block after
+        // the layout has been computed.
+        if floated {
+            ts.position = Position::Absolute;
+        }
+
         // if is_inline {
         //     ts.text_align = TextAlign::LegacyCenter;
         //     ts.align_content = Some(AlignContent::Center);
         // }
 
+        // Taffy only knows about horizontal writing modes. In vertical writing modes, blocks are placed next to
+        // each other from right to left (or left to right), and the flex directions are logical: a row follows
+        // the inline axis (top to bottom) and a column follows the block axis.
+        let writing_mode = self.get_writing_mode();
+        if writing_mode.is_vertical() {
+            if ts.display == Display::Block {
+                ts.display = Display::Flex;
+                ts.flex_direction = FlexDirection::Column;
+                ts.flex_wrap = FlexWrap::NoWrap;
+            }
+
+            let rl = writing_mode == CssWritingMode::VerticalRl;
+            ts.flex_direction = match (ts.flex_direction, self.is_rtl()) {
+                (FlexDirection::Row, false) | (FlexDirection::RowReverse, true) => FlexDirection::Column,
+                (FlexDirection::Row, true) | (FlexDirection::RowReverse, false) => FlexDirection::ColumnReverse,
+                (FlexDirection::Column, _) if rl => FlexDirection::RowReverse,
+                (FlexDirection::Column, _) => FlexDirection::Row,
+                (FlexDirection::ColumnReverse, _) if rl => FlexDirection::Row,
+                (FlexDirection::ColumnReverse, _) => FlexDirection::RowReverse,
+            };
+
+            // Lines of a wrapping row are stacked along the block axis, which runs from right to left
+            if rl && ts.flex_wrap == FlexWrap::Wrap {
+                ts.flex_wrap = FlexWrap::WrapReverse;
+            }
+        } else if self.is_rtl() {
+            // Taffy does not know about direction. A row in right-to-left direction starts at the right, which is
+            // the same as a reversed row in left-to-right direction.
+            ts.flex_direction = match ts.flex_direction {
+                FlexDirection::Row => FlexDirection::RowReverse,
+                FlexDirection::RowReverse => FlexDirection::Row,
+                direction => direction,
+            };
+
+            // Children (including the paragraphs of inline content) that are narrower than the block start at
+            // the right side
+            if ts.text_align == TextAlign::Auto {
+                ts.text_align = TextAlign::LegacyRight;
+            }
+        }
+
         ts
     }
 
@@ -205,6 +271,11 @@ impl CssTaffyConverter {
                     CssDisplay::Inline => Display::Block,  // We override this later
                     CssDisplay::Flex => Display::Flex,
                     CssDisplay::None => Display::None,
+                    // The rows and columns of the grid are set up by the table layout of the layouter
+                    CssDisplay::Grid | CssDisplay::InlineGrid => Display::Grid,
+                    CssDisplay::Table | CssDisplay::InlineTable => Display::Grid,
+                    // Columns do not generate boxes, they only provide the widths of the columns
+                    CssDisplay::TableColumn | CssDisplay::TableColumnGroup => Display::None,
                     _ => {
                         Display::Block
                         // unimplemented!("Display type not implemented: {:?}", val)
@@ -215,6 +286,86 @@ impl CssTaffyConverter {
         }
     }
 
+    /// Returns true when the element is positioned relative to the viewport
+    pub fn is_fixed(&self) -> bool {
+        self.is_position("fixed")
+    }
+
+    /// Returns true when the element is offset from its normal position (position: relative)
+    pub fn is_relative(&self) -> bool {
+        self.is_position("relative")
+    }
+
+    /// Returns true when the element is the containing block of its absolutely positioned descendants
+    pub fn is_positioned(&self) -> bool {
+        ["relative", "absolute", "fixed", "sticky"].iter().any(|position| self.is_position(position))
+    }
+
+    /// Returns how the element moves when the document is scrolled. The rectangles of sticky elements are known
+    /// once the layout has been computed.
+    pub fn get_scroll_position(&self) -> ScrollPosition {
+        if self.is_fixed() {
+            return ScrollPosition::Fixed;
+        }
+
+        match self.get_sticky_insets() {
+            Some(inset) => ScrollPosition::Sticky(StickyConstraint {
+                rect: geo::Rect::ZERO,
+                container: geo::Rect::ZERO,
+                top: inset.top.map(f64::from),
+                right: inset.right.map(f64::from),
+                bottom: inset.bottom.map(f64::from),
+                left: inset.left.map(f64::from),
+            }),
+            None => ScrollPosition::Scrolled,
+        }
+    }
+
+    /// Returns the insets of a sticky element in pixels (top, right, bottom, left), or None when the element is not
+    /// sticky. These are the distances that the element keeps to the edges of the viewport. Auto and percentage
+    /// insets do not constrain the element.
+    pub fn get_sticky_insets(&self) -> Option<Rect<Option<f32>>> {
+        if !self.is_position("sticky") {
+            return None;
+        }
+
+        let px = |value: LengthPercentageAuto| match value {
+            LengthPercentageAuto::Length(value) => Some(value),
+            _ => None,
+        };
+        let inset = self.get_inset(Rect::auto());
+        Some(Rect {
+            top: px(inset.top),
+            right: px(inset.right),
+            bottom: px(inset.bottom),
+            left: px(inset.left),
+        })
+    }
+
+    fn is_position(&self, position: &str) -> bool {
+        matches!(self.data.get_property(StyleProperty::Position), Some(StyleValue::Keyword(value)) if value == position)
+    }
+
+    /// Returns the float of the element. Absolutely positioned elements do not float.
+    pub fn get_float(&self) -> CssFloat {
+        if self.get_position(Position::Relative) == Position::Absolute {
+            return CssFloat::None;
+        }
+
+        match self.data.get_property(StyleProperty::Float) {
+            Some(StyleValue::Float(float)) => float.resolve(self.is_rtl()),
+            _ => CssFloat::None,
+        }
+    }
+
+    /// Returns the (physical) sides of the floats that the element is moved below
+    pub fn get_clear(&self) -> CssClear {
+        match self.data.get_property(StyleProperty::Clear) {
+            Some(StyleValue::Clear(clear)) => clear.resolve(self.is_rtl()),
+            _ => CssClear::None,
+        }
+    }
+
     fn get_position(&self, default: Position) -> Position {
         let Some(val) = self.data.get_property(StyleProperty::Position) else {
             return default;
@@ -226,7 +377,9 @@ impl CssTaffyConverter {
                     "relative" => Position::Relative,
                     "absolute" => Position::Absolute,
                     "static" => Position::Relative,
+                    // The taffy layouter places fixed elements in a box the size of the viewport
                     "fixed" => Position::Absolute,
+                    // Sticky elements are laid out at their normal position, the compositor moves them
                     "sticky" => Position::Relative,
                     _ => default,
                 }
@@ -244,7 +397,7 @@ impl CssTaffyConverter {
             StyleValue::Unit(value, unit) => {
                 match unit {
                     CssUnit::Px => LengthPercentageAuto::Length(*value),
-                    CssUnit::Percent => LengthPercentageAuto::Percent(*value),
+                    CssUnit::Percent => LengthPercentageAuto::Percent(*value / 100.0),
                     _ => default,
                 }
             }
@@ -263,7 +416,7 @@ impl CssTaffyConverter {
             StyleValue::Unit(value, unit) => {
                 match unit {
                     CssUnit::Px => LengthPercentage::Length(*value),
-                    CssUnit::Percent => LengthPercentage::Percent(*value),
+                    CssUnit::Percent => LengthPercentage::Percent(*value / 100.0),
                     _ => default,
                 }
             }
@@ -281,7 +434,7 @@ impl CssTaffyConverter {
             StyleValue::Unit(value, unit) => {
                 match unit {
                     CssUnit::Px => Dimension::from_length(*value),
-                    CssUnit::Percent => Dimension::from_length(*value),
+                    CssUnit::Percent => Dimension::Percent(*value / 100.0),
                     _ => default,
                 }
             }
@@ -299,7 +452,7 @@ impl CssTaffyConverter {
             StyleValue::Unit(value, unit) => {
                 match unit {
                     CssUnit::Px => Size::length(*value),
-                    CssUnit::Percent => Size::percent(*value),
+                    CssUnit::Percent => Size::percent(*value / 100.0),
                     _ => default,
                 }
             }
@@ -398,14 +551,41 @@ impl CssTaffyConverter {
     }
 
     fn get_inset(&self, default: Rect<LengthPercentageAuto>) -> Rect<LengthPercentageAuto> {
+        // The inline start is on the right side for right-to-left text
+        let (inline_start, inline_end) = if self.is_rtl() {
+            (StyleProperty::InsetInlineEnd, StyleProperty::InsetInlineStart)
+        } else {
+            (StyleProperty::InsetInlineStart, StyleProperty::InsetInlineEnd)
+        };
+        let (block_start, block_end) = (StyleProperty::InsetBlockStart, StyleProperty::InsetBlockEnd);
+
+        // Sides in (top, right, bottom, left) order. In vertical writing modes the inline axis runs from top to
+        // bottom and the block axis from right to left (vertical-rl) or left to right (vertical-lr).
+        let (top, right, bottom, left) = match self.get_writing_mode() {
+            CssWritingMode::HorizontalTb => (block_start, inline_end, block_end, inline_start),
+            CssWritingMode::VerticalRl => (inline_start, block_start, inline_end, block_end),
+            CssWritingMode::VerticalLr => (inline_start, block_end, inline_end, block_start),
+        };
+
         Rect {
-            top: self.get_lpa(StyleProperty::InsetBlockStart, default.top),
-            right: self.get_lpa(StyleProperty::InsetInlineEnd, default.right),
-            bottom: self.get_lpa(StyleProperty::InsetBlockEnd, default.bottom),
-            left: self.get_lpa(StyleProperty::InsetInlineStart, default.left),
+            top: self.get_lpa(top, default.top),
+            right: self.get_lpa(right, default.right),
+            bottom: self.get_lpa(bottom, default.bottom),
+            left: self.get_lpa(left, default.left),
         }
     }
 
+    fn get_writing_mode(&self) -> CssWritingMode {
+        match self.data.get_property(StyleProperty::WritingMode) {
+            Some(StyleValue::WritingMode(writing_mode)) => *writing_mode,
+            _ => CssWritingMode::HorizontalTb,
+        }
+    }
+
+    fn is_rtl(&self) -> bool {
+        matches!(self.data.get_property(StyleProperty::Direction), Some(StyleValue::Direction(CssDirection::Rtl)))
+    }
+
     fn get_overflow(&self, prop: StyleProperty, default: Overflow) -> Overflow {
         let Some(val) = self.data.get_property(prop) else {
             return default;
@@ -448,6 +628,8 @@ impl CssTaffyConverter {
         };
 
         match val {
+            StyleValue::None => Vec::new(),
+            StyleValue::GridTracks(list) => list.tracks.iter().map(to_track_sizing_function).collect(),
             StyleValue::Keyword(ref val) => {
                 match val.as_str() {
                     "none" => Vec::new(),
@@ -469,7 +651,7 @@ impl CssTaffyConverter {
                 match val.as_str() {
                     "row" => GridAutoFlow::Row,
                     "column" => GridAutoFlow::Column,
-                    "row dense" => GridAutoFlow::RowDense,
+                    "row dense" | "dense" => GridAutoFlow::RowDense,
                     "column dense" => GridAutoFlow::ColumnDense,
                     _ => default,
                 }
@@ -479,18 +661,24 @@ impl CssTaffyConverter {
     }
 
     fn get_grid_line(&self, prop: StyleProperty, default: Line<GridPlacement>) -> Line<GridPlacement> {
-        let Some(val) = self.data.get_property(prop) else {
+        let Some(val) = self.data.get_property(prop.clone()) else {
             return default;
         };
 
         match val {
+            StyleValue::GridLine(grid_line) => {
+                let line_names = self.container_line_names(prop);
+                Line {
+                    start: to_grid_placement(&grid_line.start, &line_names, "start"),
+                    end: to_grid_placement(&grid_line.end, &line_names, "end"),
+                }
+            }
             StyleValue::Keyword(ref val) => {
                 match val.as_str() {
                     "auto" => Line { start: GridPlacement::Auto, end: GridPlacement::Auto },
                     _ => default,
                 }
             },
-            // StyleValue::Number(val) => Line { start: GridPlacement::Line(val.into()), end: GridPlacement::Line(val.into()) },
             _ => default,
         }
     }
@@ -501,6 +689,7 @@ impl CssTaffyConverter {
         };
 
         match val {
+            StyleValue::GridAutoTracks(sizes) => sizes.iter().map(to_track_size).collect(),
             StyleValue::Keyword(ref val) => {
                 match val.as_str() {
                     "auto" => Vec::new(),
@@ -510,4 +699,138 @@ impl CssTaffyConverter {
             _ => default,
         }
     }
-}
\ No newline at end of file
+
+    /// Returns the names of the lines of the grid container, in the axis of the given placement property. Lines
+    /// are named in the track list, and the areas of grid-template-areas name the lines around them implicitly.
+    fn container_line_names(&self, prop: StyleProperty) -> Vec<Vec<String>> {
+        let Some(container) = &self.container else {
+            return Vec::new();
+        };
+
+        let rows = prop == StyleProperty::GridRow;
+        let template = if rows { StyleProperty::GridTemplateRows } else { StyleProperty::GridTemplateColumns };
+        let mut line_names = match container.get_property(template) {
+            Some(StyleValue::GridTracks(list)) => list.line_names.clone(),
+            _ => Vec::new(),
+        };
+
+        let Some(StyleValue::GridAreas(areas)) = container.get_property(StyleProperty::GridTemplateAreas) else {
+            return line_names;
+        };
+
+        // The first and last track of each area
+        let mut bounds: Vec<(&String, usize, usize)> = Vec::new();
+        for (row, cells) in areas.iter().enumerate() {
+            for (column, name) in cells.iter().enumerate() {
+                if name == "." {
+                    continue;
+                }
+                let track = if rows { row } else { column };
+                match bounds.iter_mut().find(|(area, _, _)| *area == name) {
+                    Some((_, first, last)) => {
+                        *first = (*first).min(track);
+                        *last = (*last).max(track);
+                    }
+                    None => bounds.push((name, track, track)),
+                }
+            }
+        }
+
+        for (name, first, last) in bounds {
+            if line_names.len() < last + 2 {
+                line_names.resize(last + 2, Vec::new());
+            }
+            line_names[first].push(format!("{}-start", name));
+            line_names[last + 1].push(format!("{}-end", name));
+        }
+
+        line_names
+    }
+}
+
+fn to_track_sizing_function(track: &GridTrack) -> TrackSizingFunction {
+    match track {
+        GridTrack::Size(size) => TrackSizingFunction::Single(to_track_size(size)),
+        GridTrack::Repeat(repetition, sizes) => TrackSizingFunction::Repeat(
+            match repetition {
+                GridRepetition::AutoFill => GridTrackRepetition::AutoFill,
+                GridRepetition::AutoFit => GridTrackRepetition::AutoFit,
+            },
+            sizes.iter().map(to_track_size).collect(),
+        ),
+    }
+}
+
+fn to_track_size(size: &TrackSize) -> NonRepeatedTrackSizingFunction {
+    match size {
+        TrackSize::MinMax(min, max) => NonRepeatedTrackSizingFunction {
+            min: to_min_track_size(min),
+            max: to_max_track_size(max),
+        },
+        size => NonRepeatedTrackSizingFunction {
+            min: to_min_track_size(size),
+            max: to_max_track_size(size),
+        },
+    }
+}
+
+/// Flexible and fit-content sizes are not allowed as minimum, and have an automatic minimum instead
+fn to_min_track_size(size: &TrackSize) -> MinTrackSizingFunction {
+    match size {
+        TrackSize::Length(value) => MinTrackSizingFunction::Fixed(LengthPercentage::Length(*value)),
+        TrackSize::Percent(value) => MinTrackSizingFunction::Fixed(LengthPercentage::Percent(*value / 100.0)),
+        TrackSize::MinContent => MinTrackSizingFunction::MinContent,
+        TrackSize::MaxContent => MinTrackSizingFunction::MaxContent,
+        TrackSize::MinMax(min, _) => to_min_track_size(min),
+        TrackSize::Auto | TrackSize::Fraction(_) | TrackSize::FitContent(_) => MinTrackSizingFunction::Auto,
+    }
+}
+
+fn to_max_track_size(size: &TrackSize) -> MaxTrackSizingFunction {
+    match size {
+        TrackSize::Length(value) => MaxTrackSizingFunction::Fixed(LengthPercentage::Length(*value)),
+        TrackSize::Percent(value) => MaxTrackSizingFunction::Fixed(LengthPercentage::Percent(*value / 100.0)),
+        TrackSize::Fraction(value) => MaxTrackSizingFunction::Fraction(*value),
+        TrackSize::Auto => MaxTrackSizingFunction::Auto,
+        TrackSize::MinContent => MaxTrackSizingFunction::MinContent,
+        TrackSize::MaxContent => MaxTrackSizingFunction::MaxContent,
+        TrackSize::FitContent(value) => MaxTrackSizingFunction::FitContent(LengthPercentage::Length(*value)),
+        TrackSize::MinMax(_, max) => to_max_track_size(max),
+    }
+}
+
+/// Converts a grid placement into a taffy grid placement. Named lines are resolved against the line names of the
+/// grid container. A name on its own refers to the implicit line of an area first (like "main-start" for the start
+/// of "main"). Names that cannot be resolved place the item automatically.
+fn to_grid_placement(placement: &CssGridPlacement, line_names: &[Vec<String>], side: &str) -> GridPlacement {
+    match placement {
+        CssGridPlacement::Auto => GridPlacement::Auto,
+        CssGridPlacement::Line(index) => line(*index),
+        CssGridPlacement::Span(count) => GridPlacement::Span(*count),
+        CssGridPlacement::Named(name, nth) => {
+            let lines_named = |name: &str| -> Vec<i16> {
+                line_names
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, names)| names.iter().any(|n| n == name))
+                    .map(|(index, _)| index as i16 + 1)
+                    .collect()
+            };
+
+            let mut lines = match nth {
+                None => lines_named(&format!("{}-{}", name, side)),
+                Some(_) => Vec::new(),
+            };
+            if lines.is_empty() {
+                lines = lines_named(name);
+            }
+
+            // Negative numbers count from the last line with the name
+            let found = match nth.unwrap_or(1) {
+                nth if nth > 0 => lines.get(nth as usize - 1),
+                nth => lines.len().checked_sub(nth.unsigned_abs() as usize).and_then(|index| lines.get(index)),
+            };
+            found.map_or(GridPlacement::Auto, |index| line(*index))
+        }
+    }
+}
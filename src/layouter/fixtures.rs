@@ -0,0 +1,43 @@
+use crate::common::document::document::Document;
+use crate::common::document::node::NodeId;
+use crate::common::document::style::{Display, StyleProperty, StylePropertyList, StyleValue, Unit};
+use crate::common::geo::{Dimension, Rect};
+use crate::layouter::text::NoShaper;
+use crate::layouter::{layouter_by_name, LayoutTree, Viewport};
+use crate::rendertree_builder::RenderTree;
+use std::sync::Arc;
+
+// Helpers for the tests of the layouters. Tests that run against every layouter in LAYOUTER_NAMES check that the
+// layouters agree on the same document.
+
+/// Returns the style of a block. A width or height of 0 leaves it auto. The margin is set at the top and bottom,
+/// the padding only at the top.
+pub fn block_style(width: f32, height: f32, margin: f32, padding: f32) -> StylePropertyList {
+    let mut style = StylePropertyList::new();
+    style.set_property(StyleProperty::Display, StyleValue::Display(Display::Block));
+    style.set_property(StyleProperty::MarginTop, StyleValue::Unit(margin, Unit::Px));
+    style.set_property(StyleProperty::MarginBottom, StyleValue::Unit(margin, Unit::Px));
+    style.set_property(StyleProperty::PaddingTop, StyleValue::Unit(padding, Unit::Px));
+    if width > 0.0 {
+        style.set_property(StyleProperty::Width, StyleValue::Unit(width, Unit::Px));
+    }
+    if height > 0.0 {
+        style.set_property(StyleProperty::Height, StyleValue::Unit(height, Unit::Px));
+    }
+    style
+}
+
+/// Lays out the document with the named layouter in a viewport of 800x600, without measuring text
+pub fn layout(name: &str, doc: Document) -> LayoutTree {
+    let mut render_tree = RenderTree::new(Arc::new(doc));
+    render_tree.parse();
+
+    let mut layouter = layouter_by_name(name, Arc::new(NoShaper)).expect("Unknown layouter");
+    layouter.layout(render_tree, Viewport::new(Some(Dimension::new(800.0, 600.0)), 1.0))
+}
+
+/// Returns the border box of the element of the DOM node
+pub fn border_box(layout_tree: &LayoutTree, dom_node_id: NodeId) -> Rect {
+    let el = layout_tree.arena.values().find(|el| el.dom_node_id == dom_node_id).unwrap();
+    el.box_model.border_box()
+}
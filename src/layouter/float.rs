@@ -0,0 +1,222 @@
+use crate::common::document::style::{Clear, Float};
+use crate::layouter::text::{TextShaper, TextStyle};
+
+// Taffy has no floats. The taffy layouter positions floats as absolute boxes inside their containing block, and
+// places them with a float context after each layout pass. Text next to floats is broken into lines that are
+// shortened around the floats.
+
+/// A float that has been placed inside its containing block. Coordinates are relative to the content box of the
+/// containing block, and describe the margin box of the float.
+#[derive(Clone, Debug)]
+struct PlacedFloat {
+    side: Float,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl PlacedFloat {
+    fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    /// Returns true when the float overlaps the vertical band between top and bottom
+    fn overlaps(&self, top: f64, bottom: f64) -> bool {
+        self.y < bottom && self.bottom() > top
+    }
+}
+
+/// Floats of a single containing block
+#[derive(Clone, Debug)]
+pub struct FloatContext {
+    /// Width of the content box of the containing block
+    width: f64,
+    floats: Vec<PlacedFloat>,
+}
+
+impl FloatContext {
+    pub fn new(width: f64) -> Self {
+        Self {
+            width,
+            floats: Vec::new(),
+        }
+    }
+
+    /// Places a float with the given (margin box) size. The float is placed as high as possible, but not above the
+    /// given y position or any float that was placed before it. It moves down until it fits next to the other
+    /// floats, unless it does not fit in the containing block at all. Returns the position of the float.
+    pub fn place(&mut self, side: Float, width: f64, height: f64, y: f64, clear: Clear) -> (f64, f64) {
+        self.place_within(side, width, height, y, clear, (0.0, self.width))
+    }
+
+    /// Places a float like place(), for a float whose own containing block is narrower than the containing block
+    /// of the floats (like a float in a nested block). The band gives the left and right side of the content box
+    /// of that block.
+    pub fn place_within(&mut self, side: Float, width: f64, height: f64, y: f64, clear: Clear, band: (f64, f64)) -> (f64, f64) {
+        let mut y = self.clearance(clear, y);
+        if let Some(last) = self.floats.last() {
+            y = y.max(last.y);
+        }
+
+        loop {
+            let (left, right) = self.insets(y, y + height.max(f64::EPSILON));
+            let (left, right) = (left.max(band.0), (self.width - right).min(band.1));
+            let fits = right - left >= width || (left == band.0 && right == band.1);
+            if fits {
+                let x = match side {
+                    Float::Right => right - width,
+                    _ => left,
+                };
+                self.floats.push(PlacedFloat { side, x, y, width, height });
+                return (x, y);
+            }
+
+            // Try again below the first float that ends in the band
+            match self.floats.iter().filter(|f| f.overlaps(y, y + height.max(f64::EPSILON))).map(|f| f.bottom()).reduce(f64::min) {
+                Some(bottom) if bottom > y => y = bottom,
+                _ => {
+                    self.floats.push(PlacedFloat { side, x: left, y, width, height });
+                    return (left, y);
+                }
+            }
+        }
+    }
+
+    /// Returns the position below the floats that are cleared, or the given position when it is already below them
+    pub fn clearance(&self, clear: Clear, y: f64) -> f64 {
+        self.floats
+            .iter()
+            .filter(|f| clear.clears(f.side))
+            .map(|f| f.bottom())
+            .fold(y, f64::max)
+    }
+
+    /// Returns the space taken by floats on the left and right side of the band between top and bottom
+    pub fn insets(&self, top: f64, bottom: f64) -> (f64, f64) {
+        let mut left: f64 = 0.0;
+        let mut right: f64 = 0.0;
+        for f in self.floats.iter().filter(|f| f.overlaps(top, bottom)) {
+            match f.side {
+                Float::Right => right = right.max(self.width - f.x),
+                _ => left = left.max(f.x + f.width),
+            }
+        }
+
+        (left, right)
+    }
+
+    /// Returns the bottom of the lowest float
+    pub fn bottom(&self) -> f64 {
+        self.floats.iter().map(|f| f.bottom()).fold(0.0, f64::max)
+    }
+
+    /// Returns the parts of the floats that overlap a box at the given position, as insets of the box. Floats that
+    /// end above the box cannot overlap it anymore, but floats below the box can once its content grows.
+    pub fn exclusions(&self, x: f64, y: f64, width: f64) -> Vec<Exclusion> {
+        self.floats
+            .iter()
+            .filter(|f| f.bottom() > y)
+            .filter_map(|f| {
+                let (left, right) = match f.side {
+                    Float::Right => (0.0, (x + width - f.x).min(width)),
+                    _ => ((f.x + f.width - x).min(width), 0.0),
+                };
+                if left <= 0.0 && right <= 0.0 {
+                    return None;
+                }
+                Some(Exclusion {
+                    top: f.y - y,
+                    bottom: f.bottom() - y,
+                    left: left.max(0.0),
+                    right: right.max(0.0),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Part of a box that is taken by a float. The band between top and bottom (relative to the top of the box) is
+/// shortened by left and right.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exclusion {
+    pub top: f64,
+    pub bottom: f64,
+    pub left: f64,
+    pub right: f64,
+}
+
+/// A line of text that flows around floats. The position is relative to the content box of the text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlowLine {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+}
+
+/// Breaks the text into lines that are shortened by the exclusions they overlap. Lines are broken one at a time,
+/// as the width of each line depends on its position. When not even a single word fits next to the floats, the
+/// line moves down below them.
+pub fn flow_text(shaper: &dyn TextShaper, text: &str, style: &TextStyle, max_width: f64, exclusions: &[Exclusion]) -> anyhow::Result<Vec<FlowLine>> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    let mut y = 0.0;
+
+    while !rest.is_empty() {
+        let overlapping = exclusions.iter().filter(|e| e.top < y + style.line_height && e.bottom > y);
+        let (left, right) = overlapping.fold((0.0f64, 0.0f64), |(left, right), e| (left.max(e.left), right.max(e.right)));
+        let width = (max_width - left - right).max(0.0);
+
+        let first = shaper.line_break(rest, style, width)?.into_iter().next();
+        let Some(first) = first.filter(|line| line.range.end > 0) else {
+            break;
+        };
+
+        let narrowed = left > 0.0 || right > 0.0;
+        if narrowed && first.width > width {
+            y += style.line_height;
+            continue;
+        }
+
+        let end = first.range.end.min(rest.len());
+        lines.push(FlowLine {
+            text: rest[..end].trim_end().to_string(),
+            x: left,
+            y,
+            width,
+        });
+        rest = rest[end..].trim_start_matches(['\n', ' ']);
+        y += style.line_height;
+    }
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_floats() {
+        let mut ctx = FloatContext::new(300.0);
+
+        assert_eq!(ctx.place(Float::Left, 100.0, 50.0, 0.0, Clear::None), (0.0, 0.0));
+        assert_eq!(ctx.place(Float::Right, 100.0, 80.0, 0.0, Clear::None), (200.0, 0.0));
+        assert_eq!(ctx.place(Float::Left, 100.0, 20.0, 0.0, Clear::None), (100.0, 0.0));
+
+        // Does not fit next to the other floats, so it moves below the first float that ends
+        assert_eq!(ctx.place(Float::Left, 150.0, 20.0, 0.0, Clear::None), (0.0, 50.0));
+
+        // Cleared floats start below the floats on that side
+        assert_eq!(ctx.place(Float::Left, 50.0, 10.0, 0.0, Clear::Right), (0.0, 80.0));
+
+        assert_eq!(ctx.insets(0.0, 10.0), (200.0, 100.0));
+        assert_eq!(ctx.clearance(Clear::Left, 0.0), 90.0);
+        assert_eq!(ctx.bottom(), 90.0);
+
+        let exclusions = ctx.exclusions(20.0, 60.0, 280.0);
+        assert_eq!(exclusions.len(), 3);
+        assert_eq!(exclusions[0], Exclusion { top: -60.0, bottom: 20.0, left: 0.0, right: 100.0 });
+    }
+}
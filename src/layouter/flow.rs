@@ -0,0 +1,1468 @@
+use crate::common::document::node::NodeType;
+use crate::common::document::style::{Clear, Display as CssDisplay, Float, PseudoElement, StyleProperty, StyleValue};
+use crate::common::font::face;
+use crate::common::geo::{self, Coordinate};
+use crate::layouter::box_model::{BoxModel, Edges};
+use crate::layouter::context::{replaced_context, text_context};
+use crate::layouter::css_taffy_converter::CssTaffyConverter;
+use crate::layouter::float::{flow_text, Exclusion, FloatContext};
+use crate::layouter::replaced;
+use crate::layouter::srcset::Environment;
+use crate::layouter::taffy::{is_table_part, TaffyLayouter};
+use crate::layouter::text::{wrap_text, Alignment, TextShaper};
+use crate::layouter::{
+    to_rectangle, CanLayout, ElementContext, LayoutElementId, LayoutElementNode, LayoutTree, ScrollPosition, Viewport,
+};
+use crate::rendertree_builder::{RenderNodeId, RenderTree};
+use rstar::primitives::GeomWithData;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use taffy::{BoxSizing, Dimension, LengthPercentage, LengthPercentageAuto, Overflow, Position, Style};
+
+// The flow layouter lays out the normal flow of CSS itself: blocks are stacked in block formatting contexts with
+// collapsing margins, inline content is broken into line boxes, and floats are placed while the content around
+// them is laid out. Flex, grid and table containers are laid out by taffy, and their subtrees are grafted into
+// the layout tree. Both layouters take the same render tree, so their layouts can be compared.
+//
+// Content on a line is aligned at the top of the line, and atomic inlines are placed at the start of the line
+// regardless of text-align.
+
+const NO_EDGES: Edges = Edges { top: 0.0, right: 0.0, bottom: 0.0, left: 0.0 };
+
+/// Layouter for block, inline and float layout, which delegates flex, grid and table containers to taffy
+pub struct FlowLayouter {
+    /// Shaper that is used for measuring text
+    shaper: Arc<dyn TextShaper>,
+    /// Layouter for the subtrees of flex, grid and table containers
+    taffy: TaffyLayouter,
+    /// Size of the viewport, which is the containing block of the root and of fixed elements
+    viewport: geo::Dimension,
+    /// Environment that the sources of responsive images are selected for
+    env: Environment,
+    /// How each element takes part in the layout
+    boxes: HashMap<LayoutElementId, FlowBox>,
+    /// Absolutely positioned elements, which are laid out after the normal flow
+    absolutes: Vec<AbsoluteBox>,
+}
+
+/// How an element is laid out by its parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outer {
+    /// Block-level box in the normal flow
+    Block,
+    /// Inline box, whose content is placed on the lines of its block container
+    Inline,
+    /// Inline-level box that is placed on a line as a whole (inline-blocks, images and inline grids)
+    AtomicInline,
+    Float(Float),
+    /// Absolutely positioned or fixed box
+    Absolute,
+}
+
+/// How an element lays out its own content
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Inner {
+    /// Block container or inline box
+    Flow,
+    Text,
+    /// Image or SVG, which is sized by its media
+    Replaced,
+    /// Flex, grid and table containers, and vertical writing modes, are laid out by taffy
+    Delegated,
+}
+
+#[derive(Clone, Debug)]
+struct FlowBox {
+    /// Style of the element, as converted for taffy
+    style: Style,
+    outer: Outer,
+    inner: Inner,
+    clear: Clear,
+    /// The box is moved from its normal position by its insets (position: relative)
+    relative: bool,
+    /// The box is the containing block of its absolutely positioned descendants
+    positioned: bool,
+    /// List markers are positioned against their list item
+    marker: bool,
+    parent: Option<LayoutElementId>,
+}
+
+/// Absolutely positioned element, with its static position relative to the content box of the block container
+/// it was found in. The static position moves along when the container is moved (like floats are).
+#[derive(Clone, Debug)]
+struct AbsoluteBox {
+    id: LayoutElementId,
+    anchor: LayoutElementId,
+    offset: Coordinate,
+}
+
+/// Where the parent lays out a box. Coordinates are absolute.
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    /// Left side of the margin box
+    x: f64,
+    /// Top of the border box. The top margin has been collapsed and applied by the parent.
+    y: f64,
+    /// Width of the containing block
+    cb_width: f64,
+    /// Height of the containing block, when it is definite
+    cb_height: Option<f64>,
+    /// Width of the border box, when it has been decided by the parent (floats, inline-blocks and absolutes)
+    width: Option<f64>,
+    /// Height of the border box, when it has been decided by the parent (absolutes with a top and bottom inset)
+    height: Option<f64>,
+}
+
+/// Result of laying out a box
+#[derive(Clone, Copy, Debug)]
+struct Laid {
+    /// Height of the border box
+    height: f64,
+    /// Bottom margin, collapsed with the bottom margins of the last children that collapse through the bottom
+    bottom_margin: CollapsedMargin,
+    /// The box has no content and no height, so its top and bottom margins collapse together
+    empty: bool,
+}
+
+/// Where the children of a block container end
+struct FlowEnd {
+    /// Bottom of the last child, or of the last line
+    bottom: f64,
+    /// Margin below the last child that has not been applied yet
+    margin: CollapsedMargin,
+    has_content: bool,
+}
+
+/// Adjoining vertical margins collapse into the largest positive margin plus the most negative margin
+#[derive(Clone, Copy, Debug, Default)]
+struct CollapsedMargin {
+    positive: f64,
+    negative: f64,
+}
+
+impl CollapsedMargin {
+    fn new(margin: f64) -> Self {
+        Self {
+            positive: margin.max(0.0),
+            negative: margin.min(0.0),
+        }
+    }
+
+    fn collapse(self, other: Self) -> Self {
+        Self {
+            positive: self.positive.max(other.positive),
+            negative: self.negative.min(other.negative),
+        }
+    }
+
+    fn resolve(self) -> f64 {
+        self.positive + self.negative
+    }
+}
+
+/// Block formatting context, which holds the floats of the blocks inside it
+struct Bfc {
+    /// Top left of the content box of the box that establishes the context
+    origin: Coordinate,
+    width: f64,
+    floats: FloatContext,
+}
+
+impl Bfc {
+    fn new(origin: Coordinate, width: f64) -> Self {
+        Self {
+            origin,
+            width,
+            floats: FloatContext::new(width),
+        }
+    }
+
+    /// Returns the left and right side of the space between left and right that is not taken by floats, in the
+    /// band between top and bottom
+    fn free_space(&self, top: f64, bottom: f64, left: f64, right: f64) -> (f64, f64) {
+        let bottom = bottom.max(top + f64::EPSILON);
+        let (inset_left, inset_right) = self.floats.insets(top - self.origin.y, bottom - self.origin.y);
+        (left.max(self.origin.x + inset_left), right.min(self.origin.x + self.width - inset_right))
+    }
+
+    /// Returns the position below the floats that are cleared, or the given position when it is below them already
+    fn clearance(&self, clear: Clear, y: f64) -> f64 {
+        self.floats.clearance(clear, y - self.origin.y) + self.origin.y
+    }
+
+    /// Returns the parts of the floats that overlap a box at the given position
+    fn exclusions(&self, x: f64, y: f64, width: f64) -> Vec<Exclusion> {
+        self.floats.exclusions(x - self.origin.x, y - self.origin.y, width)
+    }
+
+    /// Returns the bottom of the first float that ends below the given position, among the floats next to it
+    fn next_float_bottom(&self, x: f64, y: f64, width: f64) -> Option<f64> {
+        self.exclusions(x, y, width)
+            .iter()
+            .filter(|exclusion| exclusion.top <= 0.0 && exclusion.bottom > 0.0)
+            .map(|exclusion| y + exclusion.bottom)
+            .reduce(f64::min)
+    }
+}
+
+/// Line box that inline content is placed on
+struct LineBox {
+    top: f64,
+    /// Height of the content that has been placed on the line
+    height: f64,
+    /// Position where the next content is placed
+    x: f64,
+    /// Nothing has been placed on the line yet
+    empty: bool,
+}
+
+/// Inline formatting context of a block container
+struct InlineContext {
+    /// Block container, which anchors the absolutely positioned elements in the inline content
+    anchor: LayoutElementId,
+    /// Content box of the block container
+    left: f64,
+    top: f64,
+    width: f64,
+    line: LineBox,
+    has_content: bool,
+}
+
+impl InlineContext {
+    /// Returns the left and right side of the current line, next to the floats
+    fn line_range(&self, bfc: &Bfc, height: f64) -> (f64, f64) {
+        bfc.free_space(self.line.top, self.line.top + height, self.left, self.left + self.width)
+    }
+
+    /// Starts a new line below the current line
+    fn next_line(&mut self, bfc: &Bfc) {
+        self.line.top += self.line.height;
+        self.line.height = 0.0;
+        self.line.empty = true;
+        self.line.x = self.line_range(bfc, 0.0).0;
+    }
+
+    /// Marks that content of the given size has been placed at the end of the current line
+    fn advance(&mut self, width: f64, height: f64) {
+        self.line.x += width;
+        self.line.height = self.line.height.max(height);
+        self.line.empty = false;
+        self.has_content = true;
+    }
+}
+
+impl FlowLayouter {
+    pub fn new(shaper: Arc<dyn TextShaper>) -> Self {
+        Self {
+            taffy: TaffyLayouter::new(shaper.clone()),
+            shaper,
+            viewport: geo::Dimension::ZERO,
+            env: Environment::new(None, 1.0),
+            boxes: HashMap::new(),
+            absolutes: Vec::new(),
+        }
+    }
+}
+
+impl CanLayout for FlowLayouter {
+    fn layout(&mut self, render_tree: RenderTree, viewport: Viewport) -> LayoutTree {
+        // Web fonts must be available before any text is measured
+        face::load_font_faces(&render_tree.doc.font_faces);
+
+        // Images are selected for the viewport when the tree is generated
+        self.env = Environment::from(viewport);
+        let viewport = viewport.size;
+
+        let root_id = render_tree.root_id.unwrap();
+        let Some(mut layout_tree) = self.generate_tree(render_tree, root_id) else {
+            panic!("Failed to generate root node render tree");
+        };
+        let root_id = layout_tree.root_id;
+
+        // Without a viewport, the document is as wide as its content
+        self.viewport = match viewport {
+            Some(viewport) => viewport,
+            None => geo::Dimension::new(self.max_content_width(&layout_tree, root_id), 0.0),
+        };
+
+        let margin = margin_edges(&self.boxes[&root_id].style.margin, self.viewport.width);
+        let placement = Placement {
+            x: 0.0,
+            y: margin.top,
+            cb_width: self.viewport.width,
+            cb_height: Some(self.viewport.height),
+            width: None,
+            height: None,
+        };
+        let mut bfc = Bfc::new(Coordinate::ZERO, self.viewport.width);
+        self.layout_box(&mut layout_tree, root_id, placement, &mut bfc);
+        self.layout_absolutes(&mut layout_tree);
+        self.resolve_sticky(&mut layout_tree);
+
+        let root = layout_tree.get_node_by_id(root_id).unwrap().box_model.margin_box;
+        layout_tree.root_dimension = geo::Dimension::new(root.width, root.height);
+
+        let entries = layout_tree
+            .arena
+            .values()
+            .map(|el| GeomWithData::new(to_rectangle(el.box_model.margin_box), el.id))
+            .collect();
+        layout_tree.rstar_tree = rstar::RTree::bulk_load(entries);
+
+        layout_tree
+    }
+
+    /// The flow layouter keeps no state between layouts, so the tree is laid out again from its render tree. All
+    /// elements are returned, as they are all new.
+    fn relayout(&mut self, layout_tree: &mut LayoutTree, viewport: Viewport) -> Vec<LayoutElementId> {
+        *layout_tree = self.layout(layout_tree.render_tree.clone(), viewport);
+        layout_tree.arena.keys().copied().collect()
+    }
+
+    /// There is nothing to mark, as relayout() lays out the whole tree again. Returns false, so the caller lays out
+    /// the changed document with layout().
+    fn mark_dirty(&mut self, _layout_tree: &mut LayoutTree, _layout_id: LayoutElementId) -> bool {
+        false
+    }
+}
+
+impl FlowLayouter {
+    /// Returns the viewport for the subtrees that are laid out by taffy, which select their images for the same
+    /// environment as the rest of the document
+    fn taffy_viewport(&self) -> Viewport {
+        Viewport::new(Some(self.viewport), self.env.device_pixel_ratio)
+    }
+
+    /// Generate the layout tree from the render tree
+    fn generate_tree(&mut self, render_tree: RenderTree, root_id: RenderNodeId) -> Option<LayoutTree> {
+        self.boxes.clear();
+        self.absolutes.clear();
+
+        let mut layout_tree = LayoutTree {
+            render_tree,
+            arena: HashMap::new(),
+            root_id: LayoutElementId::new(0), // Will be filled in later
+            next_node_id: Arc::new(RwLock::new(LayoutElementId::new(0))),
+            root_dimension: geo::Dimension::ZERO,
+            rstar_tree: rstar::RTree::new(),
+        };
+
+        layout_tree.root_id = self.generate_node(&mut layout_tree, root_id, None, 0)?;
+        Some(layout_tree)
+    }
+
+    /// Generates the layout element of a render node and its children. inline_element_counter is the number of
+    /// inline elements that precede the node in its container (see context::text_context).
+    fn generate_node(
+        &mut self,
+        layout_tree: &mut LayoutTree,
+        render_node_id: RenderNodeId,
+        parent: Option<LayoutElementId>,
+        inline_element_counter: usize,
+    ) -> Option<LayoutElementId> {
+        let render_node = layout_tree.render_tree.get_node_by_id(render_node_id)?;
+        let (dom_node_id, render_node_children) = (render_node.dom_node_id, render_node.children.clone());
+        let doc = layout_tree.render_tree.doc.clone();
+        let dom_node = doc.get_node_by_id(dom_node_id)?;
+
+        let (flow_box, context, scroll_position) = match &dom_node.node_type {
+            NodeType::Element(data) => {
+                let conv = CssTaffyConverter::for_node(&doc, dom_node);
+                let style = conv.convert(dom_node.node_id, false);
+                let display = data.get_style(StyleProperty::Display);
+                let vertical = matches!(
+                    data.get_style(StyleProperty::WritingMode),
+                    Some(StyleValue::WritingMode(writing_mode)) if writing_mode.is_vertical()
+                );
+
+                // Images and SVGs are replaced elements, which are sized by their media
+                let context = replaced_context(&doc, dom_node, &self.env)?;
+                let inner = if !matches!(context, ElementContext::None) {
+                    Inner::Replaced
+                } else if vertical
+                    || is_table_part(display)
+                    || matches!(display, Some(StyleValue::Display(CssDisplay::Flex | CssDisplay::Grid | CssDisplay::InlineGrid)))
+                {
+                    Inner::Delegated
+                } else {
+                    Inner::Flow
+                };
+
+                let float = conv.get_float();
+                let outer = if float != Float::None {
+                    Outer::Float(float)
+                } else if style.position == Position::Absolute {
+                    Outer::Absolute
+                } else {
+                    match display {
+                        Some(StyleValue::Display(CssDisplay::Inline)) if inner == Inner::Flow => Outer::Inline,
+                        Some(StyleValue::Display(
+                            CssDisplay::Inline | CssDisplay::InlineBlock | CssDisplay::InlineGrid | CssDisplay::InlineTable,
+                        )) => Outer::AtomicInline,
+                        _ => Outer::Block,
+                    }
+                };
+
+                let flow_box = FlowBox {
+                    outer,
+                    inner,
+                    clear: conv.get_clear(),
+                    relative: conv.is_relative(),
+                    positioned: conv.is_positioned(),
+                    marker: data.tag_name == PseudoElement::Marker.tag_name(),
+                    parent,
+                    style,
+                };
+                (flow_box, context, conv.get_scroll_position())
+            }
+            NodeType::Text(..) => {
+                let context = text_context(&doc, dom_node, inline_element_counter)?;
+                let flow_box = FlowBox {
+                    style: Style::default(),
+                    outer: Outer::Inline,
+                    inner: Inner::Text,
+                    clear: Clear::None,
+                    relative: false,
+                    positioned: false,
+                    marker: false,
+                    parent,
+                };
+                (flow_box, context, ScrollPosition::Scrolled)
+            }
+            NodeType::Comment(_) => return None,
+        };
+
+        let mut element_node = LayoutElementNode {
+            id: layout_tree.next_node_id(),
+            dom_node_id,
+            render_node_id,
+            box_model: BoxModel::ZERO,
+            children: vec![],
+            context,
+            scroll_position,
+        };
+        let id = element_node.id;
+        let lays_out_children = flow_box.inner == Inner::Flow;
+        self.boxes.insert(id, flow_box);
+
+        // Taffy generates the children of the containers it lays out, and the children of replaced elements are
+        // not rendered
+        if lays_out_children {
+            let mut inline_element_counter = if dom_node.is_inline_element() { inline_element_counter } else { 0 };
+            for child_id in render_node_children {
+                let Some(child_layout_id) = self.generate_node(layout_tree, child_id, Some(id), inline_element_counter) else {
+                    continue;
+                };
+
+                // Out-of-flow children (like list markers) do not separate the inline content
+                if matches!(self.boxes[&child_layout_id].outer, Outer::Inline | Outer::AtomicInline) {
+                    inline_element_counter += 1;
+                }
+                element_node.children.push(child_layout_id);
+            }
+        }
+
+        layout_tree.arena.insert(id, element_node);
+        Some(id)
+    }
+
+    /// Lays out a box and its descendants
+    fn layout_box(&mut self, tree: &mut LayoutTree, id: LayoutElementId, placement: Placement, bfc: &mut Bfc) -> Laid {
+        let laid = match self.boxes[&id].inner {
+            Inner::Replaced => self.layout_replaced(tree, id, placement),
+            Inner::Delegated => self.layout_delegated(tree, id, placement),
+            _ => self.layout_flow(tree, id, placement, bfc),
+        };
+
+        // Relatively positioned boxes are moved once they have been laid out, which does not affect the flow
+        let flow_box = &self.boxes[&id];
+        if flow_box.relative {
+            let inset = &flow_box.style.inset;
+            let (cb_width, cb_height) = (placement.cb_width, placement.cb_height.unwrap_or(0.0));
+            let dx = match (length_auto(inset.left, cb_width), length_auto(inset.right, cb_width)) {
+                (Some(left), _) => left,
+                (None, Some(right)) => -right,
+                _ => 0.0,
+            };
+            let dy = match (length_auto(inset.top, cb_height), length_auto(inset.bottom, cb_height)) {
+                (Some(top), _) => top,
+                (None, Some(bottom)) => -bottom,
+                _ => 0.0,
+            };
+            if dx != 0.0 || dy != 0.0 {
+                translate(tree, id, dx, dy);
+            }
+        }
+
+        laid
+    }
+
+    /// Lays out a block container. Its children are either all block-level or all inline-level, as the render
+    /// tree wraps inline content next to blocks in anonymous blocks.
+    fn layout_flow(&mut self, tree: &mut LayoutTree, id: LayoutElementId, p: Placement, bfc: &mut Bfc) -> Laid {
+        let style = self.boxes[&id].style.clone();
+        let (margin, border_width) = self.resolve_width(&style, &p, None);
+        let border = edges(&style.border, p.cb_width);
+        let padding = edges(&style.padding, p.cb_width);
+        let vertical_edges = border.top + border.bottom + padding.top + padding.bottom;
+
+        let border_x = p.x + margin.left;
+        let content = geo::Rect::new(
+            border_x + border.left + padding.left,
+            p.y + border.top + padding.top,
+            (border_width - border.left - border.right - padding.left - padding.right).max(0.0),
+            0.0,
+        );
+        let fixed_height = p.height.or_else(|| border_size(style.size.height, p.cb_height, style.box_sizing, vertical_edges));
+        let cb_height = fixed_height.map(|height| (height - vertical_edges).max(0.0));
+
+        // Boxes that establish a block formatting context contain their floats
+        let new_bfc = self.is_bfc_root(id);
+        let mut own_bfc;
+        let bfc = if new_bfc {
+            own_bfc = Bfc::new(Coordinate::new(content.x, content.y), content.width);
+            &mut own_bfc
+        } else {
+            bfc
+        };
+
+        let children = tree.get_node_by_id(id).map_or_else(Vec::new, |el| el.children.clone());
+        let inline = children.iter().any(|child| matches!(self.boxes[child].outer, Outer::Inline | Outer::AtomicInline));
+        let end = if inline {
+            self.layout_inline_content(tree, id, &children, content, cb_height, bfc)
+        } else {
+            self.layout_block_children(tree, id, &children, content, cb_height, bfc)
+        };
+
+        // The bottom margin of the last child collapses through the bottom of a box without padding and border
+        let collapses_bottom = !new_bfc
+            && !inline
+            && fixed_height.is_none()
+            && is_zero(style.border.bottom)
+            && is_zero(style.padding.bottom);
+        let mut content_height = end.bottom - content.y;
+        if !collapses_bottom {
+            content_height += end.margin.resolve();
+        }
+        if new_bfc {
+            content_height = content_height.max(bfc.floats.bottom());
+        }
+
+        let border_height = fixed_height.unwrap_or(content_height + vertical_edges);
+        let border_height = clamp_size(
+            border_height,
+            border_size(style.min_size.height, p.cb_height, style.box_sizing, vertical_edges),
+            border_size(style.max_size.height, p.cb_height, style.box_sizing, vertical_edges),
+        );
+        set_box_model(tree, id, geo::Rect::new(border_x, p.y, border_width, border_height), margin, border, padding);
+
+        let mut bottom_margin = CollapsedMargin::new(margin.bottom);
+        if collapses_bottom {
+            bottom_margin = bottom_margin.collapse(end.margin);
+        }
+        Laid {
+            height: border_height,
+            bottom_margin,
+            empty: !end.has_content && border_height == 0.0,
+        }
+    }
+
+    /// Stacks the block-level children of a block container. Adjoining margins collapse, also through empty
+    /// blocks, and blocks that clear floats are moved below them.
+    fn layout_block_children(
+        &mut self,
+        tree: &mut LayoutTree,
+        id: LayoutElementId,
+        children: &[LayoutElementId],
+        content: geo::Rect,
+        cb_height: Option<f64>,
+        bfc: &mut Bfc,
+    ) -> FlowEnd {
+        let mut cursor = content.y;
+        let mut pending = CollapsedMargin::default();
+        let mut has_content = false;
+        // The top margins of the first child have been collapsed into the top margin of this box
+        let mut collapsed_top = self.collapses_top(tree, id);
+
+        for &child_id in children {
+            let child = &self.boxes[&child_id];
+            match child.outer {
+                Outer::Float(side) => {
+                    self.layout_float(tree, child_id, side, content, cursor + pending.resolve(), cb_height, bfc);
+                }
+                Outer::Absolute => self.absolutes.push(AbsoluteBox {
+                    id: child_id,
+                    anchor: id,
+                    offset: Coordinate::new(0.0, cursor + pending.resolve() - content.y),
+                }),
+                _ => {
+                    let clear = child.clear;
+                    let margin = if collapsed_top {
+                        CollapsedMargin::default()
+                    } else {
+                        pending.collapse(self.leading_margin(tree, child_id, content.width))
+                    };
+                    collapsed_top = false;
+
+                    let mut y = cursor + margin.resolve();
+                    let cleared = clear != Clear::None && bfc.clearance(clear, y) > y;
+                    if cleared {
+                        y = bfc.clearance(clear, y);
+                    }
+
+                    let placement = Placement {
+                        x: content.x,
+                        y,
+                        cb_width: content.width,
+                        cb_height,
+                        width: None,
+                        height: None,
+                    };
+                    let laid = self.layout_box(tree, child_id, placement, bfc);
+                    if laid.empty && !cleared {
+                        pending = margin.collapse(laid.bottom_margin);
+                    } else {
+                        cursor = y + laid.height;
+                        pending = laid.bottom_margin;
+                        has_content = true;
+                    }
+                }
+            }
+        }
+
+        FlowEnd {
+            bottom: cursor,
+            margin: pending,
+            has_content,
+        }
+    }
+
+    /// Places the inline content of a block container on lines
+    fn layout_inline_content(
+        &mut self,
+        tree: &mut LayoutTree,
+        id: LayoutElementId,
+        children: &[LayoutElementId],
+        content: geo::Rect,
+        cb_height: Option<f64>,
+        bfc: &mut Bfc,
+    ) -> FlowEnd {
+        let mut ctx = InlineContext {
+            anchor: id,
+            left: content.x,
+            top: content.y,
+            width: content.width,
+            line: LineBox { top: content.y, height: 0.0, x: content.x, empty: true },
+            has_content: false,
+        };
+        ctx.line.x = ctx.line_range(bfc, 0.0).0;
+
+        for &child_id in children {
+            self.layout_inline_item(tree, child_id, &mut ctx, cb_height, bfc);
+        }
+
+        FlowEnd {
+            bottom: ctx.line.top + ctx.line.height,
+            margin: CollapsedMargin::default(),
+            has_content: ctx.has_content,
+        }
+    }
+
+    /// Places an inline-level element on the lines. Returns the part of the lines that the element takes, which
+    /// gives the box of the inline boxes around it.
+    fn layout_inline_item(
+        &mut self,
+        tree: &mut LayoutTree,
+        id: LayoutElementId,
+        ctx: &mut InlineContext,
+        cb_height: Option<f64>,
+        bfc: &mut Bfc,
+    ) -> Option<geo::Rect> {
+        let flow_box = &self.boxes[&id];
+        match (flow_box.outer, flow_box.inner) {
+            (Outer::Float(side), _) => {
+                let content = geo::Rect::new(ctx.left, ctx.top, ctx.width, 0.0);
+                self.layout_float(tree, id, side, content, ctx.line.top, cb_height, bfc);
+                // A float that is placed next to an empty line moves the start of the line
+                if ctx.line.empty {
+                    ctx.line.x = ctx.line_range(bfc, 0.0).0;
+                }
+                None
+            }
+            (Outer::Absolute, _) => {
+                self.absolutes.push(AbsoluteBox {
+                    id,
+                    anchor: ctx.anchor,
+                    offset: Coordinate::new(ctx.line.x - ctx.left, ctx.line.top - ctx.top),
+                });
+                None
+            }
+            (_, Inner::Text) => self.layout_text(tree, id, ctx, bfc),
+            (Outer::Inline, _) => Some(self.layout_inline_box(tree, id, ctx, cb_height, bfc)),
+            _ => Some(self.layout_atomic(tree, id, ctx, cb_height, bfc)),
+        }
+    }
+
+    /// Places text on the lines. Text at the start of an empty line that is not next to floats is wrapped for
+    /// the width of the container. Other text is broken into lines one at a time (see float::flow_text): the
+    /// first line starts after the content that is already on it. The box of the text spans the width of the
+    /// container, so the lines can be aligned.
+    fn layout_text(&mut self, tree: &mut LayoutTree, id: LayoutElementId, ctx: &mut InlineContext, bfc: &Bfc) -> Option<geo::Rect> {
+        let ElementContext::Text(mut text_ctx) = tree.get_node_by_id(id)?.context.clone() else {
+            return None;
+        };
+        let style = text_ctx.text_style();
+        let line_height = text_ctx.line_height;
+        let shaper = self.shaper.clone();
+        let measure = |text: &str| shaper.measure(text, &style, f64::MAX).map_or(0.0, |size| size.width);
+
+        // Lines as (top, left, width) relative to the box of the text, with the width of the line box they are on
+        let mut lines = Vec::new();
+        let mut exclusions = bfc.exclusions(ctx.left, ctx.line.top, ctx.width);
+        let indent = ctx.line.x - ctx.left;
+        if indent <= 0.0 && exclusions.is_empty() {
+            match wrap_text(shaper.as_ref(), &text_ctx.text, &style, &text_ctx.wrapping, ctx.width) {
+                Ok(text) => text_ctx.text = text,
+                Err(e) => log::warn!("Failed to wrap text: {:?}", e),
+            }
+            for (index, line) in text_ctx.text.split('\n').enumerate() {
+                lines.push((index as f64 * line_height, 0.0, measure(line), ctx.width));
+            }
+        } else {
+            if indent > 0.0 {
+                exclusions.push(Exclusion { top: 0.0, bottom: line_height, left: indent, right: 0.0 });
+            }
+            let mut flow_lines = flow_text(shaper.as_ref(), &text_ctx.text, &style, ctx.width, &exclusions).unwrap_or_else(|e| {
+                log::warn!("Failed to flow text around floats: {:?}", e);
+                Vec::new()
+            });
+
+            // The next lines start below the content on the current line, which can be higher than the text
+            let shift = (ctx.line.height - line_height).max(0.0);
+            for line in flow_lines.iter_mut().filter(|line| line.y >= line_height) {
+                line.y += shift;
+            }
+            for line in &flow_lines {
+                lines.push((line.y, line.x, measure(&line.text), line.width));
+            }
+            text_ctx.lines = flow_lines;
+            text_ctx.exclusions = exclusions;
+        }
+
+        let height = lines.last().map_or(0.0, |line| line.0 + line_height);
+        set_box_model(tree, id, geo::Rect::new(ctx.left, ctx.line.top, ctx.width, height), NO_EDGES, NO_EDGES, NO_EDGES);
+
+        let extent = lines
+            .iter()
+            .map(|&(top, left, width, line_width)| {
+                let offset = match text_ctx.alignment {
+                    Alignment::End => line_width - left - width,
+                    Alignment::Middle => (line_width - left - width) / 2.0,
+                    _ => 0.0,
+                };
+                geo::Rect::new(ctx.left + left + offset.max(0.0), ctx.line.top + top, width, line_height)
+            })
+            .reduce(|a, b| a.union(&b));
+
+        if let Some(el) = tree.get_node_by_id_mut(id) {
+            el.context = ElementContext::Text(text_ctx);
+        }
+
+        if let Some(&(top, left, width, _)) = lines.last() {
+            if top > 0.0 {
+                ctx.line.top += top;
+                ctx.line.height = 0.0;
+            }
+            ctx.line.x = ctx.left + left;
+            ctx.advance(width, line_height);
+        }
+
+        extent
+    }
+
+    /// Places the content of an inline box on the lines, between its horizontal margins, borders and paddings.
+    /// Returns the margin box, which covers its content on all lines.
+    fn layout_inline_box(
+        &mut self,
+        tree: &mut LayoutTree,
+        id: LayoutElementId,
+        ctx: &mut InlineContext,
+        cb_height: Option<f64>,
+        bfc: &mut Bfc,
+    ) -> geo::Rect {
+        let style = self.boxes[&id].style.clone();
+        // Vertical margins of inline boxes do not take space
+        let margin = Edges { top: 0.0, bottom: 0.0, ..margin_edges(&style.margin, ctx.width) };
+        let border = edges(&style.border, ctx.width);
+        let padding = edges(&style.padding, ctx.width);
+
+        ctx.line.x += margin.left + border.left + padding.left;
+        let start = Coordinate::new(ctx.line.x, ctx.line.top);
+
+        let children = tree.get_node_by_id(id).map_or_else(Vec::new, |el| el.children.clone());
+        let mut content: Option<geo::Rect> = None;
+        for child_id in children {
+            if let Some(extent) = self.layout_inline_item(tree, child_id, ctx, cb_height, bfc) {
+                content = Some(content.map_or(extent, |content| content.union(&extent)));
+            }
+        }
+        ctx.line.x += padding.right + border.right + margin.right;
+
+        let content = content.unwrap_or_else(|| geo::Rect::new(start.x, start.y, 0.0, ctx.line.height));
+        let border_box = geo::Rect::new(
+            content.x - padding.left - border.left,
+            content.y - padding.top - border.top,
+            content.width + padding.left + padding.right + border.left + border.right,
+            content.height + padding.top + padding.bottom + border.top + border.bottom,
+        );
+        set_box_model(tree, id, border_box, margin, border, padding);
+
+        tree.get_node_by_id(id).map_or(border_box, |el| el.box_model.margin_box)
+    }
+
+    /// Places an inline-block, image or inline grid on the line. It moves to the next line when it does not fit
+    /// next to the content on the current line, and below the floats when it does not fit next to them.
+    fn layout_atomic(
+        &mut self,
+        tree: &mut LayoutTree,
+        id: LayoutElementId,
+        ctx: &mut InlineContext,
+        cb_height: Option<f64>,
+        bfc: &mut Bfc,
+    ) -> geo::Rect {
+        let style = self.boxes[&id].style.clone();
+        let margin = margin_edges(&style.margin, ctx.width);
+        let border_width = self.shrink_to_fit(tree, id, ctx.width);
+        let outer_width = border_width + margin.left + margin.right;
+
+        if !ctx.line.empty && ctx.line.x + outer_width > ctx.line_range(bfc, 0.0).1 {
+            ctx.next_line(bfc);
+        }
+        loop {
+            let (left, right) = ctx.line_range(bfc, 0.0);
+            if left + outer_width <= right || (left <= ctx.left && right >= ctx.left + ctx.width) {
+                break;
+            }
+            match bfc.next_float_bottom(ctx.left, ctx.line.top, ctx.width) {
+                Some(bottom) if bottom > ctx.line.top => {
+                    ctx.line.top = bottom;
+                    ctx.line.x = ctx.line_range(bfc, 0.0).0;
+                }
+                _ => break,
+            }
+        }
+
+        let placement = Placement {
+            x: ctx.line.x,
+            y: ctx.line.top + margin.top,
+            cb_width: ctx.width,
+            cb_height,
+            width: Some(border_width),
+            height: None,
+        };
+        let laid = self.layout_box(tree, id, placement, bfc);
+
+        let margin_box = geo::Rect::new(ctx.line.x, ctx.line.top, outer_width, laid.height + margin.top + margin.bottom);
+        ctx.advance(margin_box.width, margin_box.height);
+        margin_box
+    }
+
+    /// Lays out a float, and places it in the float context. The float is placed as high as the given position,
+    /// which is the current line or the bottom of the block before it.
+    #[allow(clippy::too_many_arguments)]
+    fn layout_float(
+        &mut self,
+        tree: &mut LayoutTree,
+        id: LayoutElementId,
+        side: Float,
+        cb: geo::Rect,
+        y: f64,
+        cb_height: Option<f64>,
+        bfc: &mut Bfc,
+    ) {
+        let flow_box = &self.boxes[&id];
+        let clear = flow_box.clear;
+        let margin = margin_edges(&flow_box.style.margin, cb.width);
+        let border_width = self.shrink_to_fit(tree, id, cb.width);
+
+        // The height of the float is known once it has been laid out, so it is laid out at the origin first
+        let placement = Placement {
+            x: 0.0,
+            y: margin.top,
+            cb_width: cb.width,
+            cb_height,
+            width: Some(border_width),
+            height: None,
+        };
+        let laid = self.layout_box(tree, id, placement, bfc);
+
+        let band = (cb.x - bfc.origin.x, cb.x + cb.width - bfc.origin.x);
+        let (x, top) = bfc.floats.place_within(
+            side,
+            border_width + margin.left + margin.right,
+            laid.height + margin.top + margin.bottom,
+            y - bfc.origin.y,
+            clear,
+            band,
+        );
+        translate(tree, id, bfc.origin.x + x, bfc.origin.y + top);
+    }
+
+    /// Lays out an image or SVG
+    fn layout_replaced(&mut self, tree: &mut LayoutTree, id: LayoutElementId, p: Placement) -> Laid {
+        let style = self.boxes[&id].style.clone();
+        let size = self.replaced_size(tree, id, Some(p.cb_width), p.cb_height);
+        let (margin, width) = self.resolve_width(&style, &p, Some(size.width));
+        let height = p.height.unwrap_or(size.height);
+
+        let border = edges(&style.border, p.cb_width);
+        let padding = edges(&style.padding, p.cb_width);
+        set_box_model(tree, id, geo::Rect::new(p.x + margin.left, p.y, width, height), margin, border, padding);
+
+        Laid {
+            height,
+            bottom_margin: CollapsedMargin::new(margin.bottom),
+            empty: false,
+        }
+    }
+
+    /// Lays out a flex, grid or table container with taffy, and grafts the subtree that taffy has laid out into
+    /// the layout tree
+    fn layout_delegated(&mut self, tree: &mut LayoutTree, id: LayoutElementId, p: Placement) -> Laid {
+        let style = self.boxes[&id].style.clone();
+        let (margin, border_width) = self.resolve_width(&style, &p, None);
+        let origin = Coordinate::new(p.x + margin.left, p.y);
+
+        let render_node_id = tree.get_node_by_id(id).map(|el| el.render_node_id);
+        let subtree = render_node_id.and_then(|render_node_id| {
+            self.taffy.layout_subtree(tree.render_tree.clone(), render_node_id, Some(border_width), self.taffy_viewport())
+        });
+        let height = match subtree {
+            Some(subtree) => graft(tree, id, subtree, origin, margin),
+            None => {
+                set_box_model(tree, id, geo::Rect::new(origin.x, origin.y, border_width, 0.0), margin, NO_EDGES, NO_EDGES);
+                0.0
+            }
+        };
+
+        Laid {
+            height,
+            bottom_margin: CollapsedMargin::new(margin.bottom),
+            empty: false,
+        }
+    }
+
+    /// Lays out the absolutely positioned elements once the normal flow has been laid out. Elements that are
+    /// found inside them are laid out after them.
+    fn layout_absolutes(&mut self, tree: &mut LayoutTree) {
+        let mut index = 0;
+        while let Some(absolute) = self.absolutes.get(index).cloned() {
+            index += 1;
+
+            let Some(anchor) = tree.get_node_by_id(absolute.anchor).map(|el| el.box_model.content_box()) else {
+                continue;
+            };
+            let static_position = Coordinate::new(anchor.x + absolute.offset.x, anchor.y + absolute.offset.y);
+            let cb = self.containing_block(tree, absolute.id);
+
+            let flow_box = self.boxes[&absolute.id].clone();
+            let style = &flow_box.style;
+            let margin = margin_edges(&style.margin, cb.width);
+            let border = edges(&style.border, cb.width);
+            let padding = edges(&style.padding, cb.width);
+            let horizontal_edges = border.left + border.right + padding.left + padding.right;
+            let left = length_auto(style.inset.left, cb.width);
+            let right = length_auto(style.inset.right, cb.width);
+            let top = length_auto(style.inset.top, cb.height);
+            let bottom = length_auto(style.inset.bottom, cb.height);
+
+            // Without a width, the box is stretched between its insets, or shrinks to fit its content
+            let width = match (border_size(style.size.width, Some(cb.width), style.box_sizing, horizontal_edges), left, right) {
+                (Some(width), _, _) => clamp_width(style, Some(cb.width), horizontal_edges, width),
+                (None, Some(left), Some(right)) if flow_box.inner != Inner::Replaced => {
+                    clamp_width(style, Some(cb.width), horizontal_edges, cb.width - left - right - margin.left - margin.right)
+                }
+                _ => self.shrink_to_fit(tree, absolute.id, cb.width),
+            };
+            let height = match (top, bottom, style.size.height) {
+                (Some(top), Some(bottom), Dimension::Auto) => Some(cb.height - top - bottom - margin.top - margin.bottom),
+                _ => None,
+            };
+
+            let x = match (left, right) {
+                (Some(left), _) => cb.x + left,
+                (None, Some(right)) => cb.x + cb.width - right - width - margin.left - margin.right,
+                _ => static_position.x,
+            };
+            let y = top.map_or(static_position.y, |top| cb.y + top) + margin.top;
+
+            let placement = Placement {
+                x,
+                y,
+                cb_width: cb.width,
+                cb_height: Some(cb.height),
+                width: Some(width),
+                height,
+            };
+            let mut bfc = Bfc::new(Coordinate::new(x, y), width);
+            let laid = self.layout_box(tree, absolute.id, placement, &mut bfc);
+
+            if let (None, Some(bottom)) = (top, bottom) {
+                let bottom_y = cb.y + cb.height - bottom - margin.bottom - laid.height;
+                translate(tree, absolute.id, 0.0, bottom_y - y);
+            }
+        }
+    }
+
+    /// Returns the padding box of the containing block of an absolutely positioned element. This is the nearest
+    /// positioned ancestor, or the viewport for fixed elements and when there is no positioned ancestor. List
+    /// markers are positioned against their list item.
+    fn containing_block(&self, tree: &LayoutTree, id: LayoutElementId) -> geo::Rect {
+        let viewport = geo::Rect::new(0.0, 0.0, self.viewport.width, self.viewport.height);
+        if tree.get_node_by_id(id).is_some_and(|el| matches!(el.scroll_position, ScrollPosition::Fixed)) {
+            return viewport;
+        }
+
+        let marker = self.boxes[&id].marker;
+        let mut parent = self.boxes[&id].parent;
+        while let Some(parent_id) = parent {
+            if marker || self.boxes[&parent_id].positioned {
+                return tree.get_node_by_id(parent_id).map_or(viewport, |el| el.box_model.padding_box());
+            }
+            parent = self.boxes[&parent_id].parent;
+        }
+
+        viewport
+    }
+
+    /// Sets the rectangles of the sticky elements. They stay inside the content box of their parent.
+    fn resolve_sticky(&self, tree: &mut LayoutTree) {
+        for (id, flow_box) in &self.boxes {
+            let Some(container) = flow_box.parent.and_then(|parent_id| tree.get_node_by_id(parent_id)).map(|el| el.box_model.content_box()) else {
+                continue;
+            };
+            let Some(el) = tree.get_node_by_id_mut(*id) else {
+                continue;
+            };
+            let rect = el.box_model.border_box();
+            if let ScrollPosition::Sticky(constraint) = &mut el.scroll_position {
+                constraint.rect = rect;
+                constraint.container = container;
+            }
+        }
+    }
+
+    /// Resolves the horizontal margins and the width of the border box of a block-level box. Auto margins take
+    /// the space that is left next to a box with a width, which centers the box when both margins are auto.
+    /// Boxes without a width fill their containing block. The width of replaced elements is given.
+    fn resolve_width(&self, style: &Style, p: &Placement, intrinsic_width: Option<f64>) -> (Edges, f64) {
+        let mut margin = margin_edges(&style.margin, p.cb_width);
+        if let Some(width) = p.width {
+            return (margin, width);
+        }
+
+        let border = edges(&style.border, p.cb_width);
+        let padding = edges(&style.padding, p.cb_width);
+        let horizontal_edges = border.left + border.right + padding.left + padding.right;
+
+        let width = border_size(style.size.width, Some(p.cb_width), style.box_sizing, horizontal_edges).or(intrinsic_width);
+        let Some(width) = width else {
+            let width = clamp_width(style, Some(p.cb_width), horizontal_edges, p.cb_width - margin.left - margin.right);
+            return (margin, width);
+        };
+
+        let width = clamp_width(style, Some(p.cb_width), horizontal_edges, width);
+        let rest = (p.cb_width - width - margin.left - margin.right).max(0.0);
+        match (style.margin.left, style.margin.right) {
+            (LengthPercentageAuto::Auto, LengthPercentageAuto::Auto) => {
+                margin.left = rest / 2.0;
+                margin.right = rest / 2.0;
+            }
+            (LengthPercentageAuto::Auto, _) => margin.left = rest,
+            (_, LengthPercentageAuto::Auto) => margin.right = rest,
+            _ => {}
+        }
+
+        (margin, width)
+    }
+
+    /// Returns the width of the border box of a float, inline-block or absolutely positioned element without a
+    /// width: its content is not wrapped, unless it does not fit in the containing block
+    fn shrink_to_fit(&mut self, tree: &LayoutTree, id: LayoutElementId, cb_width: f64) -> f64 {
+        let style = self.boxes[&id].style.clone();
+        let margin = margin_edges(&style.margin, cb_width);
+        let horizontal_edges = horizontal_edges(&style, cb_width);
+
+        if self.boxes[&id].inner == Inner::Replaced {
+            return self.replaced_size(tree, id, Some(cb_width), None).width;
+        }
+        if let Some(width) = border_size(style.size.width, Some(cb_width), style.box_sizing, horizontal_edges) {
+            return clamp_width(&style, Some(cb_width), horizontal_edges, width);
+        }
+
+        let preferred = self.max_content_width(tree, id) - margin.left - margin.right;
+        let available = (cb_width - margin.left - margin.right).max(0.0);
+        clamp_width(&style, Some(cb_width), horizontal_edges, preferred.min(available))
+    }
+
+    /// Returns the width of the margin box of an element when none of its content is wrapped. Percentages are
+    /// unknown at this point and count as zero.
+    fn max_content_width(&mut self, tree: &LayoutTree, id: LayoutElementId) -> f64 {
+        let flow_box = self.boxes[&id].clone();
+        let style = &flow_box.style;
+        let margin = margin_edges(&style.margin, 0.0);
+        let margins = margin.left + margin.right;
+
+        match flow_box.inner {
+            Inner::Text => match tree.get_node_by_id(id).map(|el| &el.context) {
+                Some(ElementContext::Text(text_ctx)) => self
+                    .shaper
+                    .measure(&text_ctx.text, &text_ctx.text_style(), f64::MAX)
+                    .map_or(0.0, |size| size.width),
+                _ => 0.0,
+            },
+            Inner::Replaced => self.replaced_size(tree, id, None, None).width + margins,
+            Inner::Delegated => {
+                let render_node_id = tree.get_node_by_id(id).map(|el| el.render_node_id);
+                let width = render_node_id
+                    .and_then(|render_node_id| self.taffy.layout_subtree(tree.render_tree.clone(), render_node_id, None, self.taffy_viewport()))
+                    .and_then(|subtree| subtree.get_node_by_id(subtree.root_id).map(|el| el.box_model.border_box().width));
+                width.unwrap_or(0.0) + margins
+            }
+            Inner::Flow => {
+                let horizontal_edges = horizontal_edges(style, 0.0);
+                if let Some(width) = border_size(style.size.width, None, style.box_sizing, horizontal_edges) {
+                    return width + margins;
+                }
+
+                // Inline content is placed on a single line, blocks are stacked
+                let children = tree.get_node_by_id(id).map_or_else(Vec::new, |el| el.children.clone());
+                let inline = children.iter().any(|child| matches!(self.boxes[child].outer, Outer::Inline | Outer::AtomicInline));
+                let mut width: f64 = 0.0;
+                for child_id in children {
+                    if self.boxes[&child_id].outer == Outer::Absolute {
+                        continue;
+                    }
+                    let child_width = self.max_content_width(tree, child_id);
+                    width = if inline { width + child_width } else { width.max(child_width) };
+                }
+
+                clamp_width(style, None, horizontal_edges, width + horizontal_edges) + margins
+            }
+        }
+    }
+
+    /// Returns the size of the border box of an image or SVG (see replaced::content_size). A maximum width (like
+    /// max-width: 100%) scales the media down, keeping its aspect ratio.
+    fn replaced_size(&self, tree: &LayoutTree, id: LayoutElementId, cb_width: Option<f64>, cb_height: Option<f64>) -> geo::Dimension {
+        let style = &self.boxes[&id].style;
+        let base = cb_width.unwrap_or(0.0);
+        let border = edges(&style.border, base);
+        let padding = edges(&style.padding, base);
+        let horizontal_edges = border.left + border.right + padding.left + padding.right;
+        let vertical_edges = border.top + border.bottom + padding.top + padding.bottom;
+
+        let natural = match tree.get_node_by_id(id).map(|el| &el.context) {
+            Some(ElementContext::Image(image_ctx)) => image_ctx.dimension,
+            Some(ElementContext::Svg(svg_ctx)) => svg_ctx.dimension,
+            _ => geo::Dimension::ZERO,
+        };
+
+        let content = |size: Option<f64>, edges: f64| size.map(|size| (size - edges).max(0.0));
+        let mut width = content(border_size(style.size.width, cb_width, style.box_sizing, horizontal_edges), horizontal_edges);
+        let height = content(border_size(style.size.height, cb_height, style.box_sizing, vertical_edges), vertical_edges);
+        if width.is_none() && height.is_none() {
+            let max_width = content(border_size(style.max_size.width, cb_width, style.box_sizing, horizontal_edges), horizontal_edges);
+            width = max_width.filter(|max_width| *max_width < replaced::content_size(natural, None, None).width);
+        }
+
+        let size = replaced::content_size(natural, width, height);
+        geo::Dimension::new(size.width + horizontal_edges, size.height + vertical_edges)
+    }
+
+    /// Returns true when the box establishes a new block formatting context, which contains its floats and
+    /// whose margins do not collapse with the margins of its children
+    fn is_bfc_root(&self, id: LayoutElementId) -> bool {
+        let flow_box = &self.boxes[&id];
+        flow_box.parent.is_none()
+            || flow_box.outer != Outer::Block
+            || flow_box.inner != Inner::Flow
+            || flow_box.style.overflow.x != Overflow::Visible
+            || flow_box.style.overflow.y != Overflow::Visible
+    }
+
+    /// Returns true when the top margin of a block collapses with the top margin of its first child
+    fn collapses_top(&self, tree: &LayoutTree, id: LayoutElementId) -> bool {
+        let style = &self.boxes[&id].style;
+        if self.is_bfc_root(id) || !is_zero(style.border.top) || !is_zero(style.padding.top) {
+            return false;
+        }
+
+        self.first_in_flow(tree, id).is_some_and(|child_id| self.boxes[&child_id].outer == Outer::Block)
+    }
+
+    fn first_in_flow(&self, tree: &LayoutTree, id: LayoutElementId) -> Option<LayoutElementId> {
+        tree.get_node_by_id(id)?
+            .children
+            .iter()
+            .copied()
+            .find(|child_id| !matches!(self.boxes[child_id].outer, Outer::Float(_) | Outer::Absolute))
+    }
+
+    /// Returns the top margin of a block, collapsed with the top margins of the first children it collapses with
+    fn leading_margin(&self, tree: &LayoutTree, id: LayoutElementId, cb_width: f64) -> CollapsedMargin {
+        let mut margin = CollapsedMargin::default();
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let top = length_auto(self.boxes[&id].style.margin.top, cb_width).unwrap_or(0.0);
+            margin = margin.collapse(CollapsedMargin::new(top));
+            current = if self.collapses_top(tree, id) { self.first_in_flow(tree, id) } else { None };
+        }
+
+        margin
+    }
+}
+
+/// Moves the nodes of a subtree that has been laid out by taffy into the layout tree, with their border boxes
+/// relative to the given origin. The root of the subtree is the delegated element itself, which gets the margins
+/// resolved by the flow layouter. Returns the height of its border box.
+fn graft(tree: &mut LayoutTree, id: LayoutElementId, subtree: LayoutTree, origin: Coordinate, margin: Edges) -> f64 {
+    remove_descendants(tree, id);
+
+    let ids: HashMap<LayoutElementId, LayoutElementId> = subtree
+        .arena
+        .keys()
+        .map(|sub_id| (*sub_id, if *sub_id == subtree.root_id { id } else { tree.next_node_id() }))
+        .collect();
+
+    let mut height = 0.0;
+    for (sub_id, mut node) in subtree.arena {
+        node.id = ids[&sub_id];
+        node.children = node.children.iter().filter_map(|child_id| ids.get(child_id).copied()).collect();
+        node.box_model.margin_box = node.box_model.margin_box.shift(origin);
+        if let ScrollPosition::Sticky(constraint) = &mut node.scroll_position {
+            constraint.rect = constraint.rect.shift(origin);
+            constraint.container = constraint.container.shift(origin);
+        }
+
+        if sub_id == subtree.root_id {
+            let border_box = node.box_model.border_box();
+            height = border_box.height;
+            node.box_model.margin = margin;
+            node.box_model.margin_box = geo::Rect::new(
+                border_box.x - margin.left,
+                border_box.y - margin.top,
+                border_box.width + margin.left + margin.right,
+                border_box.height + margin.top + margin.bottom,
+            );
+            if let Some(el) = tree.get_node_by_id(id) {
+                node.scroll_position = el.scroll_position.clone();
+            }
+        }
+
+        tree.arena.insert(node.id, node);
+    }
+
+    height
+}
+
+/// Removes the descendants of an element from the layout tree
+fn remove_descendants(tree: &mut LayoutTree, id: LayoutElementId) {
+    let mut stack = tree.get_node_by_id_mut(id).map(|el| std::mem::take(&mut el.children)).unwrap_or_default();
+    while let Some(child_id) = stack.pop() {
+        if let Some(child) = tree.arena.remove(&child_id) {
+            stack.extend(child.children);
+        }
+    }
+}
+
+/// Moves an element and its descendants
+fn translate(tree: &mut LayoutTree, id: LayoutElementId, dx: f64, dy: f64) {
+    let offset = Coordinate::new(dx, dy);
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        let Some(el) = tree.get_node_by_id_mut(id) else {
+            continue;
+        };
+        el.box_model.margin_box = el.box_model.margin_box.shift(offset);
+        stack.extend(el.children.iter().copied());
+    }
+}
+
+fn set_box_model(tree: &mut LayoutTree, id: LayoutElementId, border_box: geo::Rect, margin: Edges, border: Edges, padding: Edges) {
+    let Some(el) = tree.get_node_by_id_mut(id) else {
+        return;
+    };
+
+    el.box_model = BoxModel {
+        margin_box: geo::Rect::new(
+            border_box.x - margin.left,
+            border_box.y - margin.top,
+            border_box.width + margin.left + margin.right,
+            border_box.height + margin.top + margin.bottom,
+        ),
+        margin,
+        border,
+        padding,
+    };
+}
+
+fn length(value: LengthPercentage, base: f64) -> f64 {
+    match value {
+        LengthPercentage::Length(value) => value as f64,
+        LengthPercentage::Percent(value) => value as f64 * base,
+    }
+}
+
+/// Returns the length, or None when it is auto
+fn length_auto(value: LengthPercentageAuto, base: f64) -> Option<f64> {
+    match value {
+        LengthPercentageAuto::Length(value) => Some(value as f64),
+        LengthPercentageAuto::Percent(value) => Some(value as f64 * base),
+        LengthPercentageAuto::Auto => None,
+    }
+}
+
+fn is_zero(value: LengthPercentage) -> bool {
+    matches!(value, LengthPercentage::Length(value) | LengthPercentage::Percent(value) if value == 0.0)
+}
+
+fn edges(rect: &taffy::Rect<LengthPercentage>, base: f64) -> Edges {
+    Edges {
+        top: length(rect.top, base),
+        right: length(rect.right, base),
+        bottom: length(rect.bottom, base),
+        left: length(rect.left, base),
+    }
+}
+
+/// Returns the margins, with auto margins as zero. Percentages are relative to the width of the containing block.
+fn margin_edges(rect: &taffy::Rect<LengthPercentageAuto>, base: f64) -> Edges {
+    Edges {
+        top: length_auto(rect.top, base).unwrap_or(0.0),
+        right: length_auto(rect.right, base).unwrap_or(0.0),
+        bottom: length_auto(rect.bottom, base).unwrap_or(0.0),
+        left: length_auto(rect.left, base).unwrap_or(0.0),
+    }
+}
+
+fn horizontal_edges(style: &Style, base: f64) -> f64 {
+    let border = edges(&style.border, base);
+    let padding = edges(&style.padding, base);
+    border.left + border.right + padding.left + padding.right
+}
+
+/// Returns the size of the border box for a width or height, or None when it is auto or a percentage of an
+/// unknown size
+fn border_size(value: Dimension, base: Option<f64>, box_sizing: BoxSizing, edges: f64) -> Option<f64> {
+    let size = match value {
+        Dimension::Length(value) => value as f64,
+        Dimension::Percent(value) => value as f64 * base?,
+        Dimension::Auto => return None,
+    };
+
+    Some(match box_sizing {
+        BoxSizing::ContentBox => size + edges,
+        BoxSizing::BorderBox => size.max(edges),
+    })
+}
+
+/// Clamps the width of a border box between the minimum and maximum width of the box
+fn clamp_width(style: &Style, cb_width: Option<f64>, edges: f64, width: f64) -> f64 {
+    let width = clamp_size(
+        width,
+        border_size(style.min_size.width, cb_width, style.box_sizing, edges),
+        border_size(style.max_size.width, cb_width, style.box_sizing, edges),
+    );
+    width.max(edges)
+}
+
+fn clamp_size(size: f64, min: Option<f64>, max: Option<f64>) -> f64 {
+    let size = max.map_or(size, |max| size.min(max));
+    min.map_or(size, |min| size.max(min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::style::Direction;
+    use crate::layouter::fixtures::{block_style, border_box, layout};
+
+    #[test]
+    fn test_float_and_clear() {
+        // <body><div style="float: left"/><div style="clear: left"/></body>
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, Some(block_style(0.0, 0.0, 0.0, 0.0)));
+        let mut float_style = block_style(100.0, 50.0, 0.0, 0.0);
+        float_style.set_property(StyleProperty::Float, StyleValue::Float(Float::Left));
+        let float_id = doc.new_element(Some(body_id), "div", None, false, Some(float_style));
+        let mut clear_style = block_style(0.0, 20.0, 0.0, 0.0);
+        clear_style.set_property(StyleProperty::Clear, StyleValue::Clear(Clear::Left));
+        let clear_id = doc.new_element(Some(body_id), "div", None, false, Some(clear_style));
+        doc.add_child(body_id, float_id);
+        doc.add_child(body_id, clear_id);
+        doc.set_root(body_id);
+
+        let layout_tree = layout("flow", doc);
+        let border_box = |dom_node_id| border_box(&layout_tree, dom_node_id);
+        assert_eq!(border_box(float_id), geo::Rect::new(0.0, 0.0, 100.0, 50.0));
+        assert_eq!(border_box(clear_id).y, 50.0);
+        // The body contains its floats
+        assert_eq!(border_box(body_id).height, 70.0);
+    }
+
+    #[test]
+    fn test_logical_float_and_clear() {
+        // <body dir="rtl"><div style="float: inline-start"/><div style="float: inline-end"/><div style="clear: inline-start"/></body>
+        let rtl_style = |width, height, float: Option<Float>, clear: Option<Clear>| {
+            let mut style = block_style(width, height, 0.0, 0.0);
+            style.set_property(StyleProperty::Direction, StyleValue::Direction(Direction::Rtl));
+            if let Some(float) = float {
+                style.set_property(StyleProperty::Float, StyleValue::Float(float));
+            }
+            if let Some(clear) = clear {
+                style.set_property(StyleProperty::Clear, StyleValue::Clear(clear));
+            }
+            Some(style)
+        };
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, rtl_style(0.0, 0.0, None, None));
+        let start_id = doc.new_element(Some(body_id), "div", None, false, rtl_style(100.0, 50.0, Some(Float::InlineStart), None));
+        let end_id = doc.new_element(Some(body_id), "div", None, false, rtl_style(100.0, 30.0, Some(Float::InlineEnd), None));
+        let clear_id = doc.new_element(Some(body_id), "div", None, false, rtl_style(0.0, 20.0, None, Some(Clear::InlineStart)));
+        for child_id in [start_id, end_id, clear_id] {
+            doc.add_child(body_id, child_id);
+        }
+        doc.set_root(body_id);
+
+        // The inline start is on the right side for right-to-left text
+        let layout_tree = layout("flow", doc);
+        let border_box = |dom_node_id| border_box(&layout_tree, dom_node_id);
+        assert_eq!(border_box(start_id), geo::Rect::new(700.0, 0.0, 100.0, 50.0));
+        assert_eq!(border_box(end_id), geo::Rect::new(0.0, 0.0, 100.0, 30.0));
+        assert_eq!(border_box(clear_id).y, 50.0);
+    }
+
+    #[test]
+    fn test_delegated_flex() {
+        // <body><div style="display: flex"><div/><div/></div></body>
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, Some(block_style(0.0, 0.0, 0.0, 10.0)));
+        let mut flex_style = block_style(0.0, 0.0, 0.0, 0.0);
+        flex_style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::Flex));
+        let flex_id = doc.new_element(Some(body_id), "div", None, false, Some(flex_style));
+        let first_id = doc.new_element(Some(flex_id), "div", None, false, Some(block_style(50.0, 30.0, 0.0, 0.0)));
+        let last_id = doc.new_element(Some(flex_id), "div", None, false, Some(block_style(50.0, 30.0, 0.0, 0.0)));
+        doc.add_child(body_id, flex_id);
+        doc.add_child(flex_id, first_id);
+        doc.add_child(flex_id, last_id);
+        doc.set_root(body_id);
+
+        let layout_tree = layout("flow", doc);
+        let border_box = |dom_node_id| border_box(&layout_tree, dom_node_id);
+        assert_eq!(border_box(flex_id), geo::Rect::new(0.0, 10.0, 800.0, 30.0));
+        assert_eq!(border_box(first_id), geo::Rect::new(0.0, 10.0, 50.0, 30.0));
+        assert_eq!(border_box(last_id), geo::Rect::new(50.0, 10.0, 50.0, 30.0));
+    }
+}
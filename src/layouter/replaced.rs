@@ -0,0 +1,104 @@
+use crate::common::document::style::{ObjectFit, Unit};
+use crate::common::geo::{Dimension, Rect};
+
+// Images and SVGs are replaced elements: their content comes from their media, which has a natural size. Sides
+// of the box that are not set by CSS (or by the width and height attributes) follow the natural size, keeping
+// its aspect ratio. The media is then fitted into the content box with object-fit and object-position. The
+// layouters and the painter share these rules.
+
+/// Size of replaced elements whose media has no natural size, like an SVG without a size or view box
+pub const DEFAULT_OBJECT_SIZE: Dimension = Dimension { width: 300.0, height: 150.0 };
+
+/// Returns the aspect ratio (width / height) of the natural size, or None when the media has no size
+pub fn aspect_ratio(natural: Dimension) -> Option<f64> {
+    (natural.width > 0.0 && natural.height > 0.0).then(|| natural.width / natural.height)
+}
+
+/// Returns the size of the content box of a replaced element, when only some of its sides are set. A missing side
+/// follows from the other side through the aspect ratio, or is the natural size when neither side is set.
+pub fn content_size(natural: Dimension, width: Option<f64>, height: Option<f64>) -> Dimension {
+    let ratio = aspect_ratio(natural);
+    let natural = if ratio.is_some() { natural } else { DEFAULT_OBJECT_SIZE };
+
+    match (width, height, ratio) {
+        (Some(width), Some(height), _) => Dimension::new(width, height),
+        (Some(width), None, Some(ratio)) => Dimension::new(width, width / ratio),
+        (None, Some(height), Some(ratio)) => Dimension::new(height * ratio, height),
+        (width, height, _) => Dimension::new(width.unwrap_or(natural.width), height.unwrap_or(natural.height)),
+    }
+}
+
+/// Returns where the media is painted, for a content box and the object-fit and object-position of the element.
+/// The rectangle can be larger than the content box (cover and none), in which case the media is clipped.
+pub fn fit_rect(content_box: Rect, natural: Dimension, fit: ObjectFit, position: &((f32, Unit), (f32, Unit))) -> Rect {
+    let Some(ratio) = aspect_ratio(natural) else {
+        return content_box;
+    };
+
+    let contain = if content_box.width / content_box.height > ratio {
+        Dimension::new(content_box.height * ratio, content_box.height)
+    } else {
+        Dimension::new(content_box.width, content_box.width / ratio)
+    };
+    let size = match fit {
+        ObjectFit::Fill => return content_box,
+        ObjectFit::Contain => contain,
+        ObjectFit::Cover if content_box.width / content_box.height > ratio => {
+            Dimension::new(content_box.width, content_box.width / ratio)
+        }
+        ObjectFit::Cover => Dimension::new(content_box.height * ratio, content_box.height),
+        ObjectFit::None => natural,
+        ObjectFit::ScaleDown if contain.width < natural.width => contain,
+        ObjectFit::ScaleDown => natural,
+    };
+
+    let offset = |(value, unit): &(f32, Unit), free: f64| match unit {
+        Unit::Percent => *value as f64 / 100.0 * free,
+        _ => *value as f64,
+    };
+    Rect::new(
+        content_box.x + offset(&position.0, content_box.width - size.width),
+        content_box.y + offset(&position.1, content_box.height - size.height),
+        size.width,
+        size.height,
+    )
+}
+
+/// Parses the width or height attribute of an image or SVG, which are pixels. Percentages are not supported.
+pub fn parse_size_attribute(value: &str) -> Option<f64> {
+    let value = value.trim().trim_end_matches("px").trim();
+    value.parse::<f64>().ok().filter(|value| *value >= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_size() {
+        let natural = Dimension::new(400.0, 200.0);
+        assert_eq!(content_size(natural, None, None), natural);
+        assert_eq!(content_size(natural, Some(100.0), None), Dimension::new(100.0, 50.0));
+        assert_eq!(content_size(natural, None, Some(100.0)), Dimension::new(200.0, 100.0));
+        assert_eq!(content_size(natural, Some(10.0), Some(10.0)), Dimension::new(10.0, 10.0));
+        assert_eq!(content_size(Dimension::ZERO, None, Some(10.0)), Dimension::new(300.0, 10.0));
+    }
+
+    #[test]
+    fn test_fit_rect() {
+        let content_box = Rect::new(10.0, 10.0, 100.0, 100.0);
+        let natural = Dimension::new(400.0, 200.0);
+        let center = ((50.0, Unit::Percent), (50.0, Unit::Percent));
+
+        assert_eq!(fit_rect(content_box, natural, ObjectFit::Fill, &center), content_box);
+        assert_eq!(fit_rect(content_box, natural, ObjectFit::Contain, &center), Rect::new(10.0, 35.0, 100.0, 50.0));
+        assert_eq!(fit_rect(content_box, natural, ObjectFit::Cover, &center), Rect::new(-40.0, 10.0, 200.0, 100.0));
+        assert_eq!(fit_rect(content_box, natural, ObjectFit::None, &center), Rect::new(-140.0, -40.0, 400.0, 200.0));
+        assert_eq!(fit_rect(content_box, natural, ObjectFit::ScaleDown, &center), Rect::new(10.0, 35.0, 100.0, 50.0));
+
+        // Small media is not scaled up by scale-down, and is placed at the top left corner
+        let top_left = ((0.0, Unit::Px), (0.0, Unit::Percent));
+        let small = Dimension::new(40.0, 20.0);
+        assert_eq!(fit_rect(content_box, small, ObjectFit::ScaleDown, &top_left), Rect::new(10.0, 10.0, 40.0, 20.0));
+    }
+}
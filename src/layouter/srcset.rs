@@ -0,0 +1,299 @@
+use crate::layouter::Viewport;
+
+// Responsive images: an <img> (or a <source> in a <picture>) can offer the same image in several resolutions with
+// srcset. Each candidate has a width descriptor ("hero-800.jpg 800w") or a pixel density descriptor ("hero@2x.jpg
+// 2x"). With width descriptors, the sizes attribute tells how wide the image is displayed, so the density of each
+// candidate follows from its width. The candidate is picked for the viewport width and the device pixel ratio,
+// so small windows do not download and decode images that are much larger than they are displayed.
+//
+// See https://html.spec.whatwg.org/multipage/images.html#srcset-attributes
+
+/// Font size that em and rem lengths in sizes and media queries are relative to
+const DEFAULT_FONT_SIZE: f64 = 16.0;
+
+/// Media types of images that can be decoded
+const SUPPORTED_TYPES: [&str; 8] = [
+    "image/png",
+    "image/jpeg",
+    "image/jpg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/svg+xml",
+    "image/x-icon",
+];
+
+/// Environment that image candidates are selected for
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Environment {
+    /// Width of the viewport in CSS pixels, or None when the layout has no viewport
+    pub viewport_width: Option<f64>,
+    /// Number of device pixels per CSS pixel
+    pub device_pixel_ratio: f64,
+}
+
+impl Environment {
+    pub fn new(viewport_width: Option<f64>, device_pixel_ratio: f64) -> Self {
+        Self { viewport_width, device_pixel_ratio }
+    }
+}
+
+impl From<Viewport> for Environment {
+    fn from(viewport: Viewport) -> Self {
+        Self::new(viewport.size.map(|size| size.width), viewport.device_pixel_ratio)
+    }
+}
+
+/// Descriptor of an image candidate
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Descriptor {
+    /// Width of the image in pixels (800w)
+    Width(f64),
+    /// Pixel density of the image (2x)
+    Density(f64),
+}
+
+/// Image candidate from a srcset attribute
+#[derive(Clone, PartialEq, Debug)]
+pub struct Candidate {
+    pub url: String,
+    pub descriptor: Descriptor,
+}
+
+impl Candidate {
+    pub fn new(url: &str, descriptor: Descriptor) -> Self {
+        Self { url: url.to_string(), descriptor }
+    }
+}
+
+/// Parses a srcset attribute into its image candidates. Candidates with invalid descriptors are dropped, and
+/// candidates without a descriptor have a density of 1x.
+pub fn parse_srcset(srcset: &str) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+
+        let url_end = rest.find(|c: char| c.is_ascii_whitespace()).unwrap_or(rest.len());
+        let url = &rest[..url_end];
+        rest = &rest[url_end..];
+
+        // A comma at the end of the url ends the candidate, without descriptors
+        let descriptors = if url.ends_with(',') {
+            ""
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let descriptors = &rest[..end];
+            rest = &rest[end..];
+            descriptors
+        };
+        let url = url.trim_end_matches(',');
+
+        if let Some(descriptor) = parse_descriptors(descriptors) {
+            candidates.push(Candidate::new(url, descriptor));
+        }
+    }
+
+    candidates
+}
+
+/// Parses the descriptors of a candidate. Height descriptors are only allowed together with a width, and are
+/// ignored.
+fn parse_descriptors(descriptors: &str) -> Option<Descriptor> {
+    let mut descriptor = None;
+    for token in descriptors.split_ascii_whitespace() {
+        let kind = token.chars().last()?;
+        let value = token[..token.len() - kind.len_utf8()].parse::<f64>().ok().filter(|value| *value > 0.0)?;
+        match (kind, descriptor) {
+            ('w', None) if value.fract() == 0.0 => descriptor = Some(Descriptor::Width(value)),
+            ('x', None) => descriptor = Some(Descriptor::Density(value)),
+            ('h', Some(Descriptor::Width(_))) if value.fract() == 0.0 => {}
+            _ => return None,
+        }
+    }
+
+    Some(descriptor.unwrap_or(Descriptor::Density(1.0)))
+}
+
+/// Returns the width in CSS pixels that the image is displayed at, from a sizes attribute. The first entry whose
+/// media condition matches is used, and the image takes the width of the viewport when there is none. Returns
+/// None when the width depends on the viewport and there is no viewport.
+pub fn parse_sizes(sizes: Option<&str>, env: &Environment) -> Option<f64> {
+    for entry in sizes.unwrap_or_default().split(',') {
+        let entry = entry.trim();
+        let (condition, size) = match entry.rfind(|c: char| c.is_ascii_whitespace() || c == ')') {
+            Some(pos) => entry.split_at(pos + 1),
+            None => ("", entry),
+        };
+        let Some(size) = parse_length(size.trim(), env) else {
+            continue;
+        };
+        if condition.trim().is_empty() || media_matches(condition, env) {
+            return size;
+        }
+    }
+
+    env.viewport_width
+}
+
+/// Parses a length of the sizes attribute. Returns Some(None) for a viewport length without a viewport.
+fn parse_length(length: &str, env: &Environment) -> Option<Option<f64>> {
+    if length == "0" {
+        return Some(Some(0.0));
+    }
+
+    let unit_start = length.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = length.split_at(unit_start);
+    let value = value.parse::<f64>().ok().filter(|value| *value >= 0.0)?;
+    match unit.to_ascii_lowercase().as_str() {
+        "px" => Some(Some(value)),
+        "em" | "rem" => Some(Some(value * DEFAULT_FONT_SIZE)),
+        "vw" => Some(env.viewport_width.map(|width| value * width / 100.0)),
+        _ => None,
+    }
+}
+
+/// Returns true when the media query matches the environment. Only the media types and the width features are
+/// supported: queries with other features do not match. Width features do not match without a viewport.
+pub fn media_matches(query: &str, env: &Environment) -> bool {
+    // A comma separated list matches when one of its queries matches
+    query.split(',').any(|query| {
+        let query = query.trim().to_ascii_lowercase();
+        let (negated, query) = match query.strip_prefix("not ") {
+            Some(query) => (true, query.trim().to_string()),
+            None => (false, query.trim_start_matches("only ").to_string()),
+        };
+        let matches = query.split(" and ").all(|term| term_matches(term.trim(), env));
+        matches != negated
+    })
+}
+
+fn term_matches(term: &str, env: &Environment) -> bool {
+    match term {
+        "" | "all" | "screen" => return true,
+        "print" => return false,
+        _ => {}
+    }
+
+    let Some(feature) = term.strip_prefix('(').and_then(|term| term.strip_suffix(')')) else {
+        return false;
+    };
+    let Some((name, value)) = feature.split_once(':') else {
+        return false;
+    };
+    let (Some(Some(value)), Some(width)) = (parse_length(value.trim(), env), env.viewport_width) else {
+        return false;
+    };
+
+    match name.trim() {
+        "min-width" => width >= value,
+        "max-width" => width <= value,
+        "width" => width == value,
+        _ => false,
+    }
+}
+
+/// Returns true when images of the media type (of a <source> element) can be decoded
+pub fn is_supported_type(media_type: &str) -> bool {
+    let media_type = media_type.split(';').next().unwrap_or_default().trim();
+    SUPPORTED_TYPES.iter().any(|supported| supported.eq_ignore_ascii_case(media_type))
+}
+
+/// Selects the candidate for the environment, and returns it with its pixel density. This is the candidate with
+/// the smallest density that is at least the device pixel ratio, or the candidate with the largest density when
+/// there is none. Without a viewport, the width of the image that width descriptors are relative to is not known,
+/// so the widest candidate is selected at 1x.
+pub fn select(candidates: &[Candidate], sizes: Option<&str>, env: &Environment) -> Option<(String, f64)> {
+    let source_size = parse_sizes(sizes, env);
+
+    let densities = candidates.iter().map(|candidate| {
+        let density = match (candidate.descriptor, source_size) {
+            (Descriptor::Density(density), _) => density,
+            (Descriptor::Width(width), Some(size)) if size > 0.0 => width / size,
+            (Descriptor::Width(width), _) => width,
+        };
+        (candidate, density)
+    });
+
+    let selected = densities
+        .clone()
+        .filter(|(_, density)| *density >= env.device_pixel_ratio)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .or_else(|| densities.max_by(|a, b| a.1.total_cmp(&b.1)))?;
+
+    match (selected.0.descriptor, source_size) {
+        (Descriptor::Width(_), None) => Some((selected.0.url.clone(), 1.0)),
+        _ => Some((selected.0.url.clone(), selected.1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_srcset() {
+        assert_eq!(
+            parse_srcset("small.jpg 400w, large.jpg 1600w,huge.jpg   3200w 1800h"),
+            vec![
+                Candidate::new("small.jpg", Descriptor::Width(400.0)),
+                Candidate::new("large.jpg", Descriptor::Width(1600.0)),
+                Candidate::new("huge.jpg", Descriptor::Width(3200.0)),
+            ]
+        );
+        assert_eq!(
+            parse_srcset("a.png, b.png 2x,c.png, d.png 1.5x 2x, e.png -1x"),
+            vec![
+                Candidate::new("a.png", Descriptor::Density(1.0)),
+                Candidate::new("b.png", Descriptor::Density(2.0)),
+                Candidate::new("c.png", Descriptor::Density(1.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_sizes() {
+        let env = Environment::new(Some(800.0), 1.0);
+        let sizes = Some("(max-width: 600px) 100vw, (max-width: 1000px) 50vw, 400px");
+        assert_eq!(parse_sizes(sizes, &env), Some(400.0));
+        assert_eq!(parse_sizes(sizes, &Environment::new(Some(500.0), 1.0)), Some(500.0));
+        assert_eq!(parse_sizes(sizes, &Environment::new(Some(1200.0), 1.0)), Some(400.0));
+        assert_eq!(parse_sizes(Some("20em"), &env), Some(320.0));
+        assert_eq!(parse_sizes(None, &env), Some(800.0));
+        assert_eq!(parse_sizes(None, &Environment::new(None, 1.0)), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let candidates = parse_srcset("hero-800.jpg 800w, hero-1600.jpg 1600w, hero-3840.jpg 3840w");
+
+        let env = Environment::new(Some(800.0), 1.0);
+        assert_eq!(select(&candidates, None, &env), Some(("hero-800.jpg".to_string(), 1.0)));
+        let env = Environment::new(Some(800.0), 2.0);
+        assert_eq!(select(&candidates, None, &env), Some(("hero-1600.jpg".to_string(), 2.0)));
+        let env = Environment::new(Some(2560.0), 2.0);
+        assert_eq!(select(&candidates, None, &env), Some(("hero-3840.jpg".to_string(), 1.5)));
+        let env = Environment::new(Some(2560.0), 2.0);
+        assert_eq!(select(&candidates, Some("400px"), &env), Some(("hero-800.jpg".to_string(), 2.0)));
+
+        let candidates = parse_srcset("logo.png, logo@2x.png 2x");
+        let env = Environment::new(None, 2.0);
+        assert_eq!(select(&candidates, None, &env), Some(("logo@2x.png".to_string(), 2.0)));
+        assert_eq!(select(&[], None, &env), None);
+    }
+
+    #[test]
+    fn test_media_matches() {
+        let env = Environment::new(Some(800.0), 1.0);
+        assert!(media_matches("(min-width: 600px)", &env));
+        assert!(!media_matches("(min-width: 60em)", &env));
+        assert!(media_matches("screen and (max-width: 800px)", &env));
+        assert!(media_matches("print, (orientation: landscape), (max-width: 900px)", &env));
+        assert!(media_matches("not print", &env));
+        assert!(!media_matches("(min-width: 600px)", &Environment::new(None, 1.0)));
+    }
+}
@@ -0,0 +1,151 @@
+// Taffy has no table layout, so the taffy layouter lays out a table as a CSS grid. This module computes the
+// structure of that grid: which slots each cell occupies, taking the colspan and rowspan attributes into
+// account.
+
+/// Cell as found in a row of the table
+#[derive(Clone, Debug)]
+pub struct TableCell<T> {
+    pub id: T,
+    pub column_span: usize,
+    /// A row span of 0 spans the cell to the last row of its row group
+    pub row_span: usize,
+}
+
+/// Cell together with the slots it occupies in the table. Rows and columns start at 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlacedCell<T> {
+    pub id: T,
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+}
+
+/// Slots of the table that are occupied by its cells
+#[derive(Clone, Debug)]
+pub struct TableGrid<T> {
+    pub cells: Vec<PlacedCell<T>>,
+    pub row_count: usize,
+    pub column_count: usize,
+}
+
+impl<T> Default for TableGrid<T> {
+    fn default() -> Self {
+        Self {
+            cells: Vec::new(),
+            row_count: 0,
+            column_count: 0,
+        }
+    }
+}
+
+impl<T> TableGrid<T> {
+    /// Places the rows of a row group below the rows that have already been placed. A cell takes the first free
+    /// slot of its row, as slots can already be taken by cells of previous rows that span multiple rows. Row
+    /// spans never cross the end of the row group. Returns the index of the first row of the group.
+    pub fn add_row_group(&mut self, rows: Vec<Vec<TableCell<T>>>) -> usize {
+        let first_row = self.row_count;
+        let group_rows = rows.len();
+        let mut occupied: Vec<Vec<bool>> = vec![Vec::new(); group_rows];
+
+        for (row, cells) in rows.into_iter().enumerate() {
+            let mut column = 0;
+            for cell in cells {
+                while occupied[row].get(column).copied().unwrap_or(false) {
+                    column += 1;
+                }
+
+                let row_span = match cell.row_span {
+                    0 => group_rows - row,
+                    span => span.min(group_rows - row),
+                };
+                let column_span = cell.column_span.max(1);
+
+                for slots in &mut occupied[row..row + row_span] {
+                    if slots.len() < column + column_span {
+                        slots.resize(column + column_span, false);
+                    }
+                    slots[column..column + column_span].fill(true);
+                }
+
+                self.column_count = self.column_count.max(column + column_span);
+                self.cells.push(PlacedCell {
+                    id: cell.id,
+                    row: first_row + row,
+                    column,
+                    row_span,
+                    column_span,
+                });
+                column += column_span;
+            }
+        }
+
+        self.row_count += group_rows;
+        first_row
+    }
+}
+
+/// Returns the number of columns spanned by a cell with the given colspan attribute
+pub fn column_span(value: Option<&String>) -> usize {
+    match value.and_then(|value| value.trim().parse::<usize>().ok()) {
+        Some(span) if span > 0 => span.min(1000),
+        _ => 1,
+    }
+}
+
+/// Returns the number of rows spanned by a cell with the given rowspan attribute. A value of 0 means that the cell
+/// spans all remaining rows of its row group.
+pub fn row_span(value: Option<&String>) -> usize {
+    match value.and_then(|value| value.trim().parse::<usize>().ok()) {
+        Some(span) => span.min(65534),
+        None => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(id: char, column_span: usize, row_span: usize) -> TableCell<char> {
+        TableCell { id, column_span, row_span }
+    }
+
+    fn slots(grid: &TableGrid<char>, id: char) -> (usize, usize, usize, usize) {
+        let cell = grid.cells.iter().find(|cell| cell.id == id).unwrap();
+        (cell.row, cell.column, cell.row_span, cell.column_span)
+    }
+
+    #[test]
+    fn test_place_cells() {
+        let mut grid = TableGrid::default();
+
+        // a a b
+        // c d b
+        // c e f
+        let first_row = grid.add_row_group(vec![
+            vec![cell('a', 2, 1), cell('b', 1, 2)],
+            vec![cell('c', 1, 2), cell('d', 1, 1)],
+            vec![cell('e', 1, 1), cell('f', 1, 1)],
+        ]);
+        assert_eq!(first_row, 0);
+        assert_eq!(slots(&grid, 'a'), (0, 0, 1, 2));
+        assert_eq!(slots(&grid, 'b'), (0, 2, 2, 1));
+        assert_eq!(slots(&grid, 'c'), (1, 0, 2, 1));
+        assert_eq!(slots(&grid, 'd'), (1, 1, 1, 1));
+        assert_eq!(slots(&grid, 'e'), (2, 1, 1, 1));
+        assert_eq!(slots(&grid, 'f'), (2, 2, 1, 1));
+
+        // Row spans stop at the end of the row group, and a span of 0 takes all remaining rows of the group
+        let first_row = grid.add_row_group(vec![
+            vec![cell('g', 1, 0), cell('h', 1, 5)],
+            vec![cell('i', 2, 1)],
+        ]);
+        assert_eq!(first_row, 3);
+        assert_eq!(slots(&grid, 'g'), (3, 0, 2, 1));
+        assert_eq!(slots(&grid, 'h'), (3, 1, 2, 1));
+        assert_eq!(slots(&grid, 'i'), (4, 2, 1, 2));
+
+        assert_eq!(grid.row_count, 5);
+        assert_eq!(grid.column_count, 4);
+    }
+}
@@ -1,23 +1,29 @@
-use crate::common::document::node::{NodeId as DomNodeId, NodeType};
-use crate::common::document::style::{FontWeight, StyleProperty, StyleValue, TextAlign, Unit};
+use crate::common::document::node::{Node, NodeType};
+use crate::common::document::style::{BorderCollapse, CaptionSide, Display as CssDisplay, StyleProperty, StylePropertyList, StyleValue, TableLayout, Float, Clear, Unit};
+use crate::common::font::face;
 use crate::common::geo::Coordinate;
-use crate::common::media::{Media, MediaId, MediaType};
-use crate::common::{geo, get_media_store};
+use crate::common::geo;
+use crate::layouter::context::{replaced_context, text_context};
 use crate::layouter::css_taffy_converter::CssTaffyConverter;
-use crate::layouter::text::{get_text_layout, Alignment};
+use crate::layouter::float::{flow_text, FloatContext};
+use crate::layouter::replaced;
+use crate::layouter::srcset::Environment;
+use crate::layouter::table::{column_span, row_span, TableCell, TableGrid};
+use crate::layouter::text::{measure_vertical, vertical_columns, wrap_text, TextShaper};
 use crate::layouter::{
-    box_model, CanLayout, ElementContext, ElementContextImage, ElementContextSvg,
-    ElementContextText, LayoutElementId, LayoutElementNode, LayoutTree,
+    box_model, CanLayout, ElementContext, ScrollPosition, ElementContextImage, ElementContextSvg,
+    ElementContextText, LayoutElementId, LayoutElementNode, LayoutTree, Viewport,
 };
+use crate::rendertree_builder::tree::is_block_container;
 use crate::rendertree_builder::{RenderNodeId, RenderTree};
-use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::sync::{Arc, RwLock};
 use taffy::prelude::*;
-use taffy::NodeId as TaffyNodeId;
+use taffy::{NodeId as TaffyNodeId, Overflow};
 
-const DEFAULT_FONT_SIZE: f64 = 16.0;
-const DEFAULT_FONT_FAMILY: &str = "Sans";
+/// Maximum number of times the layout is computed again after placing floats
+const MAX_FLOAT_PASSES: usize = 3;
 
 /// Layouter structure that uses taffy as layout engine
 pub struct TaffyLayouter {
@@ -27,6 +33,44 @@ pub struct TaffyLayouter {
     root_id: TaffyNodeId,
     /// Mapping of layout element id to taffy node id
     layout_taffy_mapping: HashMap<LayoutElementId, TaffyNodeId>,
+    /// Shaper that is used for measuring text
+    shaper: Arc<dyn TextShaper>,
+    /// Floats in document order
+    floats: Vec<FloatBox>,
+    /// Floats that have not been assigned to a block container yet (indices into floats)
+    pending_floats: Vec<usize>,
+    /// Block containers that have floats, in the order they have been generated
+    float_containers: Vec<FloatContainer>,
+    /// Blocks that clear floats, with their original top margin and the clearance that has been added to it
+    clears: HashMap<TaffyNodeId, (Clear, LengthPercentageAuto, f32)>,
+    /// Elements with position: fixed
+    fixed: Vec<TaffyNodeId>,
+    /// Box of the viewport, which is the containing block of fixed elements
+    viewport_id: Option<TaffyNodeId>,
+    /// Elements whose style or text has changed since the layout was computed
+    dirty: HashSet<LayoutElementId>,
+    /// Number of inline elements that precede each text node in its container
+    inline_positions: HashMap<LayoutElementId, usize>,
+    /// Environment that the sources of responsive images are selected for
+    env: Environment,
+}
+
+/// A float together with the block container it is placed in
+#[derive(Clone, Debug)]
+struct FloatBox {
+    id: TaffyNodeId,
+    side: Float,
+    clear: Clear,
+}
+
+/// Block container that has floats
+#[derive(Clone, Debug)]
+struct FloatContainer {
+    id: TaffyNodeId,
+    /// Containers that establish a block formatting context grow to contain their floats
+    contains_floats: bool,
+    /// Minimum height of the container before it was grown
+    min_height: Dimension,
 }
 
 /// Context structures to pass to taffy measure functions so we can calculate the size of the text or image.
@@ -37,59 +81,22 @@ pub enum TaffyContext {
     Svg(ElementContextSvg),
 }
 
-impl TaffyContext {
-    fn text(
-        font_family: &str,
-        font_size: f64,
-        font_weight: usize,
-        line_height: f64,
-        alignment: Alignment,
-        text: &str,
-        node_id: DomNodeId,
-        text_offset: Coordinate,
-    ) -> TaffyContext {
-        TaffyContext::Text(ElementContextText {
-            node_id,
-            font_family: font_family.to_string(),
-            font_size,
-            font_weight,
-            line_height,
-            text: text.to_string(),
-            text_offset,
-            alignment,
-        })
-    }
-
-    fn image(
-        src: &str,
-        media_id: MediaId,
-        dimension: geo::Dimension,
-        node_id: DomNodeId,
-    ) -> TaffyContext {
-        TaffyContext::Image(ElementContextImage {
-            node_id,
-            src: src.to_string(),
-            media_id,
-            dimension,
-        })
-    }
-
-    fn svg(src: &str, media_id: MediaId, node_id: DomNodeId) -> TaffyContext {
-        TaffyContext::Svg(ElementContextSvg {
-            node_id,
-            src: src.to_string(),
-            media_id,
-            dimension: geo::Dimension::ZERO,
-        })
-    }
-}
-
 impl TaffyLayouter {
-    pub fn new() -> Self {
+    pub fn new(shaper: Arc<dyn TextShaper>) -> Self {
         Self {
             tree: TaffyTree::new(),
             root_id: TaffyNodeId::new(0),
             layout_taffy_mapping: HashMap::new(),
+            shaper,
+            floats: Vec::new(),
+            pending_floats: Vec::new(),
+            float_containers: Vec::new(),
+            clears: HashMap::new(),
+            fixed: Vec::new(),
+            viewport_id: None,
+            dirty: HashSet::new(),
+            inline_positions: HashMap::new(),
+            env: Environment::new(None, 1.0),
         }
     }
 
@@ -99,68 +106,304 @@ impl TaffyLayouter {
 }
 
 impl CanLayout for TaffyLayouter {
-    fn layout(&mut self, render_tree: RenderTree, viewport: Option<geo::Dimension>) -> LayoutTree {
+    fn layout(&mut self, render_tree: RenderTree, viewport: Viewport) -> LayoutTree {
+        // Web fonts must be available before any text is measured
+        face::load_font_faces(&render_tree.doc.font_faces);
+
+        // Images are selected for the viewport when the tree is generated
+        self.env = Environment::from(viewport);
+        let viewport = viewport.size;
+
         let root_id = render_tree.root_id.unwrap();
         let Some(mut layout_tree) = self.generate_tree(render_tree, root_id) else {
             panic!("Failed to generate root node render tree");
         };
 
-        // // Compute the layout based on the viewport
-        let size = match viewport {
-            Some(viewport) => Size {
-                width: AvailableSpace::Definite(viewport.width as f32),
-                height: AvailableSpace::Definite(viewport.height as f32),
+        // Every element gets its box model
+        self.dirty = layout_tree.arena.keys().copied().collect();
+        self.compute_layout(&mut layout_tree, viewport, available_space(viewport));
+
+        layout_tree
+    }
+
+    /// Computes the layout again after elements have been marked dirty, or for another viewport. The taffy tree
+    /// is kept between layouts, so taffy only recomputes the nodes that are dirty or that depend on the available
+    /// space. Only the box models that have changed are updated. Returns the elements whose box model or text
+    /// has changed. Responsive images whose source changes with the viewport are loaded again.
+    fn relayout(&mut self, layout_tree: &mut LayoutTree, viewport: Viewport) -> Vec<LayoutElementId> {
+        self.reselect_images(layout_tree, viewport);
+        self.compute_layout(layout_tree, viewport.size, available_space(viewport.size))
+    }
+
+    /// Marks an element dirty after its style or text has changed in the document of the layout tree. The taffy
+    /// style or text context is regenerated from the document, and is laid out by the next relayout(). Changes
+    /// to the structure of the tree (display, position, floats and table parts) cannot be applied this way:
+    /// false is returned, and the document must be laid out again with layout(). Text nodes hold a copy of the
+    /// styles of their parent, so they are marked dirty on their own.
+    fn mark_dirty(&mut self, layout_tree: &mut LayoutTree, layout_id: LayoutElementId) -> bool {
+        let Some(taffy_id) = self.layout_taffy_mapping.get(&layout_id).copied() else {
+            return false;
+        };
+        let Some(dom_node_id) = layout_tree.get_node_by_id(layout_id).map(|el| el.dom_node_id) else {
+            return false;
+        };
+        let doc = layout_tree.render_tree.doc.clone();
+        let Some(dom_node) = doc.get_node_by_id(dom_node_id) else {
+            return false;
+        };
+
+        match &dom_node.node_type {
+            NodeType::Element(data) => {
+                let parent = dom_node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id));
+                let parent_display = match parent.map(|parent| &parent.node_type) {
+                    Some(NodeType::Element(parent_data)) => parent_data.get_style(StyleProperty::Display),
+                    _ => None,
+                };
+                if is_table_part(data.get_style(StyleProperty::Display)) || is_table_part(parent_display) {
+                    return false;
+                }
+
+                let conv = CssTaffyConverter::for_node(&doc, dom_node);
+                let mut style = conv.convert(dom_node.node_id, false);
+                let Ok(current) = self.tree.style(taffy_id).cloned() else {
+                    return false;
+                };
+                let float = self.floats.iter().find(|float| float.id == taffy_id).map_or(Float::None, |float| float.side);
+                let Some(el) = layout_tree.get_node_by_id_mut(layout_id) else {
+                    return false;
+                };
+                if style.display != current.display
+                    || style.position != current.position
+                    || conv.get_float() != float
+                    || conv.is_fixed() != matches!(el.scroll_position, ScrollPosition::Fixed)
+                    || conv.get_sticky_insets().is_some() != matches!(el.scroll_position, ScrollPosition::Sticky(_))
+                {
+                    return false;
+                }
+
+                if let (ScrollPosition::Sticky(constraint), Some(inset)) = (&mut el.scroll_position, conv.get_sticky_insets()) {
+                    constraint.top = inset.top.map(f64::from);
+                    constraint.right = inset.right.map(f64::from);
+                    constraint.bottom = inset.bottom.map(f64::from);
+                    constraint.left = inset.left.map(f64::from);
+                }
+
+                // Floats keep their place until they are placed again, and clearance is computed again
+                if float != Float::None {
+                    style.inset = current.inset;
+                    if let Some(float) = self.floats.iter_mut().find(|float| float.id == taffy_id) {
+                        float.clear = conv.get_clear();
+                    }
+                } else {
+                    let clear = conv.get_clear();
+                    if clear != Clear::None && style.position != Position::Absolute {
+                        self.clears.insert(taffy_id, (clear, style.margin.top, 0.0));
+                    } else {
+                        self.clears.remove(&taffy_id);
+                    }
+                }
+                if let Some(container) = self.float_containers.iter_mut().find(|container| container.id == taffy_id) {
+                    container.min_height = style.min_size.height;
+                }
+
+                let _ = self.tree.set_style(taffy_id, style);
+            }
+            NodeType::Text(..) => {
+                let inline_position = self.inline_positions.get(&layout_id).copied().unwrap_or(0);
+                let Some(context) = text_context(&doc, dom_node, inline_position) else {
+                    // The text collapsed to nothing, so the node must be removed
+                    return false;
+                };
+                let _ = self.tree.set_node_context(taffy_id, to_taffy_context(context));
+                let _ = self.tree.mark_dirty(taffy_id);
+            }
+            NodeType::Comment(_) => return false,
+        }
+
+        self.dirty.insert(layout_id);
+        true
+    }
+}
+
+impl TaffyLayouter {
+    /// Lays out the subtree of the render tree at the given node, for layouters that delegate the layout of flex,
+    /// grid and table containers to taffy. The margins and position of the root are resolved by the other
+    /// layouter, so its border box is laid out at the origin with the given width. Without a width, the root
+    /// takes its max-content width.
+    pub fn layout_subtree(
+        &mut self,
+        render_tree: RenderTree,
+        root_id: RenderNodeId,
+        width: Option<f64>,
+        viewport: Viewport,
+    ) -> Option<LayoutTree> {
+        self.env = Environment::from(viewport);
+        let viewport = viewport.size;
+        let mut layout_tree = self.generate_tree(render_tree, root_id)?;
+
+        let mut style = self.tree.style(self.root_id).ok()?.clone();
+        style.position = Position::Relative;
+        style.inset = Rect::auto();
+        style.margin = Rect::zero();
+        if let Some(width) = width {
+            style.size.width = length(width as f32);
+            style.box_sizing = BoxSizing::BorderBox;
+        }
+        let _ = self.tree.set_style(self.root_id, style);
+
+        let available = Size {
+            width: match width {
+                Some(width) => AvailableSpace::Definite(width as f32),
+                None => AvailableSpace::MaxContent,
             },
-            None => Size::MAX_CONTENT,
+            height: available_space(viewport).height,
         };
+        self.dirty = layout_tree.arena.keys().copied().collect();
+        self.compute_layout(&mut layout_tree, viewport, available);
+
+        Some(layout_tree)
+    }
+}
+
+impl TaffyLayouter {
+    /// Selects the sources of the images in the layout tree again for the viewport. Images whose source or
+    /// natural size has changed get the context of their new media, and are marked dirty.
+    fn reselect_images(&mut self, layout_tree: &mut LayoutTree, viewport: Viewport) {
+        let env = Environment::from(viewport);
+        if env == self.env {
+            return;
+        }
+        self.env = env;
+
+        let doc = layout_tree.render_tree.doc.clone();
+        for el in layout_tree.arena.values_mut() {
+            let (ElementContext::Image(ElementContextImage { src, dimension, .. })
+            | ElementContext::Svg(ElementContextSvg { src, dimension, .. })) = &el.context
+            else {
+                continue;
+            };
+            let Some(dom_node) = doc.get_node_by_id(el.dom_node_id) else {
+                continue;
+            };
+            let NodeType::Element(data) = &dom_node.node_type else {
+                continue;
+            };
+            if !data.tag_name.eq_ignore_ascii_case("img") {
+                // Inline SVGs have no sources to select
+                continue;
+            }
+
+            let Some(context) = replaced_context(&doc, dom_node, &self.env) else {
+                // The new source cannot be loaded, so the current image is kept
+                continue;
+            };
+            let unchanged = match &context {
+                ElementContext::Image(ctx) => ctx.src == *src && ctx.dimension == *dimension,
+                ElementContext::Svg(ctx) => ctx.src == *src && ctx.dimension == *dimension,
+                _ => true,
+            };
+            if unchanged {
+                continue;
+            }
+
+            let Some(taffy_id) = self.layout_taffy_mapping.get(&el.id).copied() else {
+                continue;
+            };
+            if let Some(taffy_context) = to_taffy_context(context.clone()) {
+                let _ = self.tree.set_node_context(taffy_id, Some(taffy_context));
+                let _ = self.tree.mark_dirty(taffy_id);
+            }
+            el.context = context;
+            self.dirty.insert(el.id);
+        }
+    }
+
+    /// Computes the layout in the given space for the given viewport, and updates the box models that have changed
+    fn compute_layout(&mut self, layout_tree: &mut LayoutTree, viewport: Option<geo::Dimension>, size: Size<AvailableSpace>) -> Vec<LayoutElementId> {
+        let viewport_id = self.setup_viewport(viewport);
+        let shaper = self.shaper.clone();
 
         /// Compute the layout with a measure function
-        self.tree
-            .compute_layout_with_measure(self.root_id, size, |v_kd, v_as, v_ni, v_nc, v_s| {
-                match v_nc {
-                    // Calculate text node
-                    Some(TaffyContext::Text(text_ctx)) => {
-                        let font_size = text_ctx.font_size;
-                        let font_weight = text_ctx.font_weight;
-                        let font_family = text_ctx.font_family.as_str();
-                        let text = text_ctx.text.as_str();
-                        let line_height = text_ctx.line_height;
-                        let alignment = text_ctx.alignment;
-
-                        let max_width = match v_as.width {
-                            AvailableSpace::Definite(width) => width as f64,
-                            AvailableSpace::MaxContent => f64::MAX,
-                            AvailableSpace::MinContent => 0.0,
-                        };
+        let mut measure = |v_kd: Size<Option<f32>>, v_as: Size<AvailableSpace>, _v_ni: TaffyNodeId, v_nc: Option<&mut TaffyContext>, v_s: &Style| {
+            match v_nc {
+                // Images and SVGs are sized by their media
+                Some(TaffyContext::Image(image_ctx)) => measure_replaced(image_ctx.dimension, v_kd, v_as, v_s),
+                Some(TaffyContext::Svg(svg_ctx)) => measure_replaced(svg_ctx.dimension, v_kd, v_as, v_s),
+                // Calculate text node
+                Some(TaffyContext::Text(text_ctx)) if text_ctx.writing_mode.is_vertical() => {
+                    let max_height = match (v_kd.height, v_as.height) {
+                        (Some(height), _) => height as f64,
+                        (None, AvailableSpace::Definite(height)) => height as f64,
+                        (None, AvailableSpace::MaxContent) => f64::MAX,
+                        (None, AvailableSpace::MinContent) => 0.0,
+                    };
 
-                        // Calculate the text layout dimensions and return it to taffy
-                        let text_layout = get_text_layout(
-                            text,
-                            font_family,
-                            font_size,
-                            font_weight,
-                            line_height,
-                            max_width,
-                            alignment,
-                        );
-                        match text_layout {
-                            Ok(text_layout) => Size {
-                                width: text_layout.width as f32,
-                                height: text_layout.height as f32,
+                    // Vertical text is measured as columns of upright characters
+                    let style = text_ctx.text_style();
+                    let text_layout = vertical_columns(shaper.as_ref(), &text_ctx.text, &style, max_height)
+                        .and_then(|columns| measure_vertical(shaper.as_ref(), &columns, &style));
+                    match text_layout {
+                        Ok(text_layout) => Size {
+                            width: text_layout.width as f32,
+                            height: text_layout.height as f32,
+                        },
+                        Err(_) => Size::ZERO,
+                    }
+                }
+                Some(TaffyContext::Text(text_ctx)) => {
+                    let max_width = match v_as.width {
+                        AvailableSpace::Definite(width) => width as f64,
+                        AvailableSpace::MaxContent => f64::MAX,
+                        AvailableSpace::MinContent => 0.0,
+                    };
+
+                    // Calculate the text layout dimensions and return it to taffy
+                    let style = text_ctx.text_style();
+                    if !text_ctx.exclusions.is_empty() && max_width < f64::MAX {
+                        return match flow_text(shaper.as_ref(), &text_ctx.text, &style, max_width, &text_ctx.exclusions) {
+                            Ok(lines) => Size {
+                                width: max_width as f32,
+                                height: lines.last().map_or(0.0, |line| line.y + text_ctx.line_height) as f32,
                             },
                             Err(_) => Size::ZERO,
-                        }
+                        };
+                    }
+                    let text = wrap_text(shaper.as_ref(), &text_ctx.text, &style, &text_ctx.wrapping, max_width)
+                        .unwrap_or_else(|_| text_ctx.text.clone());
+                    let text_layout = shaper.measure(text.as_str(), &style, max_width);
+                    match text_layout {
+                        Ok(text_layout) => Size {
+                            width: text_layout.width as f32,
+                            height: text_layout.height as f32,
+                        },
+                        Err(_) => Size::ZERO,
                     }
-                    _ => Size::ZERO,
                 }
-            })
-            .unwrap();
+                _ => Size::ZERO,
+            }
+        };
+        self.tree.compute_layout_with_measure(self.root_id, size, &mut measure).unwrap();
+
+        // Floats are placed after the layout has been computed. Placing them changes the space that is left for
+        // the content next to them, so the layout is computed again until the floats stay where they are.
+        for _ in 0..MAX_FLOAT_PASSES {
+            if !self.place_floats() {
+                break;
+            }
+            self.tree.compute_layout_with_measure(self.root_id, size, &mut measure).unwrap();
+        }
 
         // Since we are not interested in taffy layout after this stage in the pipeline, we convert
         // the taffy layout to a box model layout tree. This makes the rest of the pipeline
         // layout-engine agnostic.
         let root_id = layout_tree.root_id;
-        self.populate_boxmodel(&mut layout_tree, root_id, Coordinate::ZERO);
+        let mut child_offsets = HashMap::new();
+        if let Ok(viewport_layout) = self.tree.layout(viewport_id) {
+            child_offsets.insert(viewport_id, Coordinate::new(viewport_layout.location.x as f64, viewport_layout.location.y as f64));
+        }
+        let mut changed = Vec::new();
+        self.populate_boxmodel(layout_tree, root_id, Coordinate::ZERO, &mut child_offsets, &mut changed);
+        self.dirty.clear();
 
         // get dimension of the root node
         let root = layout_tree.get_node_by_id(root_id).unwrap();
@@ -168,40 +411,104 @@ impl CanLayout for TaffyLayouter {
         let h = root.box_model.margin_box.height as f32;
         layout_tree.root_dimension = geo::Dimension::new(w as f64, h as f64);
 
-        layout_tree
+        changed
     }
 }
 
 impl TaffyLayouter {
-    // Populate the layout tree with the box models that we now can generate
+    // Populate the layout tree with the box models that we now can generate. Only the elements that are dirty or
+    // whose box model has changed are updated, and added to the changed elements.
     fn populate_boxmodel(
         &self,
         layout_tree: &mut LayoutTree,
         layout_node_id: LayoutElementId,
         offset: Coordinate,
+        child_offsets: &mut HashMap<TaffyNodeId, Coordinate>,
+        changed: &mut Vec<LayoutElementId>,
     ) {
         let taffy_node_id = self.layout_taffy_mapping.get(&layout_node_id).unwrap();
         let layout = self.tree.layout(*taffy_node_id).unwrap().clone();
+        let box_model = taffy_layout_to_boxmodel(&layout, offset);
 
         let el = layout_tree.get_node_by_id_mut(layout_node_id).unwrap();
-        el.box_model = taffy_layout_to_boxmodel(&layout, offset);
+        let content_box = box_model.content_box();
+        if self.dirty.contains(&layout_node_id) || el.box_model != box_model {
+            let old_box = el.box_model.margin_box;
+            el.box_model = box_model;
+            // The text is wrapped for the width of the box, so it starts again from the text that was measured
+            el.context = to_element_context(self.tree.get_node_context(*taffy_node_id));
+            self.finish_context(el, *taffy_node_id);
+
+            layout_tree.update_spatial_index(layout_node_id, old_box, box_model.margin_box);
+            changed.push(layout_node_id);
+        }
+        let el = layout_tree.get_node_by_id(layout_node_id).unwrap();
         let child_ids = el.children.clone();
 
+        // Taffy positions the children relative to the border box of their parent
+        let child_offset = Coordinate::new(
+            offset.x + layout.location.x as f64,
+            offset.y + layout.location.y as f64,
+        );
+        child_offsets.insert(*taffy_node_id, child_offset);
+
         for child_id in child_ids {
-            self.populate_boxmodel(
-                layout_tree,
-                child_id,
-                Coordinate::new(
-                    offset.x
-                        + layout.location.x as f64
-                        + layout.padding.left as f64
-                        + layout.margin.left as f64,
-                    offset.y
-                        + layout.location.y as f64
-                        + layout.padding.top as f64
-                        + layout.margin.top as f64,
-                ),
-            );
+            // Taffy positions a node relative to its parent in the taffy tree, which is not always its parent in
+            // the layout tree: table cells are laid out by the table instead of by their row, floats by their
+            // containing block and fixed elements by the viewport.
+            let offset = self
+                .layout_taffy_mapping
+                .get(&child_id)
+                .and_then(|child_taffy_id| self.tree.parent(*child_taffy_id))
+                .and_then(|parent_taffy_id| child_offsets.get(&parent_taffy_id).copied())
+                .unwrap_or(child_offset);
+
+            self.populate_boxmodel(layout_tree, child_id, offset, child_offsets, changed);
+
+            // Sticky elements stay inside the content box of their parent
+            if let Some(child) = layout_tree.get_node_by_id_mut(child_id) {
+                if let ScrollPosition::Sticky(constraint) = &mut child.scroll_position {
+                    if constraint.container != content_box {
+                        constraint.container = content_box;
+                        if !changed.contains(&child_id) {
+                            changed.push(child_id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finishes the context of an element once its box model is known: the lines of the text can be balanced
+    /// and words can be broken.
+    fn finish_context(&self, el: &mut LayoutElementNode, taffy_node_id: TaffyNodeId) {
+        let exclusions = match self.tree.get_node_context(taffy_node_id) {
+            Some(TaffyContext::Text(text_ctx)) => text_ctx.exclusions.clone(),
+            _ => Vec::new(),
+        };
+        let content_box = el.box_model.content_box();
+        let content_width = content_box.width;
+        if let ElementContext::Text(text_ctx) = &mut el.context {
+            if text_ctx.writing_mode.is_vertical() {
+                match vertical_columns(self.shaper.as_ref(), &text_ctx.text, &text_ctx.text_style(), content_box.height) {
+                    Ok(columns) => text_ctx.columns = columns,
+                    Err(e) => log::warn!("Failed to break vertical text into columns: {:?}", e),
+                }
+            } else if !exclusions.is_empty() {
+                match flow_text(self.shaper.as_ref(), &text_ctx.text, &text_ctx.text_style(), content_width, &exclusions) {
+                    Ok(lines) => text_ctx.lines = lines,
+                    Err(e) => log::warn!("Failed to flow text around floats: {:?}", e),
+                }
+                text_ctx.exclusions = exclusions;
+            } else {
+                match wrap_text(self.shaper.as_ref(), &text_ctx.text, &text_ctx.text_style(), &text_ctx.wrapping, content_width) {
+                    Ok(text) => text_ctx.text = text,
+                    Err(e) => log::warn!("Failed to wrap text: {:?}", e),
+                }
+            }
+        }
+        if let ScrollPosition::Sticky(constraint) = &mut el.scroll_position {
+            constraint.rect = el.box_model.border_box();
         }
     }
 
@@ -211,8 +518,18 @@ impl TaffyLayouter {
         render_tree: RenderTree,
         root_id: RenderNodeId,
     ) -> Option<LayoutTree> {
-        self.tree = TaffyTree::new();
+        // The taffy tree is reused, a tree that has been laid out before is laid out again with relayout()
+        self.tree.clear();
+        self.layout_taffy_mapping.clear();
         self.root_id = TaffyNodeId::new(0); // Will be filled in later
+        self.floats.clear();
+        self.pending_floats.clear();
+        self.float_containers.clear();
+        self.clears.clear();
+        self.fixed.clear();
+        self.viewport_id = None;
+        self.dirty.clear();
+        self.inline_positions.clear();
 
         let mut layout_tree = LayoutTree {
             render_tree,
@@ -232,6 +549,9 @@ impl TaffyLayouter {
         layout_tree.root_id = layout_element_root_id;
         self.root_id = taffy_root_id;
 
+        // The root contains all floats that are not inside another block container
+        self.assign_floats(taffy_root_id, 0, true);
+
         Some(layout_tree)
     }
 
@@ -250,184 +570,55 @@ impl TaffyLayouter {
         let Some(dom_node) = layout_tree
             .render_tree
             .doc
-            .get_node_by_id(DomNodeId::from(render_node.node_id))
+            .get_node_by_id(render_node.dom_node_id)
         else {
             return None;
         };
         let render_node_children = render_node.children.clone();
 
-        let base_url = layout_tree.render_tree.doc.base_url();
-
         // Create taffy context and style, which depends on type of node we have
         let mut taffy_context = None;
         let mut taffy_style = Style::default();
+        let mut table_styles = None;
+        let mut float = Float::None;
+        let mut block_container = false;
+        let mut contains_floats = false;
+        let mut clear = Clear::None;
+        let mut scroll_position = ScrollPosition::Scrolled;
+        let mut inline_position = None;
 
         match &dom_node.node_type {
             // Node is an element node (like a div, span, etc.)
             NodeType::Element(data) => {
                 // Create the taffy style from our CSS and push it into the stack
-                let conv = CssTaffyConverter::new(&data.styles);
+                let conv = CssTaffyConverter::for_node(&layout_tree.render_tree.doc, dom_node);
                 taffy_style = conv.convert(dom_node.node_id, false);
 
-                // Check if element type is an image, if so, set the taffy context
-                if data.tag_name.eq_ignore_ascii_case("img") {
-                    let src = data.get_attribute("src").unwrap();
-                    let src = to_absolute_url(src, base_url);
-
-                    println!("Loading (image) resource: {}", src);
-
-                    let media_store = get_media_store();
-                    let Ok(media_id) = media_store.read().unwrap().load_media(src.as_str()) else {
-                        // Could not load media
-                        log::info!("Could not load media from path: {}", src);
-                        return None;
-                    };
-
-                    let media_store = get_media_store();
-                    let binding = media_store.read().unwrap();
-                    let media = binding.get(media_id, MediaType::Image);
-                    taffy_context = match media.borrow() {
-                        Media::Svg(_) => {
-                            Some(TaffyContext::svg(src.as_str(), media_id, dom_node.node_id))
-                        }
-                        Media::Image(media_image) => {
-                            let dimension = geo::Dimension::new(
-                                media_image.image.width() as f64,
-                                media_image.image.height() as f64,
-                            );
-                            Some(TaffyContext::image(
-                                src.as_str(),
-                                media_id,
-                                dimension,
-                                dom_node.node_id,
-                            ))
-                        }
-                    }
+                if matches!(data.get_style(StyleProperty::Display), Some(StyleValue::Display(CssDisplay::Table | CssDisplay::InlineTable))) {
+                    table_styles = Some(data.styles.clone());
                 }
 
-                if data.tag_name.eq_ignore_ascii_case("svg") {
-                    let inner_html = layout_tree.render_tree.doc.inner_html(dom_node.node_id);
-
-                    let store = get_media_store();
-                    match store
-                        .read()
-                        .unwrap()
-                        .load_media_from_data(MediaType::Svg, inner_html.into_bytes().as_slice())
-                    {
-                        Ok(media_id) => {
-                            taffy_context = Some(TaffyContext::svg(
-                                "gosub://internal",
-                                media_id,
-                                dom_node.node_id,
-                            ));
-                        }
-                        Err(e) => {
-                            log::info!("Could not load SVG media: {:?}", e);
-                        }
-                    }
-                }
+                scroll_position = conv.get_scroll_position();
+
+                // Floats are placed in their containing block, which is the nearest block container. Floats are
+                // block containers themselves.
+                float = conv.get_float();
+                block_container = float != Float::None || is_block_container(Some(dom_node));
+                contains_floats = float != Float::None
+                    || taffy_style.overflow.y != Overflow::Visible
+                    || matches!(
+                        data.get_style(StyleProperty::Display),
+                        Some(StyleValue::Display(CssDisplay::InlineBlock | CssDisplay::TableCell | CssDisplay::TableCaption))
+                    );
+                clear = conv.get_clear();
+
+                // Images and SVGs are replaced elements, which are sized by their media
+                let context = replaced_context(&layout_tree.render_tree.doc, dom_node, &self.env)?;
+                taffy_context = to_taffy_context(context);
             }
-            NodeType::Text(text, node_style) => {
-                let parent_node = match dom_node.parent_id {
-                    Some(parent_id) => layout_tree.render_tree.doc.get_node_by_id(parent_id),
-                    None => None,
-                };
-                if parent_node.is_none() {
-                    return None;
-                }
-
-                // Default font
-                let mut font_size = DEFAULT_FONT_SIZE;
-                let mut font_family = DEFAULT_FONT_FAMILY.to_string();
-
-                match node_style.get_property(StyleProperty::FontSize) {
-                    Some(StyleValue::Unit(value, unit)) => match unit {
-                        Unit::Px => font_size = *value as f64,
-                        Unit::Em => panic!("Don't know how to deal with em units for fonts"),
-                        Unit::Rem => panic!("Don't know how to deal with rem units for fonts"),
-                        _ => panic!("Incorrect font-size property unit"),
-                    },
-                    _ => {}
-                }
-
-                match node_style.get_property(StyleProperty::FontFamily) {
-                    Some(StyleValue::Keyword(value)) => font_family = value.clone(),
-                    _ => {}
-                }
-
-                let font_weight = match node_style.get_property(StyleProperty::FontWeight) {
-                    Some(StyleValue::FontWeight(weight)) => match weight {
-                        FontWeight::Normal => 400.0,
-                        FontWeight::Bold => 700.0,
-                        FontWeight::Number(value) => *value as f64,
-                        FontWeight::Bolder => {
-                            unimplemented!("FontWeight::Bolder is not implemented yet")
-                        }
-                        FontWeight::Lighter => {
-                            unimplemented!("FontWeight::Lighter is not implemented yet")
-                        }
-                    },
-                    _ => 400.0,
-                };
-
-                let alignment = match node_style.get_property(StyleProperty::TextAlign) {
-                    Some(StyleValue::TextAlign(value)) => match value {
-                        TextAlign::Center => Alignment::Middle,
-                        TextAlign::Right => Alignment::Start,
-                        TextAlign::Left => Alignment::End,
-                        TextAlign::Justify => Alignment::Justified,
-                        TextAlign::Start => Alignment::Start,
-                        TextAlign::End => Alignment::Start,
-                        TextAlign::MatchParent => {
-                            unimplemented!("TextAlign::MatchParent is not implemented yet")
-                        }
-                        TextAlign::Initial => {
-                            unimplemented!("TextAlign::Initial is not implemented yet")
-                        }
-                        TextAlign::Inherit => {
-                            unimplemented!("TextAlign::Inherit is not implemented yet")
-                        }
-                        TextAlign::Revert => {
-                            unimplemented!("TextAlign::Revert is not implemented yet")
-                        }
-                        TextAlign::Unset => {
-                            unimplemented!("TextAlign::Unset is not implemented yet")
-                        }
-                    },
-                    _ => Alignment::Start,
-                };
-
-                let line_height = match node_style.get_property(StyleProperty::LineHeight) {
-                    Some(StyleValue::Unit(value, unit)) => match unit {
-                        Unit::Px => *value as f64,
-                        Unit::Em => panic!("Don't know how to deal with em units for line-height"),
-                        Unit::Rem => {
-                            panic!("Don't know how to deal with rem units for line-height")
-                        }
-                        _ => panic!("Incorrect line-height property unit"),
-                    },
-                    _ => font_size,
-                };
-
-                // Calculate vertical offset for centering based on the line height.
-                let text_offset = Coordinate::new(0.0, (line_height - font_size) / 2.0);
-
-                let mut text = text.clone();
-                if inline_element_counter > 0 {
-                    // If we are in an inline container, we need to add a space between the text nodes
-                    text = format!(" {}", text).clone()
-                }
-
-                taffy_context = Some(TaffyContext::text(
-                    font_family.as_str(),
-                    font_size,
-                    font_weight as usize,
-                    line_height,
-                    alignment,
-                    text.as_str(),
-                    dom_node.node_id,
-                    text_offset,
-                ));
+            NodeType::Text(..) => {
+                taffy_context = to_taffy_context(text_context(&layout_tree.render_tree.doc, dom_node, inline_element_counter)?);
+                inline_position = Some(inline_element_counter);
             }
             NodeType::Comment(_) => {
                 // No need to layout for comment nodes. In fact, they should have been removed already
@@ -451,50 +642,6 @@ impl TaffyLayouter {
             return None;
         };
 
-        // Our inline container ID (if any is defined)
-        let mut inline_container_id = None;
-
-        // If the node is a block element, and it has inline children, we need to create a flex container
-        if dom_node.is_block_element() {
-            // Check if the node actually has inline children
-            let has_inline_children = render_node_children.iter().any(|child_id| {
-                let Some(child) = layout_tree.render_tree.get_node_by_id(*child_id) else {
-                    return false;
-                };
-                let Some(child_dom_node) = layout_tree
-                    .render_tree
-                    .doc
-                    .get_node_by_id(DomNodeId::from(child.node_id))
-                else {
-                    return false;
-                };
-
-                child_dom_node.is_inline_element() || child_dom_node.is_text()
-            });
-
-            if has_inline_children {
-                // Create our (anonymous) container and add to the taffy tree
-                let Ok(taffy_container_id) = self.tree.new_leaf(Style {
-                    display: Display::Block,
-                    // flex_direction: FlexDirection::Row,
-                    // flex_wrap: FlexWrap::Wrap,
-                    // size: Size{width: Dimension::Length(200.0), height: Dimension::Length(100.0)},
-                    // min_size: Size{width: Dimension::Percent(1.0), height: Dimension::Percent(1.0)},
-                    // max_size: Size{width: Dimension::Percent(1.0), height: Dimension::Percent(1.0)},
-                    // text_align: taffy::style::TextAlign::LegacyRight,
-                    // align_items: Some(AlignItems::Center),
-                    // justify_items: Some(JustifyItems::Center),
-                    // align_content: Some(AlignContent::Center),
-                    ..Style::default()
-                }) else {
-                    return None;
-                };
-
-                self.tree.add_child(leaf_id, taffy_container_id).unwrap();
-                inline_container_id = Some(taffy_container_id);
-            }
-        }
-
         // Create the element node in our layout tree
         let mut element_node = LayoutElementNode {
             id: layout_tree.next_node_id(),
@@ -503,29 +650,57 @@ impl TaffyLayouter {
             box_model: box_model::BoxModel::ZERO,
             children: vec![],
             context: element_context,
+            scroll_position,
         };
 
-        let mut inline_element_counter = inline_element_counter;
+        // Inline content of a block container is laid out in the container itself, as the render tree has
+        // already wrapped inline content next to blocks in anonymous blocks.
+        let mut inline_element_counter = if dom_node.is_inline_element() { inline_element_counter } else { 0 };
+        let first_pending_float = self.pending_floats.len();
 
         for child_id in render_node_children {
             if let Some((child_layout_element_id, child_taffy_id)) =
                 self.generate_node(layout_tree, child_id, inline_element_counter)
             {
-                let _ = match inline_container_id {
-                    Some(container_id) => {
-                        inline_element_counter += 1;
-                        self.tree.add_child(container_id, child_taffy_id)
-                    }
-                    None => self.tree.add_child(leaf_id, child_taffy_id),
-                };
+                let _ = self.tree.add_child(leaf_id, child_taffy_id);
+
+                // Out-of-flow children (like list markers) do not separate the inline content
+                let out_of_flow = self.tree.style(child_taffy_id).is_ok_and(|style| style.position == Position::Absolute);
+                let is_inline = layout_tree
+                    .get_node_by_id(child_layout_element_id)
+                    .and_then(|child| layout_tree.render_tree.doc.get_node_by_id(child.dom_node_id))
+                    .is_some_and(|child| child.is_inline_element() || child.is_text());
+                if is_inline && !out_of_flow {
+                    inline_element_counter += 1;
+                }
 
                 // Add child to layout element
                 element_node.children.push(child_layout_element_id);
             }
         }
 
+        if let Some(table_styles) = table_styles {
+            self.setup_table(layout_tree, leaf_id, &element_node.children, &table_styles);
+        }
+
+        if block_container {
+            self.assign_floats(leaf_id, first_pending_float, contains_floats);
+        }
+        if matches!(element_node.scroll_position, ScrollPosition::Fixed) {
+            self.fixed.push(leaf_id);
+        }
+        if float != Float::None {
+            self.pending_floats.push(self.floats.len());
+            self.floats.push(FloatBox { id: leaf_id, side: float, clear });
+        } else if clear != Clear::None && taffy_style.position != Position::Absolute {
+            self.clears.insert(leaf_id, (clear, taffy_style.margin.top, 0.0));
+        }
+
         // Insert element node into our arena
         let layout_element_id = element_node.id;
+        if let Some(inline_position) = inline_position {
+            self.inline_positions.insert(layout_element_id, inline_position);
+        }
         layout_tree.arena.insert(layout_element_id, element_node);
 
         /// Create a mapping between the layout element id and the taffy node id
@@ -533,25 +708,479 @@ impl TaffyLayouter {
 
         Some((layout_element_id, leaf_id))
     }
-}
 
-fn to_absolute_url(uri: &str, base_uri: &str) -> String {
-    if uri.starts_with("http://") || uri.starts_with("https://") {
-        return uri.to_string();
+    /// Adds the box of the viewport to the root, and moves the fixed elements into it. The viewport is the
+    /// containing block of fixed elements. Without a viewport, the box takes the size of the root. When the
+    /// viewport has been set up already, only its size is updated. Returns the taffy node of the viewport.
+    fn setup_viewport(&mut self, viewport: Option<geo::Dimension>) -> TaffyNodeId {
+        let size = match viewport {
+            Some(viewport) => Size { width: length(viewport.width as f32), height: length(viewport.height as f32) },
+            None => Size { width: percent(1.0), height: percent(1.0) },
+        };
+        let style = Style {
+            position: Position::Absolute,
+            inset: Rect { left: length(0.0), right: auto(), top: length(0.0), bottom: auto() },
+            size,
+            ..Style::default()
+        };
+
+        if let Some(viewport_id) = self.viewport_id {
+            if self.tree.style(viewport_id).is_ok_and(|current| current.size != style.size) {
+                let _ = self.tree.set_style(viewport_id, style);
+            }
+            return viewport_id;
+        }
+
+        let viewport_id = self.tree.new_leaf(style).expect("Failed to create viewport node");
+        self.viewport_id = Some(viewport_id);
+        let _ = self.tree.add_child(self.root_id, viewport_id);
+
+        for fixed_id in self.fixed.clone() {
+            if let Some(parent_id) = self.tree.parent(fixed_id) {
+                let _ = self.tree.remove_child(parent_id, fixed_id);
+            }
+            let _ = self.tree.add_child(viewport_id, fixed_id);
+        }
+
+        viewport_id
+    }
+
+    /// Assigns the floats that have been generated since the given pending float to the block container. Floats
+    /// that are nested deeper inside the container (in inline elements for instance) are moved into the container,
+    /// just before the child of the container that holds them.
+    fn assign_floats(&mut self, container_id: TaffyNodeId, first_pending: usize, contains_floats: bool) {
+        if self.pending_floats.len() <= first_pending {
+            return;
+        }
+
+        for index in self.pending_floats.split_off(first_pending) {
+            let float_id = self.floats[index].id;
+            let Some(parent_id) = self.tree.parent(float_id) else {
+                continue;
+            };
+            if parent_id == container_id {
+                continue;
+            }
+
+            let mut holder_id = parent_id;
+            while let Some(id) = self.tree.parent(holder_id).filter(|id| *id != container_id) {
+                holder_id = id;
+            }
+            let _ = self.tree.remove_child(parent_id, float_id);
+            let position = self.tree.children(container_id).ok().and_then(|children| children.iter().position(|id| *id == holder_id));
+            let _ = self.tree.insert_child_at_index(container_id, position.unwrap_or(0), float_id);
+        }
+
+        let min_height = self.tree.style(container_id).map_or(Dimension::Auto, |style| style.min_size.height);
+        self.float_containers.push(FloatContainer { id: container_id, contains_floats, min_height });
+    }
+
+    /// Places the floats of all block containers, based on the current layout. Returns true when anything has
+    /// changed, in which case the layout must be computed again.
+    fn place_floats(&mut self) -> bool {
+        let mut changed = false;
+        for container in self.float_containers.clone() {
+            changed |= self.place_container_floats(&container);
+        }
+
+        changed
+    }
+
+    /// Places the floats of a block container. The children of the container are visited in order: floats are
+    /// placed at the bottom of the in-flow content before them, blocks that clear floats are moved below these
+    /// floats with additional top margin, and text is shortened around the floats.
+    fn place_container_floats(&mut self, container: &FloatContainer) -> bool {
+        let Ok(layout) = self.tree.layout(container.id).cloned() else {
+            return false;
+        };
+        let content_left = layout.padding.left + layout.border.left;
+        let content_top = layout.padding.top + layout.border.top;
+        let width = layout.size.width - content_left - layout.padding.right - layout.border.right;
+
+        let mut ctx = FloatContext::new(width.max(0.0) as f64);
+        let mut changed = false;
+        let mut y = 0.0;
+
+        for child_id in self.tree.children(container.id).unwrap_or_default() {
+            let (Ok(child_layout), Ok(child_style)) = (self.tree.layout(child_id).cloned(), self.tree.style(child_id).cloned()) else {
+                continue;
+            };
+            let margin_top = (child_layout.location.y - child_layout.margin.top - content_top) as f64;
+
+            if let Some(float) = self.floats.iter().find(|float| float.id == child_id).cloned() {
+                let (x, top) = ctx.place(
+                    float.side,
+                    (child_layout.size.width + child_layout.margin.left + child_layout.margin.right) as f64,
+                    (child_layout.size.height + child_layout.margin.top + child_layout.margin.bottom) as f64,
+                    y,
+                    float.clear,
+                );
+
+                // Insets of absolute boxes are relative to the padding box of the container
+                let inset = Rect {
+                    left: length(x as f32 + layout.padding.left),
+                    right: auto(),
+                    top: length(top as f32 + layout.padding.top),
+                    bottom: auto(),
+                };
+                if child_style.inset != inset {
+                    let mut style = child_style;
+                    style.inset = inset;
+                    let _ = self.tree.set_style(child_id, style);
+                    changed = true;
+                }
+                continue;
+            }
+
+            if child_style.position == Position::Absolute {
+                continue;
+            }
+
+            if let Some((clear, margin, added)) = self.clears.get(&child_id).copied() {
+                // The border box of the block starts below the cleared floats
+                let original_margin = child_layout.margin.top - added;
+                let border_top = margin_top + original_margin as f64;
+                let clearance = (ctx.clearance(clear, border_top) - border_top) as f32;
+                if (clearance - added).abs() > 0.5 {
+                    let mut style = child_style;
+                    style.margin.top = if clearance > 0.0 { length(original_margin + clearance) } else { margin };
+                    let _ = self.tree.set_style(child_id, style);
+                    self.clears.insert(child_id, (clear, margin, clearance));
+                    changed = true;
+                }
+            }
+
+            let x = (child_layout.location.x - content_left) as f64;
+            let top = (child_layout.location.y - content_top) as f64;
+            changed |= self.exclude_floats(child_id, x, top, &ctx);
+
+            y = top + (child_layout.size.height + child_layout.margin.bottom) as f64;
+        }
+
+        // A container that establishes a block formatting context is at least as high as its floats
+        if container.contains_floats {
+            changed |= self.contain_floats(container, &layout, ctx.bottom() as f32);
+        }
+
+        changed
+    }
+
+    /// Sets the parts of the floats that overlap the text inside the given node (at the given position in the
+    /// content box of the container). Floats and their content, and boxes that contain their own floats, are not
+    /// affected. Returns true when any of the exclusions have changed.
+    fn exclude_floats(&mut self, node_id: TaffyNodeId, x: f64, y: f64, ctx: &FloatContext) -> bool {
+        let Ok(layout) = self.tree.layout(node_id).cloned() else {
+            return false;
+        };
+
+        if let Some(TaffyContext::Text(text_ctx)) = self.tree.get_node_context_mut(node_id) {
+            let exclusions = ctx.exclusions(x, y, layout.size.width as f64);
+            if text_ctx.exclusions == exclusions {
+                return false;
+            }
+            text_ctx.exclusions = exclusions;
+            let _ = self.tree.mark_dirty(node_id);
+            return true;
+        }
+
+        let mut changed = false;
+        for child_id in self.tree.children(node_id).unwrap_or_default() {
+            let skip = self.floats.iter().any(|float| float.id == child_id)
+                || self.float_containers.iter().any(|container| container.id == child_id && container.contains_floats)
+                || self.tree.style(child_id).is_ok_and(|style| style.position == Position::Absolute);
+            if skip {
+                continue;
+            }
+
+            let Ok(child_layout) = self.tree.layout(child_id) else {
+                continue;
+            };
+            let (child_x, child_y) = (x + child_layout.location.x as f64, y + child_layout.location.y as f64);
+            changed |= self.exclude_floats(child_id, child_x, child_y, ctx);
+        }
+
+        changed
+    }
+
+    /// Grows the minimum height of the container so it contains floats that end at the given position of its
+    /// content box. Returns true when the minimum height has changed.
+    fn contain_floats(&mut self, container: &FloatContainer, layout: &Layout, floats_bottom: f32) -> bool {
+        let Ok(style) = self.tree.style(container.id).cloned() else {
+            return false;
+        };
+
+        let height = match style.box_sizing {
+            BoxSizing::BorderBox => floats_bottom + layout.padding.top + layout.padding.bottom + layout.border.top + layout.border.bottom,
+            BoxSizing::ContentBox => floats_bottom,
+        };
+        let min_height = match container.min_height {
+            Dimension::Length(min_height) if floats_bottom > 0.0 => length(min_height.max(height)),
+            Dimension::Auto if floats_bottom > 0.0 => length(height),
+            min_height => min_height,
+        };
+        if style.min_size.height == min_height {
+            return false;
+        }
+
+        let mut style = style;
+        style.min_size.height = min_height;
+        let _ = self.tree.set_style(container.id, style);
+        true
+    }
+
+    /// Lays out a table as a grid, as taffy has no table layout. The cells of all rows become grid items of the
+    /// table, so the columns line up across rows and cells can span multiple rows and columns. Rows and row
+    /// groups are placed on top of the grid rows they contain, which gives them the box of these rows.
+    fn setup_table(
+        &mut self,
+        layout_tree: &LayoutTree,
+        table_id: TaffyNodeId,
+        children: &[LayoutElementId],
+        styles: &StylePropertyList,
+    ) {
+        let collapse = matches!(
+            styles.get_property(StyleProperty::BorderCollapse),
+            Some(StyleValue::BorderCollapse(BorderCollapse::Collapse))
+        );
+        let (h_spacing, v_spacing) = match styles.get_property(StyleProperty::BorderSpacing) {
+            Some(StyleValue::BorderSpacing(h, v)) if !collapse => (*h, *v),
+            _ => (0.0, 0.0),
+        };
+
+        // Header groups are placed before, and footer groups after the other row groups. Rows that are direct
+        // children of the table form an (anonymous) row group of their own.
+        let mut top_captions = Vec::new();
+        let mut bottom_captions = Vec::new();
+        let mut headers = Vec::new();
+        let mut bodies: Vec<(Option<LayoutElementId>, Vec<LayoutElementId>)> = Vec::new();
+        let mut footers = Vec::new();
+        let mut columns = Vec::new();
+        for &child_id in children {
+            match table_display(layout_tree, child_id) {
+                Some(CssDisplay::TableCaption) => match style_of(layout_tree, child_id, StyleProperty::CaptionSide) {
+                    Some(StyleValue::CaptionSide(CaptionSide::Bottom)) => bottom_captions.push(child_id),
+                    _ => top_captions.push(child_id),
+                },
+                Some(CssDisplay::TableHeaderGroup) => headers.push((Some(child_id), layout_children(layout_tree, child_id))),
+                Some(CssDisplay::TableFooterGroup) => footers.push((Some(child_id), layout_children(layout_tree, child_id))),
+                Some(CssDisplay::TableRowGroup) => bodies.push((Some(child_id), layout_children(layout_tree, child_id))),
+                Some(CssDisplay::TableColumn) => columns.push(child_id),
+                Some(CssDisplay::TableColumnGroup) => {
+                    let cols = layout_children(layout_tree, child_id);
+                    if cols.is_empty() { columns.push(child_id) } else { columns.extend(cols) }
+                }
+                _ => match bodies.last_mut() {
+                    Some((None, rows)) => rows.push(child_id),
+                    _ => bodies.push((None, vec![child_id])),
+                },
+            }
+        }
+
+        // Place the cells of all row groups. Anything in a row that is not a cell, and anything in a row group
+        // that is not a row, is handled as if it was wrapped in a cell of its own.
+        let mut grid = TableGrid::default();
+        let mut groups = Vec::new();
+        let mut rows = Vec::new();
+        for (group_id, group_rows) in headers.into_iter().chain(bodies).chain(footers) {
+            let mut group_cells = Vec::new();
+            for (index, row_id) in group_rows.iter().enumerate() {
+                let cells = if table_display(layout_tree, *row_id) == Some(CssDisplay::TableRow) {
+                    rows.push((*row_id, grid.row_count + index));
+                    layout_children(layout_tree, *row_id)
+                } else {
+                    vec![*row_id]
+                };
+
+                group_cells.push(
+                    cells
+                        .into_iter()
+                        .filter(|cell_id| self.taffy_style(*cell_id).is_some_and(|style| style.display != Display::None))
+                        .map(|cell_id| TableCell {
+                            id: cell_id,
+                            column_span: column_span(attribute(layout_tree, cell_id, "colspan")),
+                            row_span: row_span(attribute(layout_tree, cell_id, "rowspan")),
+                        })
+                        .collect(),
+                );
+            }
+
+            let first_row = grid.add_row_group(group_cells);
+            if let Some(group_id) = group_id {
+                groups.push((group_id, first_row, group_rows.len()));
+            }
+        }
+
+        // Captions take a grid row of their own, above or below the rows of the table
+        let row_offset = top_captions.len();
+        let row_count = row_offset + grid.row_count + bottom_captions.len();
+
+        let Ok(table_style) = self.tree.style(table_id) else {
+            return;
+        };
+        let mut table_style = table_style.clone();
+        table_style.display = Display::Grid;
+        let fixed = table_style.size.width != Dimension::Auto
+            && matches!(styles.get_property(StyleProperty::TableLayout), Some(StyleValue::TableLayout(TableLayout::Fixed)));
+        table_style.grid_template_columns = self.column_tracks(layout_tree, &grid, &columns, fixed);
+        table_style.grid_template_rows = vec![auto(); row_count];
+        table_style.gap = Size { width: length(h_spacing), height: length(v_spacing) };
+        if collapse {
+            // A table with collapsing borders has no padding
+            table_style.padding = Rect::zero();
+        } else {
+            // The spacing is also found between the outer cells and the border of the table
+            table_style.padding = Rect {
+                left: add_length(table_style.padding.left, h_spacing),
+                right: add_length(table_style.padding.right, h_spacing),
+                top: add_length(table_style.padding.top, v_spacing),
+                bottom: add_length(table_style.padding.bottom, v_spacing),
+            };
+        }
+        let _ = self.tree.set_style(table_id, table_style);
+
+        let mut items = Vec::new();
+
+        // Captions span the full width of the table, outside the spacing around the cells
+        let captions = top_captions.iter().map(|id| (*id, true)).chain(bottom_captions.iter().map(|id| (*id, false)));
+        for (index, (caption_id, top)) in captions.enumerate() {
+            let row = if top { index } else { index + grid.row_count };
+            self.place_table_item(caption_id, row..row + 1, None, &mut items, |style| {
+                style.margin.left = add_length_auto(style.margin.left, -h_spacing);
+                style.margin.right = add_length_auto(style.margin.right, -h_spacing);
+                if top {
+                    style.margin.top = add_length_auto(style.margin.top, -v_spacing);
+                } else {
+                    style.margin.bottom = add_length_auto(style.margin.bottom, -v_spacing);
+                }
+            });
+        }
+
+        for (group_id, first_row, group_row_count) in groups {
+            if group_row_count == 0 {
+                self.place_table_item(group_id, 0..1, None, &mut items, |style| style.display = Display::None);
+                continue;
+            }
+            let first_row = row_offset + first_row;
+            self.place_table_item(group_id, first_row..first_row + group_row_count, None, &mut items, |_| {});
+        }
+
+        for (row_id, row) in rows {
+            let row = row_offset + row;
+            self.place_table_item(row_id, row..row + 1, None, &mut items, |_| {});
+        }
+
+        for cell in &grid.cells {
+            let row = row_offset + cell.row;
+            let columns = cell.column..cell.column + cell.column_span;
+            self.place_table_item(cell.id, row..row + cell.row_span, Some(columns), &mut items, |style| {
+                // Adjacent cells share their border. This keeps the border at the top and left side of the table,
+                // and the border that is closest to the bottom right of the other borders.
+                if collapse && cell.row > 0 {
+                    style.border.top = LengthPercentage::Length(0.0);
+                }
+                if collapse && cell.column > 0 {
+                    style.border.left = LengthPercentage::Length(0.0);
+                }
+            });
+        }
+
+        let _ = self.tree.set_children(table_id, &items);
+    }
+
+    /// Places a table item in the given rows and columns of the grid of the table. Without columns, the item
+    /// spans all columns.
+    fn place_table_item(
+        &mut self,
+        layout_id: LayoutElementId,
+        rows: Range<usize>,
+        columns: Option<Range<usize>>,
+        items: &mut Vec<TaffyNodeId>,
+        adjust: impl FnOnce(&mut Style),
+    ) {
+        let Some(taffy_id) = self.layout_taffy_mapping.get(&layout_id).copied() else {
+            return;
+        };
+        let Ok(style) = self.tree.style(taffy_id) else {
+            return;
+        };
+
+        let mut style = style.clone();
+        style.grid_row = to_grid_line(rows);
+        style.grid_column = match columns {
+            Some(columns) => to_grid_line(columns),
+            None => Line { start: line(1), end: line(-1) },
+        };
+        adjust(&mut style);
+
+        let _ = self.tree.set_style(taffy_id, style);
+        items.push(taffy_id);
     }
 
-    // We have a relative path, so we need to prepend the base URL
-    // Make sure we don't have double slashes
-    if base_uri.ends_with("/") && uri.starts_with("/") {
-        return format!("{}{}", base_uri, &uri[1..]).to_string();
+    /// Returns the grid tracks for the columns of the table. With the automatic table layout, columns are sized
+    /// to their content. With the fixed table layout, the widths of the columns are taken from the column
+    /// elements and the cells of the first row, and the remaining width is divided over the other columns.
+    fn column_tracks(
+        &self,
+        layout_tree: &LayoutTree,
+        grid: &TableGrid<LayoutElementId>,
+        columns: &[LayoutElementId],
+        fixed: bool,
+    ) -> Vec<TrackSizingFunction> {
+        if !fixed {
+            return vec![auto(); grid.column_count];
+        }
+
+        let mut widths: Vec<Option<TrackSizingFunction>> = vec![None; grid.column_count];
+
+        let mut column = 0;
+        for &column_id in columns {
+            let span = column_span(attribute(layout_tree, column_id, "span"));
+            let width = match style_of(layout_tree, column_id, StyleProperty::Width) {
+                Some(StyleValue::Unit(width, Unit::Px)) => Some(length(*width)),
+                _ => None,
+            };
+            for slot in widths.iter_mut().skip(column).take(span) {
+                *slot = width.clone();
+            }
+            column += span;
+        }
+
+        for cell in grid.cells.iter().filter(|cell| cell.row == 0 && cell.column_span == 1) {
+            if widths[cell.column].is_some() {
+                continue;
+            }
+            let Some(style) = self.taffy_style(cell.id) else {
+                continue;
+            };
+            widths[cell.column] = match style.size.width {
+                Dimension::Length(width) => Some(length(width + horizontal_edges(style))),
+                Dimension::Percent(width) => Some(percent(width)),
+                Dimension::Auto => None,
+            };
+        }
+
+        // Content does not change the width of the columns
+        widths
+            .into_iter()
+            .map(|width| width.unwrap_or_else(|| minmax(MinTrackSizingFunction::Fixed(length(0.0)), MaxTrackSizingFunction::Fraction(1.0))))
+            .collect()
     }
 
-    // Neither has a /
-    if !base_uri.ends_with("/") && !uri.starts_with("/") {
-        return format!("{}/{}", base_uri, uri).to_string();
+    fn taffy_style(&self, layout_id: LayoutElementId) -> Option<&Style> {
+        let taffy_id = self.layout_taffy_mapping.get(&layout_id)?;
+        self.tree.style(*taffy_id).ok()
     }
+}
 
-    format!("{}{}", base_uri, uri).to_string()
+/// Returns the space that the root is laid out in, which is the viewport
+fn available_space(viewport: Option<geo::Dimension>) -> Size<AvailableSpace> {
+    match viewport {
+        Some(viewport) => Size {
+            width: AvailableSpace::Definite(viewport.width as f32),
+            height: AvailableSpace::Definite(viewport.height as f32),
+        },
+        None => Size::MAX_CONTENT,
+    }
 }
 
 /// Convert a taffy context to an element context. Optionally, these two structures should be merged
@@ -559,11 +1188,12 @@ fn to_absolute_url(uri: &str, base_uri: &str) -> String {
 fn to_element_context(taffy_context: Option<&TaffyContext>) -> ElementContext {
     match taffy_context {
         Some(TaffyContext::Text(text_ctx)) => ElementContext::text(
-            text_ctx.font_family.as_str(),
-            text_ctx.font_size,
-            text_ctx.font_weight,
+            &text_ctx.font,
             text_ctx.line_height,
             text_ctx.alignment,
+            text_ctx.direction,
+            text_ctx.wrapping,
+            text_ctx.writing_mode,
             text_ctx.text.as_str(),
             text_ctx.node_id,
             text_ctx.text_offset,
@@ -584,26 +1214,148 @@ fn to_element_context(taffy_context: Option<&TaffyContext>) -> ElementContext {
     }
 }
 
-/// Returns true if there is a margin on the rect (basically, if the rect is non-zero)
-fn has_margin(src: Rect<LengthPercentageAuto>) -> bool {
-    let is_zero = (src.top == LengthPercentageAuto::Length(0.0)
-        || src.top == LengthPercentageAuto::Percent(0.0))
-        && (src.right == LengthPercentageAuto::Length(0.0)
-            || src.right == LengthPercentageAuto::Percent(0.0))
-        && (src.bottom == LengthPercentageAuto::Length(0.0)
-            || src.bottom == LengthPercentageAuto::Percent(0.0))
-        && (src.left == LengthPercentageAuto::Length(0.0)
-            || src.left == LengthPercentageAuto::Percent(0.0));
-
-    !is_zero
+/// Converts an element context into the context of a taffy node, which is passed to the measure function
+fn to_taffy_context(context: ElementContext) -> Option<TaffyContext> {
+    match context {
+        ElementContext::Text(text_ctx) => Some(TaffyContext::Text(text_ctx)),
+        ElementContext::Image(image_ctx) => Some(TaffyContext::Image(image_ctx)),
+        ElementContext::Svg(svg_ctx) => Some(TaffyContext::Svg(svg_ctx)),
+        ElementContext::None => None,
+    }
 }
 
-/// Converts a taffy layout to our own BoxModel structure
+/// Returns the size of the content box of an image or SVG with the given natural size. Taffy passes the sides that
+/// are set by the style as known dimensions. A maximum width (like max-width: 100%) scales the media down, keeping
+/// its aspect ratio, as taffy only clamps the width after measuring.
+fn measure_replaced(natural: geo::Dimension, known: Size<Option<f32>>, available: Size<AvailableSpace>, style: &Style) -> Size<f32> {
+    let mut width = known.width.map(|width| width as f64);
+    let height = known.height.map(|height| height as f64);
+
+    if width.is_none() && height.is_none() {
+        let max_width = match (style.max_size.width, available.width) {
+            (Dimension::Length(max_width), _) => Some(max_width),
+            (Dimension::Percent(fraction), AvailableSpace::Definite(available)) => Some(fraction * available),
+            _ => None,
+        };
+        let inset = |value: LengthPercentage| match value {
+            LengthPercentage::Length(value) => value,
+            LengthPercentage::Percent(_) => 0.0,
+        };
+        let max_width = max_width.map(|max_width| match style.box_sizing {
+            taffy::BoxSizing::BorderBox => {
+                let edges = inset(style.padding.left) + inset(style.padding.right) + inset(style.border.left) + inset(style.border.right);
+                (max_width - edges).max(0.0) as f64
+            }
+            taffy::BoxSizing::ContentBox => max_width as f64,
+        });
+        width = max_width.filter(|max_width| *max_width < replaced::content_size(natural, None, None).width);
+    }
+
+    let size = replaced::content_size(natural, width, height);
+    Size {
+        width: size.width as f32,
+        height: size.height as f32,
+    }
+}
+
+/// Returns true when the display makes the element a table or a part of a table, which are laid out together
+pub(crate) fn is_table_part(display: Option<&StyleValue>) -> bool {
+    matches!(
+        display,
+        Some(StyleValue::Display(
+            CssDisplay::Table
+                | CssDisplay::InlineTable
+                | CssDisplay::TableCaption
+                | CssDisplay::TableCell
+                | CssDisplay::TableFooterGroup
+                | CssDisplay::TableHeaderGroup
+                | CssDisplay::TableRow
+                | CssDisplay::TableRowGroup
+                | CssDisplay::TableColumn
+                | CssDisplay::TableColumnGroup
+        ))
+    )
+}
+
+/// Returns the display of the DOM node of the layout element
+fn table_display(layout_tree: &LayoutTree, layout_id: LayoutElementId) -> Option<CssDisplay> {
+    match style_of(layout_tree, layout_id, StyleProperty::Display) {
+        Some(StyleValue::Display(display)) => Some(display.clone()),
+        _ => None,
+    }
+}
+
+/// Returns a style property of the DOM element of the layout element
+fn style_of(layout_tree: &LayoutTree, layout_id: LayoutElementId, prop: StyleProperty) -> Option<&StyleValue> {
+    match &dom_node_of(layout_tree, layout_id)?.node_type {
+        NodeType::Element(data) => data.get_style(prop),
+        _ => None,
+    }
+}
+
+/// Returns an attribute of the DOM element of the layout element
+fn attribute<'a>(layout_tree: &'a LayoutTree, layout_id: LayoutElementId, name: &str) -> Option<&'a String> {
+    match &dom_node_of(layout_tree, layout_id)?.node_type {
+        NodeType::Element(data) => data.get_attribute(name),
+        _ => None,
+    }
+}
+
+fn dom_node_of(layout_tree: &LayoutTree, layout_id: LayoutElementId) -> Option<&Node> {
+    let el = layout_tree.get_node_by_id(layout_id)?;
+    layout_tree.render_tree.doc.get_node_by_id(el.dom_node_id)
+}
+
+fn layout_children(layout_tree: &LayoutTree, layout_id: LayoutElementId) -> Vec<LayoutElementId> {
+    layout_tree.get_node_by_id(layout_id).map_or_else(Vec::new, |el| el.children.clone())
+}
+
+/// Returns the width of the horizontal padding and border, when these are not part of the width of the box
+fn horizontal_edges(style: &Style) -> f32 {
+    if style.box_sizing == BoxSizing::BorderBox {
+        return 0.0;
+    }
+
+    [style.padding.left, style.padding.right, style.border.left, style.border.right]
+        .iter()
+        .map(|edge| match edge {
+            LengthPercentage::Length(value) => *value,
+            LengthPercentage::Percent(_) => 0.0,
+        })
+        .sum()
+}
+
+/// Converts a range of grid tracks (starting at 0) into the grid lines around these tracks. Taffy numbers its
+/// grid lines with an i16, so tracks beyond that are clamped to the last line.
+fn to_grid_line(tracks: Range<usize>) -> Line<GridPlacement> {
+    Line {
+        start: line(i16::try_from(tracks.start + 1).unwrap_or(i16::MAX)),
+        end: span(u16::try_from(tracks.len().max(1)).unwrap_or(u16::MAX)),
+    }
+}
+
+fn add_length(value: LengthPercentage, extra: f32) -> LengthPercentage {
+    match value {
+        LengthPercentage::Length(value) => LengthPercentage::Length(value + extra),
+        value => value,
+    }
+}
+
+fn add_length_auto(value: LengthPercentageAuto, extra: f32) -> LengthPercentageAuto {
+    match value {
+        LengthPercentageAuto::Length(value) => LengthPercentageAuto::Length(value + extra),
+        value => value,
+    }
+}
+
+/// Converts a taffy layout to our own BoxModel structure. The location of a taffy layout is the position of the
+/// border box, and its margins are the margins after collapsing (the top margin of a block that collapses with
+/// the margin of its first child holds the collapsed margin of both).
 pub fn taffy_layout_to_boxmodel(layout: &Layout, offset: Coordinate) -> box_model::BoxModel {
     box_model::BoxModel {
         margin_box: geo::Rect {
-            x: offset.x + layout.location.x as f64,
-            y: offset.y + layout.location.y as f64,
+            x: offset.x + layout.location.x as f64 - layout.margin.left as f64,
+            y: offset.y + layout.location.y as f64 - layout.margin.top as f64,
             width: layout.size.width as f64
                 + layout.margin.left as f64
                 + layout.margin.right as f64,
@@ -631,3 +1383,166 @@ pub fn taffy_layout_to_boxmodel(layout: &Layout, offset: Coordinate) -> box_mode
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::document::document::Document;
+    use crate::common::document::node::{AttrMap, NodeId};
+    use crate::common::document::style::StylePropertyList;
+    use crate::layouter::fixtures::{block_style, border_box, layout};
+    use crate::layouter::text::NoShaper;
+
+    fn table_style(props: Vec<(StyleProperty, StyleValue)>) -> Option<StylePropertyList> {
+        let mut style = StylePropertyList::new();
+        style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::Table));
+        style.set_property(StyleProperty::Width, StyleValue::Unit(300.0, Unit::Px));
+        style.set_property(StyleProperty::TableLayout, StyleValue::TableLayout(TableLayout::Fixed));
+        for (prop, value) in props {
+            style.set_property(prop, value);
+        }
+        Some(style)
+    }
+
+    fn table_part(display: CssDisplay, height: f32, border: f32) -> Option<StylePropertyList> {
+        let mut style = StylePropertyList::new();
+        style.set_property(StyleProperty::Display, StyleValue::Display(display));
+        if height > 0.0 {
+            style.set_property(StyleProperty::Height, StyleValue::Unit(height, Unit::Px));
+        }
+        for prop in [StyleProperty::BorderTopWidth, StyleProperty::BorderRightWidth, StyleProperty::BorderBottomWidth, StyleProperty::BorderLeftWidth] {
+            style.set_property(prop, StyleValue::Unit(border, Unit::Px));
+        }
+        Some(style)
+    }
+
+    /// Builds a table of rows with cells of 50px high in a body, cells that span rows are stretched over them. The cells are given as (colspan, rowspan). Captions
+    /// are placed before the rows. Returns the document with the ids of the table, the captions and the cells.
+    fn table_doc(
+        table: Option<StylePropertyList>,
+        captions: &[CaptionSide],
+        rows: &[&[(usize, usize)]],
+        cell_border: f32,
+    ) -> (Document, NodeId, Vec<NodeId>, Vec<NodeId>) {
+        let mut doc = Document::new("https://example.com");
+        let body_id = doc.new_element(None, "body", None, false, Some(block_style(0.0, 0.0, 0.0, 0.0)));
+        let table_id = doc.new_element(Some(body_id), "table", None, false, table);
+        doc.add_child(body_id, table_id);
+
+        let mut caption_ids = Vec::new();
+        for side in captions {
+            let mut style = table_part(CssDisplay::TableCaption, 20.0, 0.0);
+            if let Some(style) = style.as_mut() {
+                style.set_property(StyleProperty::CaptionSide, StyleValue::CaptionSide(*side));
+            }
+            let caption_id = doc.new_element(Some(table_id), "caption", None, false, style);
+            doc.add_child(table_id, caption_id);
+            caption_ids.push(caption_id);
+        }
+
+        let mut cell_ids = Vec::new();
+        for cells in rows {
+            let row_id = doc.new_element(Some(table_id), "tr", None, false, table_part(CssDisplay::TableRow, 0.0, 0.0));
+            doc.add_child(table_id, row_id);
+            for &(colspan, rowspan) in cells.iter() {
+                let mut attributes = AttrMap::new();
+                attributes.set("colspan", &colspan.to_string());
+                attributes.set("rowspan", &rowspan.to_string());
+                let height = if rowspan > 1 { 0.0 } else { 50.0 };
+                let style = table_part(CssDisplay::TableCell, height, cell_border);
+                let cell_id = doc.new_element(Some(row_id), "td", Some(attributes), false, style);
+                doc.add_child(row_id, cell_id);
+                cell_ids.push(cell_id);
+            }
+        }
+        doc.set_root(body_id);
+
+        (doc, table_id, caption_ids, cell_ids)
+    }
+
+    #[test]
+    fn test_table_spans() {
+        // a a b
+        // c d b
+        let (doc, table_id, _, cells) = table_doc(table_style(vec![]), &[], &[&[(2, 1), (1, 2)], &[(1, 1), (1, 1)]], 0.0);
+        let layout_tree = layout("taffy", doc);
+
+        assert_eq!(border_box(&layout_tree, table_id), geo::Rect::new(0.0, 0.0, 300.0, 100.0));
+        assert_eq!(border_box(&layout_tree, cells[0]), geo::Rect::new(0.0, 0.0, 200.0, 50.0));
+        assert_eq!(border_box(&layout_tree, cells[1]), geo::Rect::new(200.0, 0.0, 100.0, 100.0));
+        assert_eq!(border_box(&layout_tree, cells[2]), geo::Rect::new(0.0, 50.0, 100.0, 50.0));
+        assert_eq!(border_box(&layout_tree, cells[3]), geo::Rect::new(100.0, 50.0, 100.0, 50.0));
+    }
+
+    #[test]
+    fn test_table_border_spacing() {
+        let table = table_style(vec![(StyleProperty::BorderSpacing, StyleValue::BorderSpacing(10.0, 5.0))]);
+        let (doc, table_id, _, cells) = table_doc(table, &[], &[&[(1, 1), (1, 1)], &[(1, 1), (1, 1)]], 0.0);
+        let layout_tree = layout("taffy", doc);
+
+        // The spacing is found between the cells, and between the outer cells and the border of the table
+        let table_box = border_box(&layout_tree, table_id);
+        assert_eq!(table_box.height, 5.0 + 50.0 + 5.0 + 50.0 + 5.0);
+        let column_width = (table_box.width - 3.0 * 10.0) / 2.0;
+        assert_eq!(border_box(&layout_tree, cells[0]), geo::Rect::new(10.0, 5.0, column_width, 50.0));
+        assert_eq!(border_box(&layout_tree, cells[1]), geo::Rect::new(20.0 + column_width, 5.0, column_width, 50.0));
+        assert_eq!(border_box(&layout_tree, cells[2]), geo::Rect::new(10.0, 60.0, column_width, 50.0));
+    }
+
+    #[test]
+    fn test_table_border_collapse() {
+        let table = table_style(vec![
+            (StyleProperty::BorderCollapse, StyleValue::BorderCollapse(BorderCollapse::Collapse)),
+            (StyleProperty::BorderSpacing, StyleValue::BorderSpacing(10.0, 10.0)),
+        ]);
+        let (doc, _, _, cells) = table_doc(table, &[], &[&[(1, 1), (1, 1)], &[(1, 1), (1, 1)]], 2.0);
+        let layout_tree = layout("taffy", doc);
+        let border = |dom_node_id| {
+            let el = layout_tree.arena.values().find(|el| el.dom_node_id == dom_node_id).unwrap();
+            (el.box_model.border.top, el.box_model.border.right, el.box_model.border.bottom, el.box_model.border.left)
+        };
+
+        // There is no spacing, and adjacent cells share their border
+        assert_eq!(border_box(&layout_tree, cells[0]).x, 0.0);
+        assert_eq!(border_box(&layout_tree, cells[2]).y, 50.0);
+        assert_eq!(border(cells[0]), (2.0, 2.0, 2.0, 2.0));
+        assert_eq!(border(cells[1]), (2.0, 2.0, 2.0, 0.0));
+        assert_eq!(border(cells[2]), (0.0, 2.0, 2.0, 2.0));
+        assert_eq!(border(cells[3]), (0.0, 2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_table_captions() {
+        let table = table_style(vec![(StyleProperty::BorderSpacing, StyleValue::BorderSpacing(10.0, 10.0))]);
+        let captions = [CaptionSide::Bottom, CaptionSide::Top];
+        let (doc, table_id, captions, cells) = table_doc(table, &captions, &[&[(1, 1)]], 0.0);
+        let layout_tree = layout("taffy", doc);
+
+        // Captions span the full width of the table, outside the spacing around the cells
+        let table_box = border_box(&layout_tree, table_id);
+        assert_eq!(border_box(&layout_tree, captions[1]), geo::Rect::new(0.0, 0.0, table_box.width, 20.0));
+        assert_eq!(border_box(&layout_tree, cells[0]), geo::Rect::new(10.0, 30.0, table_box.width - 20.0, 50.0));
+        assert_eq!(border_box(&layout_tree, captions[0]), geo::Rect::new(0.0, 90.0, table_box.width, 20.0));
+        assert_eq!(table_box.height, 110.0);
+    }
+
+    #[test]
+    fn test_inline_table() {
+        let mut table = table_style(vec![]);
+        if let Some(style) = table.as_mut() {
+            style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::InlineTable));
+        }
+        let (doc, table_id, _, cells) = table_doc(table, &[], &[&[(1, 1), (1, 1)]], 0.0);
+        let layout_tree = layout("taffy", doc);
+
+        assert_eq!(border_box(&layout_tree, table_id).height, 50.0);
+        assert_eq!(border_box(&layout_tree, cells[1]), geo::Rect::new(150.0, 0.0, 150.0, 50.0));
+    }
+
+    #[test]
+    fn test_grid_line_overflow() {
+        let grid_line = to_grid_line(usize::MAX - 1..usize::MAX);
+        assert_eq!(grid_line.start, line(i16::MAX));
+        assert_eq!(grid_line.end, span(1));
+    }
+}
@@ -1,32 +1,705 @@
+use std::ops::Range;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use hyphenation::{Hyphenator, Language, Load, Standard};
+use crate::common::document::style::{Direction, Hyphens, OverflowWrap, TextWrap, UnicodeBidi, WhiteSpace, WordBreak};
+use crate::common::font::FontDescriptor;
+use crate::common::geo::Dimension;
+
 #[cfg(not(any(feature = "text_parley", feature = "text_pango", feature = "text_skia")))]
 compile_error!("Either the 'text_parley' 'text_skia' or 'text_pango' feature must be enabled");
 
+// Each text backend implements the TextShaper trait. Multiple backends can be compiled in at the same
+// time, and the layouter gets the shaper it should use at runtime.
+
 #[cfg(feature = "text_parley")]
 pub mod parley;
-#[cfg(feature = "text_parley")]
-pub use crate::layouter::text::parley::get_text_layout;
-
 #[cfg(feature = "text_pango")]
 pub mod pango;
-#[cfg(feature = "text_pango")]
-pub use crate::layouter::text::pango::get_text_layout;
-
-
 #[cfg(feature = "text_skia")]
 pub mod skia;
-#[cfg(feature = "text_skia")]
-pub use crate::layouter::text::skia::get_text_layout;
-
 
-/// Text alignment
-#[derive(Clone, Debug, Copy)]
+/// Text alignment. The layouter resolves the logical (start, end) and physical (left, right) CSS values
+/// against the direction of the text, so start and end are always the left and right side of the line box.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Alignment {
-    /// Alignment of text is at the start (depends on LTR)
+    /// Lines are aligned to the left
     Start,
-    /// Alignment of text is at the end (depends on LTR)
+    /// Lines are aligned to the right
     End,
     /// Alignment is centered
     Middle,
-    /// alignment is justified (full column width)
+    /// Alignment is justified (full column width), except for the last line which is aligned to the start
     Justified,
-}
\ No newline at end of file
+    /// All lines are justified, including the last line (text-align-last: justify)
+    JustifiedAll,
+}
+
+/// Base direction of a paragraph. The bidi algorithm of the text backend orders the characters inside the
+/// lines, the base direction decides the direction of runs with mixed or neutral characters.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+/// Style of the text that is needed for shaping and line breaking
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+    /// Font that is used for the text
+    pub font: FontDescriptor,
+    /// Height of each line in pixels
+    pub line_height: f64,
+    /// Alignment of the lines
+    pub alignment: Alignment,
+    /// Base direction of the paragraph
+    pub direction: TextDirection,
+}
+
+/// A single positioned glyph. The position is relative to the top left of the text box and points to
+/// the baseline of the glyph.
+#[derive(Clone, Debug)]
+pub struct Glyph {
+    /// Glyph ID inside the font
+    pub id: u32,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A run of glyphs that are all shaped with the same font
+#[derive(Clone, Debug)]
+pub struct GlyphRun {
+    /// Font family that has been selected for this run
+    pub font_family: String,
+    /// Size of the font in pixels
+    pub font_size: f64,
+    /// Positioned glyphs of the run
+    pub glyphs: Vec<Glyph>,
+}
+
+/// A single line of text after line breaking
+#[derive(Clone, Debug)]
+pub struct TextLine {
+    /// Byte range of the line inside the text
+    pub range: Range<usize>,
+    /// Offset of the line from the left of the text box
+    pub x: f64,
+    /// Width of the line
+    pub width: f64,
+    /// Position of the baseline from the top of the text box
+    pub baseline: f64,
+    /// Height of the line
+    pub height: f64,
+}
+
+/// A text shaper measures, shapes and line-breaks text with a specific text library. All methods lay out
+/// the text within the given maximum width.
+pub trait TextShaper: Send + Sync {
+    /// Name of the shaper ("skia", "parley" or "pango")
+    fn name(&self) -> &'static str;
+    /// Returns the dimension of the laid out text
+    fn measure(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Dimension>;
+    /// Shapes the text into positioned glyph runs
+    fn shape(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<GlyphRun>>;
+    /// Breaks the text into lines
+    fn line_break(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<TextLine>>;
+}
+
+/// Returns all text shapers that are compiled in
+pub fn available_shapers() -> Vec<Arc<dyn TextShaper>> {
+    vec![
+        #[cfg(feature = "text_skia")]
+        Arc::new(skia::SkiaShaper::new()),
+        #[cfg(feature = "text_parley")]
+        Arc::new(parley::ParleyShaper::new()),
+        #[cfg(feature = "text_pango")]
+        Arc::new(pango::PangoShaper::new()),
+    ]
+}
+
+/// Returns the text shaper with the given name, or None when it is not compiled in
+pub fn shaper_by_name(name: &str) -> Option<Arc<dyn TextShaper>> {
+    available_shapers().into_iter().find(|shaper| shaper.name() == name)
+}
+
+/// Returns the first text shaper that is compiled in
+pub fn default_shaper() -> Arc<dyn TextShaper> {
+    available_shapers().into_iter().next().expect("No text shaper compiled in")
+}
+
+/// How lines of text are wrapped and where words may be broken (text-wrap, word-break, overflow-wrap and
+/// hyphens). The shapers only know about regular line breaking, so these are applied on the text itself:
+/// see prepare_text() and wrap_text().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextWrapping {
+    pub wrap: TextWrap,
+    pub word_break: WordBreak,
+    pub overflow_wrap: OverflowWrap,
+    pub hyphens: Hyphens,
+}
+
+impl Default for TextWrapping {
+    fn default() -> Self {
+        Self {
+            wrap: TextWrap::Wrap,
+            word_break: WordBreak::Normal,
+            overflow_wrap: OverflowWrap::Normal,
+            hyphens: Hyphens::Manual,
+        }
+    }
+}
+
+const SOFT_HYPHEN: char = '\u{ad}';
+const ZERO_WIDTH_SPACE: char = '\u{200b}';
+const WORD_JOINER: char = '\u{2060}';
+const NO_BREAK_SPACE: char = '\u{a0}';
+
+/// Maximum number of lines that are balanced. Longer text is wrapped normally.
+const BALANCE_MAX_LINES: usize = 6;
+/// Number of iterations when searching for the balanced width
+const BALANCE_ITERATIONS: usize = 10;
+/// Number of narrower widths that are tried to avoid a single word on the last line
+const PRETTY_ATTEMPTS: usize = 8;
+/// Fraction of the width that is removed on each pretty attempt
+const PRETTY_STEP: f64 = 0.03;
+
+/// Applies the parts of the text wrapping that do not depend on the available width: break opportunities
+/// for word-break, no-break spaces for text-wrap: nowrap and (soft) hyphens. Automatic hyphenation is only
+/// done for english text, as that is the only dictionary that is available.
+pub fn prepare_text(text: &str, wrapping: &TextWrapping, lang: Option<&str>) -> String {
+    let mut text = match wrapping.word_break {
+        WordBreak::BreakAll => insert_between_chars(text, ZERO_WIDTH_SPACE, |prev, next| !is_space(prev) && !is_space(next)),
+        WordBreak::KeepAll => insert_between_chars(text, WORD_JOINER, |prev, next| is_cjk(prev) && is_cjk(next)),
+        WordBreak::Normal | WordBreak::BreakWord => text.to_string(),
+    };
+
+    match wrapping.hyphens {
+        Hyphens::None => text.retain(|c| c != SOFT_HYPHEN),
+        Hyphens::Auto if lang.is_some_and(|lang| lang.to_ascii_lowercase().starts_with("en")) => {
+            text = hyphenate(&text);
+        }
+        _ => {}
+    }
+
+    if wrapping.wrap == TextWrap::NoWrap {
+        text = text.replace(' ', &NO_BREAK_SPACE.to_string());
+    }
+
+    text
+}
+
+/// Applies the parts of the text wrapping that depend on the available width: breaking of overflowing words
+/// (overflow-wrap), balancing of lines (text-wrap: balance and pretty) and hyphens at soft hyphen breaks. When
+/// lines have been moved around, the text is returned with explicit line breaks, so it will be broken in the
+/// same way by the rasterizer.
+pub fn wrap_text(shaper: &dyn TextShaper, text: &str, style: &TextStyle, wrapping: &TextWrapping, max_width: f64) -> anyhow::Result<String> {
+    // Overflow-wrap: break-word does not influence the min-content size, anywhere does
+    let break_words = match wrapping.overflow_wrap {
+        OverflowWrap::Anywhere => true,
+        OverflowWrap::BreakWord => max_width > 0.0,
+        OverflowWrap::Normal => wrapping.word_break == WordBreak::BreakWord,
+    };
+    let has_soft_hyphens = text.contains(SOFT_HYPHEN);
+    let rebalance = matches!(wrapping.wrap, TextWrap::Balance | TextWrap::Pretty);
+
+    if !(break_words || has_soft_hyphens || rebalance) || !max_width.is_finite() || max_width >= f64::MAX / 2.0 {
+        return Ok(text.to_string());
+    }
+
+    let text = if break_words {
+        break_overflowing_words(shaper, text, style, max_width)?
+    } else {
+        text.to_string()
+    };
+
+    let lines = shaper.line_break(&text, style, max_width)?;
+    let width = match wrapping.wrap {
+        TextWrap::Balance => balanced_width(shaper, &text, style, max_width, lines.len())?,
+        TextWrap::Pretty => pretty_width(shaper, &text, style, max_width, lines.len())?,
+        _ => max_width,
+    };
+
+    if width == max_width && !has_soft_hyphens {
+        return Ok(text);
+    }
+
+    let lines = if width == max_width { lines } else { shaper.line_break(&text, style, width)? };
+    Ok(join_lines(&text, &lines))
+}
+
+/// Inserts the given character between each pair of characters for which the predicate holds. Characters
+/// that extend the previous character (like combining marks) are never separated from it.
+fn insert_between_chars(text: &str, insert: char, predicate: impl Fn(char, char) -> bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if let Some(p) = prev {
+            if !is_extending(c) && p != '\u{200d}' && predicate(p, c) {
+                result.push(insert);
+            }
+        }
+        result.push(c);
+        prev = Some(c);
+    }
+
+    result
+}
+
+fn is_space(c: char) -> bool {
+    c.is_whitespace() || c == ZERO_WIDTH_SPACE
+}
+
+/// Returns true for characters that belong to the previous character: combining marks, variation selectors
+/// and joiners
+fn is_extending(c: char) -> bool {
+    matches!(c, '\u{300}'..='\u{36f}' | '\u{1ab0}'..='\u{1aff}' | '\u{20d0}'..='\u{20ff}' | '\u{fe00}'..='\u{fe0f}' | '\u{fe20}'..='\u{fe2f}' | '\u{200d}' | '\u{e0100}'..='\u{e01ef}')
+}
+
+/// Returns true for Chinese, Japanese and Korean letters
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{1100}'..='\u{11ff}' | '\u{3040}'..='\u{30ff}' | '\u{3130}'..='\u{318f}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}' | '\u{f900}'..='\u{faff}' | '\u{20000}'..='\u{2fa1f}')
+}
+
+/// Inserts soft hyphens at the hyphenation points of all words
+fn hyphenate(text: &str) -> String {
+    static DICTIONARY: OnceLock<Option<Standard>> = OnceLock::new();
+    let dictionary = DICTIONARY.get_or_init(|| match Standard::from_embedded(Language::EnglishUS) {
+        Ok(dictionary) => Some(dictionary),
+        Err(e) => {
+            log::warn!("Failed to load hyphenation dictionary: {:?}", e);
+            None
+        }
+    });
+    let Some(dictionary) = dictionary else {
+        return text.to_string();
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (idx, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if c.is_alphabetic() {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+
+        if let Some(start) = word_start.take() {
+            let word = &text[start..idx];
+            let mut last = 0;
+            for brk in dictionary.hyphenate(word).breaks {
+                result.push_str(&word[last..brk]);
+                result.push(SOFT_HYPHEN);
+                last = brk;
+            }
+            result.push_str(&word[last..]);
+        }
+        if idx < text.len() {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Adds break opportunities between all characters of the words that do not fit on a line by themselves
+fn break_overflowing_words(shaper: &dyn TextShaper, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    for (idx, word) in text.split(' ').enumerate() {
+        if idx > 0 {
+            result.push(' ');
+        }
+
+        if !word.is_empty() && shaper.measure(word, style, f64::MAX)?.width > max_width {
+            result.push_str(&insert_between_chars(word, ZERO_WIDTH_SPACE, |_, _| true));
+        } else {
+            result.push_str(word);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns the smallest width at which the text still has the same number of lines
+fn balanced_width(shaper: &dyn TextShaper, text: &str, style: &TextStyle, max_width: f64, line_count: usize) -> anyhow::Result<f64> {
+    if !(2..=BALANCE_MAX_LINES).contains(&line_count) {
+        return Ok(max_width);
+    }
+
+    let mut low = max_width / line_count as f64;
+    let mut high = max_width;
+    for _ in 0..BALANCE_ITERATIONS {
+        let mid = (low + high) / 2.0;
+        if shaper.line_break(text, style, mid)?.len() > line_count {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(high.ceil().min(max_width))
+}
+
+/// Returns a width (slightly) smaller than the max width that avoids a single word on the last line without
+/// adding lines. Returns the max width when there is no such width.
+fn pretty_width(shaper: &dyn TextShaper, text: &str, style: &TextStyle, max_width: f64, line_count: usize) -> anyhow::Result<f64> {
+    let has_single_word = |lines: &[TextLine]| match lines.last() {
+        Some(last) => text.get(last.range.clone()).is_some_and(|line| !line.trim().contains([' ', ZERO_WIDTH_SPACE, SOFT_HYPHEN])),
+        None => false,
+    };
+
+    if line_count < 2 || !has_single_word(&shaper.line_break(text, style, max_width)?) {
+        return Ok(max_width);
+    }
+
+    for attempt in 1..=PRETTY_ATTEMPTS {
+        let width = max_width * (1.0 - PRETTY_STEP * attempt as f64);
+        let lines = shaper.line_break(text, style, width)?;
+        if lines.len() > line_count {
+            break;
+        }
+        if !has_single_word(&lines) {
+            return Ok(width);
+        }
+    }
+
+    Ok(max_width)
+}
+
+/// Joins the lines with explicit line breaks. Lines that end at a soft hyphen get a visible hyphen.
+fn join_lines(text: &str, lines: &[TextLine]) -> String {
+    let starts: Vec<usize> = lines.iter().skip(1).map(|line| line.range.start).collect();
+    if starts.iter().any(|start| !text.is_char_boundary(*start)) {
+        return text.to_string();
+    }
+
+    let mut result = String::with_capacity(text.len() + lines.len());
+    let mut start = 0;
+    for end in starts {
+        if end <= start {
+            continue;
+        }
+
+        let line = &text[start..end];
+        let trimmed = line.trim_end_matches([' ', ZERO_WIDTH_SPACE]);
+        if trimmed.ends_with('\n') {
+            result.push_str(line);
+        } else if let Some(hyphenated) = trimmed.strip_suffix(SOFT_HYPHEN) {
+            result.push_str(hyphenated);
+            result.push_str("-\n");
+        } else {
+            result.push_str(trimmed);
+            result.push('\n');
+        }
+        start = end;
+    }
+    result.push_str(&text[start..]);
+
+    result
+}
+
+/// Returns the style that is used to shape a column of vertical text. Each character is set on its own line,
+/// centered in the column, so the line height is the advance of a single character.
+pub fn vertical_style(style: &TextStyle) -> TextStyle {
+    TextStyle {
+        line_height: style.font.size,
+        alignment: Alignment::Middle,
+        ..style.clone()
+    }
+}
+
+/// Breaks vertical text into columns of upright characters. A new column is started at each forced line
+/// break, and when the column is longer than the max height. Each column is returned as a text with one
+/// character per line, which can be shaped as a normal horizontal paragraph with the vertical_style(). Sideways
+/// glyphs (text-orientation: sideways, and non-CJK text in mixed orientation) would need rotated glyph runs,
+/// so all characters are set upright.
+pub fn vertical_columns(shaper: &dyn TextShaper, text: &str, style: &TextStyle, max_height: f64) -> anyhow::Result<Vec<String>> {
+    let style = vertical_style(style);
+
+    let chars_per_column = if max_height.is_finite() && max_height < f64::MAX / 2.0 {
+        let advance = shaper.measure("\u{56fd}", &style, f64::MAX)?.height.max(1.0);
+        ((max_height / advance).floor() as usize).max(1)
+    } else {
+        usize::MAX
+    };
+
+    Ok(split_columns(text, chars_per_column))
+}
+
+/// Returns the dimension of the columns of vertical text. Columns are placed next to each other, each column
+/// is as wide as the line height.
+pub fn measure_vertical(shaper: &dyn TextShaper, columns: &[String], style: &TextStyle) -> anyhow::Result<Dimension> {
+    let vertical_style = vertical_style(style);
+
+    let mut height: f64 = 0.0;
+    for column in columns {
+        height = height.max(shaper.measure(column, &vertical_style, style.line_height)?.height);
+    }
+
+    Ok(Dimension::new(columns.len() as f64 * style.line_height, height))
+}
+
+/// Splits the text into columns with at most the given number of characters. Characters that extend the
+/// previous character (like combining marks) stay together with it.
+fn split_columns(text: &str, chars_per_column: usize) -> Vec<String> {
+    let mut columns = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut column: Vec<String> = Vec::new();
+        for c in paragraph.chars() {
+            match column.last_mut() {
+                Some(last) if is_extending(c) => last.push(c),
+                _ => {
+                    if column.len() == chars_per_column {
+                        columns.push(column.join("\n"));
+                        column.clear();
+                    }
+                    column.push(c.to_string());
+                }
+            }
+        }
+        columns.push(column.join("\n"));
+    }
+
+    columns
+}
+
+const LEFT_TO_RIGHT_OVERRIDE: char = '\u{202d}';
+const RIGHT_TO_LEFT_OVERRIDE: char = '\u{202e}';
+const POP_DIRECTIONAL_FORMATTING: char = '\u{202c}';
+
+/// Resolves the base direction of the text from the direction and unicode-bidi properties. Each text node is
+/// laid out as its own paragraph, so embedding and isolation have no effect inside the text. An override is
+/// applied by wrapping the text in explicit directional formatting characters, which all text backends
+/// support. Returns the (possibly wrapped) text together with its base direction.
+pub fn resolve_bidi(text: &str, direction: Direction, unicode_bidi: UnicodeBidi) -> (String, TextDirection) {
+    let base = match direction {
+        Direction::Ltr => TextDirection::Ltr,
+        Direction::Rtl => TextDirection::Rtl,
+    };
+
+    match unicode_bidi {
+        UnicodeBidi::Plaintext => (text.to_string(), detect_direction(text).unwrap_or(base)),
+        UnicodeBidi::BidiOverride | UnicodeBidi::IsolateOverride => {
+            let mark = match base {
+                TextDirection::Ltr => LEFT_TO_RIGHT_OVERRIDE,
+                TextDirection::Rtl => RIGHT_TO_LEFT_OVERRIDE,
+            };
+            (format!("{}{}{}", mark, text, POP_DIRECTIONAL_FORMATTING), base)
+        }
+        UnicodeBidi::Normal | UnicodeBidi::Embed | UnicodeBidi::Isolate => (text.to_string(), base),
+    }
+}
+
+/// Returns the direction of the first strong character in the text (rule P2 of the bidi algorithm). Letters
+/// of the right-to-left scripts (Hebrew, Arabic, Syriac, Thaana, N'Ko and others) are detected by their block.
+pub fn detect_direction(text: &str) -> Option<TextDirection> {
+    text.chars().find_map(|c| {
+        if is_rtl_letter(c) {
+            Some(TextDirection::Rtl)
+        } else if c.is_alphabetic() {
+            Some(TextDirection::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+fn is_rtl_letter(c: char) -> bool {
+    // Arabic-Indic digits are in the Arabic block, but they are not strong characters
+    if matches!(c, '\u{660}'..='\u{669}' | '\u{6f0}'..='\u{6f9}') {
+        return false;
+    }
+
+    matches!(c, '\u{590}'..='\u{8ff}' | '\u{fb1d}'..='\u{fdff}' | '\u{fe70}'..='\u{fefe}' | '\u{10800}'..='\u{10fff}' | '\u{1e800}'..='\u{1efff}')
+}
+
+/// Number of columns between tab stops (tab-size)
+const TAB_SIZE: usize = 8;
+
+/// Processes the white space of a text node according to the white-space property. Collapsible white space
+/// is collapsed (and trimmed, as each text node is laid out as its own box), tabs are expanded to the next
+/// tab stop and, when lines may not wrap, spaces are replaced by no-break spaces so the shapers will not
+/// wrap the text. Newlines that must be kept are left in, as all shapers treat them as forced line breaks.
+pub fn process_white_space(text: &str, white_space: WhiteSpace) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let is_space = |c: char| matches!(c, ' ' | '\t' | '\x0C');
+
+    let text = match white_space {
+        WhiteSpace::Normal | WhiteSpace::NoWrap => collapse_spaces(&text, |c| is_space(c) || c == '\n'),
+        WhiteSpace::PreLine => text
+            .split('\n')
+            .map(|line| collapse_spaces(line, is_space))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::BreakSpaces => expand_tabs(&text),
+    };
+
+    if white_space.wraps() {
+        text
+    } else {
+        text.replace(' ', "\u{a0}")
+    }
+}
+
+/// Collapses each run of white space into a single space, and removes leading and trailing white space
+fn collapse_spaces(text: &str, is_space: impl Fn(char) -> bool) -> String {
+    text.split(is_space).filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// Replaces tabs with spaces up to the next tab stop
+fn expand_tabs(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = TAB_SIZE - column % TAB_SIZE;
+                result.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                result.push(c);
+                column = 0;
+            }
+            _ => {
+                result.push(c);
+                column += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// Shaper for documents without text
+#[cfg(test)]
+pub(crate) struct NoShaper;
+
+#[cfg(test)]
+impl TextShaper for NoShaper {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+    fn measure(&self, _text: &str, _style: &TextStyle, _max_width: f64) -> anyhow::Result<Dimension> {
+        Ok(Dimension::ZERO)
+    }
+    fn shape(&self, _text: &str, _style: &TextStyle, _max_width: f64) -> anyhow::Result<Vec<GlyphRun>> {
+        Ok(Vec::new())
+    }
+    fn line_break(&self, _text: &str, _style: &TextStyle, _max_width: f64) -> anyhow::Result<Vec<TextLine>> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_white_space() {
+        let text = "  fn main() {\n\tprintln!(\"hello   world\");\n}  ";
+
+        assert_eq!(process_white_space(text, WhiteSpace::Normal), "fn main() { println!(\"hello world\"); }");
+        assert_eq!(process_white_space(text, WhiteSpace::PreLine), "fn main() {\nprintln!(\"hello world\");\n}");
+        assert_eq!(process_white_space(text, WhiteSpace::PreWrap), "  fn main() {\n        println!(\"hello   world\");\n}  ");
+        assert_eq!(process_white_space("a  b\nc", WhiteSpace::NoWrap), "a\u{a0}b\u{a0}c");
+        assert_eq!(process_white_space("a\tb\nc", WhiteSpace::Pre), "a\u{a0}\u{a0}\u{a0}\u{a0}\u{a0}\u{a0}\u{a0}b\nc");
+    }
+
+    #[test]
+    fn test_prepare_text() {
+        let mut wrapping = TextWrapping::default();
+        assert_eq!(prepare_text("co\u{ad}op", &wrapping, None), "co\u{ad}op");
+
+        wrapping.hyphens = Hyphens::None;
+        assert_eq!(prepare_text("co\u{ad}op", &wrapping, None), "coop");
+
+        wrapping.word_break = WordBreak::BreakAll;
+        assert_eq!(prepare_text("ab c", &wrapping, None), "a\u{200b}b c");
+
+        wrapping.word_break = WordBreak::KeepAll;
+        assert_eq!(prepare_text("日本語 a", &wrapping, None), "日\u{2060}本\u{2060}語 a");
+
+        wrapping.word_break = WordBreak::Normal;
+        wrapping.wrap = TextWrap::NoWrap;
+        assert_eq!(prepare_text("a b", &wrapping, None), "a\u{a0}b");
+    }
+
+    #[test]
+    fn test_line_alignment() {
+        crate::common::font::face::use_test_fonts().unwrap();
+
+        let text = "The quick brown fox jumps over the lazy dog, again and again and again";
+        let max_width = 200.0;
+        // Lines may (or may not) include their trailing space, depending on the shaper
+        let tolerance = 8.0;
+        let style = |alignment| TextStyle {
+            font: FontDescriptor::new("sans-serif", 16.0),
+            line_height: 20.0,
+            alignment,
+            direction: TextDirection::Ltr,
+        };
+
+        for shaper in available_shapers() {
+            let name = shaper.name();
+
+            let lines = shaper.line_break(text, &style(Alignment::Start), max_width).unwrap();
+            assert!(lines.len() > 1, "{}: text must wrap", name);
+            for line in &lines {
+                assert!(line.x.abs() < tolerance, "{}: start aligned line at {}", name, line.x);
+            }
+
+            for line in shaper.line_break(text, &style(Alignment::End), max_width).unwrap() {
+                let right = line.x + line.width;
+                assert!((right - max_width).abs() < tolerance, "{}: end aligned line ends at {}", name, right);
+            }
+
+            for line in shaper.line_break(text, &style(Alignment::Middle), max_width).unwrap() {
+                let center = line.x + line.width / 2.0;
+                assert!((center - max_width / 2.0).abs() < tolerance, "{}: centered line centered at {}", name, center);
+            }
+
+            // Justified lines start at the left, but the glyphs of all but the last line are spread out
+            let justified = shaper.line_break(text, &style(Alignment::Justified), max_width).unwrap();
+            assert!(justified.iter().all(|line| line.x.abs() < tolerance), "{}: justified lines must start at the left", name);
+
+            let first_line_end = |alignment| {
+                let runs = shaper.shape(text, &style(alignment), max_width).unwrap();
+                let baseline = runs.iter().flat_map(|run| &run.glyphs).map(|glyph| glyph.y).fold(f64::MAX, f64::min);
+                runs.iter()
+                    .flat_map(|run| &run.glyphs)
+                    .filter(|glyph| (glyph.y - baseline).abs() < 1.0)
+                    .map(|glyph| glyph.x)
+                    .fold(0.0, f64::max)
+            };
+            assert!(first_line_end(Alignment::Justified) > first_line_end(Alignment::Start) + 1.0, "{}: first line must be justified", name);
+        }
+    }
+
+    #[test]
+    fn test_resolve_bidi() {
+        assert_eq!(detect_direction("123 שלום world"), Some(TextDirection::Rtl));
+        assert_eq!(detect_direction("١٢٣ hello مرحبا"), Some(TextDirection::Ltr));
+        assert_eq!(detect_direction("123"), None);
+
+        assert_eq!(resolve_bidi("hello", Direction::Rtl, UnicodeBidi::Normal), ("hello".to_string(), TextDirection::Rtl));
+        assert_eq!(resolve_bidi("مرحبا", Direction::Ltr, UnicodeBidi::Plaintext), ("مرحبا".to_string(), TextDirection::Rtl));
+        assert_eq!(resolve_bidi("abc", Direction::Rtl, UnicodeBidi::BidiOverride), ("\u{202e}abc\u{202c}".to_string(), TextDirection::Rtl));
+    }
+
+    #[test]
+    fn test_split_columns() {
+        assert_eq!(split_columns("日本語の文章", 4), vec!["日\n本\n語\nの", "文\n章"]);
+        assert_eq!(split_columns("ab\nc", usize::MAX), vec!["a\nb", "c"]);
+        assert_eq!(split_columns("e\u{301}f", 1), vec!["e\u{301}", "f"]);
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let line = |range: Range<usize>| TextLine { range, x: 0.0, width: 0.0, baseline: 0.0, height: 0.0 };
+
+        let text = "balanced text\u{ad}ing here";
+        let lines = [line(0..9), line(9..15), line(15..text.len())];
+        assert_eq!(join_lines(text, &lines), "balanced\ntext-\ning here");
+    }
+}
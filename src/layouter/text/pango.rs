@@ -1,46 +1,105 @@
-use gtk4::cairo::{Context, Error, Format, ImageSurface};
-use gtk4::pango::{FontDescription, SCALE, Layout};
-use pangocairo::functions::{context_set_resolution, create_layout};
-use pangocairo::pango::WrapMode;
-use crate::common::font::pango::{find_available_font, to_pango_weight};
+use gtk4::pango::{SCALE, Layout};
+use gtk4::prelude::FontExt;
+use crate::common::font::pango::get_pango_layout;
 use crate::common::geo::Dimension;
-use crate::layouter::text::Alignment;
-
-/// Retrieves the pango layout for the given text, font family, font size and maximum width.
-/// it will wrap any long lines based on the pixels found in width.
-pub fn get_text_layout(text: &str, font_family: &str, font_size: f64, font_weight: usize, line_height: f64, max_width: f64, alignment: Alignment) -> Result<Dimension, Error> {
-    println!("get_text_layout: text: {}, font_family: {}, font_size: {}, font_weight: {}, line_height: {}, max_width: {}, alignment: {:?}", text, font_family, font_size, font_weight, line_height, max_width, alignment);
-    let surface = ImageSurface::create(Format::ARgb32, 1, 1)?;
-    let cr = Context::new(&surface)?;
-    let layout = create_layout(&cr);
-
-    // @TODO: I need to set the DPI resolution to 72dpi, otherwise the text will be too large
-    context_set_resolution(&layout.context(), 72.0);
-
-    let selected_family = find_available_font(font_family, &layout.context());
-    let mut font_desc = FontDescription::new();
-    font_desc.set_family(&selected_family);
-    font_desc.set_size((font_size * SCALE as f64) as i32);
-    font_desc.set_weight(to_pango_weight(font_weight));
-    layout.set_font_description(Some(&font_desc));
-
-    layout.set_text(text);
-    layout.set_width((max_width * SCALE as f64) as i32);
-
-    // @TODO: This should be configurable
-    layout.set_wrap(WrapMode::Word);
-
-    layout.set_spacing(0);
-    layout.set_line_spacing(0.0);
-
-    match alignment {
-        Alignment::Left => layout.set_alignment(gtk4::pango::Alignment::Left),
-        Alignment::Center => layout.set_alignment(gtk4::pango::Alignment::Center),
-        Alignment::Right => layout.set_alignment(gtk4::pango::Alignment::Right),
+use crate::layouter::text::{Glyph, GlyphRun, TextLine, TextShaper, TextStyle};
+
+/// Text shaper that uses pango
+pub struct PangoShaper;
+
+impl PangoShaper {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn layout(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Layout> {
+        get_pango_layout(text, &style.font, style.line_height, max_width, style.alignment, style.direction)
+    }
+}
+
+impl Default for PangoShaper {
+    fn default() -> Self {
+        Self::new()
     }
+}
+
+impl TextShaper for PangoShaper {
+    fn name(&self) -> &'static str {
+        "pango"
+    }
+
+    fn measure(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Dimension> {
+        let layout = self.layout(text, style, max_width)?;
+
+        Ok(Dimension {
+            width: layout.extents().1.width() as f64 / SCALE as f64,
+            height: layout.extents().1.height() as f64 / SCALE as f64,
+        })
+    }
+
+    fn shape(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<GlyphRun>> {
+        let layout = self.layout(text, style, max_width)?;
 
-    Ok(Dimension {
-        width: layout.extents().1.width() as f64 / SCALE as f64,
-        height: layout.extents().1.height() as f64 / SCALE as f64,
-    })
-}
\ No newline at end of file
+        let mut runs = Vec::new();
+        let mut iter = layout.iter();
+        loop {
+            if let Some(run) = iter.run_readonly() {
+                let baseline = iter.baseline() as f64 / SCALE as f64;
+                let mut x = iter.run_extents().1.x() as f64 / SCALE as f64;
+
+                let glyphs = run.glyph_string().glyph_info().iter().map(|info| {
+                    let geometry = info.geometry();
+                    let glyph = Glyph {
+                        id: info.glyph(),
+                        x: x + geometry.x_offset() as f64 / SCALE as f64,
+                        y: baseline + geometry.y_offset() as f64 / SCALE as f64,
+                    };
+                    x += geometry.width() as f64 / SCALE as f64;
+                    glyph
+                }).collect();
+
+                let font_desc = run.item().analysis().font().describe();
+                runs.push(GlyphRun {
+                    font_family: font_desc.family().map(|f| f.to_string()).unwrap_or_default(),
+                    font_size: font_desc.size() as f64 / SCALE as f64,
+                    glyphs,
+                });
+            }
+
+            if !iter.next_run() {
+                break;
+            }
+        }
+
+        Ok(runs)
+    }
+
+    fn line_break(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<TextLine>> {
+        let layout = self.layout(text, style, max_width)?;
+
+        let mut lines: Vec<TextLine> = Vec::new();
+        let mut iter = layout.iter();
+        loop {
+            let extents = iter.line_extents().1;
+            lines.push(TextLine {
+                range: iter.index() as usize..text.len(),
+                x: extents.x() as f64 / SCALE as f64,
+                width: extents.width() as f64 / SCALE as f64,
+                baseline: iter.baseline() as f64 / SCALE as f64,
+                height: extents.height() as f64 / SCALE as f64,
+            });
+
+            if !iter.next_line() {
+                break;
+            }
+        }
+
+        // Pango only gives us the start index of each line, so each line ends where the next one starts
+        for i in 1..lines.len() {
+            let start = lines[i].range.start;
+            lines[i - 1].range.end = start;
+        }
+
+        Ok(lines)
+    }
+}
@@ -1,14 +1,84 @@
-use crate::layouter::text::Alignment;
-use std::fmt::Error;
+use parley::layout::PositionedLayoutItem;
 use crate::common::font::parley::get_parley_layout;
 use crate::common::geo::Dimension;
+use crate::layouter::text::{Glyph, GlyphRun, TextLine, TextShaper, TextStyle};
 
+/// Text shaper that uses parley
+pub struct ParleyShaper;
 
-pub fn get_text_layout(text: &str, font_family: &str, font_size: f64, _font_weight: usize, line_height: f64, max_width: f64, alignment: Alignment) -> Result<Dimension, Error> {
-    let layout = get_parley_layout(text, font_family, font_size, line_height, max_width, alignment);
+impl ParleyShaper {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for ParleyShaper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextShaper for ParleyShaper {
+    fn name(&self) -> &'static str {
+        "parley"
+    }
+
+    fn measure(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Dimension> {
+        let layout = get_parley_layout(text, &style.font, style.line_height, max_width, style.alignment, style.direction);
+
+        Ok(Dimension {
+            width: layout.width() as f64,
+            height: layout.height() as f64,
+        })
+    }
+
+    fn shape(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<GlyphRun>> {
+        let layout = get_parley_layout(text, &style.font, style.line_height, max_width, style.alignment, style.direction);
+
+        let mut runs = Vec::new();
+        for line in layout.lines() {
+            for item in line.items() {
+                let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+                    continue;
+                };
+
+                let mut x = glyph_run.offset();
+                let y = glyph_run.baseline();
+                let glyphs = glyph_run.glyphs().map(|glyph| {
+                    let g = Glyph {
+                        id: glyph.id as u32,
+                        x: (x + glyph.x) as f64,
+                        y: (y + glyph.y) as f64,
+                    };
+                    x += glyph.advance;
+                    g
+                }).collect();
+
+                let run = glyph_run.run();
+                runs.push(GlyphRun {
+                    // Parley does not expose the family name of the selected font, only the font data
+                    font_family: style.font.family.clone(),
+                    font_size: run.font_size() as f64,
+                    glyphs,
+                });
+            }
+        }
+
+        Ok(runs)
+    }
+
+    fn line_break(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<TextLine>> {
+        let layout = get_parley_layout(text, &style.font, style.line_height, max_width, style.alignment, style.direction);
 
-    Ok(Dimension {
-        width: layout.width() as f64,
-        height: layout.height() as f64,
-    })
+        Ok(layout.lines().map(|line| {
+            let metrics = line.metrics();
+            TextLine {
+                range: line.text_range(),
+                x: metrics.offset as f64,
+                width: metrics.advance as f64,
+                baseline: metrics.baseline as f64,
+                height: metrics.line_height as f64,
+            }
+        }).collect())
+    }
 }
@@ -1,14 +1,79 @@
-use crate::layouter::text::Alignment;
-use std::fmt::Error;
-use crate::common::font::skia::get_skia_paragraph;
+use crate::common::font::skia::{get_skia_paragraph, SharedParagraph};
 use crate::common::geo::Dimension;
+use crate::layouter::text::{Glyph, GlyphRun, TextLine, TextShaper, TextStyle};
 
+/// Text shaper that uses skia's paragraph layout
+pub struct SkiaShaper;
 
-pub fn get_text_layout(text: &str, font_family: &str, font_size: f64, _font_weight: usize, line_height: f64, max_width: f64, alignment: Alignment) -> Result<Dimension, Error> {
-    let paragraph = get_skia_paragraph(text, font_family, font_size, line_height, max_width, alignment, None);
+impl SkiaShaper {
+    pub fn new() -> Self {
+        Self {}
+    }
 
-    Ok(Dimension {
-        width: paragraph.max_width() as f64,
-        height: paragraph.height() as f64,
-    })
+    fn paragraph(&self, text: &str, style: &TextStyle, max_width: f64) -> SharedParagraph {
+        get_skia_paragraph(text, &style.font, style.line_height, max_width, style.alignment, style.direction)
+    }
+}
+
+impl Default for SkiaShaper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextShaper for SkiaShaper {
+    fn name(&self) -> &'static str {
+        "skia"
+    }
+
+    fn measure(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Dimension> {
+        let paragraph = self.paragraph(text, style, max_width);
+        let paragraph = paragraph.borrow();
+
+        Ok(Dimension {
+            width: paragraph.max_width() as f64,
+            height: paragraph.height() as f64,
+        })
+    }
+
+    fn shape(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<GlyphRun>> {
+        let paragraph = self.paragraph(text, style, max_width);
+        let mut paragraph = paragraph.borrow_mut();
+
+        let mut runs = Vec::new();
+        paragraph.visit(|_line_number, info| {
+            // A visit without info marks the end of a line
+            let Some(info) = info else {
+                return;
+            };
+
+            let origin = info.origin();
+            let glyphs = info.glyphs().iter().zip(info.positions()).map(|(id, pos)| Glyph {
+                id: *id as u32,
+                x: (origin.x + pos.x) as f64,
+                y: (origin.y + pos.y) as f64,
+            }).collect();
+
+            runs.push(GlyphRun {
+                font_family: info.font().typeface().family_name(),
+                font_size: info.font().size() as f64,
+                glyphs,
+            });
+        });
+
+        Ok(runs)
+    }
+
+    fn line_break(&self, text: &str, style: &TextStyle, max_width: f64) -> anyhow::Result<Vec<TextLine>> {
+        let paragraph = self.paragraph(text, style, max_width);
+        let paragraph = paragraph.borrow();
+
+        Ok(paragraph.get_line_metrics().iter().map(|lm| TextLine {
+            range: lm.start_index..lm.end_index,
+            x: lm.left,
+            width: lm.width,
+            baseline: lm.baseline,
+            height: lm.ascent + lm.descent,
+        }).collect())
+    }
 }
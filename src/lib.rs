@@ -3,6 +3,7 @@ pub mod rendertree_builder;
 #[allow(unused)]
 pub mod layouter;
 pub mod layering;
+pub mod display_list;
 #[allow(unused)]
 pub mod tiler;
 #[allow(unused)]
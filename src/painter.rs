@@ -5,9 +5,12 @@ use std::sync::Arc;
 use rand::Rng;
 use crate::common::browser_state::{get_browser_state, BrowserState, WireframeState};
 use crate::common::document::node::{Node, NodeType};
-use crate::common::document::style::{StyleProperty, StyleValue, Color as StyleColor};
+use crate::common::document::style::{StyleProperty, StylePropertyList, StyleValue, Color as StyleColor, TextDecorationStyle as StyleTextDecorationStyle, Unit, WritingMode, ListStyleType, PseudoElement, ObjectFit};
+use crate::common::geo::{Dimension, Rect};
 use crate::layering::layer::LayerList;
-use crate::layouter::{ElementContext, LayoutElementNode};
+use crate::layouter::replaced;
+use crate::layouter::text::Alignment;
+use crate::layouter::{ElementContext, LayoutElementId, LayoutElementNode};
 use crate::painter::commands::brush::Brush;
 use crate::painter::commands::color::Color;
 use crate::painter::commands::rectangle::{Radius, Rectangle};
@@ -15,12 +18,11 @@ use crate::painter::commands::PaintCommand;
 use crate::common::get_media_store;
 use crate::common::media::{Media, MediaType};
 use crate::painter::commands::border::{Border, BorderStyle};
-use crate::painter::commands::text::Text;
-use crate::tiler::{Tile, TiledLayoutElement};
+use crate::painter::commands::text::{Text, TextDecoration, TextDecorationStyle};
 
 /// Painter works with the layout tree and generates paint commands for the renderer. It does not
-/// generate a new data structure as output, but will update the existing layout elements with
-/// paint commands.
+/// generate a new data structure as output, but returns the paint commands for a single layout
+/// element. The display list stage uses this to paint each element exactly once.
 pub struct Painter {
     layer_list: Arc<LayerList>,
 }
@@ -32,11 +34,11 @@ impl Painter {
         }
     }
 
-    // Generate paint commands for the given tile
-    pub fn paint(&self, element: &TiledLayoutElement) -> Vec<PaintCommand> {
+    // Generate paint commands for the given layout element
+    pub fn paint(&self, element_id: LayoutElementId) -> Vec<PaintCommand> {
         let mut commands = Vec::new();
 
-        let Some(layout_element) = self.layer_list.layout_tree.get_node_by_id(element.id) else {
+        let Some(layout_element) = self.layer_list.layout_tree.get_node_by_id(element_id) else {
             return Vec::new();
         };
         let Some(dom_node) = self.layer_list.layout_tree.render_tree.doc.get_node_by_id(layout_element.dom_node_id) else {
@@ -100,6 +102,48 @@ impl Painter {
         self.get_brush(parent, css_prop, default)
     }
 
+    /// Returns the text decoration for the given (text) node. Decorations are not inherited, but they are
+    /// propagated to the descendants of the element that sets them (ie: all text inside a link is underlined),
+    /// so we walk up the tree until we find an element with decoration lines.
+    fn get_text_decoration(&self, node: &Node, text_brush: Brush) -> Option<TextDecoration> {
+        let doc = &self.layer_list.layout_tree.render_tree.doc;
+
+        let mut current = Some(node);
+        while let Some(node) = current {
+            let styles = match &node.node_type {
+                NodeType::Element(data) => Some(&data.styles),
+                NodeType::Text(_, styles) => Some(styles),
+                _ => None,
+            };
+
+            if let Some(decoration) = styles.and_then(|styles| to_text_decoration(styles, &text_brush)) {
+                return Some(decoration);
+            }
+
+            current = node.parent_id.and_then(|parent_id| doc.get_node_by_id(parent_id));
+        }
+
+        None
+    }
+
+    /// Returns where the media of an image or SVG is painted inside its content box (object-fit and object-position)
+    fn get_object_rect(&self, node: &Node, content_box: Rect, natural: Dimension) -> Rect {
+        let NodeType::Element(data) = &node.node_type else {
+            return content_box;
+        };
+
+        let fit = match data.get_style(StyleProperty::ObjectFit) {
+            Some(StyleValue::ObjectFit(fit)) => *fit,
+            _ => ObjectFit::Fill,
+        };
+        let position = match data.get_style(StyleProperty::ObjectPosition) {
+            Some(StyleValue::ObjectPosition(x, y)) => (x.clone(), y.clone()),
+            _ => ((50.0, Unit::Percent), (50.0, Unit::Percent)),
+        };
+
+        replaced::fit_rect(content_box, natural, fit, &position)
+    }
+
     /// Generates the wireframe commands for the given layout element
     fn generate_wireframe_commands(&self, layout_element: &LayoutElementNode) -> Vec<PaintCommand> {
         let mut commands = Vec::new();
@@ -130,6 +174,35 @@ impl Painter {
         commands
     }
 
+    /// Generates the paint commands for the marker of a list item. Textual markers are painted by their text
+    /// node, so only bullets are painted here.
+    fn generate_marker_commands(&self, layout_element: &LayoutElementNode, dom_node: &Node) -> Vec<PaintCommand> {
+        let mut commands = Vec::new();
+
+        let NodeType::Element(data) = &dom_node.node_type else {
+            return commands;
+        };
+        let brush = self.get_brush(dom_node, StyleProperty::Color, Brush::solid(Color::BLACK));
+
+        let r = layout_element.box_model.content_box();
+        let radius = Radius::new(r.width.min(r.height) / 2.0);
+        match data.get_style(StyleProperty::ListStyleType) {
+            Some(StyleValue::ListStyleType(ListStyleType::Circle)) => {
+                let border = Border::new(1.0, BorderStyle::Solid, brush);
+                commands.push(PaintCommand::rectangle(Rectangle::new(r).with_border(border).with_radius(radius)));
+            }
+            Some(StyleValue::ListStyleType(ListStyleType::Square)) => {
+                commands.push(PaintCommand::rectangle(Rectangle::new(r).with_background(brush)));
+            }
+            Some(StyleValue::ListStyleType(ListStyleType::Disc)) | None => {
+                commands.push(PaintCommand::rectangle(Rectangle::new(r).with_background(brush).with_radius(radius)));
+            }
+            _ => {}
+        }
+
+        commands
+    }
+
     /// Generates the paint commands for the given layout element
     fn generate_element_commands(&self, layout_element: &LayoutElementNode, dom_node: &Node) -> Vec<PaintCommand> {
         let mut commands = Vec::new();
@@ -140,17 +213,61 @@ impl Painter {
 
                 let r = layout_element.box_model.content_box().shift(ctx.text_offset);
                 let r = layout_element.box_model.content_box();
+
+                // Vertical text is painted column by column, each column holding one upright character per line
+                if ctx.writing_mode.is_vertical() {
+                    for (i, column) in ctx.columns.iter().enumerate() {
+                        let x = match ctx.writing_mode {
+                            WritingMode::VerticalLr => r.x + i as f64 * ctx.line_height,
+                            _ => r.x + r.width - (i + 1) as f64 * ctx.line_height,
+                        };
+                        let column_rect = Rect::new(x, r.y, ctx.line_height, r.height);
+                        let t = Text::new(
+                            column_rect,
+                            column,
+                            &ctx.font,
+                            ctx.font.size,
+                            brush.clone(),
+                            Alignment::Middle,
+                        );
+                        commands.push(PaintCommand::text(t));
+                    }
+                    return commands;
+                }
+
+                // Text that flows around floats is painted line by line, as each line has its own position and width
+                if !ctx.lines.is_empty() {
+                    let decoration = self.get_text_decoration(dom_node, brush.clone());
+                    for line in &ctx.lines {
+                        let line_rect = Rect::new(r.x + line.x, r.y + line.y, line.width, ctx.line_height);
+                        let mut t = Text::new(
+                            line_rect,
+                            &line.text,
+                            &ctx.font,
+                            ctx.line_height,
+                            brush.clone(),
+                            ctx.alignment,
+                        ).with_direction(ctx.direction);
+                        if let Some(decoration) = decoration.clone() {
+                            t = t.with_decoration(decoration);
+                        }
+                        commands.push(PaintCommand::text(t));
+                    }
+                    return commands;
+                }
+
                 // let brush = Brush::solid(Color::from_rgb8(130, 130, 130));
-                let t = Text::new(
+                let mut t = Text::new(
                     r,
                     &ctx.text,
-                    &ctx.font_family,
-                    ctx.font_size,
-                    ctx.font_weight,
+                    &ctx.font,
                     ctx.line_height,
-                    brush,
+                    brush.clone(),
                     ctx.alignment,
-                );
+                ).with_direction(ctx.direction);
+                if let Some(decoration) = self.get_text_decoration(dom_node, brush) {
+                    t = t.with_decoration(decoration);
+                }
                 commands.push(PaintCommand::text(t));
 
                 // let border = Border::new(1.0, BorderStyle::Solid, Brush::Solid(Color::RED));
@@ -159,18 +276,17 @@ impl Painter {
                 // commands.push(PaintCommand::rectangle(r));
             }
             ElementContext::Svg(svg_ctx) => {
-                // let binding = get_svg_store();
-                // let svg_store = binding.read().expect("Failed to get svg store");
-                // let svg = svg_store.get(svg_ctx.svg_id).unwrap();
-
-                let brush = Brush::solid(Color::from_rgb8(130, 130, 130));
-                let r = Rectangle::new(layout_element.box_model.border_box()).with_background(brush);
-                commands.push(PaintCommand::svg(svg_ctx.media_id, r));
+                let content_box = layout_element.box_model.content_box();
+                let rect = self.get_object_rect(dom_node, content_box, svg_ctx.dimension);
+                commands.push(PaintCommand::svg(svg_ctx.media_id, Rectangle::new(rect), content_box));
             }
             ElementContext::Image(image_ctx) => {
-                let brush = Brush::image(image_ctx.media_id);
-                let r = Rectangle::new(layout_element.box_model.border_box()).with_background(brush);
-                commands.push(PaintCommand::rectangle(r));
+                let content_box = layout_element.box_model.content_box();
+                let rect = self.get_object_rect(dom_node, content_box, image_ctx.dimension);
+                commands.push(PaintCommand::image(image_ctx.media_id, rect, content_box));
+            }
+            ElementContext::None if is_marker(dom_node) => {
+                commands.extend(self.generate_marker_commands(layout_element, dom_node));
             }
             ElementContext::None => {
                 // Paint a normal element. This function will most likely be much more complex as it is now, because we need to
@@ -221,6 +337,53 @@ impl Painter {
     }
 }
 
+/// Returns true when the node is the generated marker of a list item
+fn is_marker(node: &Node) -> bool {
+    matches!(&node.node_type, NodeType::Element(data) if data.tag_name == PseudoElement::Marker.tag_name())
+}
+
+/// Converts the text-decoration properties into a text decoration. Returns None when there are no decoration lines.
+fn to_text_decoration(styles: &StylePropertyList, text_brush: &Brush) -> Option<TextDecoration> {
+    let Some(StyleValue::TextDecorationLine(line)) = styles.get_property(StyleProperty::TextDecorationLine) else {
+        return None;
+    };
+    if line.is_none() {
+        return None;
+    }
+
+    let style = match styles.get_property(StyleProperty::TextDecorationStyle) {
+        Some(StyleValue::TextDecorationStyle(StyleTextDecorationStyle::Double)) => TextDecorationStyle::Double,
+        Some(StyleValue::TextDecorationStyle(StyleTextDecorationStyle::Dotted)) => TextDecorationStyle::Dotted,
+        Some(StyleValue::TextDecorationStyle(StyleTextDecorationStyle::Dashed)) => TextDecorationStyle::Dashed,
+        Some(StyleValue::TextDecorationStyle(StyleTextDecorationStyle::Wavy)) => TextDecorationStyle::Wavy,
+        _ => TextDecorationStyle::Solid,
+    };
+
+    // The decoration color defaults to the color of the element that sets the decoration (currentcolor)
+    let decoration_color = styles.get_property(StyleProperty::TextDecorationColor).filter(|value| {
+        !matches!(value, StyleValue::Color(StyleColor::Named(name)) if name.eq_ignore_ascii_case("currentcolor"))
+    });
+    let brush = match decoration_color.or(styles.get_property(StyleProperty::Color)) {
+        Some(StyleValue::Color(css_color)) => Brush::solid(convert_css_color(css_color)),
+        _ => text_brush.clone(),
+    };
+
+    // Auto and from-font use the thickness of the font
+    let thickness = match styles.get_property(StyleProperty::TextDecorationThickness) {
+        Some(StyleValue::Unit(value, Unit::Px)) => Some(*value as f64),
+        _ => None,
+    };
+
+    Some(TextDecoration {
+        underline: line.underline,
+        overline: line.overline,
+        line_through: line.line_through,
+        style,
+        brush,
+        thickness,
+    })
+}
+
 /// Converts a css style color to a paint command color
 fn convert_css_color(css_color: &StyleColor) -> Color {
     log::info!("Converting css color: {:?}", css_color);
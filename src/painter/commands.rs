@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+use crate::common::geo::Rect;
 use crate::common::media::MediaId;
 use crate::painter::commands::rectangle::Rectangle;
 use crate::painter::commands::text::Text;
@@ -10,7 +12,7 @@ pub mod rectangle;
 pub mod brush;
 
 /// Generic that defines a top, right, bottom, and left value.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Trbl<T> {
     top: T,
     right: T,
@@ -18,17 +20,30 @@ pub struct Trbl<T> {
     left: T,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PaintSvg {
+    /// Where the SVG is rendered, as fitted with object-fit and object-position
     pub rect: Rectangle,
+    /// Content box of the element. The SVG is clipped to it.
+    pub clip: Rect,
     pub media_id: MediaId,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaintImage {
+    /// Where the image is scaled to, as fitted with object-fit and object-position
+    pub rect: Rect,
+    /// Content box of the element. The image is clipped to it.
+    pub clip: Rect,
+    pub media_id: MediaId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PaintCommand {
     Text(Text),
     Rectangle(Rectangle),
     Svg(PaintSvg),
+    Image(PaintImage),
 }
 
 impl PaintCommand {
@@ -36,9 +51,18 @@ impl PaintCommand {
         PaintCommand::Text(text)
     }
 
-    pub fn svg(media_id: MediaId, rect: Rectangle) -> Self {
+    pub fn svg(media_id: MediaId, rect: Rectangle, clip: Rect) -> Self {
         PaintCommand::Svg(PaintSvg{
             rect,
+            clip,
+            media_id,
+        })
+    }
+
+    pub fn image(media_id: MediaId, rect: Rect, clip: Rect) -> Self {
+        PaintCommand::Image(PaintImage {
+            rect,
+            clip,
             media_id,
         })
     }
@@ -46,4 +70,43 @@ impl PaintCommand {
     pub fn rectangle(rectangle: Rectangle) -> Self {
         PaintCommand::Rectangle(rectangle)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::font::FontDescriptor;
+    use crate::layouter::text::Alignment;
+    use crate::painter::commands::border::{Border, BorderStyle};
+    use crate::painter::commands::brush::Brush;
+    use crate::painter::commands::color::Color;
+    use crate::painter::commands::rectangle::Radius;
+
+    #[test]
+    fn test_paint_command_roundtrip() {
+        let rect = Rectangle::new(Rect::new(10.0, 20.0, 100.0, 50.0))
+            .with_background(Brush::solid(Color::RED))
+            .with_border(Border::new(2.0, BorderStyle::Dashed, Brush::solid(Color::BLACK)))
+            .with_radius(Radius::new(4.0));
+        let text = Text::new(
+            Rect::new(0.0, 0.0, 200.0, 16.0),
+            "hello world",
+            &FontDescriptor::new("Sans", 16.0),
+            16.0,
+            Brush::solid(Color::BLUE),
+            Alignment::Start,
+        );
+
+        let commands = vec![
+            PaintCommand::rectangle(rect),
+            PaintCommand::text(text),
+            PaintCommand::svg(MediaId::new(100), Rectangle::new(Rect::ZERO), Rect::ZERO),
+            PaintCommand::image(MediaId::new(101), Rect::new(-10.0, 0.0, 120.0, 60.0), Rect::new(0.0, 0.0, 100.0, 60.0)),
+        ];
+
+        let json = serde_json::to_string(&commands).unwrap();
+        let decoded: Vec<PaintCommand> = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&decoded).unwrap());
+        assert_eq!(decoded.len(), 4);
+    }
+}
@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use crate::painter::commands::brush::Brush;
 use crate::painter::commands::Trbl;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum BorderStyle {
     Solid,
     Dashed,
@@ -21,7 +22,7 @@ impl Default for BorderStyle {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum BorderRadius {
     Uniform(f32),
     Elliptical {
@@ -30,7 +31,7 @@ pub enum BorderRadius {
     },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Border {
     width: f32,
     style: BorderStyle,
@@ -1,8 +1,9 @@
+use serde::{Deserialize, Serialize};
 use crate::common::media::MediaId;
 use crate::painter::commands::color::Color;
 use crate::painter::commands::image::Image;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Brush {
     /// Paint with fixed solid color
     Solid(Color),
@@ -1,8 +1,9 @@
 use csscolorparser::Color as ccpColor;
+use serde::{Deserialize, Serialize};
 
 /// Our colors are internally f32 (0.0 to 1.0) but we can convert them to u8 (0 to 255) with r8, g8, b8, a8
 /// It also allows creating colors by css name
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Color {
     r: f32,
     g: f32,
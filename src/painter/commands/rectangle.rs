@@ -1,8 +1,9 @@
+use serde::{Deserialize, Serialize};
 use crate::common::geo::Rect;
 use crate::painter::commands::border::Border;
 use crate::painter::commands::brush::Brush;
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, Serialize, Deserialize)]
 pub struct Radius {
     pub x: f64,
     pub y: f64,
@@ -30,7 +31,7 @@ impl Radius {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Rectangle {
     rect: Rect,
     background: Option<Brush>,
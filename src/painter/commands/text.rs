@@ -1,17 +1,15 @@
-use crate::common::geo::Rect;
-use crate::layouter::text::Alignment;
+use serde::{Deserialize, Serialize};
+use crate::common::font::FontDescriptor;
+use crate::common::geo::{Coordinate, Rect};
+use crate::layouter::text::{Alignment, TextDirection};
 use crate::painter::commands::brush::Brush;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Text {
     /// The rectangle in which the text should be drawn
     pub rect: Rect,
-    /// Font family (can be multiple fonts separated by ,)
-    pub font_family: String,
-    /// Size of font in pixels
-    pub font_size: f64,
-    /// Weight of the font 100-700
-    pub font_weight: usize,
+    /// Font to draw the text with
+    pub font: FontDescriptor,
     /// Height of each line (line-spacing)
     pub line_height: f64,
     /// Actual text
@@ -19,21 +17,212 @@ pub struct Text {
     /// Brush to paint the text with
     pub brush: Brush,
     /// Text alignment
-    pub alignment: Alignment
+    pub alignment: Alignment,
+    /// Base direction of the text
+    #[serde(default)]
+    pub direction: TextDirection,
+    /// Decoration lines (underline, overline, line-through) of the text
+    #[serde(default)]
+    pub decoration: Option<TextDecoration>,
 }
 
 impl Text {
-    pub fn new(rect: Rect, text: &str, font_family: &str, font_size: f64, font_weight: usize, line_height: f64, brush: Brush, alignment: Alignment) -> Self {
+    pub fn new(rect: Rect, text: &str, font: &FontDescriptor, line_height: f64, brush: Brush, alignment: Alignment) -> Self {
         Text {
             rect,
-            font_family: font_family.to_string(),
-            font_size,
-            font_weight,
+            font: font.clone(),
             line_height,
             text: text.to_string(),
             brush,
             alignment,
+            direction: TextDirection::Ltr,
+            decoration: None,
         }
     }
+
+    pub fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_decoration(mut self, decoration: TextDecoration) -> Self {
+        self.decoration = Some(decoration);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TextDecorationStyle {
+    Solid,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
+/// Decoration lines that are drawn on top of the text
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextDecoration {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+    /// Style of the decoration lines
+    pub style: TextDecorationStyle,
+    /// Brush to paint the decoration lines with
+    pub brush: Brush,
+    /// Thickness of the lines in pixels. When not set, the thickness found in the font is used
+    pub thickness: Option<f64>,
+}
+
+/// Font metrics that are needed to position the decoration lines. All values are in pixels, and the offsets
+/// point to the top of the stroke relative to the baseline (positive is below the baseline).
+#[derive(Clone, Copy, Debug)]
+pub struct DecorationMetrics {
+    pub underline_offset: f64,
+    pub underline_thickness: f64,
+    pub strikeout_offset: f64,
+    pub strikeout_thickness: f64,
+    /// Ascent of the font (positive is above the baseline)
+    pub ascent: f64,
+}
+
+impl DecorationMetrics {
+    /// Approximated metrics for fonts that do not provide any underline or strikeout information
+    pub fn from_font_size(font_size: f64) -> Self {
+        let thickness = (font_size / 14.0).max(1.0);
+
+        Self {
+            underline_offset: font_size * 0.1,
+            underline_thickness: thickness,
+            strikeout_offset: -font_size * 0.3,
+            strikeout_thickness: thickness,
+            ascent: font_size * 0.8,
+        }
+    }
+}
+
+/// A single decoration line that must be drawn by the rasterizer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecorationLine {
+    /// Start of the line
+    pub x: f64,
+    /// Vertical center of the (first) stroke
+    pub y: f64,
+    pub width: f64,
+    pub thickness: f64,
+    pub style: TextDecorationStyle,
 }
 
+impl TextDecoration {
+    /// Returns the decoration lines for a single line (or glyph run) of text that starts at x and has the
+    /// given width and baseline.
+    pub fn lines(&self, x: f64, width: f64, baseline: f64, metrics: &DecorationMetrics) -> Vec<DecorationLine> {
+        let mut lines = Vec::new();
+        if width <= 0.0 {
+            return lines;
+        }
+
+        let mut push = |top: f64, thickness: f64| {
+            lines.push(DecorationLine {
+                x,
+                y: top + thickness / 2.0,
+                width,
+                thickness,
+                style: self.style,
+            });
+        };
+
+        if self.underline {
+            let thickness = self.thickness.unwrap_or(metrics.underline_thickness);
+            push(baseline + metrics.underline_offset, thickness);
+        }
+        if self.overline {
+            let thickness = self.thickness.unwrap_or(metrics.underline_thickness);
+            push(baseline - metrics.ascent, thickness);
+        }
+        if self.line_through {
+            let thickness = self.thickness.unwrap_or(metrics.strikeout_thickness);
+            push(baseline + metrics.strikeout_offset, thickness);
+        }
+
+        lines
+    }
+}
+
+impl DecorationLine {
+    /// Returns the vertical centers of the strokes. A double line has a second stroke below the first one.
+    pub fn strokes(&self) -> Vec<f64> {
+        match self.style {
+            TextDecorationStyle::Double => vec![self.y, self.y + self.thickness * 2.0],
+            _ => vec![self.y],
+        }
+    }
+
+    /// Returns the (on, off) dash pattern for dotted and dashed lines
+    pub fn dash_pattern(&self) -> Option<(f64, f64)> {
+        match self.style {
+            TextDecorationStyle::Dotted => Some((self.thickness, self.thickness)),
+            TextDecorationStyle::Dashed => Some((self.thickness * 3.0, self.thickness * 3.0)),
+            _ => None,
+        }
+    }
+
+    /// Returns the quadratic curve segments (control point, end point) of a wavy line. The wave starts at
+    /// (x, y) and is cut off at the end of the line.
+    pub fn wave_segments(&self) -> Vec<(Coordinate, Coordinate)> {
+        let half_wavelength = (self.thickness * 3.0).max(2.0);
+        let amplitude = (self.thickness * 1.5).max(1.0);
+
+        let mut segments = Vec::new();
+        let mut x = self.x;
+        let mut up = true;
+        while x < self.x + self.width {
+            let end_x = (x + half_wavelength).min(self.x + self.width);
+            let control_y = if up { self.y - amplitude } else { self.y + amplitude };
+            segments.push((
+                Coordinate::new((x + end_x) / 2.0, control_y),
+                Coordinate::new(end_x, self.y),
+            ));
+
+            x = end_x;
+            up = !up;
+        }
+
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::painter::commands::color::Color;
+
+    #[test]
+    fn test_decoration_lines() {
+        let metrics = DecorationMetrics {
+            underline_offset: 2.0,
+            underline_thickness: 1.0,
+            strikeout_offset: -5.0,
+            strikeout_thickness: 2.0,
+            ascent: 12.0,
+        };
+
+        let decoration = TextDecoration {
+            underline: true,
+            overline: true,
+            line_through: true,
+            style: TextDecorationStyle::Solid,
+            brush: Brush::solid(Color::BLACK),
+            thickness: None,
+        };
+
+        let lines = decoration.lines(10.0, 100.0, 20.0, &metrics);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].y, 22.5);
+        assert_eq!(lines[1].y, 8.5);
+        assert_eq!(lines[2].y, 16.0);
+        assert_eq!(lines[2].thickness, 2.0);
+
+        assert!(decoration.lines(10.0, 0.0, 20.0, &metrics).is_empty());
+    }
+}
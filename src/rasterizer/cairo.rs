@@ -1,4 +1,6 @@
 use gtk4::cairo;
+use crate::painter::commands::brush::Brush;
+use crate::painter::commands::rectangle::Rectangle;
 use crate::painter::commands::PaintCommand;
 use crate::rasterizer::Rasterable;
 use crate::common::texture::TextureId;
@@ -37,6 +39,15 @@ impl Rasterable for CairoRasterizer {
                         PaintCommand::Rectangle(command) => {
                             rectangle::do_paint_rectangle(&cr.clone(), &tile, &command);
                         }
+                        PaintCommand::Image(command) => {
+                            // The image brush scales the image to the rectangle, which can be larger than the content box
+                            _ = cr.save();
+                            cr.rectangle(command.clip.x - tile.rect.x, command.clip.y - tile.rect.y, command.clip.width, command.clip.height);
+                            cr.clip();
+                            let rect = Rectangle::new(command.rect).with_background(Brush::image(command.media_id));
+                            rectangle::do_paint_rectangle(&cr.clone(), &tile, &rect);
+                            _ = cr.restore();
+                        }
                         PaintCommand::Text(command) => {
                             match do_paint_text(&cr.clone(), &tile, &command) {
                                 Ok(_) => {}
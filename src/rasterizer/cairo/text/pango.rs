@@ -1,11 +1,9 @@
 use gtk4::cairo::{Context, Error, Format, ImageSurface};
-use gtk4::pango::SCALE;
-use pangocairo::functions::{context_set_resolution, create_layout};
-use pangocairo::pango::FontDescription;
-use crate::painter::commands::text::Text;
+use gtk4::pango::{Layout, SCALE};
+use crate::painter::commands::text::{DecorationLine, DecorationMetrics, Text, TextDecoration, TextDecorationStyle};
 use crate::rasterizer::cairo::brush::set_brush;
 use crate::tiler::Tile;
-use crate::common::font::pango::{find_available_font, to_pango_weight};
+use crate::common::font::pango::get_pango_layout;
 
 pub(crate) fn do_paint_text(cr: &Context, tile: &Tile, cmd: &Text) -> Result<(), Error> {
     let surface = create_text_layout(cmd)?;
@@ -30,29 +28,87 @@ pub(crate) fn do_paint_text(cr: &Context, tile: &Tile, cmd: &Text) -> Result<(),
 fn create_text_layout(cmd: &Text) -> Result<ImageSurface, Error> {
     let surface = ImageSurface::create(Format::ARgb32, cmd.rect.width as i32, cmd.rect.height as i32)?;
     let cr = Context::new(&surface)?;
-    let layout = create_layout(&cr);
 
-    // @TODO: I need to set the DPI resolution to 72dpi, otherwise the text will be too large
-    context_set_resolution(&layout.context(), 72.0);
-
-    let selected_family = find_available_font(cmd.font_family.as_str(), &layout.context());
-    let mut font_desc = FontDescription::new();
-    font_desc.set_family(&selected_family);
-    font_desc.set_size((cmd.font_size * SCALE as f64) as i32);
-    font_desc.set_weight(to_pango_weight(cmd.font_weight));
-    layout.set_font_description(Some(&font_desc));
-
-    layout.set_text(cmd.text.as_str());
-    layout.set_width((cmd.rect.width * SCALE as f64) as i32);
-    layout.set_wrap(gtk4::pango::WrapMode::Word);
-
-    layout.set_spacing(0);
-    layout.set_line_spacing(0.0);
+    // The layout is shared with the layouter (through the cache), so it must not be changed here
+    let layout = match get_pango_layout(cmd.text.as_str(), &cmd.font, cmd.line_height, cmd.rect.width, cmd.alignment, cmd.direction) {
+        Ok(layout) => layout,
+        Err(e) => {
+            println!("Failed to create text layout: {:?}", e);
+            return Err(Error::InvalidFormat);
+        }
+    };
 
     set_brush(&cr, &cmd.brush, cmd.rect);
     cr.move_to(0.0, 0.0);
     pangocairo::functions::show_layout(&cr, &layout);
 
+    if let Some(decoration) = &cmd.decoration {
+        set_brush(&cr, &decoration.brush, cmd.rect);
+        for line in layout_decoration_lines(&layout, decoration) {
+            draw_decoration_line(&cr, &line)?;
+        }
+    }
+
     Ok(surface)
 }
 
+/// Returns the decoration lines for each line in the layout, based on the metrics of the layout's font
+fn layout_decoration_lines(layout: &Layout, decoration: &TextDecoration) -> Vec<DecorationLine> {
+    let scale = SCALE as f64;
+    let font_metrics = layout.context().metrics(layout.font_description().as_ref(), None);
+
+    // Pango positions are the distance of the top of the stroke above the baseline
+    let metrics = DecorationMetrics {
+        underline_offset: -font_metrics.underline_position() as f64 / scale,
+        underline_thickness: font_metrics.underline_thickness() as f64 / scale,
+        strikeout_offset: -font_metrics.strikethrough_position() as f64 / scale,
+        strikeout_thickness: font_metrics.strikethrough_thickness() as f64 / scale,
+        ascent: font_metrics.ascent() as f64 / scale,
+    };
+
+    let mut lines = Vec::new();
+    let mut iter = layout.iter();
+    loop {
+        let (_, logical) = iter.line_extents();
+        let baseline = iter.baseline() as f64 / scale;
+        lines.extend(decoration.lines(logical.x() as f64 / scale, logical.width() as f64 / scale, baseline, &metrics));
+
+        if !iter.next_line() {
+            break;
+        }
+    }
+
+    lines
+}
+
+/// Draws a single decoration line with the current source of the context
+fn draw_decoration_line(cr: &Context, line: &DecorationLine) -> Result<(), Error> {
+    cr.save()?;
+    cr.set_line_width(line.thickness);
+    if let Some((on, off)) = line.dash_pattern() {
+        cr.set_dash(&[on, off], 0.0);
+    }
+
+    if line.style == TextDecorationStyle::Wavy {
+        // Cairo has no quadratic curves, so they are converted into cubic curves
+        let (mut x, mut y) = (line.x, line.y);
+        cr.move_to(x, y);
+        for (control, end) in line.wave_segments() {
+            cr.curve_to(
+                x + 2.0 / 3.0 * (control.x - x), y + 2.0 / 3.0 * (control.y - y),
+                end.x + 2.0 / 3.0 * (control.x - end.x), end.y + 2.0 / 3.0 * (control.y - end.y),
+                end.x, end.y,
+            );
+            (x, y) = (end.x, end.y);
+        }
+        cr.stroke()?;
+    } else {
+        for y in line.strokes() {
+            cr.move_to(line.x, y);
+            cr.line_to(line.x + line.width, y);
+            cr.stroke()?;
+        }
+    }
+
+    cr.restore()
+}
@@ -1,3 +1,6 @@
+use crate::common::geo::Rect;
+use crate::painter::commands::brush::Brush;
+use crate::painter::commands::rectangle::Rectangle;
 use crate::painter::commands::PaintCommand;
 use crate::rasterizer::Rasterable;
 use crate::common::texture::TextureId;
@@ -49,7 +52,18 @@ impl Rasterable for SkiaRasterizer {
                         }
                     }
                     PaintCommand::Svg(command) => {
+                        canvas.save();
+                        canvas.clip_rect(to_skia_rect(command.clip), None, None);
                         svg::do_paint_svg(canvas, &tile, command.media_id, &command.rect);
+                        canvas.restore();
+                    }
+                    PaintCommand::Image(command) => {
+                        // The image brush scales the image to the rectangle, which can be larger than the content box
+                        canvas.save();
+                        canvas.clip_rect(to_skia_rect(command.clip), None, None);
+                        let rect = Rectangle::new(command.rect).with_background(Brush::image(command.media_id));
+                        rectangle::do_paint_rectangle(canvas, &tile, &rect);
+                        canvas.restore();
                     }
                 }
             }
@@ -64,4 +78,8 @@ impl Rasterable for SkiaRasterizer {
 
         texture_id
     }
-}
\ No newline at end of file
+}
+
+fn to_skia_rect(rect: Rect) -> skia_safe::Rect {
+    skia_safe::Rect::from_xywh(rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32)
+}
@@ -1,3 +1,4 @@
+use crate::common::geo::Dimension;
 use crate::common::get_media_store;
 use crate::common::media::MediaId;
 use crate::painter::commands::rectangle::Rectangle;
@@ -30,16 +31,25 @@ pub(crate) fn do_paint_svg(
     // With "normal" images, we would just scale the image, but since SVG is vector-based, we want to re-render it from
     // the source. It might be better to either render each dimension into a separate media, or store only an X amount of
     // different dimensions. This is a trade-off between memory and CPU usage.
-    if  media_dimension != rect.rect().dimension() {
-        let pixmap_size = media.svg.tree.size().to_int_size();
+    let dimension = Dimension::new(rect.rect().width.round(), rect.rect().height.round());
+    if dimension.width < 1.0 || dimension.height < 1.0 {
+        return;
+    }
+    if media_dimension != dimension {
+        // The SVG is scaled to the rectangle, which is fitted to the content box by the painter
+        let tree_size = media.svg.tree.size();
+        let transform = Transform::from_scale(
+            dimension.width as f32 / tree_size.width(),
+            dimension.height as f32 / tree_size.height(),
+        );
         let mut pixmap =
-            resvg::tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height()).unwrap();
-        resvg::render(&media.svg.tree, Transform::default(), &mut pixmap.as_mut());
+            resvg::tiny_skia::Pixmap::new(dimension.width as u32, dimension.height as u32).unwrap();
+        resvg::render(&media.svg.tree, transform, &mut pixmap.as_mut());
 
         let mut var = media.svg.rendered_data.write().unwrap();
         *var = pixmap.data().to_vec();
         let mut var = media.svg.rendered_dimension.write().unwrap();
-        *var = rect.rect().dimension();
+        *var = dimension;
     }
 
     // At this point, we have the SVG rendered to raw image data. We can now render that data onto an image.
@@ -1,22 +1,47 @@
 use std::fmt::Error;
+use skia_safe::canvas::SaveLayerRec;
+use skia_safe::BlendMode;
 use crate::painter::commands::text::Text;
-use crate::common::font::skia::get_skia_paragraph;
+use crate::common::font::skia::{draw_decoration_line, get_skia_paragraph, paragraph_decoration_lines};
 use crate::rasterizer::skia::paint::create_paint;
 use crate::tiler::Tile;
 
 pub fn do_paint_text(canvas: &skia_safe::Canvas, _tile: &Tile, cmd: &Text) -> Result<(), Error> {
-    let skia_paint = create_paint(&cmd.brush);
     let paragraph = get_skia_paragraph(
         cmd.text.as_str(),
-        cmd.font_family.as_str(),
-        cmd.font_size,
+        &cmd.font,
         cmd.line_height,
         cmd.rect.width,
         cmd.alignment,
-        Some(skia_paint.paint()),
+        cmd.direction,
     );
+    let paragraph = paragraph.borrow();
+
+    // The paragraph is shared with the layouter, so it does not know about our brush. We paint the glyphs
+    // into a separate layer, and fill that layer with the brush only where the glyphs are.
+    let bounds = skia_safe::Rect::from_xywh(
+        cmd.rect.x as f32,
+        cmd.rect.y as f32,
+        cmd.rect.width as f32,
+        paragraph.height().max(cmd.rect.height as f32),
+    );
+    canvas.save_layer(&SaveLayerRec::default().bounds(&bounds));
 
     paragraph.paint(canvas, (cmd.rect.x as f32, cmd.rect.y as f32));
 
+    let mut skia_paint = create_paint(&cmd.brush);
+    skia_paint.paint_mut().set_blend_mode(BlendMode::SrcIn);
+    canvas.draw_rect(bounds, skia_paint.paint());
+
+    canvas.restore();
+
+    // Decorations can have a different color than the text, so they are painted with their own brush
+    if let Some(decoration) = &cmd.decoration {
+        let paint = create_paint(&decoration.brush);
+        for line in paragraph_decoration_lines(&paragraph, decoration, (cmd.rect.x, cmd.rect.y)) {
+            draw_decoration_line(canvas, &line, paint.paint());
+        }
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
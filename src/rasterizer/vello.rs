@@ -1,5 +1,7 @@
 use crate::rasterizer::vello::text::do_paint_text;
 use std::cell::RefCell;
+use crate::painter::commands::brush::Brush;
+use crate::painter::commands::rectangle::Rectangle;
 use crate::painter::commands::PaintCommand;
 use vello::peniko::{Color, Mix};
 use vello::{AaConfig, Renderer, Scene};
@@ -55,7 +57,16 @@ impl Rasterable for VelloRasterizer<'_> {
             for command in &element.paint_commands {
                 match command {
                     PaintCommand::Svg(command) => {
+                        scene.push_layer(Mix::Clip, 1.0, affine, &to_kurbo_rect(command.clip));
                         svg::do_paint_svg(&mut scene, command.media_id, &command.rect, affine);
+                        scene.pop_layer();
+                    }
+                    PaintCommand::Image(command) => {
+                        // The image brush scales the image to the rectangle, which can be larger than the content box
+                        scene.push_layer(Mix::Clip, 1.0, affine, &to_kurbo_rect(command.clip));
+                        let rect = Rectangle::new(command.rect).with_background(Brush::image(command.media_id));
+                        rectangle::do_paint_rectangle(&mut scene, &rect, affine);
+                        scene.pop_layer();
                     }
                     PaintCommand::Rectangle(command) => {
                         rectangle::do_paint_rectangle(&mut scene, &command, affine);
@@ -101,6 +112,10 @@ impl Rasterable for VelloRasterizer<'_> {
     }
 }
 
+fn to_kurbo_rect(rect: crate::common::geo::Rect) -> Rect {
+    Rect::new(rect.x, rect.y, rect.x + rect.width, rect.y + rect.height)
+}
+
 fn create_offscreen_texture(device: &Device, width: u32, height: u32) -> Texture {
     device.create_texture(&TextureDescriptor {
         label: Some("Tile texture"),
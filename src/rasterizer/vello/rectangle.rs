@@ -1,6 +1,6 @@
 use vello::kurbo;
 use vello::kurbo::{Affine, PathEl, Point, Rect, RoundedRect, Shape};
-use vello::peniko::{Fill};
+use vello::peniko::{Brush as VelloBrush, Fill};
 use crate::painter::commands::border::BorderStyle;
 use crate::painter::commands::rectangle::Rectangle;
 use crate::rasterizer::vello::brush::set_brush;
@@ -12,11 +12,20 @@ pub(crate) fn do_paint_rectangle(scene: &mut vello::Scene, rect: &Rectangle, aff
             let vello_rect = setup_rectangle_path(rect);
             let vello_brush = set_brush(brush, rect.rect());
 
+            // Images are scaled to the rectangle
+            let brush_transform = match &vello_brush {
+                VelloBrush::Image(image) if image.width > 0 && image.height > 0 => Some(
+                    Affine::translate((rect.rect().x, rect.rect().y))
+                        * Affine::scale_non_uniform(rect.rect().width / image.width as f64, rect.rect().height / image.height as f64),
+                ),
+                _ => None,
+            };
+
             scene.fill(
                 Fill::NonZero,
                 affine,
                 &vello_brush,
-                None,
+                brush_transform,
                 &vello_rect,
             );
         }
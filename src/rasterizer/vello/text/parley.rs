@@ -4,19 +4,23 @@ use crate::painter::commands::text::Text;
 use crate::tiler::Tile;
 use crate::common::font::parley::get_parley_layout;
 use parley::layout::{GlyphRun, PositionedLayoutItem};
-use vello::kurbo::Affine;
+use vello::kurbo::{Affine, BezPath, Line, Stroke};
 use vello::peniko::Fill;
 use crate::common::geo::{Dimension, Rect};
 use crate::painter::commands::brush::Brush;
+use crate::painter::commands::text::{DecorationLine, DecorationMetrics, TextDecoration, TextDecorationStyle};
 use crate::rasterizer::vello::brush::set_brush;
 
 pub fn do_paint_text(scene: &mut Scene,  cmd: &Text, _tile_size: Dimension, affine: Affine) -> Result<(), Error> {
-    let layout = get_parley_layout(cmd.text.as_str(), cmd.font_family.as_str(), cmd.font_size, cmd.line_height, cmd.rect.width, cmd.alignment);
+    let layout = get_parley_layout(cmd.text.as_str(), &cmd.font, cmd.line_height, cmd.rect.width, cmd.alignment, cmd.direction);
 
     for line in layout.lines() {
         for item in line.items() {
             match item {
                 PositionedLayoutItem::GlyphRun(glyph_run) => {
+                    if let Some(decoration) = &cmd.decoration {
+                        render_decoration(scene, &glyph_run, decoration, &cmd.rect, affine);
+                    }
                     render_glyph_run(scene, glyph_run, &cmd.brush, &cmd.rect, affine);
                 }
                 PositionedLayoutItem::InlineBox(_inline_box) => {
@@ -32,8 +36,6 @@ pub fn do_paint_text(scene: &mut Scene,  cmd: &Text, _tile_size: Dimension, affi
 fn render_glyph_run(scene: &mut Scene, glyph_run: GlyphRun<[u8;4]>, brush: &Brush, rect: &Rect, affine: Affine) {
     let vello_brush = set_brush(brush, *rect);
 
-    // @TODO: we need font decorations like underline, strike through, maybe sub sup?
-
     let mut x = glyph_run.offset() + rect.x as f32;
     let y = glyph_run.baseline() + rect.y as f32;
     let run = glyph_run.run();
@@ -65,6 +67,45 @@ fn render_glyph_run(scene: &mut Scene, glyph_run: GlyphRun<[u8;4]>, brush: &Brus
                 }
             })
         );
+}
 
-        // @TODO: Do strike through
+/// Renders the decoration lines of a single glyph run, based on the metrics of the font in the run
+fn render_decoration(scene: &mut Scene, glyph_run: &GlyphRun<[u8;4]>, decoration: &TextDecoration, rect: &Rect, affine: Affine) {
+    // Parley offsets are positive above the baseline, ours are positive below the baseline
+    let run_metrics = glyph_run.run().metrics();
+    let metrics = DecorationMetrics {
+        underline_offset: -run_metrics.underline_offset as f64,
+        underline_thickness: run_metrics.underline_size as f64,
+        strikeout_offset: -run_metrics.strikethrough_offset as f64,
+        strikeout_thickness: run_metrics.strikethrough_size as f64,
+        ascent: run_metrics.ascent as f64,
+    };
+
+    let x = rect.x + glyph_run.offset() as f64;
+    let baseline = rect.y + glyph_run.baseline() as f64;
+    for line in decoration.lines(x, glyph_run.advance() as f64, baseline, &metrics) {
+        render_decoration_line(scene, &line, &decoration.brush, rect, affine);
+    }
+}
+
+fn render_decoration_line(scene: &mut Scene, line: &DecorationLine, brush: &Brush, rect: &Rect, affine: Affine) {
+    let vello_brush = set_brush(brush, *rect);
+    let mut stroke = Stroke::new(line.thickness);
+    if let Some((on, off)) = line.dash_pattern() {
+        stroke = stroke.with_dashes(0.0, [on, off]);
+    }
+
+    if line.style == TextDecorationStyle::Wavy {
+        let mut path = BezPath::new();
+        path.move_to((line.x, line.y));
+        for (control, end) in line.wave_segments() {
+            path.quad_to((control.x, control.y), (end.x, end.y));
+        }
+        scene.stroke(&stroke, affine, &vello_brush, None, &path);
+        return;
+    }
+
+    for y in line.strokes() {
+        scene.stroke(&stroke, affine, &vello_brush, None, &Line::new((line.x, y), (line.x + line.width, y)));
+    }
 }
@@ -4,11 +4,13 @@ use vello::kurbo::Affine;
 use vello::peniko::Blob;
 use vello::Scene;
 use crate::painter::commands::text::Text;
-use crate::common::font::skia::get_skia_paragraph;
+use crate::common::font::skia::{draw_decoration_line, get_skia_paragraph, paragraph_decoration_lines};
 use crate::common::geo::Dimension;
+use crate::painter::commands::brush::Brush;
 
 pub fn do_paint_text(scene: &mut Scene, cmd: &Text, tile_size: Dimension, affine: Affine) -> Result<(), Error> {
-    let paragraph = get_skia_paragraph(cmd.text.as_str(), cmd.font_family.as_str(), cmd.font_size, cmd.line_height, cmd.rect.width, cmd.alignment, None);
+    let paragraph = get_skia_paragraph(cmd.text.as_str(), &cmd.font, cmd.line_height, cmd.rect.width, cmd.alignment, cmd.direction);
+    let paragraph = paragraph.borrow();
 
     // Create a (skia) surface to render onto
     // @TODO: THIS IS CPU, NOT GPU!
@@ -24,6 +26,18 @@ pub fn do_paint_text(scene: &mut Scene, cmd: &Text, tile_size: Dimension, affine
     // paragraph.paint(&mut canvas, (-(transform.x - cmd.rect.x) as f32, -(transform.y - cmd.rect.y) as f32));
     paragraph.paint(&mut canvas, (cmd.rect.x as f32, cmd.rect.y as f32));
 
+    if let Some(decoration) = &cmd.decoration {
+        // The surface is read back as RGBA, while skia stores it as BGRA, so we swap the channels here
+        let color = match &decoration.brush {
+            Brush::Solid(color) => skia_safe::Color4f::new(color.b(), color.g(), color.r(), color.a()),
+            Brush::Image(_) => skia_safe::Color4f::new(0.0, 0.0, 0.0, 1.0),
+        };
+        let paint = skia_safe::Paint::new(color, None);
+        for line in paragraph_decoration_lines(&paragraph, decoration, (cmd.rect.x, cmd.rect.y)) {
+            draw_decoration_line(canvas, &line, &paint);
+        }
+    }
+
     // let img = surface.image_snapshot();
     // let data = img.encode_to_data(skia_safe::EncodedImageFormat::PNG).unwrap();
     // let b = data.as_bytes();
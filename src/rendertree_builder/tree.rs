@@ -2,8 +2,8 @@ use std::collections::HashMap;
 use std::ops::AddAssign;
 use std::sync::Arc;
 use crate::common::document::document::Document;
-use crate::common::document::node::{Node, NodeType, NodeId};
-use crate::common::document::style::{StyleProperty, StyleValue, Display as CssDisplay};
+use crate::common::document::node::{AttrMap, ElementData, Node, NodeType, NodeId};
+use crate::common::document::style::{StyleProperty, StylePropertyList, StyleValue, Display as CssDisplay, ContentItem, ListStylePosition, ListStyleType, PseudoElement, Unit, WhiteSpace, Direction, Float};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RenderNodeId(u64);
@@ -18,12 +18,6 @@ impl RenderNodeId {
     }
 }
 
-impl From<NodeId> for RenderNodeId {
-    fn from(node_id: NodeId) -> Self {
-        Self(node_id.to_u64())
-    }
-}
-
 impl AddAssign<i32> for RenderNodeId {
     fn add_assign(&mut self, rhs: i32) {
         self.0 += rhs as u64;
@@ -40,17 +34,22 @@ impl std::fmt::Display for RenderNodeId {
 #[derive(Clone)]
 pub struct RenderNode {
     pub node_id: RenderNodeId,
+    /// Node in the document that this render node is generated from. For generated content (like ::before and
+    /// list markers) this is an anonymous node that only exists in the document of the render tree.
+    pub dom_node_id: NodeId,
     pub children: Vec<RenderNodeId>,
 }
 
 
 /// A RenderTree holds both the DOM and the render tree. This tree holds all the visible nodes in
-/// the DOM.
+/// the DOM, and the boxes that are generated by CSS (pseudo-elements). The nodes of generated boxes are
+/// added to a copy of the document, so the original document is never modified.
 #[derive(Clone)]
 pub struct RenderTree {
     pub doc: Arc<Document>,
     pub arena: HashMap<RenderNodeId, RenderNode>,
     pub root_id: Option<RenderNodeId>,
+    next_node_id: RenderNodeId,
 }
 
 impl std::fmt::Debug for RenderTree {
@@ -84,7 +83,7 @@ impl RenderTree {
         };
 
         let indent = " ".repeat(level * 4);
-        println!("{}{}", indent, node.node_id);
+        println!("{}{} ({})", indent, node.node_id, node.dom_node_id);
         for child_id in &node.children {
             self.print_node(*child_id, level + 1);
         }
@@ -93,12 +92,106 @@ impl RenderTree {
 
 const INVISIBLE_ELEMENTS: [&str; 6] = [ "head",  "style",  "script",  "meta",  "link",  "title" ];
 
+/// Elements that reset the list-item counter for their list items
+const LIST_ELEMENTS: [&str; 3] = [ "ol", "ul", "menu" ];
+
+/// Name of the counter that numbers the list items
+const LIST_ITEM_COUNTER: &str = "list-item";
+
+/// Inherited properties that are copied to anonymous boxes and markers
+const INHERITED_PROPERTIES: [StyleProperty; 19] = [
+    StyleProperty::Color,
+    StyleProperty::FontSize,
+    StyleProperty::FontFamily,
+    StyleProperty::FontWeight,
+    StyleProperty::FontStyle,
+    StyleProperty::FontStretch,
+    StyleProperty::LineHeight,
+    StyleProperty::Direction,
+    StyleProperty::WritingMode,
+    StyleProperty::TextOrientation,
+    StyleProperty::TextAlign,
+    StyleProperty::TextAlignLast,
+    StyleProperty::WhiteSpace,
+    StyleProperty::TextWrap,
+    StyleProperty::WordBreak,
+    StyleProperty::OverflowWrap,
+    StyleProperty::Hyphens,
+    StyleProperty::ListStyleType,
+    StyleProperty::ListStylePosition,
+];
+
+/// Tag name of the anonymous block boxes that wrap inline content next to blocks
+pub const ANONYMOUS_BLOCK_TAG: &str = "::anonymous-block";
+
+/// How a box takes part in the layout of its parent
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoxLevel {
+    Inline,
+    Block,
+    /// Absolutely positioned boxes do not take part in the flow of their siblings
+    OutOfFlow,
+}
+
+/// CSS counters (and quotes) that are in scope while walking the tree. A counter created by an element is visible to the
+/// element, its following siblings and their descendants, so it goes out of scope when we leave the parent of the element.
+/// A counter-reset creates a new counter that hides the counters of the ancestors, which is how nested lists get their own
+/// numbering. See https://drafts.csswg.org/css-lists-3/#nested-counters
+#[derive(Default)]
+struct Counters {
+    /// Name, value and tree depth of the element that created the counter
+    counters: Vec<(String, i32, usize)>,
+    /// Tree depth of the element whose counters are being updated
+    depth: usize,
+    /// Step of the list-item counter. Reversed lists count down.
+    list_item_step: i32,
+    /// Nesting level of quotes generated by open-quote and close-quote
+    quote_depth: usize,
+}
+
+impl Counters {
+    fn reset(&mut self, name: &str, value: i32) {
+        // A counter created by the element itself or by a preceding sibling is replaced
+        if let Some(idx) = self.counters.iter().rposition(|(n, _, _)| n == name) {
+            if self.counters[idx].2 == self.depth {
+                self.counters.remove(idx);
+            }
+        }
+        self.counters.push((name.to_string(), value, self.depth));
+    }
+
+    fn increment(&mut self, name: &str, value: i32) {
+        match self.counters.iter_mut().rev().find(|(n, _, _)| n == name) {
+            Some(counter) => counter.1 += value,
+            // Incrementing a counter that does not exist creates it
+            None => self.counters.push((name.to_string(), value, self.depth)),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: i32) {
+        match self.counters.iter_mut().rev().find(|(n, _, _)| n == name) {
+            Some(counter) => counter.1 = value,
+            None => self.counters.push((name.to_string(), value, self.depth)),
+        }
+    }
+
+    fn get(&self, name: &str) -> i32 {
+        self.counters.iter().rev().find(|(n, _, _)| n == name).map_or(0, |(_, value, _)| *value)
+    }
+
+    /// Returns the values of all counters with the given name, from the outermost to the innermost
+    fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = i32> + 'a {
+        self.counters.iter().filter(move |(n, _, _)| n == name).map(|(_, value, _)| *value)
+    }
+}
+
 impl RenderTree {
     pub fn new(doc: Arc<Document>) -> Self {
         RenderTree {
             doc: doc.clone(),
             arena: HashMap::new(),
             root_id: None,
+            next_node_id: RenderNodeId::new(1),
         }
     }
 
@@ -107,9 +200,9 @@ impl RenderTree {
             panic!("Document has no root node");
         };
 
-        let doc = &self.doc;
-        match self.build_rendertree(root_id) {
-            Some(render_node_id) => self.root_id = Some(render_node_id),
+        let mut counters = Counters { list_item_step: 1, ..Counters::default() };
+        match self.build_rendertree(root_id, &mut counters).first() {
+            Some(render_node_id) => self.root_id = Some(*render_node_id),
             None => panic!("Failed to build rendertree"),
         }
     }
@@ -138,30 +231,669 @@ impl RenderTree {
         }
     }
 
-    fn build_rendertree(&mut self, node_id: NodeId) -> Option<RenderNodeId> {
+    /// Builds the render nodes for the given node. This is normally a single render node, but an inline element
+    /// that contains blocks is split into multiple render nodes around these blocks. Returns an empty list when
+    /// the node is not visible.
+    fn build_rendertree(&mut self, node_id: NodeId, counters: &mut Counters) -> Vec<RenderNodeId> {
         let Some(node) = self.doc.get_node_by_id(node_id) else {
-            return None;
+            return Vec::new();
         };
 
         if !self.is_visible(node) {
-            return None;
+            return Vec::new();
         }
 
+        let is_list_item = is_list_item(node);
+
+        let list_item_step = counters.list_item_step;
+        self.update_counters(node_id, counters);
+
+        // Counters created by the children go out of scope when we leave the node
+        let counter_scope = counters.counters.len();
+        counters.depth += 1;
+
         let mut render_node = RenderNode {
-            node_id: RenderNodeId::from(node_id),
+            node_id: self.next_node_id(),
+            dom_node_id: node_id,
             children: Vec::new(),
         };
 
-        let children = node.children.clone();
+        // Generated boxes are placed around the children: ::marker, ::before, children, ::after
+        let mut children = Vec::new();
+        if is_list_item {
+            children.extend(self.generate_marker(node_id, counters.get(LIST_ITEM_COUNTER)));
+        }
+        children.extend(self.generate_pseudo_element(node_id, PseudoElement::Before, counters));
+        children.extend(self.doc.get_node_by_id(node_id).map_or(Vec::new(), |node| node.children.clone()));
+
         for child_id in children {
-            if let Some(render_child) = self.build_rendertree(child_id) {
-                render_node.children.push(render_child);
+            let render_children = self.build_rendertree(child_id, counters);
+            render_node.children.extend(render_children);
+        }
+
+        // ::after is generated after the children, so it sees the counters as incremented by the children
+        if let Some(after_id) = self.generate_pseudo_element(node_id, PseudoElement::After, counters) {
+            render_node.children.extend(self.build_rendertree(after_id, counters));
+        }
+
+        counters.counters.truncate(counter_scope);
+        counters.depth -= 1;
+        counters.list_item_step = list_item_step;
+
+        let node = self.doc.get_node_by_id(node_id);
+        let is_block_container = is_block_container(node);
+        // Inline grids and tables are atomic inlines, which lay out their block children themselves
+        let is_inline = node.is_some_and(|node| {
+            self.node_box_level(node) == BoxLevel::Inline
+                && !matches!(
+                    &node.node_type,
+                    NodeType::Element(data) if matches!(
+                        data.get_style(StyleProperty::Display),
+                        Some(StyleValue::Display(CssDisplay::InlineGrid | CssDisplay::InlineTable))
+                    )
+                )
+        });
+
+        if is_block_container {
+            self.wrap_inline_runs(&mut render_node);
+        } else if is_inline {
+            return self.split_inline(render_node);
+        }
+
+        let render_node_id = render_node.node_id;
+        self.arena.insert(render_node_id, render_node);
+        vec![render_node_id]
+    }
+
+    /// Returns how the box of the render node takes part in the layout of its parent
+    fn box_level(&self, render_node_id: RenderNodeId) -> BoxLevel {
+        self.get_node_by_id(render_node_id)
+            .and_then(|render_node| self.doc.get_node_by_id(render_node.dom_node_id))
+            .map_or(BoxLevel::Block, |node| self.node_box_level(node))
+    }
+
+    fn node_box_level(&self, node: &Node) -> BoxLevel {
+        let NodeType::Element(data) = &node.node_type else {
+            return BoxLevel::Inline;
+        };
+
+        if matches!(data.get_style(StyleProperty::Position), Some(StyleValue::Keyword(position)) if position == "absolute" || position == "fixed") {
+            return BoxLevel::OutOfFlow;
+        }
+        if matches!(data.get_style(StyleProperty::Float), Some(StyleValue::Float(float)) if *float != Float::None) {
+            return BoxLevel::OutOfFlow;
+        }
+
+        match data.get_style(StyleProperty::Display) {
+            Some(StyleValue::Display(
+                CssDisplay::Inline | CssDisplay::InlineBlock | CssDisplay::InlineGrid | CssDisplay::InlineTable,
+            )) => BoxLevel::Inline,
+            _ => BoxLevel::Block,
+        }
+    }
+
+    /// Splits an inline box that contains blocks. The inline content before, between and after the blocks gets
+    /// its own render node for the inline element, and the blocks become siblings of these nodes. The parent
+    /// block will wrap the inline parts in anonymous blocks.
+    fn split_inline(&mut self, render_node: RenderNode) -> Vec<RenderNodeId> {
+        let has_blocks = render_node.children.iter().any(|child_id| self.box_level(*child_id) == BoxLevel::Block);
+        if !has_blocks {
+            let render_node_id = render_node.node_id;
+            self.arena.insert(render_node_id, render_node);
+            return vec![render_node_id];
+        }
+
+        let mut fragments = Vec::new();
+        let mut run: Vec<RenderNodeId> = Vec::new();
+        for child_id in render_node.children {
+            if self.box_level(child_id) != BoxLevel::Block {
+                run.push(child_id);
+                continue;
             }
+
+            if !run.is_empty() {
+                fragments.push(self.new_render_node(render_node.dom_node_id, std::mem::take(&mut run)));
+            }
+            fragments.push(child_id);
+        }
+        if !run.is_empty() {
+            fragments.push(self.new_render_node(render_node.dom_node_id, run));
         }
 
+        fragments
+    }
+
+    /// Wraps the runs of inline content of a block container in anonymous blocks, when the block container also
+    /// has block children. A block container with only inline content lays out the inline content itself.
+    fn wrap_inline_runs(&mut self, render_node: &mut RenderNode) {
+        let levels: Vec<BoxLevel> = render_node.children.iter().map(|child_id| self.box_level(*child_id)).collect();
+        if !levels.contains(&BoxLevel::Block) || !levels.contains(&BoxLevel::Inline) {
+            return;
+        }
+
+        let mut children = Vec::new();
+        let mut run: Vec<RenderNodeId> = Vec::new();
+        for (child_id, level) in render_node.children.iter().zip(levels) {
+            match level {
+                BoxLevel::Inline => run.push(*child_id),
+                // Out-of-flow boxes stay with the inline content they are next to
+                BoxLevel::OutOfFlow if !run.is_empty() => run.push(*child_id),
+                _ => {
+                    if !run.is_empty() {
+                        children.push(self.new_anonymous_block(render_node.dom_node_id, std::mem::take(&mut run)));
+                    }
+                    children.push(*child_id);
+                }
+            }
+        }
+        if !run.is_empty() {
+            children.push(self.new_anonymous_block(render_node.dom_node_id, run));
+        }
+
+        render_node.children = children;
+    }
+
+    /// Creates an anonymous block box in the given parent. Anonymous boxes inherit the inherited properties of
+    /// their parent, and have a node in the document of the render tree only.
+    fn new_anonymous_block(&mut self, parent_id: NodeId, children: Vec<RenderNodeId>) -> RenderNodeId {
+        let mut style = match self.doc.get_node_by_id(parent_id).map(|node| &node.node_type) {
+            Some(NodeType::Element(data)) => inherited_style(&data.styles),
+            _ => StylePropertyList::new(),
+        };
+        style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::Block));
+
+        let doc = Arc::make_mut(&mut self.doc);
+        let anonymous_id = doc.new_element(Some(parent_id), ANONYMOUS_BLOCK_TAG, None, false, Some(style));
+
+        self.new_render_node(anonymous_id, children)
+    }
+
+    fn new_render_node(&mut self, dom_node_id: NodeId, children: Vec<RenderNodeId>) -> RenderNodeId {
+        let render_node = RenderNode {
+            node_id: self.next_node_id(),
+            dom_node_id,
+            children,
+        };
+
         let render_node_id = render_node.node_id;
         self.arena.insert(render_node_id, render_node);
+        render_node_id
+    }
+
+    fn next_node_id(&mut self) -> RenderNodeId {
+        let node_id = self.next_node_id;
+        self.next_node_id += 1;
+        node_id
+    }
+
+    /// Applies the counter-reset and counter-increment of the node. Lists reset the list-item counter and list
+    /// items increment it, unless the stylesheet explicitly does something with the list-item counter.
+    fn update_counters(&self, node_id: NodeId, counters: &mut Counters) {
+        let Some(node) = self.doc.get_node_by_id(node_id) else {
+            return;
+        };
+        let NodeType::Element(data) = &node.node_type else {
+            return;
+        };
+
+        let resets = match data.get_style(StyleProperty::CounterReset) {
+            Some(StyleValue::Counters(resets)) => resets.clone(),
+            _ => Vec::new(),
+        };
+        let increments = match data.get_style(StyleProperty::CounterIncrement) {
+            Some(StyleValue::Counters(increments)) => increments.clone(),
+            _ => Vec::new(),
+        };
+
+        let is_list = LIST_ELEMENTS.contains(&data.tag_name.to_lowercase().as_str());
+        if is_list && !resets.iter().any(|(name, _)| name == LIST_ITEM_COUNTER) {
+            let reversed = data.get_attribute("reversed").is_some();
+            counters.list_item_step = if reversed { -1 } else { 1 };
+
+            // A reversed list counts down from the number of its items
+            let start = match data.get_attribute("start").and_then(|start| start.trim().parse::<i32>().ok()) {
+                Some(start) => start,
+                None if reversed => self.count_list_items(node) as i32,
+                None => 1,
+            };
+            counters.reset(LIST_ITEM_COUNTER, start - counters.list_item_step);
+        }
+        for (name, value) in &resets {
+            counters.reset(name, *value);
+        }
+
+        if is_list_item(node) && !increments.iter().any(|(name, _)| name == LIST_ITEM_COUNTER) {
+            // The value attribute sets the number of the list item, the next items continue from there
+            match data.get_attribute("value").and_then(|value| value.trim().parse::<i32>().ok()) {
+                Some(value) => counters.set(LIST_ITEM_COUNTER, value),
+                None => counters.increment(LIST_ITEM_COUNTER, counters.list_item_step),
+            }
+        }
+        for (name, value) in &increments {
+            counters.increment(name, *value);
+        }
+    }
+
+    fn count_list_items(&self, node: &Node) -> usize {
+        node.children
+            .iter()
+            .filter_map(|child_id| self.doc.get_node_by_id(*child_id))
+            .filter(|child| is_list_item(child))
+            .count()
+    }
+
+    /// Generates an anonymous marker element for the list item. Textual markers get a text node with the
+    /// formatted counter, bullets are painted by the painter. Returns None when the list item has no marker.
+    fn generate_marker(&mut self, node_id: NodeId, counter: i32) -> Option<NodeId> {
+        let NodeType::Element(data) = &self.doc.get_node_by_id(node_id)?.node_type else {
+            return None;
+        };
+
+        let list_style_type = match data.get_style(StyleProperty::ListStyleType) {
+            Some(StyleValue::ListStyleType(list_style_type)) => *list_style_type,
+            _ => ListStyleType::Disc,
+        };
+        let marker_text = marker_text(list_style_type, counter)?;
+        let position = match data.get_style(StyleProperty::ListStylePosition) {
+            Some(StyleValue::ListStylePosition(position)) => *position,
+            _ => ListStylePosition::Outside,
+        };
+        // The ::marker styles (like li::marker { color: red }) take precedence over the list item styles
+        let marker_source = data.get_pseudo_style(PseudoElement::Marker).unwrap_or(&data.styles);
+        let style = marker_style(marker_source, list_style_type, position);
+
+        let doc = Arc::make_mut(&mut self.doc);
+        let marker_id = doc.new_element(Some(node_id), PseudoElement::Marker.tag_name(), None, false, Some(style.clone()));
+        if !marker_text.is_empty() {
+            let text_id = doc.new_text(Some(marker_id), &marker_text, Some(style));
+            doc.add_child(marker_id, text_id);
+        }
+
+        Some(marker_id)
+    }
+
+    /// Generates an anonymous element for the ::before or ::after pseudo-element of the node, with the
+    /// resolved content as its children. Returns None when the pseudo-element does not generate a box.
+    fn generate_pseudo_element(&mut self, node_id: NodeId, pseudo: PseudoElement, counters: &mut Counters) -> Option<NodeId> {
+        let NodeType::Element(data) = &self.doc.get_node_by_id(node_id)?.node_type else {
+            return None;
+        };
+
+        let mut style = data.get_pseudo_style(pseudo)?.clone();
+        let Some(StyleValue::Content(items)) = style.get_property(StyleProperty::Content) else {
+            return None;
+        };
+        if matches!(style.get_property(StyleProperty::Display), Some(StyleValue::Display(CssDisplay::None))) {
+            return None;
+        }
+
+        // The counters of the pseudo-element itself are applied before its content is resolved. They are
+        // removed from the generated element, so they are not applied again when its render node is built.
+        if let Some(StyleValue::Counters(resets)) = style.get_property(StyleProperty::CounterReset) {
+            for (name, value) in resets {
+                counters.reset(name, *value);
+            }
+        }
+        if let Some(StyleValue::Counters(increments)) = style.get_property(StyleProperty::CounterIncrement) {
+            for (name, value) in increments {
+                counters.increment(name, *value);
+            }
+        }
+
+        let content = resolve_content(items, data, counters);
+        style.properties.remove(&StyleProperty::CounterReset);
+        style.properties.remove(&StyleProperty::CounterIncrement);
+
+        let doc = Arc::make_mut(&mut self.doc);
+        let pseudo_id = doc.new_element(Some(node_id), pseudo.tag_name(), None, false, Some(style.clone()));
+        for part in content {
+            let child_id = match part {
+                GeneratedContent::Text(text) => doc.new_text(Some(pseudo_id), &text, Some(style.clone())),
+                // Images are generated as img elements, so they are laid out and painted like any other image
+                GeneratedContent::Image(url) => {
+                    let mut attributes = AttrMap::new();
+                    attributes.set("src", &url);
+                    let mut image_style = StylePropertyList::new();
+                    image_style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::InlineBlock));
+                    doc.new_element(Some(pseudo_id), "img", Some(attributes), true, Some(image_style))
+                }
+            };
+            doc.add_child(pseudo_id, child_id);
+        }
+
+        Some(pseudo_id)
+    }
+}
+
+/// Content of a pseudo-element after resolving attributes, counters and quotes
+#[derive(Debug, PartialEq)]
+enum GeneratedContent {
+    Text(String),
+    Image(String),
+}
+
+/// Resolves the items of the content property. Consecutive text is merged into a single text.
+fn resolve_content(items: &[ContentItem], element: &ElementData, counters: &mut Counters) -> Vec<GeneratedContent> {
+    let mut content = Vec::new();
+    let mut text = String::new();
+
+    for item in items {
+        match item {
+            ContentItem::String(value) => text.push_str(value),
+            ContentItem::Attr(name) => text.push_str(element.get_attribute(name).map_or("", String::as_str)),
+            ContentItem::Counter(name, list_style_type) => text.push_str(&format_counter(counters.get(name), *list_style_type)),
+            ContentItem::Counters(name, separator, list_style_type) => {
+                let values: Vec<String> = counters
+                    .all(name)
+                    .map(|value| format_counter(value, *list_style_type))
+                    .collect();
+                text.push_str(&values.join(separator));
+            }
+            ContentItem::OpenQuote => {
+                text.push(if counters.quote_depth.is_multiple_of(2) { '\u{201C}' } else { '\u{2018}' });
+                counters.quote_depth += 1;
+            }
+            ContentItem::CloseQuote => {
+                counters.quote_depth = counters.quote_depth.saturating_sub(1);
+                text.push(if counters.quote_depth.is_multiple_of(2) { '\u{201D}' } else { '\u{2019}' });
+            }
+            ContentItem::Url(url) => {
+                if !text.is_empty() {
+                    content.push(GeneratedContent::Text(std::mem::take(&mut text)));
+                }
+                content.push(GeneratedContent::Image(url.clone()));
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        content.push(GeneratedContent::Text(text));
+    }
+
+    content
+}
+
+/// Returns true when the node is an element that lays out its children in a block formatting context (or an inline
+/// formatting context when it only has inline children)
+pub fn is_block_container(node: Option<&Node>) -> bool {
+    let Some(NodeType::Element(data)) = node.map(|node| &node.node_type) else {
+        return false;
+    };
+
+    matches!(
+        data.get_style(StyleProperty::Display),
+        Some(StyleValue::Display(CssDisplay::Block | CssDisplay::ListItem | CssDisplay::InlineBlock | CssDisplay::TableCell | CssDisplay::TableCaption))
+    )
+}
+
+/// Returns a style list with the inherited properties of the given style
+fn inherited_style(style: &StylePropertyList) -> StylePropertyList {
+    let mut inherited = StylePropertyList::new();
+    for prop in INHERITED_PROPERTIES {
+        if let Some(value) = style.get_property(prop.clone()) {
+            inherited.set_property(prop, value.clone());
+        }
+    }
+
+    inherited
+}
+
+/// Returns true when the node is an element with display: list-item
+fn is_list_item(node: &Node) -> bool {
+    match &node.node_type {
+        NodeType::Element(data) => matches!(data.get_style(StyleProperty::Display), Some(StyleValue::Display(CssDisplay::ListItem))),
+        _ => false,
+    }
+}
+
+/// Returns the styles of the marker of a list item. Outside markers are positioned absolutely in front of the
+/// list item, inside markers are the first inline box of the list item. Bullets are boxes of about a third of
+/// the font size that are vertically centered on the first line.
+fn marker_style(list_item_style: &StylePropertyList, list_style_type: ListStyleType, position: ListStylePosition) -> StylePropertyList {
+    let mut style = inherited_style(list_item_style);
+    style.set_property(StyleProperty::WhiteSpace, StyleValue::WhiteSpace(WhiteSpace::Pre));
+
+    let font_size = match list_item_style.get_property(StyleProperty::FontSize) {
+        Some(StyleValue::Unit(value, Unit::Px)) => *value,
+        _ => 16.0,
+    };
+    let line_height = match list_item_style.get_property(StyleProperty::LineHeight) {
+        Some(StyleValue::Unit(value, Unit::Px)) => *value,
+        _ => font_size,
+    };
+
+    let gap = if list_style_type.is_bullet() {
+        let size = (font_size * 0.35).round();
+        style.set_property(StyleProperty::Width, StyleValue::Unit(size, Unit::Px));
+        style.set_property(StyleProperty::Height, StyleValue::Unit(size, Unit::Px));
+        style.set_property(StyleProperty::MarginTop, StyleValue::Unit(((line_height - size) / 2.0).max(0.0), Unit::Px));
+        font_size * 0.5
+    } else {
+        // The suffix of the marker text already separates the marker from the content
+        0.0
+    };
+    let rtl = matches!(list_item_style.get_property(StyleProperty::Direction), Some(StyleValue::Direction(Direction::Rtl)));
+    let gap_side = if rtl { StyleProperty::MarginLeft } else { StyleProperty::MarginRight };
+    style.set_property(gap_side, StyleValue::Unit(gap, Unit::Px));
+
+    match position {
+        ListStylePosition::Outside => {
+            style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::Block));
+            style.set_property(StyleProperty::Position, StyleValue::Keyword("absolute".to_string()));
+            style.set_property(StyleProperty::InsetBlockStart, StyleValue::Unit(0.0, Unit::Px));
+            style.set_property(StyleProperty::InsetInlineEnd, StyleValue::Unit(100.0, Unit::Percent));
+        }
+        ListStylePosition::Inside => {
+            style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::InlineBlock));
+        }
+    }
+
+    style
+}
+
+/// Returns the text of the marker for the given counter value. Bullets have an empty text as they are painted
+/// as shapes. Returns None when the list item has no marker.
+fn marker_text(list_style_type: ListStyleType, counter: i32) -> Option<String> {
+    match list_style_type {
+        ListStyleType::None => None,
+        ListStyleType::Disc | ListStyleType::Circle | ListStyleType::Square => Some(String::new()),
+        _ => Some(format!("{}. ", format_counter(counter, list_style_type))),
+    }
+}
+
+/// Formats the value of a counter in the given style
+fn format_counter(counter: i32, list_style_type: ListStyleType) -> String {
+    match list_style_type {
+        ListStyleType::None => String::new(),
+        ListStyleType::Disc => "\u{2022}".to_string(),
+        ListStyleType::Circle => "\u{25E6}".to_string(),
+        ListStyleType::Square => "\u{25AA}".to_string(),
+        ListStyleType::Decimal => counter.to_string(),
+        ListStyleType::DecimalLeadingZero if (0..10).contains(&counter) => format!("0{}", counter),
+        ListStyleType::DecimalLeadingZero => counter.to_string(),
+        // Alphabetic and roman numbering is only defined for positive numbers, others fall back to decimals
+        _ if counter <= 0 => counter.to_string(),
+        ListStyleType::LowerAlpha => to_alphabetic(counter as u32),
+        ListStyleType::UpperAlpha => to_alphabetic(counter as u32).to_uppercase(),
+        ListStyleType::LowerRoman if counter < 4000 => to_roman(counter as u32).to_lowercase(),
+        ListStyleType::UpperRoman if counter < 4000 => to_roman(counter as u32),
+        _ => counter.to_string(),
+    }
+}
+
+/// Converts the number to a, b, .., z, aa, ab, ..
+fn to_alphabetic(mut value: u32) -> String {
+    let mut result = Vec::new();
+    while value > 0 {
+        value -= 1;
+        result.push((b'a' + (value % 26) as u8) as char);
+        value /= 26;
+    }
+
+    result.iter().rev().collect()
+}
+
+fn to_roman(mut value: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+
+    let mut result = String::new();
+    for (amount, numeral) in NUMERALS {
+        while value >= amount {
+            result.push_str(numeral);
+            value -= amount;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_text() {
+        assert_eq!(marker_text(ListStyleType::None, 1), None);
+        assert_eq!(marker_text(ListStyleType::Disc, 1), Some("".to_string()));
+        assert_eq!(marker_text(ListStyleType::Decimal, 12), Some("12. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::DecimalLeadingZero, 7), Some("07. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::LowerAlpha, 1), Some("a. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::LowerAlpha, 28), Some("ab. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::UpperAlpha, 26), Some("Z. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::LowerRoman, 4), Some("iv. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::UpperRoman, 1994), Some("MCMXCIV. ".to_string()));
+        assert_eq!(marker_text(ListStyleType::UpperRoman, 0), Some("0. ".to_string()));
+    }
+
+    fn display_style(display: CssDisplay) -> Option<StylePropertyList> {
+        let mut style = StylePropertyList::new();
+        style.set_property(StyleProperty::Display, StyleValue::Display(display));
+        Some(style)
+    }
+
+    #[test]
+    fn test_anonymous_boxes() {
+        // <div>a<p>b</p><span>c<div>d</div>e</span></div>
+        let mut doc = Document::new("https://example.com");
+        let div_id = doc.new_element(None, "div", None, false, display_style(CssDisplay::Block));
+        let a_id = doc.new_text(Some(div_id), "a", None);
+        let p_id = doc.new_element(Some(div_id), "p", None, false, display_style(CssDisplay::Block));
+        let b_id = doc.new_text(Some(p_id), "b", None);
+        let span_id = doc.new_element(Some(div_id), "span", None, false, display_style(CssDisplay::Inline));
+        let c_id = doc.new_text(Some(span_id), "c", None);
+        let inner_id = doc.new_element(Some(span_id), "div", None, false, display_style(CssDisplay::Block));
+        let d_id = doc.new_text(Some(inner_id), "d", None);
+        let e_id = doc.new_text(Some(span_id), "e", None);
+        for (parent_id, child_id) in [(div_id, a_id), (div_id, p_id), (p_id, b_id), (div_id, span_id), (span_id, c_id), (span_id, inner_id), (inner_id, d_id), (span_id, e_id)] {
+            doc.add_child(parent_id, child_id);
+        }
+        doc.set_root(div_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+
+        let tag_name = |render_node_id: &RenderNodeId| {
+            let render_node = render_tree.get_node_by_id(*render_node_id).unwrap();
+            match &render_tree.doc.get_node_by_id(render_node.dom_node_id).unwrap().node_type {
+                NodeType::Element(data) => data.tag_name.clone(),
+                NodeType::Text(text, _) => text.clone(),
+                NodeType::Comment(_) => "#comment".to_string(),
+            }
+        };
+        let children = |render_node_id: &RenderNodeId| -> Vec<String> {
+            render_tree.get_node_by_id(*render_node_id).unwrap().children.iter().map(tag_name).collect()
+        };
+
+        let root = render_tree.root_id.unwrap();
+        assert_eq!(children(&root), vec![ANONYMOUS_BLOCK_TAG, "p", ANONYMOUS_BLOCK_TAG, "div", ANONYMOUS_BLOCK_TAG]);
+
+        // The span is split in two parts around the inner div
+        let root_children = render_tree.get_node_by_id(root).unwrap().children.clone();
+        assert_eq!(children(&root_children[0]), vec!["a"]);
+        assert_eq!(children(&root_children[2]), vec!["span"]);
+        assert_eq!(children(&root_children[4]), vec!["span"]);
+        let first_part = render_tree.get_node_by_id(root_children[2]).unwrap().children[0];
+        assert_eq!(children(&first_part), vec!["c"]);
+    }
+
+    #[test]
+    fn test_sibling_counters() {
+        // <div><h1/><h2/><h2/><h1/><h2/></div> with h1 { counter-reset: section } and
+        // h2 { counter-increment: section } h2::before { content: counter(section) }
+        let mut doc = Document::new("https://example.com");
+        let div_id = doc.new_element(None, "div", None, false, display_style(CssDisplay::Block));
+        let mut h2_ids = Vec::new();
+        for tag in ["h1", "h2", "h2", "h1", "h2"] {
+            let mut style = StylePropertyList::new();
+            style.set_property(StyleProperty::Display, StyleValue::Display(CssDisplay::Block));
+            if tag == "h1" {
+                style.set_property(StyleProperty::CounterReset, StyleValue::Counters(vec![("section".to_string(), 0)]));
+            } else {
+                style.set_property(StyleProperty::CounterIncrement, StyleValue::Counters(vec![("section".to_string(), 1)]));
+            }
+            let id = doc.new_element(Some(div_id), tag, None, false, Some(style));
+            if tag == "h2" {
+                let mut before = StylePropertyList::new();
+                before.set_property(
+                    StyleProperty::Content,
+                    StyleValue::Content(vec![ContentItem::Counter("section".to_string(), ListStyleType::Decimal)]),
+                );
+                if let NodeType::Element(data) = &mut doc.get_node_by_id_mut(id).unwrap().node_type {
+                    data.pseudo_styles.insert(PseudoElement::Before, before);
+                }
+                h2_ids.push(id);
+            }
+            doc.add_child(div_id, id);
+        }
+        doc.set_root(div_id);
+
+        let mut render_tree = RenderTree::new(Arc::new(doc));
+        render_tree.parse();
+
+        // The counter of the first h1 is in scope for the h2 siblings that follow it, until the next h1 resets it
+        let before_text = |dom_node_id: NodeId| {
+            let render_node = render_tree.arena.values().find(|render_node| render_node.dom_node_id == dom_node_id).unwrap();
+            let before = render_tree.get_node_by_id(render_node.children[0]).unwrap();
+            let text = render_tree.get_node_by_id(before.children[0]).unwrap();
+            match &render_tree.doc.get_node_by_id(text.dom_node_id).unwrap().node_type {
+                NodeType::Text(text, _) => text.clone(),
+                _ => String::new(),
+            }
+        };
+        let texts: Vec<_> = h2_ids.into_iter().map(before_text).collect();
+        assert_eq!(texts, vec!["1", "2", "1"]);
+    }
+
+    #[test]
+    fn test_resolve_content() {
+        let mut attributes = AttrMap::new();
+        attributes.set("title", "Intro");
+        let element = ElementData::new("h2".to_string(), Some(attributes), false, None);
+
+        let mut counters = Counters::default();
+        counters.reset("section", 1);
+        counters.depth += 1;
+        counters.reset("section", 3);
 
-        Some(render_node_id)
+        let items = vec![
+            ContentItem::OpenQuote,
+            ContentItem::Attr("title".to_string()),
+            ContentItem::CloseQuote,
+            ContentItem::String(" ".to_string()),
+            ContentItem::Counters("section".to_string(), ".".to_string(), ListStyleType::Decimal),
+            ContentItem::Counter("section".to_string(), ListStyleType::LowerAlpha),
+            ContentItem::Url("icon.png".to_string()),
+        ];
+        assert_eq!(
+            resolve_content(&items, &element, &mut counters),
+            vec![
+                GeneratedContent::Text("\u{201C}Intro\u{201D} 1.3c".to_string()),
+                GeneratedContent::Image("icon.png".to_string()),
+            ]
+        );
+        assert_eq!(counters.quote_depth, 0);
     }
 }
\ No newline at end of file
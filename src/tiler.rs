@@ -1,23 +1,25 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::ops::AddAssign;
 use std::sync::{Arc, RwLock};
 use rstar::AABB;
 use rstar::primitives::GeomWithData;
 use crate::common::geo::{Coordinate, Dimension, Rect};
-use crate::layering::layer::{LayerId, LayerList};
-use crate::layouter::{LayoutElementId, LayoutElementNode};
+use crate::display_list::{DisplayItem, DisplayList};
+use crate::layering::layer::LayerId;
+use crate::layouter::{LayoutElementId, LayoutTree};
 use crate::painter::commands::PaintCommand;
 use crate::common::texture::TextureId;
 
 /*
 
 TileList
-    wrapped(LayerList)
+    wrapped(DisplayList)
     tiles: hashmap<LayerId, TileLayer>
     arena of Tile
     next_node_id
     default_tile_dimension
+    grid
 
 TileLayer
     layer_id
@@ -62,7 +64,7 @@ impl std::fmt::Display for TileId {
 }
 
 /// An element that is laid out in a tile. It contains the paint commands to render the (partial)
-/// element onto the tile.
+/// element onto the tile. These paint commands are selected from the display list.
 #[derive(Debug, Clone)]
 pub struct TiledLayoutElement {
     /// Element to layout
@@ -163,8 +165,8 @@ impl TileLayer {
 /// Main list of tiles per layer.
 #[derive(Clone)]
 pub struct TileList {
-    /// Wrapped layer list
-    pub layer_list: Arc<LayerList>,
+    /// Wrapped display list
+    pub display_list: Arc<DisplayList>,
 
     // Tile info per layer
     pub tiles: HashMap<LayerId, TileLayer>,
@@ -175,6 +177,8 @@ pub struct TileList {
     next_node_id: Arc<RwLock<TileId>>,
 
     pub default_tile_dimension: Dimension,
+    /// Number of rows and columns of tiles in each layer
+    grid: (usize, usize),
 }
 
 impl Debug for TileList {
@@ -209,6 +213,22 @@ impl TileList {
         }
     }
 
+    /// Repaints all elements in the display list and marks all tiles as dirty. Note that the painter
+    /// reads the browser state, so the browser state must not be write-locked when calling this.
+    pub fn repaint_all(&mut self) {
+        self.display_list.invalidate_all();
+        self.invalidate_all();
+    }
+
+    /// Repaints the given element in the display list and marks all tiles that contain it as dirty
+    pub fn repaint_element(&mut self, element_id: LayoutElementId) {
+        self.display_list.invalidate_element(element_id);
+
+        for tile_id in self.get_tiles_for_element(element_id) {
+            self.invalidate_tile(tile_id);
+        }
+    }
+
     pub fn invalidate_tile(&mut self, tile_id: TileId) {
         let tile = self.arena.get_mut(&tile_id).unwrap();
         tile.state = TileState::Dirty;
@@ -231,27 +251,43 @@ impl TileList {
 
         tile_layer.intersects_with(viewport)
     }
+
+    /// Returns the tiles of the layer that are visible in the viewport. The compositor moves fixed and sticky layers
+    /// when the document is scrolled (see layer_offset), so another part of these layers is in view.
+    pub fn get_visible_tiles(&self, layer_id: LayerId, viewport: Rect) -> Vec<TileId> {
+        let offset = self.layer_offset(layer_id, viewport);
+        self.get_intersecting_tiles(layer_id, viewport.shift(Coordinate::new(-offset.x, -offset.y)))
+    }
+
+    /// Returns where the compositor draws the tiles of the layer, relative to their position in the document
+    pub fn layer_offset(&self, layer_id: LayerId, viewport: Rect) -> Coordinate {
+        self.display_list.layer_list.layer_offset(layer_id, viewport)
+    }
 }
 
 impl TileList {
-    pub fn new(layer_list: LayerList, dimension: Dimension) -> Self {
+    pub fn new(display_list: DisplayList, dimension: Dimension) -> Self {
         Self {
-            layer_list: Arc::new(layer_list),
+            display_list: Arc::new(display_list),
             tiles: HashMap::new(),
             arena: HashMap::new(),
             next_node_id: Arc::new(RwLock::new(TileId::new(0))),
             default_tile_dimension: dimension,
+            grid: (0, 0),
         }
     }
 
+    /// Generates the tiles for all layers, replacing any tiles that were generated before
     pub fn generate(&mut self) {
-        let rows = (self.layer_list.layout_tree.root_dimension.height / self.default_tile_dimension.height).ceil() as usize;
-        let cols = (self.layer_list.layout_tree.root_dimension.width / self.default_tile_dimension.width).ceil() as usize;
+        let (rows, cols) = self.grid_size();
+        self.grid = (rows, cols);
+        self.tiles.clear();
+        self.arena.clear();
 
-        let mut layer_list = self.layer_list.layers.read().unwrap();
+        let layer_ids = self.display_list.layer_list.layer_ids.read().unwrap().clone();
 
         // iterate each layer
-        for layer_id in self.layer_list.layer_ids.read().unwrap().iter() {
+        for layer_id in layer_ids.iter() {
             // Each layer gets a list of tiles (rows * cols). They are stored in the arena.
             let mut tile_ids = Vec::with_capacity(rows * cols);
 
@@ -296,52 +332,77 @@ impl TileList {
             };
             self.tiles.insert(*layer_id, tile_layer);
 
-            // Get elements in this layer
-            let Some(layer) = layer_list.get(&layer_id) else {
-                continue;
-            };
+            // Select the elements for each tile from the display list
+            for tile_id in tile_ids {
+                self.paint_tile(tile_id);
+            }
+        }
+    }
+
+    /// Selects all display items that intersect with the given tile, and stores them (with their paint commands)
+    /// as elements in the tile.
+    pub fn paint_tile(&mut self, tile_id: TileId) {
+        let display_list = self.display_list.clone();
+
+        let Some(tile) = self.arena.get_mut(&tile_id) else {
+            log::warn!("Tile not found: {:?}", tile_id);
+            return;
+        };
 
-            let Some(tile_layer) = self.tiles.get(&layer_id) else {
+        let tile_rect = tile.rect;
+        tile.elements = display_list
+            .intersects_with(tile.layer_id, tile_rect)
+            .into_iter()
+            .map(|item| to_tiled_element(tile_rect, item))
+            .collect();
+    }
+
+    /// Returns the layout tree so it can be laid out again, or None when the display list is shared (which
+    /// is only the case while it is being painted)
+    pub fn layout_tree_mut(&mut self) -> Option<&mut LayoutTree> {
+        Arc::get_mut(&mut self.display_list)?.layout_tree_mut()
+    }
+
+    /// Updates the display list and the tiles after the given elements have been laid out again. The tiles that
+    /// held these elements and the tiles that hold them now select their elements again and are marked as dirty.
+    /// When the document no longer fits the tile grid, all tiles are generated again.
+    pub fn relayout(&mut self, changed: &[LayoutElementId]) {
+        self.display_list.relayout(changed);
+
+        if self.grid_size() != self.grid {
+            self.generate();
+            return;
+        }
+
+        let changed: HashSet<LayoutElementId> = changed.iter().copied().collect();
+        let mut tile_ids: HashSet<TileId> = self.arena.values()
+            .filter(|tile| tile.elements.iter().any(|element| changed.contains(&element.id)))
+            .map(|tile| tile.id)
+            .collect();
+
+        for (layer_id, layer) in self.display_list.layers.read().expect("Failed to lock display list layers").iter() {
+            let Some(tile_layer) = self.tiles.get(layer_id) else {
                 continue;
             };
 
-            // iterate each element in the layer
-            for &element_id in &layer.elements {
-                // Get element
-                let Some(element) = self.layer_list.layout_tree.get_node_by_id(element_id) else {
-                    log::warn!("Warning: Element {:?} not found in layout tree!", element_id);
-                    continue;
-                };
-                let margin_box = element.box_model.margin_box;
-
-                // Find all tile_ids that contain this element
-                let matching_tile_ids = tile_layer.intersects_with(margin_box);
-                for tile_id in &matching_tile_ids {
-                    let tile = self.arena.get_mut(&tile_id).unwrap();
-
-                    let position = Coordinate::new(
-                        tile.rect.x.max(margin_box.x) - margin_box.x,
-                        tile.rect.y.max(margin_box.y) - margin_box.y
-                    );
-
-                    let dimension = Rect::new(
-                        margin_box.x.max(tile.rect.x) - tile.rect.x,
-                        margin_box.y.max(tile.rect.y) - tile.rect.y,
-                        (tile.rect.x + tile.rect.width).min(margin_box.x + margin_box.width) - tile.rect.x.max(margin_box.x),
-                        (tile.rect.y + tile.rect.height).min(margin_box.y + margin_box.height) - tile.rect.y.max(margin_box.y),
-                    );
-
-                    let tiled_element = TiledLayoutElement {
-                        id: element_id,
-                        rect: dimension,
-                        position,
-                        paint_commands: vec![],
-                    };
-
-                    tile.elements.push(tiled_element);
-                }
+            for item in layer.items.iter().filter(|item| changed.contains(&item.element_id)) {
+                tile_ids.extend(tile_layer.intersects_with(item.rect));
             }
         }
+
+        for tile_id in tile_ids {
+            self.paint_tile(tile_id);
+            self.invalidate_tile(tile_id);
+        }
+    }
+
+    /// Returns the number of rows and columns of tiles that are needed to cover the document
+    fn grid_size(&self) -> (usize, usize) {
+        let root_dimension = self.display_list.layer_list.layout_tree.root_dimension;
+        let rows = (root_dimension.height / self.default_tile_dimension.height).ceil() as usize;
+        let cols = (root_dimension.width / self.default_tile_dimension.width).ceil() as usize;
+
+        (rows, cols)
     }
 
     pub fn print_list(&self) {
@@ -363,3 +424,26 @@ impl TileList {
     }
 }
 
+/// Converts a display item into a tiled layout element for the tile with the given rect
+pub fn to_tiled_element(tile_rect: Rect, item: DisplayItem) -> TiledLayoutElement {
+    let element_rect = item.rect;
+
+    let position = Coordinate::new(
+        tile_rect.x.max(element_rect.x) - element_rect.x,
+        tile_rect.y.max(element_rect.y) - element_rect.y
+    );
+
+    let dimension = Rect::new(
+        element_rect.x.max(tile_rect.x) - tile_rect.x,
+        element_rect.y.max(tile_rect.y) - tile_rect.y,
+        (tile_rect.x + tile_rect.width).min(element_rect.x + element_rect.width) - tile_rect.x.max(element_rect.x),
+        (tile_rect.y + tile_rect.height).min(element_rect.y + element_rect.height) - tile_rect.y.max(element_rect.y),
+    );
+
+    TiledLayoutElement {
+        id: item.element_id,
+        rect: dimension,
+        position,
+        paint_commands: item.commands,
+    }
+}
@@ -20,8 +20,8 @@ async def fetch_and_parse_html(url):
         # Extract computed styles for each node
         computed_styles_script = """
         (function() {
-            function getStyles(element) {
-                let computedStyle = window.getComputedStyle(element);
+            function getStyles(element, pseudo) {
+                let computedStyle = window.getComputedStyle(element, pseudo);
                 let styles = {};
                 for (let i = 0; i < computedStyle.length; i++) {
                     let prop = computedStyle[i];
@@ -35,8 +35,16 @@ async def fetch_and_parse_html(url):
                     return { comment: element.nodeValue.trim() };
                 }
                 if (element.nodeType === Node.TEXT_NODE) {
-                    let text = element.nodeValue.trim();
-                    return text ? { text: text } : null;
+                    // White space is kept, as it is processed during layout according to the white-space property.
+                    // Only text that consists of collapsible white space is dropped.
+                    let text = element.nodeValue;
+                    let parent = element.parentElement;
+                    let whiteSpace = parent ? window.getComputedStyle(parent).whiteSpace : 'normal';
+                    let collapsible = whiteSpace === 'normal' || whiteSpace === 'nowrap';
+                    if (collapsible && text.trim() === '') {
+                        return null;
+                    }
+                    return { text: text };
                 }
 
                 let children = [];
@@ -47,11 +55,35 @@ async def fetch_and_parse_html(url):
                     }
                 }
 
+                // Pseudo-elements are only exported when they generate a box
+                let pseudoElements = {};
+                for (let pseudo of ['::before', '::after']) {
+                    let content = window.getComputedStyle(element, pseudo).content;
+                    if (content !== 'none' && content !== 'normal') {
+                        pseudoElements[pseudo] = getStyles(element, pseudo);
+                    }
+                }
+                if (window.getComputedStyle(element).display === 'list-item') {
+                    pseudoElements['::marker'] = getStyles(element, '::marker');
+                }
+
+                // Border box as laid out by Chromium, relative to the top left of the document. It is used as
+                // reference by the layout conformance tests.
+                let bounds = element.getBoundingClientRect();
+                let rect = {
+                    x: bounds.x + window.scrollX,
+                    y: bounds.y + window.scrollY,
+                    width: bounds.width,
+                    height: bounds.height
+                };
+
                 return {
                     tag: element.tagName.toLowerCase(),
                     self_closing: element.childNodes.length === 0,
                     attributes: Object.fromEntries([...element.attributes].map(attr => [attr.name, attr.value])),
                     styles: getStyles(element),
+                    pseudoElements: pseudoElements,
+                    rect: rect,
                     children: children
                 };
             }
@@ -62,8 +94,45 @@ async def fetch_and_parse_html(url):
 
         dom_tree = await page.evaluate(computed_styles_script)
 
+        # Extract the @font-face rules from all stylesheets, with the font urls resolved against the stylesheet
+        font_faces_script = """
+        (function() {
+            let fontFaces = [];
+            for (let sheet of document.styleSheets) {
+                let rules;
+                try {
+                    rules = sheet.cssRules;
+                } catch (e) {
+                    // Cross-origin stylesheets cannot be read
+                    continue;
+                }
+
+                let base = sheet.href || document.baseURI;
+                for (let rule of rules) {
+                    if (!(rule instanceof CSSFontFaceRule)) {
+                        continue;
+                    }
+
+                    let src = rule.style.getPropertyValue('src');
+                    let sources = [...src.matchAll(/url\\(\\s*['"]?([^'")]+)['"]?\\s*\\)(?:\\s*format\\(\\s*['"]?([^'")]+)['"]?\\s*\\))?/g)]
+                        .map(m => ({ url: new URL(m[1], base).href, format: m[2] || null }));
+
+                    fontFaces.push({
+                        family: rule.style.getPropertyValue('font-family'),
+                        src: sources,
+                        weight: rule.style.getPropertyValue('font-weight') || null,
+                        style: rule.style.getPropertyValue('font-style') || null,
+                    });
+                }
+            }
+            return fontFaces;
+        })();
+        """
+
+        font_faces = await page.evaluate(font_faces_script)
+
         await browser.close()
-        return {"tag": "DocumentRoot", "attributes": {}, "styles": {}, "children": [dom_tree]}
+        return {"tag": "DocumentRoot", "attributes": {}, "styles": {}, "children": [dom_tree], "font_faces": font_faces}
 
 
 async def main():